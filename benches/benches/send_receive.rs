@@ -0,0 +1,140 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use coremidi::{Client, Destinations, EventBuffer, PacketBuffer, Protocol};
+
+const NUM_MESSAGES: usize = 1000;
+
+fn packet_buffer(num_messages: usize) -> PacketBuffer {
+    let mut buffer = PacketBuffer::with_capacity(0);
+    for timestamp in 0..num_messages {
+        buffer.push_data(timestamp as u64, &[0x90, 0x40, 0x7f]);
+    }
+    buffer
+}
+
+fn event_buffer(num_messages: usize) -> EventBuffer {
+    let mut buffer = EventBuffer::new(Protocol::Midi20);
+    for timestamp in 0..num_messages {
+        buffer.push(timestamp as u64, &[0x40903c00, 0xffff0000]);
+    }
+    buffer
+}
+
+fn push(c: &mut Criterion) {
+    let mut group = c.benchmark_group("push");
+    group.throughput(Throughput::Elements(NUM_MESSAGES as u64));
+
+    group.bench_function("packet_buffer", |b| {
+        let mut buffer = packet_buffer(NUM_MESSAGES);
+        b.iter(|| {
+            buffer.clear();
+            for timestamp in 0..NUM_MESSAGES {
+                buffer.push_data(timestamp as u64, black_box(&[0x90, 0x40, 0x7f]));
+            }
+        })
+    });
+
+    group.bench_function("packet_buffer_same_timestamp", |b| {
+        let mut buffer = packet_buffer(NUM_MESSAGES);
+        b.iter(|| {
+            buffer.clear();
+            for _ in 0..NUM_MESSAGES {
+                buffer.push_data(0, black_box(&[0x90, 0x40, 0x7f]));
+            }
+        })
+    });
+
+    group.bench_function("event_buffer", |b| {
+        let mut buffer = event_buffer(NUM_MESSAGES);
+        b.iter(|| {
+            buffer.clear();
+            for timestamp in 0..NUM_MESSAGES {
+                buffer.push(timestamp as u64, black_box(&[0x40903c00, 0xffff0000]));
+            }
+        })
+    });
+
+    group.bench_function("event_buffer_from_empty", |b| {
+        b.iter_batched(
+            || EventBuffer::new(Protocol::Midi20),
+            |mut buffer| {
+                for timestamp in 0..NUM_MESSAGES {
+                    buffer.push(timestamp as u64, black_box(&[0x40903c00, 0xffff0000]));
+                }
+                buffer
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn iterate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iterate");
+    group.throughput(Throughput::Elements(NUM_MESSAGES as u64));
+
+    let packets = packet_buffer(NUM_MESSAGES);
+    group.bench_function("packet_list", |b| {
+        b.iter(|| {
+            packets
+                .iter()
+                .map(|packet| packet.data().len())
+                .sum::<usize>()
+        })
+    });
+
+    let events = event_buffer(NUM_MESSAGES);
+    group.bench_function("event_list", |b| {
+        b.iter(|| {
+            events
+                .iter()
+                .map(|packet| packet.data().len())
+                .sum::<usize>()
+        })
+    });
+
+    group.finish();
+}
+
+fn send(c: &mut Criterion) {
+    let client = Client::new("coremidi-bench").unwrap();
+    let _virtual_destination = client
+        .virtual_destination_with_protocol("coremidi-bench-destination", Protocol::Midi20, |_| {})
+        .unwrap();
+    let destination = Destinations::find_by_name("coremidi-bench-destination").unwrap();
+    let output_port = client.output_port("output").unwrap();
+
+    let mut group = c.benchmark_group("send");
+    group.throughput(Throughput::Elements(NUM_MESSAGES as u64));
+
+    let packets = packet_buffer(NUM_MESSAGES);
+    group.bench_function("packet_list", |b| {
+        b.iter(|| output_port.send(&destination, &packets).unwrap())
+    });
+
+    let events = event_buffer(NUM_MESSAGES);
+    group.bench_function("event_list", |b| {
+        b.iter(|| output_port.send(&destination, &events).unwrap())
+    });
+
+    let messages: Vec<[u8; 3]> = (0..NUM_MESSAGES).map(|_| [0x90, 0x40, 0x7f]).collect();
+    group.bench_function("iter", |b| {
+        b.iter(|| {
+            output_port
+                .send_iter(
+                    &destination,
+                    messages
+                        .iter()
+                        .enumerate()
+                        .map(|(timestamp, message)| (timestamp as u64, &message[..])),
+                )
+                .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, push, iterate, send);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+//! A smoke test of the `ios` feature, to be run on a device or in the simulator, like:
+//!
+//! cargo build --example ios --features ios --target aarch64-apple-ios-sim
+//! xcrun simctl spawn booted target/aarch64-apple-ios-sim/debug/examples/ios
+//!
+//! It exits with an error as soon as any of the checks fails.
+
+#[cfg(target_os = "ios")]
+use core_foundation::runloop::{kCFRunLoopDefaultMode, CFRunLoop};
+#[cfg(target_os = "ios")]
+use coremidi::{Client, Destinations, ForegroundRestart, Sources};
+use std::process;
+#[cfg(target_os = "ios")]
+use std::time::Duration;
+
+#[cfg(not(target_os = "ios"))]
+fn main() {
+    eprintln!("This example only runs on iOS");
+    process::exit(1);
+}
+
+#[cfg(target_os = "ios")]
+fn main() {
+    // A new client can be created after dropping the first one, as there is always the one of the process
+    let client =
+        Client::new("ios-example-client").unwrap_or_else(|status| fail("Client::new", status));
+    drop(client);
+    let client =
+        Client::new("ios-example-client").unwrap_or_else(|status| fail("Client::new", status));
+    let _output_port = client
+        .output_port("ios-example-port")
+        .unwrap_or_else(|status| fail("Client::output_port", status));
+
+    println!("Destinations: {}", Destinations::count());
+    println!("Sources: {}", Sources::count());
+
+    coremidi::restart().unwrap_or_else(|status| fail("restart", status));
+    let _restart = ForegroundRestart::with_callback(|result| {
+        println!("Back to the foreground, MIDI restarted: {:?}", result)
+    });
+
+    // Give some time to move the application to the background and back
+    println!("=== Running for 10 seconds ===");
+    CFRunLoop::run_in_mode(
+        unsafe { kCFRunLoopDefaultMode },
+        Duration::from_secs(10),
+        false,
+    );
+    println!("OK");
+}
+
+#[cfg(target_os = "ios")]
+fn fail(what: &str, status: i32) -> ! {
+    eprintln!("{} failed with status {}", what, status);
+    process::exit(1)
+}
@@ -45,7 +45,7 @@ fn main() {
     println!(
         "  My own string property: {}",
         destination
-            .get_property_string("my-own-string-property")
+            .property::<String>("my-own-string-property")
             .unwrap()
     )
 }
@@ -1,4 +1,4 @@
-use coremidi::{Client, EventList, Protocol, Source, Sources};
+use coremidi::{Client, EventList, Protocol, Source, Sources, UniqueId};
 use std::env;
 
 fn main() {
@@ -12,7 +12,7 @@ fn main() {
 
     let client = Client::new("Example Client").unwrap();
 
-    let callback = |event_list: &EventList, context: &mut u32| {
+    let callback = |event_list: &EventList, context: &mut UniqueId| {
         print!("{:08x}: {:?}", *context, event_list);
     };
 
@@ -1,4 +1,4 @@
-use coremidi::{Client, Destination, Destinations, EventBuffer, Protocol};
+use coremidi::{Client, Destination, Destinations};
 use std::env;
 use std::thread;
 use std::time::Duration;
@@ -16,18 +16,13 @@ fn main() {
     let client = Client::new("Example Client").unwrap();
     let output_port = client.output_port("Example Port").unwrap();
 
-    let note_on = EventBuffer::new(Protocol::Midi10).with_packet(0, &[0x2090407f]);
-
-    let note_off = EventBuffer::new(Protocol::Midi10).with_packet(0, &[0x2080407f]);
-
     for i in 0..10 {
         println!("[{}] Sending note ...", i);
 
-        output_port.send(&destination, &note_on).unwrap();
-        thread::sleep(Duration::from_millis(1000));
-
-        output_port.send(&destination, &note_off).unwrap();
-        thread::sleep(Duration::from_millis(100));
+        output_port
+            .play_note(&destination, 0, 0x40, 0x7f, Duration::from_millis(1000))
+            .unwrap();
+        thread::sleep(Duration::from_millis(1100));
     }
 }
 
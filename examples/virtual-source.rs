@@ -21,11 +21,13 @@ fn main() {
 }
 
 fn create_note_on(channel: u8, note: u8, velocity: u8) -> PacketBuffer {
-    let data = &[0x90 | (channel & 0x0f), note & 0x7f, velocity & 0x7f];
-    PacketBuffer::new(0, data)
+    let mut buffer = PacketBuffer::with_capacity(0);
+    buffer.push_note_on(0, channel, note, velocity);
+    buffer
 }
 
 fn create_note_off(channel: u8, note: u8, velocity: u8) -> PacketBuffer {
-    let data = &[0x80 | (channel & 0x0f), note & 0x7f, velocity & 0x7f];
-    PacketBuffer::new(0, data)
+    let mut buffer = PacketBuffer::with_capacity(0);
+    buffer.push_note_off(0, channel, note, velocity);
+    buffer
 }
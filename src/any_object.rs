@@ -1,5 +1,6 @@
-use coremidi_sys::{MIDIObjectRef, MIDIObjectType};
+use coremidi_sys::{MIDIObjectFindByUniqueID, MIDIObjectRef, MIDIObjectType};
 
+use crate::object::UniqueId;
 use crate::{Destination, Device, Entity, Object, Source};
 
 #[derive(Debug, PartialEq)]
@@ -40,6 +41,20 @@ impl AnyObject {
             _ => None,
         }
     }
+
+    /// Find an object of any type by its unique id.
+    /// See [MIDIObjectFindByUniqueID](https://developer.apple.com/documentation/coremidi/1495191-midiobjectfindbyuniqueid).
+    ///
+    pub fn from_unique_id(unique_id: UniqueId) -> Option<Self> {
+        let mut object_ref: MIDIObjectRef = 0;
+        let mut object_type: MIDIObjectType = 0;
+        let status =
+            unsafe { MIDIObjectFindByUniqueID(unique_id, &mut object_ref, &mut object_type) };
+        if status != 0 {
+            return None;
+        }
+        Self::create(object_type, object_ref)
+    }
 }
 
 impl AsRef<Object> for AnyObject {
@@ -58,6 +73,106 @@ impl AsRef<Object> for AnyObject {
     }
 }
 
+/// The references to the objects are only valid within the process, so they are serialized as their unique id,
+/// and deserialized looking them up by it, which fails when the object is not present in the system anymore.
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::any_object::AnyObject;
+    use crate::object::UniqueId;
+    use crate::{Destination, Device, Endpoint, Entity, Object, Source};
+
+    fn serialize_object<S: Serializer>(object: &Object, serializer: S) -> Result<S::Ok, S::Error> {
+        object
+            .unique_id()
+            .ok_or_else(|| S::Error::custom("the MIDI object has no unique id"))?
+            .serialize(serializer)
+    }
+
+    fn deserialize_object<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<(UniqueId, AnyObject), D::Error> {
+        let unique_id = UniqueId::deserialize(deserializer)?;
+        let object = AnyObject::from_unique_id(unique_id).ok_or_else(|| {
+            D::Error::custom(format!(
+                "there is no MIDI object with unique id {}",
+                unique_id
+            ))
+        })?;
+        Ok((unique_id, object))
+    }
+
+    macro_rules! serde_by_unique_id {
+        ($type:ty, $kind:literal, |$any:ident| $from_any:expr) => {
+            impl Serialize for $type {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serialize_object(self.as_ref(), serializer)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $type {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let (unique_id, $any) = deserialize_object(deserializer)?;
+                    let object: Option<$type> = $from_any;
+                    object.ok_or_else(|| {
+                        D::Error::custom(format!(
+                            "the MIDI object with unique id {} is not {}",
+                            unique_id, $kind
+                        ))
+                    })
+                }
+            }
+        };
+    }
+
+    impl Serialize for Object {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_object(self, serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Object {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserialize_object(deserializer).map(|(_, object)| Object(object.as_ref().0))
+        }
+    }
+
+    serde_by_unique_id!(AnyObject, "an object", |object| Some(object));
+
+    serde_by_unique_id!(Device, "a device", |object| match object {
+        AnyObject::Device(device) | AnyObject::ExternalDevice(device) => Some(device),
+        _ => None,
+    });
+
+    serde_by_unique_id!(Entity, "an entity", |object| match object {
+        AnyObject::Entity(entity) | AnyObject::ExternalEntity(entity) => Some(entity),
+        _ => None,
+    });
+
+    serde_by_unique_id!(Source, "a source", |object| match object {
+        AnyObject::Source(source) | AnyObject::ExternalSource(source) => Some(source),
+        _ => None,
+    });
+
+    serde_by_unique_id!(Destination, "a destination", |object| match object {
+        AnyObject::Destination(destination) | AnyObject::ExternalDestination(destination) => {
+            Some(destination)
+        }
+        _ => None,
+    });
+
+    serde_by_unique_id!(Endpoint, "an endpoint", |object| match object {
+        AnyObject::Source(source) | AnyObject::ExternalSource(source) => Some(source.endpoint),
+        AnyObject::Destination(destination) | AnyObject::ExternalDestination(destination) => {
+            Some(destination.endpoint)
+        }
+        _ => None,
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use crate::any_object::AnyObject;
@@ -136,4 +251,29 @@ mod tests {
     fn any_object_from_error() {
         assert_eq!(AnyObject::create(0xffff_i32, 1), None);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_by_unique_id() {
+        use crate::{Client, Protocol};
+
+        let client = Client::new("Test Client").unwrap();
+        let destination = client
+            .virtual_destination_with_protocol("A", Protocol::Midi10, |_| ())
+            .unwrap();
+        let destination = Destination::new(destination.endpoint.object.0);
+        let unique_id = destination.unique_id().unwrap();
+
+        let json = serde_json::to_string(&destination).unwrap();
+        assert_eq!(json, unique_id.to_string());
+        assert_eq!(
+            serde_json::from_str::<Destination>(&json).unwrap(),
+            destination
+        );
+        assert_eq!(
+            serde_json::from_str::<AnyObject>(&json).unwrap(),
+            AnyObject::Destination(destination)
+        );
+        assert!(serde_json::from_str::<Source>(&json).is_err());
+    }
 }
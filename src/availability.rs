@@ -0,0 +1,349 @@
+//! The CoreMIDI functions introduced with MIDI 2.0 (in macOS 11, iOS 14 and Mac Catalyst 14).
+//!
+//! By default they are linked as any other function, so the binaries don't load in older systems.
+//! With the `runtime-linking` feature they are looked up the first time they are used instead, so they
+//! run in macOS 10.11 or later: the event lists are then built by this library when CoreMIDI can not,
+//! and the other functions fail with [UNSUPPORTED_STATUS](crate::UNSUPPORTED_STATUS).
+
+#![allow(non_snake_case)]
+
+use core_foundation::string::CFStringRef;
+
+#[cfg(not(feature = "runtime-linking"))]
+pub(crate) use crate::sys::MIDIDeviceNewEntity;
+#[cfg(not(feature = "runtime-linking"))]
+pub(crate) use coremidi_sys::{
+    MIDIDestinationCreateWithProtocol, MIDIEventListAdd, MIDIEventListInit,
+    MIDIInputPortCreateWithProtocol, MIDIReceivedEventList, MIDISendEventList,
+};
+#[cfg(feature = "runtime-linking")]
+pub(crate) use runtime::{
+    MIDIDestinationCreateWithProtocol, MIDIDeviceNewEntity, MIDIEventListAdd, MIDIEventListInit,
+    MIDIInputPortCreateWithProtocol, MIDIReceivedEventList, MIDISendEventList,
+};
+
+/// Whether the MIDI 2.0 functions of CoreMIDI are available in the running system,
+/// like sending and receiving event lists, or creating ports and endpoints with a protocol.
+///
+/// It is always true, unless the `runtime-linking` feature is enabled.
+///
+pub fn is_midi2_available() -> bool {
+    #[cfg(feature = "runtime-linking")]
+    return runtime::functions().is_some();
+    #[cfg(not(feature = "runtime-linking"))]
+    true
+}
+
+/// The key of the protocol property, when the system knows about it.
+pub(crate) fn property_protocol_id() -> Option<CFStringRef> {
+    #[cfg(feature = "runtime-linking")]
+    return runtime::functions().map(|functions| functions.property_protocol_id as CFStringRef);
+    #[cfg(not(feature = "runtime-linking"))]
+    Some(unsafe { coremidi_sys::kMIDIPropertyProtocolID })
+}
+
+#[cfg(feature = "runtime-linking")]
+mod runtime {
+    use core_foundation::{
+        base::OSStatus,
+        string::{__CFString, CFStringRef},
+    };
+    use coremidi_sys::{
+        ByteCount, ItemCount, MIDIClientRef, MIDIDeviceRef, MIDIEndpointRef, MIDIEntityRef,
+        MIDIEventList, MIDIEventPacket, MIDIEventPacketNext, MIDIPortRef, MIDIProtocolID,
+        MIDIReceiveBlock, MIDITimeStamp,
+    };
+    use once_cell::sync::Lazy;
+    use std::mem::size_of;
+    use std::os::raw::{c_char, c_void};
+    use std::ptr;
+
+    use crate::sys::{dlsym, RTLD_DEFAULT};
+    use crate::UNSUPPORTED_STATUS;
+
+    type EventListInit =
+        unsafe extern "C" fn(*mut MIDIEventList, MIDIProtocolID) -> *mut MIDIEventPacket;
+    type EventListAdd = unsafe extern "C" fn(
+        *mut MIDIEventList,
+        ByteCount,
+        *mut MIDIEventPacket,
+        MIDITimeStamp,
+        ByteCount,
+        *const u32,
+    ) -> *mut MIDIEventPacket;
+    type SendEventList =
+        unsafe extern "C" fn(MIDIPortRef, MIDIEndpointRef, *const MIDIEventList) -> OSStatus;
+    type ReceivedEventList =
+        unsafe extern "C" fn(MIDIEndpointRef, *const MIDIEventList) -> OSStatus;
+    type CreateWithProtocol<T> = unsafe extern "C" fn(
+        MIDIClientRef,
+        CFStringRef,
+        MIDIProtocolID,
+        *mut T,
+        MIDIReceiveBlock,
+    ) -> OSStatus;
+    type DeviceNewEntity = unsafe extern "C" fn(
+        MIDIDeviceRef,
+        CFStringRef,
+        MIDIProtocolID,
+        u8,
+        ItemCount,
+        ItemCount,
+        *mut MIDIEntityRef,
+    ) -> OSStatus;
+
+    pub(super) struct Functions {
+        event_list_init: EventListInit,
+        event_list_add: EventListAdd,
+        send_event_list: SendEventList,
+        received_event_list: ReceivedEventList,
+        input_port_create_with_protocol: CreateWithProtocol<MIDIPortRef>,
+        destination_create_with_protocol: CreateWithProtocol<MIDIEndpointRef>,
+        device_new_entity: DeviceNewEntity,
+        // A constant CFString, kept as an address to share it between threads
+        pub(super) property_protocol_id: usize,
+    }
+
+    unsafe fn symbol(name: &[u8]) -> Option<*mut c_void> {
+        let symbol = dlsym(RTLD_DEFAULT, name.as_ptr() as *const c_char);
+        Some(symbol).filter(|symbol| !symbol.is_null())
+    }
+
+    unsafe fn function<F: Copy>(name: &[u8]) -> Option<F> {
+        let symbol = symbol(name)?;
+        debug_assert_eq!(size_of::<F>(), size_of::<*mut c_void>());
+        Some(std::mem::transmute_copy::<*mut c_void, F>(&symbol))
+    }
+
+    // Either all of them are available, or none
+    static FUNCTIONS: Lazy<Option<Functions>> = Lazy::new(|| unsafe {
+        let property_protocol_id =
+            symbol(b"kMIDIPropertyProtocolID\0")? as *const *const __CFString;
+        Some(Functions {
+            event_list_init: function(b"MIDIEventListInit\0")?,
+            event_list_add: function(b"MIDIEventListAdd\0")?,
+            send_event_list: function(b"MIDISendEventList\0")?,
+            received_event_list: function(b"MIDIReceivedEventList\0")?,
+            input_port_create_with_protocol: function(b"MIDIInputPortCreateWithProtocol\0")?,
+            destination_create_with_protocol: function(b"MIDIDestinationCreateWithProtocol\0")?,
+            device_new_entity: function(b"MIDIDeviceNewEntity\0")?,
+            property_protocol_id: *property_protocol_id as usize,
+        })
+    });
+
+    pub(super) fn functions() -> Option<&'static Functions> {
+        FUNCTIONS.as_ref()
+    }
+
+    pub(crate) unsafe fn MIDIEventListInit(
+        evtlist: *mut MIDIEventList,
+        protocol: MIDIProtocolID,
+    ) -> *mut MIDIEventPacket {
+        match functions() {
+            Some(functions) => (functions.event_list_init)(evtlist, protocol),
+            None => event_list_init(evtlist, protocol),
+        }
+    }
+
+    pub(crate) unsafe fn MIDIEventListAdd(
+        evtlist: *mut MIDIEventList,
+        listSize: ByteCount,
+        curPacket: *mut MIDIEventPacket,
+        time: MIDITimeStamp,
+        wordCount: ByteCount,
+        words: *const u32,
+    ) -> *mut MIDIEventPacket {
+        match functions() {
+            Some(functions) => {
+                (functions.event_list_add)(evtlist, listSize, curPacket, time, wordCount, words)
+            }
+            None => event_list_add(evtlist, listSize, curPacket, time, wordCount, words),
+        }
+    }
+
+    pub(crate) unsafe fn MIDISendEventList(
+        port: MIDIPortRef,
+        dest: MIDIEndpointRef,
+        evtlist: *const MIDIEventList,
+    ) -> OSStatus {
+        functions().map_or(UNSUPPORTED_STATUS, |functions| {
+            (functions.send_event_list)(port, dest, evtlist)
+        })
+    }
+
+    pub(crate) unsafe fn MIDIReceivedEventList(
+        src: MIDIEndpointRef,
+        evtlist: *const MIDIEventList,
+    ) -> OSStatus {
+        functions().map_or(UNSUPPORTED_STATUS, |functions| {
+            (functions.received_event_list)(src, evtlist)
+        })
+    }
+
+    pub(crate) unsafe fn MIDIInputPortCreateWithProtocol(
+        client: MIDIClientRef,
+        portName: CFStringRef,
+        protocol: MIDIProtocolID,
+        outPort: *mut MIDIPortRef,
+        receiveBlock: MIDIReceiveBlock,
+    ) -> OSStatus {
+        functions().map_or(UNSUPPORTED_STATUS, |functions| {
+            (functions.input_port_create_with_protocol)(
+                client,
+                portName,
+                protocol,
+                outPort,
+                receiveBlock,
+            )
+        })
+    }
+
+    pub(crate) unsafe fn MIDIDestinationCreateWithProtocol(
+        client: MIDIClientRef,
+        name: CFStringRef,
+        protocol: MIDIProtocolID,
+        outDest: *mut MIDIEndpointRef,
+        readBlock: MIDIReceiveBlock,
+    ) -> OSStatus {
+        functions().map_or(UNSUPPORTED_STATUS, |functions| {
+            (functions.destination_create_with_protocol)(client, name, protocol, outDest, readBlock)
+        })
+    }
+
+    pub(crate) unsafe fn MIDIDeviceNewEntity(
+        device: MIDIDeviceRef,
+        name: CFStringRef,
+        protocol: MIDIProtocolID,
+        embedded: u8,
+        numSourceEndpoints: ItemCount,
+        numDestinationEndpoints: ItemCount,
+        newEntity: *mut MIDIEntityRef,
+    ) -> OSStatus {
+        functions().map_or(UNSUPPORTED_STATUS, |functions| {
+            (functions.device_new_entity)(
+                device,
+                name,
+                protocol,
+                embedded,
+                numSourceEndpoints,
+                numDestinationEndpoints,
+                newEntity,
+            )
+        })
+    }
+
+    // The largest number of words declared for a packet
+    const MAX_PACKET_WORDS: usize = 64;
+
+    /// The same as `MIDIEventListInit`, for the systems without it.
+    pub(super) unsafe fn event_list_init(
+        list: *mut MIDIEventList,
+        protocol: MIDIProtocolID,
+    ) -> *mut MIDIEventPacket {
+        (*list).protocol = protocol;
+        (*list).numPackets = 0;
+        ptr::addr_of_mut!((*list).packet) as *mut MIDIEventPacket
+    }
+
+    /// The same as `MIDIEventListAdd`, for the systems without it: the words are added to the current packet
+    /// when it has the same timestamp, or otherwise to a new one, as long as there is room for them in the list.
+    pub(super) unsafe fn event_list_add(
+        list: *mut MIDIEventList,
+        list_size: ByteCount,
+        current: *mut MIDIEventPacket,
+        time: MIDITimeStamp,
+        word_count: ByteCount,
+        words: *const u32,
+    ) -> *mut MIDIEventPacket {
+        let list_end = list as usize + list_size as usize;
+        let fits = |packet: *mut MIDIEventPacket, count: usize| {
+            count <= MAX_PACKET_WORDS
+                && ptr::addr_of!((*packet).words) as usize + count * size_of::<u32>() <= list_end
+        };
+        let num_packets = (*list).numPackets;
+        let current_words = (*current).wordCount as usize;
+        if num_packets > 0
+            && (*current).timeStamp == time
+            && fits(current, current_words + word_count as usize)
+        {
+            let destination = (ptr::addr_of_mut!((*current).words) as *mut u32).add(current_words);
+            ptr::copy_nonoverlapping(words, destination, word_count as usize);
+            (*current).wordCount += word_count as u32;
+            return current;
+        }
+        let packet = if num_packets == 0 {
+            current
+        } else {
+            MIDIEventPacketNext(current) as *mut MIDIEventPacket
+        };
+        if !fits(packet, word_count as usize) {
+            return ptr::null_mut();
+        }
+        (*packet).timeStamp = time;
+        (*packet).wordCount = word_count as u32;
+        let destination = ptr::addr_of_mut!((*packet).words) as *mut u32;
+        ptr::copy_nonoverlapping(words, destination, word_count as usize);
+        (*list).numPackets = num_packets + 1;
+        packet
+    }
+}
+
+#[cfg(all(test, feature = "runtime-linking"))]
+mod tests {
+    use coremidi_sys::{ByteCount, MIDIEventList};
+    use std::mem::size_of;
+
+    use crate::availability::runtime::{event_list_add, event_list_init};
+
+    #[test]
+    fn event_list_fallback() {
+        // Room for the header of the list and two packets of one word
+        let size = 8 + 2 * (12 + 4);
+        let mut storage = vec![0u32; size / size_of::<u32>()];
+        let list = storage.as_mut_ptr() as *mut MIDIEventList;
+        unsafe {
+            let first = event_list_init(list, 1);
+            let packet =
+                event_list_add(list, size as ByteCount, first, 10, 1, [0x20904060].as_ptr());
+            assert_eq!(packet, first);
+            let next = event_list_add(
+                list,
+                size as ByteCount,
+                packet,
+                20,
+                1,
+                [0x20804060].as_ptr(),
+            );
+            assert_ne!(next, packet);
+            assert!(
+                event_list_add(list, size as ByteCount, next, 30, 1, [0x20904060].as_ptr())
+                    .is_null()
+            );
+            // No room to append to the current packet either
+            assert!(
+                event_list_add(list, size as ByteCount, next, 20, 1, [0x20904060].as_ptr())
+                    .is_null()
+            );
+
+            assert_eq!((*list).numPackets, 2);
+            assert_eq!(&storage[2..], &[10, 0, 1, 0x20904060, 20, 0, 1, 0x20804060]);
+        }
+    }
+
+    #[test]
+    fn event_list_fallback_same_timestamp() {
+        let size = 1024;
+        let mut storage = vec![0u32; size / size_of::<u32>()];
+        let list = storage.as_mut_ptr() as *mut MIDIEventList;
+        unsafe {
+            let first = event_list_init(list, 2);
+            let words = [0x40904000, 0xffff0000];
+            let packet = event_list_add(list, size as ByteCount, first, 10, 2, words.as_ptr());
+            let packet = event_list_add(list, size as ByteCount, packet, 10, 2, words.as_ptr());
+
+            assert_eq!(packet, first);
+            assert_eq!((*list).numPackets, 1);
+            assert_eq!((*packet).wordCount, 4);
+        }
+    }
+}
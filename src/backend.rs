@@ -0,0 +1,566 @@
+use core_foundation::base::OSStatus;
+use coremidi_sys::kMIDIObjectNotFound;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{
+    AnyObject, Client, Destination, Destinations, InputPort, Notification, Object, OutputPort,
+    PacketBuffer, PacketList, Source, Sources, Timestamp, UniqueId,
+};
+
+pub(crate) mod private {
+    pub trait Sealed {}
+}
+
+/// A source or destination endpoint, as seen through a [Backend].
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BackendEndpoint {
+    pub unique_id: UniqueId,
+    pub name: String,
+}
+
+/// A change of the endpoints available through a [Backend], like a device being plugged or unplugged.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BackendNotification {
+    SourceAdded(BackendEndpoint),
+    SourceRemoved(BackendEndpoint),
+    DestinationAdded(BackendEndpoint),
+    DestinationRemoved(BackendEndpoint),
+}
+
+type NotifySlot = Arc<Mutex<Option<Box<dyn FnMut(&BackendNotification) + Send>>>>;
+
+fn dispatch_notification(slot: &NotifySlot, notification: &BackendNotification) {
+    let mut callback = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(callback) = callback.as_mut() {
+        callback(notification);
+    }
+}
+
+fn set_notify(slot: &NotifySlot, callback: Box<dyn FnMut(&BackendNotification) + Send>) {
+    *slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(callback);
+}
+
+/// The MIDI operations that an application needs to find endpoints, and to send and receive messages,
+/// implemented by CoreMIDI ([CoreMidiBackend]) and by a pure Rust mock ([MockBackend]).
+///
+/// Writing the MIDI logic of an application against this trait allows to test it with the mock backend,
+/// on machines without a MIDI server (like the CI ones). The trait is sealed, so it can't be implemented
+/// outside of this crate.
+///
+/// It is a facade next to the rest of the crate, not a layer below it: [Client], the ports and the endpoints
+/// keep using CoreMIDI directly, and only the code written against this trait can switch backends.
+///
+/// ```
+/// use coremidi::{Backend, MockBackend, PacketBuffer};
+///
+/// // The logic to test: echo everything from a source into a destination
+/// fn echo<B: Backend>(backend: &B, name: &str) -> Result<B::Connection, i32> {
+///     let source = backend.sources().into_iter().find(|source| source.name == name).unwrap();
+///     let destination = backend.destinations().into_iter().find(|dest| dest.name == name).unwrap();
+///     let sender = backend.clone();
+///     backend.connect_source(&source, move |packet_list| {
+///         sender.send(&destination, packet_list).unwrap();
+///     })
+/// }
+///
+/// let backend = MockBackend::new();
+/// let source = backend.add_source("synth");
+/// let destination = backend.add_destination("synth");
+/// let _connection = echo(&backend, "synth").unwrap();
+/// backend.received(&source, &PacketBuffer::new(10, &[0x90, 0x40, 0x7f])).unwrap();
+/// assert_eq!(backend.sent(&destination), vec![(10, vec![0x90, 0x40, 0x7f])]);
+/// ```
+///
+pub trait Backend: private::Sealed + Clone + Send + 'static {
+    /// Keeps receiving the messages of a source until dropped.
+    type Connection;
+
+    fn sources(&self) -> Vec<BackendEndpoint>;
+
+    fn destinations(&self) -> Vec<BackendEndpoint>;
+
+    /// Send a list of packets to a destination.
+    ///
+    fn send(&self, destination: &BackendEndpoint, packets: &PacketList) -> Result<(), OSStatus>;
+
+    /// Call `callback` with every list of packets received from a source, until the connection is dropped.
+    ///
+    fn connect_source<F>(
+        &self,
+        source: &BackendEndpoint,
+        callback: F,
+    ) -> Result<Self::Connection, OSStatus>
+    where
+        F: FnMut(&PacketList) + Send + 'static;
+
+    /// Call `callback` with every change of the endpoints available, replacing the previous callback.
+    ///
+    fn set_notify_callback<F>(&self, callback: F)
+    where
+        F: FnMut(&BackendNotification) + Send + 'static;
+}
+
+/// The [Backend] using CoreMIDI, through a client and an output port of its own.
+///
+/// The notifications are delivered by the run loop that was current when the backend was created
+/// (see [Client::new_with_notifications]). The endpoints removed are only notified when CoreMIDI can
+/// still tell their unique ID.
+///
+#[derive(Clone)]
+pub struct CoreMidiBackend {
+    inner: Arc<CoreMidiInner>,
+}
+
+struct CoreMidiInner {
+    client: Client,
+    output_port: OutputPort,
+    notify: NotifySlot,
+}
+
+impl CoreMidiBackend {
+    pub fn new(name: &str) -> Result<Self, OSStatus> {
+        let notify = NotifySlot::default();
+        let client_notify = notify.clone();
+        let client = Client::new_with_notifications(name, move |notification: &Notification| {
+            if let Some(notification) = backend_notification(notification) {
+                dispatch_notification(&client_notify, &notification);
+            }
+        })?;
+        let output_port = client.output_port(name)?;
+        Ok(Self {
+            inner: Arc::new(CoreMidiInner {
+                client,
+                output_port,
+                notify,
+            }),
+        })
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.inner.client
+    }
+}
+
+fn backend_endpoint(object: &Object) -> Option<BackendEndpoint> {
+    Some(BackendEndpoint {
+        unique_id: object.unique_id()?,
+        name: object.name().unwrap_or_default(),
+    })
+}
+
+fn backend_notification(notification: &Notification) -> Option<BackendNotification> {
+    match notification {
+        Notification::ObjectAdded(info) => match &info.child {
+            AnyObject::Source(source) => {
+                backend_endpoint(source).map(BackendNotification::SourceAdded)
+            }
+            AnyObject::Destination(destination) => {
+                backend_endpoint(destination).map(BackendNotification::DestinationAdded)
+            }
+            _ => None,
+        },
+        Notification::ObjectRemoved(info) => match &info.child {
+            AnyObject::Source(source) => {
+                backend_endpoint(source).map(BackendNotification::SourceRemoved)
+            }
+            AnyObject::Destination(destination) => {
+                backend_endpoint(destination).map(BackendNotification::DestinationRemoved)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl private::Sealed for CoreMidiBackend {}
+
+impl Backend for CoreMidiBackend {
+    type Connection = InputPort;
+
+    fn sources(&self) -> Vec<BackendEndpoint> {
+        Sources
+            .into_iter()
+            .filter_map(|source| backend_endpoint(&source))
+            .collect()
+    }
+
+    fn destinations(&self) -> Vec<BackendEndpoint> {
+        Destinations
+            .into_iter()
+            .filter_map(|destination| backend_endpoint(&destination))
+            .collect()
+    }
+
+    fn send(&self, destination: &BackendEndpoint, packets: &PacketList) -> Result<(), OSStatus> {
+        let destination =
+            Destination::from_unique_id(destination.unique_id).ok_or(kMIDIObjectNotFound)?;
+        self.inner.output_port.send(&destination, packets)
+    }
+
+    fn connect_source<F>(
+        &self,
+        source: &BackendEndpoint,
+        callback: F,
+    ) -> Result<Self::Connection, OSStatus>
+    where
+        F: FnMut(&PacketList) + Send + 'static,
+    {
+        let endpoint = Source::from_unique_id(source.unique_id).ok_or(kMIDIObjectNotFound)?;
+        let input_port = self.inner.client.input_port(&source.name, callback)?;
+        input_port.connect_source(&endpoint)?;
+        Ok(input_port)
+    }
+
+    fn set_notify_callback<F>(&self, callback: F)
+    where
+        F: FnMut(&BackendNotification) + Send + 'static,
+    {
+        set_notify(&self.inner.notify, Box::new(callback));
+    }
+}
+
+type MockCallback = Box<dyn FnMut(&PacketList) + Send>;
+// The callbacks are taken out while delivering the packets received
+type MockConnections = Vec<(u64, Option<MockCallback>)>;
+type SentPackets = Vec<(Timestamp, Vec<u8>)>;
+
+#[derive(Default)]
+struct MockState {
+    next_unique_id: UniqueId,
+    next_connection: u64,
+    sources: Vec<(BackendEndpoint, MockConnections)>,
+    destinations: Vec<(BackendEndpoint, SentPackets)>,
+}
+
+impl MockState {
+    fn new_endpoint(&mut self, name: &str) -> BackendEndpoint {
+        self.next_unique_id += 1;
+        BackendEndpoint {
+            unique_id: self.next_unique_id,
+            name: name.to_string(),
+        }
+    }
+}
+
+/// A [Backend] living only in memory, to test the MIDI logic of an application without CoreMIDI.
+///
+/// The sources and destinations are added by the test, which plays the role of the devices:
+/// the messages "received" from a source are delivered synchronously to its connections,
+/// and the ones sent to a destination are kept to be checked later.
+/// The clones of a mock backend share the same endpoints.
+///
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    state: Arc<Mutex<MockState>>,
+    notify: NotifySlot,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_source(&self, name: &str) -> BackendEndpoint {
+        let source = {
+            let mut state = self.lock();
+            let source = state.new_endpoint(name);
+            state.sources.push((source.clone(), Vec::new()));
+            source
+        };
+        dispatch_notification(
+            &self.notify,
+            &BackendNotification::SourceAdded(source.clone()),
+        );
+        source
+    }
+
+    pub fn add_destination(&self, name: &str) -> BackendEndpoint {
+        let destination = {
+            let mut state = self.lock();
+            let destination = state.new_endpoint(name);
+            state.destinations.push((destination.clone(), Vec::new()));
+            destination
+        };
+        dispatch_notification(
+            &self.notify,
+            &BackendNotification::DestinationAdded(destination.clone()),
+        );
+        destination
+    }
+
+    /// Remove a source, as if its device was unplugged. Its connections stop receiving.
+    ///
+    pub fn remove_source(&self, source: &BackendEndpoint) {
+        let removed = {
+            let mut state = self.lock();
+            let count = state.sources.len();
+            state.sources.retain(|(endpoint, _)| endpoint != source);
+            state.sources.len() < count
+        };
+        if removed {
+            dispatch_notification(
+                &self.notify,
+                &BackendNotification::SourceRemoved(source.clone()),
+            );
+        }
+    }
+
+    /// Remove a destination, as if its device was unplugged.
+    ///
+    pub fn remove_destination(&self, destination: &BackendEndpoint) {
+        let removed = {
+            let mut state = self.lock();
+            let count = state.destinations.len();
+            state
+                .destinations
+                .retain(|(endpoint, _)| endpoint != destination);
+            state.destinations.len() < count
+        };
+        if removed {
+            dispatch_notification(
+                &self.notify,
+                &BackendNotification::DestinationRemoved(destination.clone()),
+            );
+        }
+    }
+
+    /// Deliver a list of packets from a source to all its connections, before returning.
+    ///
+    pub fn received(&self, source: &BackendEndpoint, packets: &PacketList) -> Result<(), OSStatus> {
+        // The callbacks are called without the lock, so they can use the backend
+        let mut callbacks: Vec<(u64, MockCallback)> = {
+            let mut state = self.lock();
+            let (_, connections) = state
+                .sources
+                .iter_mut()
+                .find(|(endpoint, _)| endpoint == source)
+                .ok_or(kMIDIObjectNotFound)?;
+            connections
+                .iter_mut()
+                .filter_map(|(id, callback)| Some((*id, callback.take()?)))
+                .collect()
+        };
+        for (_, callback) in callbacks.iter_mut() {
+            callback(packets);
+        }
+        let mut state = self.lock();
+        if let Some((_, connections)) = state
+            .sources
+            .iter_mut()
+            .find(|(endpoint, _)| endpoint == source)
+        {
+            // Only the connections that were not dropped meanwhile get their callback back
+            for (id, callback) in callbacks {
+                if let Some((_, slot)) = connections.iter_mut().find(|(other, _)| *other == id) {
+                    *slot = Some(callback);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Deliver a single packet from a source to all its connections, before returning.
+    ///
+    pub fn received_data(
+        &self,
+        source: &BackendEndpoint,
+        timestamp: Timestamp,
+        data: &[u8],
+    ) -> Result<(), OSStatus> {
+        self.received(source, &PacketBuffer::new(timestamp, data))
+    }
+
+    /// The timestamps and data of all the packets sent to a destination so far.
+    ///
+    pub fn sent(&self, destination: &BackendEndpoint) -> SentPackets {
+        self.lock()
+            .destinations
+            .iter()
+            .find(|(endpoint, _)| endpoint == destination)
+            .map(|(_, sent)| sent.clone())
+            .unwrap_or_default()
+    }
+
+    /// Take the packets sent to a destination so far, so the next call only returns the new ones.
+    ///
+    pub fn take_sent(&self, destination: &BackendEndpoint) -> SentPackets {
+        self.lock()
+            .destinations
+            .iter_mut()
+            .find(|(endpoint, _)| endpoint == destination)
+            .map(|(_, sent)| std::mem::take(sent))
+            .unwrap_or_default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl private::Sealed for MockBackend {}
+
+impl Backend for MockBackend {
+    type Connection = MockConnection;
+
+    fn sources(&self) -> Vec<BackendEndpoint> {
+        self.lock()
+            .sources
+            .iter()
+            .map(|(endpoint, _)| endpoint.clone())
+            .collect()
+    }
+
+    fn destinations(&self) -> Vec<BackendEndpoint> {
+        self.lock()
+            .destinations
+            .iter()
+            .map(|(endpoint, _)| endpoint.clone())
+            .collect()
+    }
+
+    fn send(&self, destination: &BackendEndpoint, packets: &PacketList) -> Result<(), OSStatus> {
+        let mut state = self.lock();
+        let (_, sent) = state
+            .destinations
+            .iter_mut()
+            .find(|(endpoint, _)| endpoint == destination)
+            .ok_or(kMIDIObjectNotFound)?;
+        sent.extend(
+            packets
+                .iter()
+                .map(|packet| (packet.timestamp(), packet.data().to_vec())),
+        );
+        Ok(())
+    }
+
+    fn connect_source<F>(
+        &self,
+        source: &BackendEndpoint,
+        callback: F,
+    ) -> Result<Self::Connection, OSStatus>
+    where
+        F: FnMut(&PacketList) + Send + 'static,
+    {
+        let mut state = self.lock();
+        state.next_connection += 1;
+        let id = state.next_connection;
+        let (_, callbacks) = state
+            .sources
+            .iter_mut()
+            .find(|(endpoint, _)| endpoint == source)
+            .ok_or(kMIDIObjectNotFound)?;
+        callbacks.push((id, Some(Box::new(callback))));
+        Ok(MockConnection {
+            backend: self.clone(),
+            id,
+        })
+    }
+
+    fn set_notify_callback<F>(&self, callback: F)
+    where
+        F: FnMut(&BackendNotification) + Send + 'static,
+    {
+        set_notify(&self.notify, Box::new(callback));
+    }
+}
+
+/// The connection to a source of a [MockBackend], which stops receiving when dropped.
+///
+pub struct MockConnection {
+    backend: MockBackend,
+    id: u64,
+}
+
+impl Drop for MockConnection {
+    fn drop(&mut self) {
+        for (_, callbacks) in self.backend.lock().sources.iter_mut() {
+            callbacks.retain(|(id, _)| *id != self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::backend::{Backend, MockBackend};
+
+    #[test]
+    fn mock_connections() {
+        let backend = MockBackend::new();
+        let source = backend.add_source("keyboard");
+        let destination = backend.add_destination("synth");
+        assert_eq!(backend.sources(), vec![source.clone()]);
+        assert_eq!(backend.destinations(), vec![destination.clone()]);
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let connection_received = received.clone();
+        let connection = backend
+            .connect_source(&source, move |packet_list| {
+                for packet in packet_list.iter() {
+                    connection_received
+                        .lock()
+                        .unwrap()
+                        .push(packet.data().to_vec());
+                }
+            })
+            .unwrap();
+        backend
+            .received_data(&source, 0, &[0x90, 0x40, 0x7f])
+            .unwrap();
+        drop(connection);
+        backend
+            .received_data(&source, 0, &[0x80, 0x40, 0x00])
+            .unwrap();
+        assert_eq!(*received.lock().unwrap(), vec![vec![0x90, 0x40, 0x7f]]);
+
+        backend.remove_source(&source);
+        assert!(backend.received_data(&source, 0, &[0xf8]).is_err());
+        assert!(backend.connect_source(&source, |_| {}).is_err());
+    }
+
+    #[test]
+    fn mock_connection_dropped_while_receiving() {
+        let backend = MockBackend::new();
+        let source = backend.add_source("keyboard");
+        let received = Arc::new(Mutex::new(0));
+        let connection = Arc::new(Mutex::new(None));
+
+        let callback_received = received.clone();
+        let callback_connection = connection.clone();
+        *connection.lock().unwrap() = Some(
+            backend
+                .connect_source(&source, move |_| {
+                    *callback_received.lock().unwrap() += 1;
+                    callback_connection.lock().unwrap().take();
+                })
+                .unwrap(),
+        );
+        backend.received_data(&source, 0, &[0xf8]).unwrap();
+        backend.received_data(&source, 0, &[0xf8]).unwrap();
+
+        assert_eq!(*received.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn mock_sent_packets() {
+        let backend = MockBackend::new();
+        let destination = backend.add_destination("synth");
+        let mut packets = crate::PacketBuffer::new(1, &[0x90, 0x40, 0x7f]);
+        packets.push_data(2, &[0x80, 0x40, 0x00]);
+        backend.send(&destination, &packets).unwrap();
+
+        assert_eq!(
+            backend.take_sent(&destination),
+            vec![(1, vec![0x90, 0x40, 0x7f]), (2, vec![0x80, 0x40, 0x00])]
+        );
+        assert!(backend.sent(&destination).is_empty());
+
+        backend.remove_destination(&destination);
+        assert!(backend.send(&destination, &packets).is_err());
+    }
+}
@@ -0,0 +1,909 @@
+//! BLE MIDI with CoreBluetooth, for the discovery and pairing that CoreMIDI leaves to the UI of the system
+//! (like `CABTMIDICentralViewController` and `CABTMIDILocalPeripheralViewController`).
+
+use core_foundation::string::CFString;
+use once_cell::sync::Lazy;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::objc::{
+    class, ns_string, selector, send, send1, send2, send3, send4, string_from, with_pool,
+    ObjcObject,
+};
+use crate::sys::{
+    class_addIvar, class_addMethod, dispatch_queue_create, dispatch_release, dispatch_sync_f,
+    objc_allocateClassPair, objc_getClass, objc_registerClassPair, object_getInstanceVariable,
+    object_setInstanceVariable, DispatchQueue, ObjcId, ObjcSel,
+};
+use crate::{duration_from_host_time, host_time_now};
+
+/// The UUID of the BLE MIDI service.
+///
+pub const BLE_MIDI_SERVICE_UUID: &str = "03B80E5A-EDE8-4B33-A751-6CE34EC4C700";
+
+/// The UUID of the characteristic of the BLE MIDI service, that carries the MIDI data.
+///
+pub const BLE_MIDI_CHARACTERISTIC_UUID: &str = "7772E5DB-3868-4112-A1A9-F2669D106BF3";
+
+/// The size of the BLE MIDI packets that fit in the default ATT MTU (of 23 bytes).
+///
+pub const BLE_MIDI_PACKET_SIZE: usize = 20;
+
+/// Encode MIDI data into BLE MIDI packets of up to `max_size` bytes, with a timestamp in milliseconds
+/// (of which only the lower 13 bits are sent).
+///
+/// Every message is kept within a packet, except for the SysEx messages, which continue in the next ones.
+///
+/// ```
+/// use coremidi::ble_midi_packets;
+/// assert_eq!(
+///     ble_midi_packets(&[0x90, 0x40, 0x7f], 0x0085, 20),
+///     vec![vec![0x81, 0x85, 0x90, 0x40, 0x7f]]
+/// );
+/// ```
+///
+pub fn ble_midi_packets(midi: &[u8], timestamp: u16, max_size: usize) -> Vec<Vec<u8>> {
+    // Enough for the header, a timestamp and a message of three bytes
+    let max_size = max_size.max(5);
+    let header = 0x80 | ((timestamp >> 7) & 0x3f) as u8;
+    let timestamp = 0x80 | (timestamp & 0x7f) as u8;
+    let mut packets = Vec::new();
+    let mut packet = vec![header];
+    let mut rest = midi;
+    while !rest.is_empty() {
+        // A status byte with its data bytes, or the data bytes continuing a SysEx message
+        let length = 1 + rest[1..].iter().take_while(|byte| **byte < 0x80).count();
+        let (message, next) = rest.split_at(length);
+        rest = next;
+        if message[0] < 0x80 || message[0] == 0xf0 {
+            let mut data = message;
+            if message[0] == 0xf0 {
+                if packet.len() + 2 > max_size {
+                    packets.push(std::mem::replace(&mut packet, vec![header]));
+                }
+                packet.extend_from_slice(&[timestamp, 0xf0]);
+                data = &message[1..];
+            }
+            for byte in data {
+                if packet.len() == max_size {
+                    packets.push(std::mem::replace(&mut packet, vec![header]));
+                }
+                packet.push(*byte);
+            }
+        } else {
+            if packet.len() + 1 + message.len() > max_size && packet.len() > 1 {
+                packets.push(std::mem::replace(&mut packet, vec![header]));
+            }
+            packet.push(timestamp);
+            packet.extend_from_slice(message);
+        }
+    }
+    if packet.len() > 1 {
+        packets.push(packet);
+    }
+    packets
+}
+
+/// The MIDI data carried by a BLE MIDI packet, without the timestamps.
+/// The messages sent with running status are kept that way.
+///
+pub fn ble_midi_data(packet: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(packet.len());
+    let body = match packet {
+        [header, body @ ..] if header & 0x80 != 0 => body,
+        _ => return data,
+    };
+    let mut bytes = body.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        if byte < 0x80 {
+            data.push(byte);
+        } else if let Some(status) = bytes.next_if(|next| *next >= 0x80) {
+            // The byte was the timestamp of this status
+            data.push(status);
+        }
+    }
+    data
+}
+
+/// The state of the Bluetooth radio, as seen by a [BluetoothCentral] or a [BluetoothPeripheral].
+/// See [CBManagerState](https://developer.apple.com/documentation/corebluetooth/cbmanagerstate).
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BluetoothState {
+    Unknown,
+    Resetting,
+    Unsupported,
+    /// The application is not allowed to use Bluetooth (see `NSBluetoothAlwaysUsageDescription`).
+    Unauthorized,
+    PoweredOff,
+    PoweredOn,
+}
+
+impl BluetoothState {
+    fn from_code(code: isize) -> Self {
+        match code {
+            1 => BluetoothState::Resetting,
+            2 => BluetoothState::Unsupported,
+            3 => BluetoothState::Unauthorized,
+            4 => BluetoothState::PoweredOff,
+            5 => BluetoothState::PoweredOn,
+            _ => BluetoothState::Unknown,
+        }
+    }
+}
+
+// The instance variable of the delegates pointing to their context
+const CONTEXT_IVAR: &[u8] = b"coremidiContext\0";
+const CONTEXT_IVAR_TYPE: &[u8] = b"^v\0";
+
+const QUEUE_LABEL: &[u8] = b"coremidi.bluetooth\0";
+
+type MethodImplementation = (ObjcSel, *const c_void, &'static [u8]);
+
+/// Define a delegate class, with a variable for its context, and the implementation of its methods.
+unsafe fn define_delegate(name: &'static [u8], methods: &[MethodImplementation]) -> usize {
+    let name = name.as_ptr() as *const c_char;
+    let class = objc_allocateClassPair(class!("NSObject"), name, 0);
+    if class.is_null() {
+        // Already defined by another copy of this library
+        return objc_getClass(name) as usize;
+    }
+    let pointer_size = std::mem::size_of::<*mut c_void>();
+    class_addIvar(
+        class,
+        CONTEXT_IVAR.as_ptr() as *const c_char,
+        pointer_size,
+        pointer_size.trailing_zeros() as u8,
+        CONTEXT_IVAR_TYPE.as_ptr() as *const c_char,
+    );
+    for (selector, implementation, types) in methods {
+        class_addMethod(
+            class,
+            *selector,
+            *implementation,
+            types.as_ptr() as *const c_char,
+        );
+    }
+    objc_registerClassPair(class);
+    class as usize
+}
+
+unsafe fn delegate_context<'a, T>(delegate: ObjcId) -> &'a T {
+    let mut context = ptr::null_mut();
+    object_getInstanceVariable(
+        delegate,
+        CONTEXT_IVAR.as_ptr() as *const c_char,
+        &mut context,
+    );
+    &*(context as *const T)
+}
+
+extern "C" fn wait_for_queue(_context: *mut c_void) {}
+
+/// A CoreBluetooth manager with its delegate, calling back from a queue of its own.
+struct Manager<T> {
+    manager: ObjcObject,
+    _delegate: ObjcObject,
+    queue: DispatchQueue,
+    context: *mut T,
+}
+
+impl<T> Manager<T> {
+    unsafe fn new(manager_class: ObjcId, delegate_class: usize, context: T) -> Self {
+        let queue = dispatch_queue_create(QUEUE_LABEL.as_ptr() as *const c_char, ptr::null_mut());
+        let context = Box::into_raw(Box::new(context));
+        let delegate: ObjcId = send(
+            send(delegate_class as ObjcId, selector!("alloc")),
+            selector!("init"),
+        );
+        object_setInstanceVariable(
+            delegate,
+            CONTEXT_IVAR.as_ptr() as *const c_char,
+            context as *mut c_void,
+        );
+        let manager: ObjcId = send2(
+            send(manager_class, selector!("alloc")),
+            selector!("initWithDelegate:queue:"),
+            delegate,
+            queue,
+        );
+        // Both were created with alloc, so they are owned already
+        Manager {
+            manager: ObjcObject(manager),
+            _delegate: ObjcObject(delegate),
+            queue,
+            context,
+        }
+    }
+
+    fn context(&self) -> &T {
+        unsafe { &*self.context }
+    }
+
+    fn state(&self) -> BluetoothState {
+        BluetoothState::from_code(unsafe { send(self.manager.0, selector!("state")) })
+    }
+}
+
+impl<T> Drop for Manager<T> {
+    fn drop(&mut self) {
+        unsafe {
+            send1::<ObjcId, ()>(self.manager.0, selector!("setDelegate:"), ptr::null_mut());
+            // Wait for the callbacks that could be running already
+            dispatch_sync_f(self.queue, ptr::null_mut(), wait_for_queue);
+            dispatch_release(self.queue);
+            drop(Box::from_raw(self.context));
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+unsafe fn uuid(uuid: &str) -> ObjcId {
+    let uuid = CFString::new(uuid);
+    send1(
+        class!("CBUUID"),
+        selector!("UUIDWithString:"),
+        ns_string(&uuid),
+    )
+}
+
+unsafe fn midi_service_uuids() -> ObjcId {
+    send1(
+        class!("NSArray"),
+        selector!("arrayWithObject:"),
+        uuid(BLE_MIDI_SERVICE_UUID),
+    )
+}
+
+unsafe fn error_description(error: ObjcId) -> String {
+    string_from(send(error, selector!("localizedDescription"))).unwrap_or_default()
+}
+
+/// A nearby BLE MIDI device, found by a [BluetoothCentral].
+///
+#[derive(Debug, Clone)]
+pub struct BluetoothDevice {
+    peripheral: ObjcObject,
+    identifier: String,
+    name: Option<String>,
+}
+
+// The peripheral is only used as an identity for the central manager, which is thread safe
+unsafe impl Send for BluetoothDevice {}
+unsafe impl Sync for BluetoothDevice {}
+
+impl BluetoothDevice {
+    unsafe fn from_peripheral(peripheral: ObjcId) -> Option<BluetoothDevice> {
+        let identifier: ObjcId = send(peripheral, selector!("identifier"));
+        Some(BluetoothDevice {
+            peripheral: ObjcObject::retain(peripheral)?,
+            identifier: string_from(send(identifier, selector!("UUIDString"))).unwrap_or_default(),
+            name: string_from(send(peripheral, selector!("name"))),
+        })
+    }
+
+    /// The identifier given by the system to the device, which is kept between runs.
+    ///
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+impl PartialEq for BluetoothDevice {
+    fn eq(&self, other: &Self) -> bool {
+        self.identifier == other.identifier
+    }
+}
+
+impl Eq for BluetoothDevice {}
+
+/// The changes reported by a [BluetoothCentral].
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BluetoothCentralEvent {
+    StateChanged(BluetoothState),
+    /// A device advertising the BLE MIDI service, with its signal strength in dBm.
+    DeviceFound {
+        device: BluetoothDevice,
+        rssi: isize,
+    },
+    Connected(BluetoothDevice),
+    ConnectionFailed(BluetoothDevice),
+    Disconnected(BluetoothDevice),
+}
+
+struct CentralContext {
+    callback: Mutex<Box<dyn FnMut(BluetoothCentralEvent) + Send>>,
+    scanning: AtomicBool,
+}
+
+unsafe fn central_callback(delegate: ObjcId, event: BluetoothCentralEvent) {
+    let context = delegate_context::<CentralContext>(delegate);
+    (lock(&context.callback))(event);
+}
+
+unsafe fn start_scan(central: ObjcId) {
+    with_pool(|| {
+        send2::<ObjcId, ObjcId, ()>(
+            central,
+            selector!("scanForPeripheralsWithServices:options:"),
+            midi_service_uuids(),
+            ptr::null_mut(),
+        )
+    })
+}
+
+extern "C" fn central_did_update_state(this: ObjcId, _: ObjcSel, central: ObjcId) {
+    unsafe {
+        let state = BluetoothState::from_code(send(central, selector!("state")));
+        let context = delegate_context::<CentralContext>(this);
+        if state == BluetoothState::PoweredOn && context.scanning.load(Ordering::Relaxed) {
+            start_scan(central);
+        }
+        central_callback(this, BluetoothCentralEvent::StateChanged(state));
+    }
+}
+
+extern "C" fn central_did_discover(
+    this: ObjcId,
+    _: ObjcSel,
+    _central: ObjcId,
+    peripheral: ObjcId,
+    _advertisement: ObjcId,
+    rssi: ObjcId,
+) {
+    unsafe {
+        if let Some(device) = BluetoothDevice::from_peripheral(peripheral) {
+            let rssi = send(rssi, selector!("integerValue"));
+            central_callback(this, BluetoothCentralEvent::DeviceFound { device, rssi });
+        }
+    }
+}
+
+extern "C" fn central_did_connect(this: ObjcId, _: ObjcSel, _central: ObjcId, peripheral: ObjcId) {
+    unsafe {
+        if let Some(device) = BluetoothDevice::from_peripheral(peripheral) {
+            central_callback(this, BluetoothCentralEvent::Connected(device));
+        }
+    }
+}
+
+extern "C" fn central_did_fail_to_connect(
+    this: ObjcId,
+    _: ObjcSel,
+    _central: ObjcId,
+    peripheral: ObjcId,
+    _error: ObjcId,
+) {
+    unsafe {
+        if let Some(device) = BluetoothDevice::from_peripheral(peripheral) {
+            central_callback(this, BluetoothCentralEvent::ConnectionFailed(device));
+        }
+    }
+}
+
+extern "C" fn central_did_disconnect(
+    this: ObjcId,
+    _: ObjcSel,
+    _central: ObjcId,
+    peripheral: ObjcId,
+    _error: ObjcId,
+) {
+    unsafe {
+        if let Some(device) = BluetoothDevice::from_peripheral(peripheral) {
+            central_callback(this, BluetoothCentralEvent::Disconnected(device));
+        }
+    }
+}
+
+static CENTRAL_DELEGATE: Lazy<usize> = Lazy::new(|| unsafe {
+    define_delegate(
+        b"CoreMidiCentralDelegate\0",
+        &[
+            (
+                selector!("centralManagerDidUpdateState:"),
+                central_did_update_state as *const c_void,
+                b"v@:@\0",
+            ),
+            (
+                selector!("centralManager:didDiscoverPeripheral:advertisementData:RSSI:"),
+                central_did_discover as *const c_void,
+                b"v@:@@@@\0",
+            ),
+            (
+                selector!("centralManager:didConnectPeripheral:"),
+                central_did_connect as *const c_void,
+                b"v@:@@\0",
+            ),
+            (
+                selector!("centralManager:didFailToConnectPeripheral:error:"),
+                central_did_fail_to_connect as *const c_void,
+                b"v@:@@@\0",
+            ),
+            (
+                selector!("centralManager:didDisconnectPeripheral:error:"),
+                central_did_disconnect as *const c_void,
+                b"v@:@@@\0",
+            ),
+        ],
+    )
+});
+
+/// Discovers the nearby BLE MIDI devices, and connects to them.
+///
+/// Once connected, a device appears in CoreMIDI as any other device, with its own sources and destinations.
+/// The callback is called from a queue of the central, and it must not drop it.
+///
+/// ```rust,no_run
+/// use std::sync::mpsc;
+/// use coremidi::{BluetoothCentral, BluetoothCentralEvent};
+///
+/// let (sender, receiver) = mpsc::channel();
+/// let central = BluetoothCentral::new(move |event| sender.send(event).unwrap());
+/// central.scan();
+/// for event in receiver {
+///     if let BluetoothCentralEvent::DeviceFound { device, .. } = event {
+///         central.stop_scan();
+///         central.connect(&device);
+///     }
+/// }
+/// ```
+///
+pub struct BluetoothCentral {
+    manager: Manager<CentralContext>,
+}
+
+impl BluetoothCentral {
+    pub fn new<F>(callback: F) -> BluetoothCentral
+    where
+        F: FnMut(BluetoothCentralEvent) + Send + 'static,
+    {
+        let context = CentralContext {
+            callback: Mutex::new(Box::new(callback)),
+            scanning: AtomicBool::new(false),
+        };
+        let manager = with_pool(|| unsafe {
+            Manager::new(class!("CBCentralManager"), *CENTRAL_DELEGATE, context)
+        });
+        BluetoothCentral { manager }
+    }
+
+    pub fn state(&self) -> BluetoothState {
+        self.manager.state()
+    }
+
+    /// Look for devices advertising the BLE MIDI service.
+    /// It starts once Bluetooth is powered on, if it is not yet.
+    ///
+    pub fn scan(&self) {
+        self.manager
+            .context()
+            .scanning
+            .store(true, Ordering::Relaxed);
+        if self.state() == BluetoothState::PoweredOn {
+            unsafe { start_scan(self.manager.manager.0) }
+        }
+    }
+
+    pub fn stop_scan(&self) {
+        self.manager
+            .context()
+            .scanning
+            .store(false, Ordering::Relaxed);
+        unsafe { send::<()>(self.manager.manager.0, selector!("stopScan")) }
+    }
+
+    pub fn connect(&self, device: &BluetoothDevice) {
+        unsafe {
+            send2::<ObjcId, ObjcId, ()>(
+                self.manager.manager.0,
+                selector!("connectPeripheral:options:"),
+                device.peripheral.0,
+                ptr::null_mut(),
+            )
+        }
+    }
+
+    pub fn disconnect(&self, device: &BluetoothDevice) {
+        unsafe {
+            send1::<ObjcId, ()>(
+                self.manager.manager.0,
+                selector!("cancelPeripheralConnection:"),
+                device.peripheral.0,
+            )
+        }
+    }
+
+    /// The BLE MIDI devices connected to the system, by this or any other application.
+    ///
+    pub fn connected_devices(&self) -> Vec<BluetoothDevice> {
+        with_pool(|| unsafe {
+            let peripherals: ObjcId = send1(
+                self.manager.manager.0,
+                selector!("retrieveConnectedPeripheralsWithServices:"),
+                midi_service_uuids(),
+            );
+            let count: usize = send(peripherals, selector!("count"));
+            (0..count)
+                .filter_map(|index| {
+                    BluetoothDevice::from_peripheral(send1(
+                        peripherals,
+                        selector!("objectAtIndex:"),
+                        index,
+                    ))
+                })
+                .collect()
+        })
+    }
+}
+
+impl Drop for BluetoothCentral {
+    fn drop(&mut self) {
+        self.stop_scan();
+    }
+}
+
+/// The changes reported by a [BluetoothPeripheral].
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BluetoothPeripheralEvent {
+    StateChanged(BluetoothState),
+    Advertising,
+    /// Either the service could not be published, or the advertising could not start.
+    Failed(String),
+    /// A central subscribed to the MIDI data sent with [BluetoothPeripheral::send].
+    Subscribed,
+    Unsubscribed,
+    /// The MIDI data written by a central.
+    Received(Vec<u8>),
+}
+
+struct PeripheralContext {
+    name: CFString,
+    callback: Mutex<Box<dyn FnMut(BluetoothPeripheralEvent) + Send>>,
+    characteristic: Mutex<Option<ObjcObject>>,
+}
+
+unsafe fn peripheral_callback(delegate: ObjcId, event: BluetoothPeripheralEvent) {
+    let context = delegate_context::<PeripheralContext>(delegate);
+    (lock(&context.callback))(event);
+}
+
+/// Publish the BLE MIDI service, with a characteristic that can be read, written without response and notified.
+unsafe fn add_midi_service(manager: ObjcId, context: &PeripheralContext) {
+    let characteristic: ObjcId = send4(
+        send(class!("CBMutableCharacteristic"), selector!("alloc")),
+        selector!("initWithType:properties:value:permissions:"),
+        uuid(BLE_MIDI_CHARACTERISTIC_UUID),
+        0x02usize | 0x04 | 0x10,
+        ptr::null_mut::<c_void>(),
+        0x01usize | 0x02,
+    );
+    let characteristic = ObjcObject(characteristic);
+    let service = ObjcObject(send2(
+        send(class!("CBMutableService"), selector!("alloc")),
+        selector!("initWithType:primary:"),
+        uuid(BLE_MIDI_SERVICE_UUID),
+        1i8,
+    ));
+    let characteristics: ObjcId = send1(
+        class!("NSArray"),
+        selector!("arrayWithObject:"),
+        characteristic.0,
+    );
+    send1::<ObjcId, ()>(service.0, selector!("setCharacteristics:"), characteristics);
+    send1::<ObjcId, ()>(manager, selector!("addService:"), service.0);
+    *lock(&context.characteristic) = Some(characteristic);
+}
+
+unsafe fn start_advertising(manager: ObjcId, context: &PeripheralContext) {
+    let (name_key, services_key) = (
+        CFString::new("kCBAdvDataLocalName"),
+        CFString::new("kCBAdvDataServiceUUIDs"),
+    );
+    let keys = [ns_string(&name_key), ns_string(&services_key)];
+    let values = [ns_string(&context.name), midi_service_uuids()];
+    let advertisement: ObjcId = send3(
+        class!("NSDictionary"),
+        selector!("dictionaryWithObjects:forKeys:count:"),
+        values.as_ptr(),
+        keys.as_ptr(),
+        keys.len(),
+    );
+    send1::<ObjcId, ()>(manager, selector!("startAdvertising:"), advertisement);
+}
+
+unsafe fn data_bytes<'a>(data: ObjcId) -> &'a [u8] {
+    let length: usize = send(data, selector!("length"));
+    let bytes: *const u8 = send(data, selector!("bytes"));
+    if bytes.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(bytes, length)
+    }
+}
+
+extern "C" fn peripheral_did_update_state(this: ObjcId, _: ObjcSel, manager: ObjcId) {
+    with_pool(|| unsafe {
+        let state = BluetoothState::from_code(send(manager, selector!("state")));
+        let context = delegate_context::<PeripheralContext>(this);
+        let published = lock(&context.characteristic).is_some();
+        if state == BluetoothState::PoweredOn && !published {
+            add_midi_service(manager, context);
+        }
+        peripheral_callback(this, BluetoothPeripheralEvent::StateChanged(state));
+    })
+}
+
+extern "C" fn peripheral_did_add_service(
+    this: ObjcId,
+    _: ObjcSel,
+    manager: ObjcId,
+    _service: ObjcId,
+    error: ObjcId,
+) {
+    with_pool(|| unsafe {
+        if error.is_null() {
+            start_advertising(manager, delegate_context::<PeripheralContext>(this));
+        } else {
+            let description = error_description(error);
+            peripheral_callback(this, BluetoothPeripheralEvent::Failed(description));
+        }
+    })
+}
+
+extern "C" fn peripheral_did_start_advertising(
+    this: ObjcId,
+    _: ObjcSel,
+    _manager: ObjcId,
+    error: ObjcId,
+) {
+    with_pool(|| unsafe {
+        let event = if error.is_null() {
+            BluetoothPeripheralEvent::Advertising
+        } else {
+            BluetoothPeripheralEvent::Failed(error_description(error))
+        };
+        peripheral_callback(this, event);
+    })
+}
+
+extern "C" fn peripheral_did_subscribe(
+    this: ObjcId,
+    _: ObjcSel,
+    _manager: ObjcId,
+    _central: ObjcId,
+    _characteristic: ObjcId,
+) {
+    unsafe { peripheral_callback(this, BluetoothPeripheralEvent::Subscribed) }
+}
+
+extern "C" fn peripheral_did_unsubscribe(
+    this: ObjcId,
+    _: ObjcSel,
+    _manager: ObjcId,
+    _central: ObjcId,
+    _characteristic: ObjcId,
+) {
+    unsafe { peripheral_callback(this, BluetoothPeripheralEvent::Unsubscribed) }
+}
+
+extern "C" fn peripheral_did_receive_writes(
+    this: ObjcId,
+    _: ObjcSel,
+    _manager: ObjcId,
+    requests: ObjcId,
+) {
+    with_pool(|| unsafe {
+        let count: usize = send(requests, selector!("count"));
+        for index in 0..count {
+            let request: ObjcId = send1(requests, selector!("objectAtIndex:"), index);
+            let data = ble_midi_data(data_bytes(send(request, selector!("value"))));
+            if !data.is_empty() {
+                peripheral_callback(this, BluetoothPeripheralEvent::Received(data));
+            }
+        }
+    })
+}
+
+extern "C" fn peripheral_did_receive_read(
+    _this: ObjcId,
+    _: ObjcSel,
+    manager: ObjcId,
+    request: ObjcId,
+) {
+    with_pool(|| unsafe {
+        // The reads are answered with no data, as the specification requires
+        let empty: ObjcId = send(class!("NSData"), selector!("data"));
+        send1::<ObjcId, ()>(request, selector!("setValue:"), empty);
+        send2::<ObjcId, isize, ()>(
+            manager,
+            selector!("respondToRequest:withResult:"),
+            request,
+            0,
+        );
+    })
+}
+
+static PERIPHERAL_DELEGATE: Lazy<usize> = Lazy::new(|| unsafe {
+    define_delegate(
+        b"CoreMidiPeripheralDelegate\0",
+        &[
+            (
+                selector!("peripheralManagerDidUpdateState:"),
+                peripheral_did_update_state as *const c_void,
+                b"v@:@\0",
+            ),
+            (
+                selector!("peripheralManager:didAddService:error:"),
+                peripheral_did_add_service as *const c_void,
+                b"v@:@@@\0",
+            ),
+            (
+                selector!("peripheralManagerDidStartAdvertising:error:"),
+                peripheral_did_start_advertising as *const c_void,
+                b"v@:@@\0",
+            ),
+            (
+                selector!("peripheralManager:central:didSubscribeToCharacteristic:"),
+                peripheral_did_subscribe as *const c_void,
+                b"v@:@@@\0",
+            ),
+            (
+                selector!("peripheralManager:central:didUnsubscribeFromCharacteristic:"),
+                peripheral_did_unsubscribe as *const c_void,
+                b"v@:@@@\0",
+            ),
+            (
+                selector!("peripheralManager:didReceiveWriteRequests:"),
+                peripheral_did_receive_writes as *const c_void,
+                b"v@:@@\0",
+            ),
+            (
+                selector!("peripheralManager:didReceiveReadRequest:"),
+                peripheral_did_receive_read as *const c_void,
+                b"v@:@@\0",
+            ),
+        ],
+    )
+});
+
+/// Advertises this device as a BLE MIDI peripheral, that other devices can connect to as a central.
+///
+/// The MIDI data written by the centrals is reported to the callback, and the data sent
+/// is notified to the centrals subscribed. The callback is called from a queue of the peripheral,
+/// and it must not drop it.
+///
+/// ```rust,no_run
+/// use coremidi::{BluetoothPeripheral, BluetoothPeripheralEvent};
+///
+/// let peripheral = BluetoothPeripheral::advertise("My Synth", |event| {
+///     if let BluetoothPeripheralEvent::Received(data) = event {
+///         println!("{:02x?}", data);
+///     }
+/// });
+/// peripheral.send(&[0x90, 0x40, 0x7f]);
+/// ```
+///
+pub struct BluetoothPeripheral {
+    manager: Manager<PeripheralContext>,
+}
+
+impl BluetoothPeripheral {
+    /// Publish the BLE MIDI service and advertise it with a name,
+    /// once Bluetooth is powered on (see [BluetoothPeripheralEvent::Advertising]).
+    ///
+    pub fn advertise<F>(name: &str, callback: F) -> BluetoothPeripheral
+    where
+        F: FnMut(BluetoothPeripheralEvent) + Send + 'static,
+    {
+        let context = PeripheralContext {
+            name: CFString::new(name),
+            callback: Mutex::new(Box::new(callback)),
+            characteristic: Mutex::new(None),
+        };
+        let manager = with_pool(|| unsafe {
+            Manager::new(class!("CBPeripheralManager"), *PERIPHERAL_DELEGATE, context)
+        });
+        BluetoothPeripheral { manager }
+    }
+
+    pub fn state(&self) -> BluetoothState {
+        self.manager.state()
+    }
+
+    /// Send MIDI data to the centrals subscribed, returning whether it could be sent.
+    /// It fails when the service is not published yet, or when the queue of updates is full.
+    ///
+    pub fn send(&self, midi: &[u8]) -> bool {
+        let characteristic = lock(&self.manager.context().characteristic);
+        let characteristic = match characteristic.as_ref() {
+            Some(characteristic) => characteristic,
+            None => return false,
+        };
+        let millis = duration_from_host_time(host_time_now()).as_millis() as u16;
+        ble_midi_packets(midi, millis, BLE_MIDI_PACKET_SIZE)
+            .iter()
+            .all(|packet| {
+                with_pool(|| unsafe {
+                    let data: ObjcId = send2(
+                        class!("NSData"),
+                        selector!("dataWithBytes:length:"),
+                        packet.as_ptr(),
+                        packet.len(),
+                    );
+                    send3::<ObjcId, ObjcId, ObjcId, i8>(
+                        self.manager.manager.0,
+                        selector!("updateValue:forCharacteristic:onSubscribedCentrals:"),
+                        data,
+                        characteristic.0,
+                        ptr::null_mut(),
+                    ) != 0
+                })
+            })
+    }
+}
+
+impl Drop for BluetoothPeripheral {
+    fn drop(&mut self) {
+        unsafe {
+            send::<()>(self.manager.manager.0, selector!("stopAdvertising"));
+            send::<()>(self.manager.manager.0, selector!("removeAllServices"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bluetooth::{ble_midi_data, ble_midi_packets, BLE_MIDI_PACKET_SIZE};
+
+    #[test]
+    fn messages_with_timestamps() {
+        let midi = [0x90, 0x3c, 0x7f, 0x80, 0x3c, 0x00, 0xf8];
+        let packets = ble_midi_packets(&midi, 0x0abc, BLE_MIDI_PACKET_SIZE);
+
+        assert_eq!(
+            packets,
+            vec![vec![
+                0x95, 0xbc, 0x90, 0x3c, 0x7f, 0xbc, 0x80, 0x3c, 0x00, 0xbc, 0xf8
+            ]]
+        );
+        assert_eq!(ble_midi_data(&packets[0]), midi.to_vec());
+        // Running status, after a timestamp and without it
+        assert_eq!(
+            ble_midi_data(&[0x80, 0x80, 0x90, 0x3c, 0x7f, 0x81, 0x3e, 0x7f, 0x40, 0x7f]),
+            vec![0x90, 0x3c, 0x7f, 0x3e, 0x7f, 0x40, 0x7f]
+        );
+        assert!(ble_midi_data(&[0x3c]).is_empty());
+    }
+
+    #[test]
+    fn sysex_across_packets() {
+        let mut sysex = vec![0xf0];
+        sysex.extend(0..30);
+        sysex.push(0xf7);
+        let packets = ble_midi_packets(&sysex, 0, BLE_MIDI_PACKET_SIZE);
+
+        assert_eq!(packets.len(), 2);
+        assert!(packets
+            .iter()
+            .all(|packet| packet.len() <= BLE_MIDI_PACKET_SIZE));
+        assert_eq!(&packets[1][..2], &[0x80, 17]);
+        let data: Vec<u8> = packets
+            .iter()
+            .flat_map(|packet| ble_midi_data(packet))
+            .collect();
+        assert_eq!(data, sysex);
+    }
+}
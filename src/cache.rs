@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use coremidi_sys::MIDIObjectRef;
+
+use crate::any_object::AnyObject;
+use crate::notifications::Notification;
+use crate::object::{Object, UniqueId};
+
+#[derive(Debug, Default)]
+struct CachedProperties {
+    display_name: Option<Option<String>>,
+    unique_id: Option<Option<UniqueId>>,
+}
+
+/// A cache for the display names and unique ids of endpoints, owned by a [Client](crate::Client).
+///
+/// Reading those properties requires a round-trip to the MIDI server and some conversions,
+/// which can be noticeable when they are accessed very often (for example when redrawing a list of ports).
+/// The cache memoizes them, and it is automatically invalidated when the client receives notifications
+/// about properties being changed or objects being removed.
+///
+/// ```rust,no_run
+/// use coremidi::{Client, Notification, Sources};
+/// let client = Client::new_with_notifications("example-client", |_: &Notification| ()).unwrap();
+/// let cache = client.endpoint_cache().unwrap();
+/// for source in Sources {
+///     println!("{}", cache.display_name(&source).unwrap_or_default());
+/// }
+/// ```
+///
+#[derive(Debug, Default)]
+pub struct EndpointCache {
+    entries: Mutex<HashMap<MIDIObjectRef, CachedProperties>>,
+}
+
+impl EndpointCache {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the display name for an object, reading it from CoreMIDI only when it is not cached.
+    ///
+    pub fn display_name(&self, object: &Object) -> Option<String> {
+        let mut entries = self.lock_entries();
+        let cached = entries.entry(object.0).or_default();
+        cached
+            .display_name
+            .get_or_insert_with(|| object.display_name())
+            .clone()
+    }
+
+    /// Get the unique id for an object, reading it from CoreMIDI only when it is not cached.
+    ///
+    pub fn unique_id(&self, object: &Object) -> Option<UniqueId> {
+        let mut entries = self.lock_entries();
+        let cached = entries.entry(object.0).or_default();
+        *cached.unique_id.get_or_insert_with(|| object.unique_id())
+    }
+
+    /// Forget the cached properties for an object.
+    ///
+    pub fn invalidate(&self, object: &Object) {
+        self.lock_entries().remove(&object.0);
+    }
+
+    /// Forget all the cached properties.
+    ///
+    pub fn clear(&self) {
+        self.lock_entries().clear();
+    }
+
+    pub(crate) fn handle_notification(&self, notification: &Notification) {
+        match notification {
+            Notification::PropertyChanged(info) => self.invalidate_object(&info.object),
+            Notification::ObjectRemoved(info) => self.invalidate_object(&info.child),
+            Notification::SetupChanged => self.clear(),
+            _ => {}
+        }
+    }
+
+    fn invalidate_object(&self, object: &AnyObject) {
+        match object {
+            AnyObject::Source(_)
+            | AnyObject::Destination(_)
+            | AnyObject::ExternalSource(_)
+            | AnyObject::ExternalDestination(_) => self.invalidate(object.as_ref()),
+            // The display names of the endpoints depend on the names of their entities and devices
+            _ => self.clear(),
+        }
+    }
+
+    fn lock_entries(&self) -> MutexGuard<'_, HashMap<MIDIObjectRef, CachedProperties>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::any_object::AnyObject;
+    use crate::cache::{CachedProperties, EndpointCache};
+    use crate::notifications::{AddedRemovedInfo, Notification, PropertyChangedInfo};
+    use crate::{Destination, Device, Object, Source};
+
+    fn cache_with_entries(object_refs: &[u32]) -> EndpointCache {
+        let cache = EndpointCache::new();
+        for object_ref in object_refs {
+            let cached = CachedProperties {
+                display_name: Some(Some(format!("endpoint-{}", object_ref))),
+                unique_id: Some(Some(*object_ref as i32)),
+            };
+            cache.lock_entries().insert(*object_ref, cached);
+        }
+        cache
+    }
+
+    #[test]
+    fn cache_hit() {
+        let cache = cache_with_entries(&[1]);
+
+        assert_eq!(
+            cache.display_name(&Object(1)),
+            Some("endpoint-1".to_string())
+        );
+        assert_eq!(cache.unique_id(&Object(1)), Some(1));
+    }
+
+    #[test]
+    fn cache_invalidated_by_endpoint_property_changed() {
+        let cache = cache_with_entries(&[1, 2]);
+
+        cache.handle_notification(&Notification::PropertyChanged(PropertyChangedInfo {
+            object: AnyObject::Source(Source::new(1)),
+            property_name: "name".to_string(),
+        }));
+
+        let entries = cache.lock_entries();
+        assert!(!entries.contains_key(&1));
+        assert!(entries.contains_key(&2));
+    }
+
+    #[test]
+    fn cache_cleared_by_device_property_changed() {
+        let cache = cache_with_entries(&[1, 2]);
+
+        cache.handle_notification(&Notification::PropertyChanged(PropertyChangedInfo {
+            object: AnyObject::Device(Device::new(3)),
+            property_name: "name".to_string(),
+        }));
+
+        assert!(cache.lock_entries().is_empty());
+    }
+
+    #[test]
+    fn cache_invalidated_by_object_removed() {
+        let cache = cache_with_entries(&[1, 2]);
+
+        cache.handle_notification(&Notification::ObjectRemoved(AddedRemovedInfo {
+            parent: AnyObject::Other(Object(0)),
+            child: AnyObject::Destination(Destination::new(2)),
+        }));
+
+        let entries = cache.lock_entries();
+        assert!(entries.contains_key(&1));
+        assert!(!entries.contains_key(&2));
+    }
+}
@@ -0,0 +1,361 @@
+use std::cell::UnsafeCell;
+use std::convert::TryInto;
+use std::mem::size_of;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::{EventList, PacketList, Timestamp};
+
+// Every packet is stored as a header with the timestamp and the length of the data, followed by the data.
+const TIMESTAMP_SIZE: usize = size_of::<Timestamp>();
+const HEADER_SIZE: usize = TIMESTAMP_SIZE + size_of::<u32>();
+// The length written in a header to tell that the rest of the buffer is empty, and the next packet is at the start.
+const WRAP_MARKER: u32 = u32::MAX;
+
+struct Shared {
+    data: Box<[UnsafeCell<u8>]>,
+    // Both are positions in bytes that only grow (wrapping around), the offset is the position modulo the capacity
+    read: AtomicUsize,
+    write: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+// The writer only writes into the bytes that the reader is not reading, and the other way around,
+// which is synchronized through the positions.
+unsafe impl Sync for Shared {}
+
+impl Shared {
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    fn data_ptr(&self) -> *mut u8 {
+        self.data.as_ptr() as *mut u8
+    }
+}
+
+/// Create a buffer of `capacity` bytes to capture the packets received by an input port at high rates.
+///
+/// The [CaptureWriter] is meant to be moved into the callback of the port, where it appends the packets
+/// into the buffer without allocating nor locking, while the [CaptureReader] drains them from another thread
+/// (or after the burst is over). All the memory is allocated when the buffer is created, and the packets
+/// that don't fit are counted as dropped.
+///
+/// ```
+/// use coremidi::{capture_buffer, PacketBuffer};
+/// let (mut writer, mut reader) = capture_buffer(64 * 1024);
+/// // From the input port callback:
+/// writer.capture_packets(&PacketBuffer::new(42, &[0x90, 0x40, 0x7f]));
+/// // Afterwards:
+/// for packet in reader.drain() {
+///     assert_eq!(packet.timestamp(), 42);
+///     assert_eq!(packet.data(), &[0x90, 0x40, 0x7f]);
+/// }
+/// ```
+///
+pub fn capture_buffer(capacity: usize) -> (CaptureWriter, CaptureReader) {
+    let shared = Arc::new(Shared {
+        data: (0..capacity.max(HEADER_SIZE))
+            .map(|_| UnsafeCell::new(0))
+            .collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+        dropped: AtomicUsize::new(0),
+    });
+    (
+        CaptureWriter {
+            shared: shared.clone(),
+        },
+        CaptureReader { shared, read: 0 },
+    )
+}
+
+/// The writing half of a [capture_buffer].
+///
+pub struct CaptureWriter {
+    shared: Arc<Shared>,
+}
+
+impl CaptureWriter {
+    /// Append the data of a packet, returning false if there was no room for it.
+    ///
+    pub fn capture(&mut self, timestamp: Timestamp, data: &[u8]) -> bool {
+        self.append(timestamp, data.len(), |dst| unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), dst, data.len())
+        })
+    }
+
+    /// Append all the packets of a list, returning the number of them that didn't fit.
+    ///
+    pub fn capture_packets(&mut self, packet_list: &PacketList) -> usize {
+        packet_list
+            .iter()
+            .filter(|packet| !self.capture(packet.timestamp(), packet.data()))
+            .count()
+    }
+
+    /// Append all the packets of a list of events, returning the number of them that didn't fit.
+    /// The words are stored in native endianness, see [CapturedPacket::words].
+    ///
+    pub fn capture_events(&mut self, event_list: &EventList) -> usize {
+        event_list
+            .iter()
+            .filter(|packet| {
+                let words = packet.data();
+                !self.append(packet.timestamp(), words.len() * 4, |dst| unsafe {
+                    ptr::copy_nonoverlapping(words.as_ptr() as *const u8, dst, words.len() * 4)
+                })
+            })
+            .count()
+    }
+
+    fn append<F>(&mut self, timestamp: Timestamp, len: usize, write_data: F) -> bool
+    where
+        F: FnOnce(*mut u8),
+    {
+        let shared = &*self.shared;
+        let capacity = shared.capacity();
+        let record_size = HEADER_SIZE + len;
+        let mut write = shared.write.load(Ordering::Relaxed);
+        let read = shared.read.load(Ordering::Acquire);
+        let free = capacity - write.wrapping_sub(read);
+        let offset = write % capacity;
+        let tail = capacity - offset;
+        let skip = if record_size <= tail { 0 } else { tail };
+        if len >= WRAP_MARKER as usize || skip + record_size > free {
+            shared.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        let data_ptr = shared.data_ptr();
+        if skip > 0 {
+            if tail >= HEADER_SIZE {
+                unsafe { write_header(data_ptr.add(offset), 0, WRAP_MARKER) };
+            }
+            write = write.wrapping_add(skip);
+        }
+        unsafe {
+            let record_ptr = data_ptr.add(write % capacity);
+            write_header(record_ptr, timestamp, len as u32);
+            write_data(record_ptr.add(HEADER_SIZE));
+        }
+        shared
+            .write
+            .store(write.wrapping_add(record_size), Ordering::Release);
+        true
+    }
+}
+
+unsafe fn write_header(ptr: *mut u8, timestamp: Timestamp, len: u32) {
+    (ptr as *mut Timestamp).write_unaligned(timestamp);
+    (ptr.add(TIMESTAMP_SIZE) as *mut u32).write_unaligned(len);
+}
+
+/// The reading half of a [capture_buffer].
+///
+pub struct CaptureReader {
+    shared: Arc<Shared>,
+    // The position up to which the packets have been drained, it is only released to the writer
+    // when the packets can't be borrowed anymore.
+    read: usize,
+}
+
+impl CaptureReader {
+    /// Iterate over the packets captured since the last call, in order.
+    ///
+    /// The packets borrow the buffer, so the room they take is given back to the writer
+    /// on the next call to [drain](CaptureReader::drain) or [release](CaptureReader::release).
+    ///
+    pub fn drain(&mut self) -> CaptureDrain<'_> {
+        self.release();
+        CaptureDrain {
+            shared: &self.shared,
+            write: self.shared.write.load(Ordering::Acquire),
+            read: &mut self.read,
+        }
+    }
+
+    /// Give the room taken by the packets drained so far back to the writer.
+    ///
+    pub fn release(&mut self) {
+        self.shared.read.store(self.read, Ordering::Release);
+    }
+
+    /// The number of bytes taken by the packets that haven't been drained yet.
+    ///
+    pub fn len(&self) -> usize {
+        let write = self.shared.write.load(Ordering::Acquire);
+        write.wrapping_sub(self.read)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of packets that were not captured because the buffer was full.
+    ///
+    pub fn dropped(&self) -> usize {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// An iterator over the packets of a [CaptureReader], see [CaptureReader::drain].
+///
+pub struct CaptureDrain<'a> {
+    shared: &'a Shared,
+    read: &'a mut usize,
+    write: usize,
+}
+
+impl<'a> Iterator for CaptureDrain<'a> {
+    type Item = CapturedPacket<'a>;
+
+    fn next(&mut self) -> Option<CapturedPacket<'a>> {
+        let capacity = self.shared.capacity();
+        while *self.read != self.write {
+            let offset = *self.read % capacity;
+            let tail = capacity - offset;
+            if tail < HEADER_SIZE {
+                *self.read = self.read.wrapping_add(tail);
+                continue;
+            }
+            let (timestamp, len) = unsafe {
+                let record_ptr = self.shared.data_ptr().add(offset);
+                (
+                    (record_ptr as *const Timestamp).read_unaligned(),
+                    (record_ptr.add(TIMESTAMP_SIZE) as *const u32).read_unaligned(),
+                )
+            };
+            if len == WRAP_MARKER {
+                *self.read = self.read.wrapping_add(tail);
+                continue;
+            }
+            let len = len as usize;
+            // The writer won't touch these bytes until the reader releases them, which needs the borrow to end
+            let data = unsafe {
+                std::slice::from_raw_parts(
+                    self.shared.data_ptr().add(offset + HEADER_SIZE) as *const u8,
+                    len,
+                )
+            };
+            *self.read = self.read.wrapping_add(HEADER_SIZE + len);
+            return Some(CapturedPacket { timestamp, data });
+        }
+        None
+    }
+}
+
+/// A packet stored in a [capture_buffer].
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapturedPacket<'a> {
+    timestamp: Timestamp,
+    data: &'a [u8],
+}
+
+impl<'a> CapturedPacket<'a> {
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The data as Universal MIDI Packet words, for the packets captured with [CaptureWriter::capture_events].
+    ///
+    pub fn words(&self) -> impl Iterator<Item = u32> + 'a {
+        self.data
+            .chunks_exact(4)
+            .map(|chunk| u32::from_ne_bytes(chunk.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::capture::{capture_buffer, HEADER_SIZE};
+    use crate::{EventBuffer, PacketBuffer, Protocol};
+
+    fn drained(reader: &mut crate::capture::CaptureReader) -> Vec<(u64, Vec<u8>)> {
+        reader
+            .drain()
+            .map(|packet| (packet.timestamp(), packet.data().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn capture_and_drain() {
+        let (mut writer, mut reader) = capture_buffer(1024);
+        let mut packets = PacketBuffer::new(1, &[0x90, 0x40, 0x7f]);
+        packets.push_data(2, &[0x80, 0x40, 0x00]);
+
+        assert_eq!(writer.capture_packets(&packets), 0);
+        assert!(!reader.is_empty());
+        assert_eq!(
+            drained(&mut reader),
+            vec![(1, vec![0x90, 0x40, 0x7f]), (2, vec![0x80, 0x40, 0x00])]
+        );
+        assert!(reader.is_empty());
+        assert_eq!(drained(&mut reader), vec![]);
+    }
+
+    #[test]
+    fn capture_events() {
+        let (mut writer, mut reader) = capture_buffer(1024);
+        let events = EventBuffer::new(Protocol::Midi20).with_packet(3, &[0x40903c00, 0xffff0000]);
+
+        assert_eq!(writer.capture_events(&events), 0);
+        let packets: Vec<(u64, Vec<u32>)> = reader
+            .drain()
+            .map(|packet| (packet.timestamp(), packet.words().collect()))
+            .collect();
+        assert_eq!(packets, vec![(3, vec![0x40903c00, 0xffff0000])]);
+    }
+
+    #[test]
+    fn drop_when_full_and_wrap_around() {
+        let record_size = HEADER_SIZE + 3;
+        let (mut writer, mut reader) = capture_buffer(record_size * 2 + 4);
+
+        assert!(writer.capture(1, &[0x90, 0x40, 0x7f]));
+        assert!(writer.capture(2, &[0x90, 0x41, 0x7f]));
+        assert!(!writer.capture(3, &[0x90, 0x42, 0x7f]));
+        assert_eq!(reader.dropped(), 1);
+
+        assert_eq!(drained(&mut reader).len(), 2);
+        reader.release();
+        // It doesn't fit in the remaining 4 bytes at the end, so it goes to the start
+        assert!(writer.capture(4, &[0x90, 0x43, 0x7f]));
+        assert!(writer.capture(5, &[0x90, 0x44, 0x7f]));
+        assert_eq!(
+            drained(&mut reader),
+            vec![(4, vec![0x90, 0x43, 0x7f]), (5, vec![0x90, 0x44, 0x7f])]
+        );
+    }
+
+    #[test]
+    fn between_threads() {
+        let (mut writer, mut reader) = capture_buffer(256);
+        let writer_thread = thread::spawn(move || {
+            for timestamp in 0..1000u64 {
+                let data = [0x90, (timestamp % 128) as u8, 0x7f];
+                while !writer.capture(timestamp, &data) {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut expected = 0;
+        while expected < 1000 {
+            for packet in reader.drain() {
+                assert_eq!(packet.timestamp(), expected);
+                assert_eq!(packet.data(), &[0x90, (expected % 128) as u8, 0x7f]);
+                expected += 1;
+            }
+        }
+        writer_thread.join().unwrap();
+    }
+}
@@ -4,23 +4,31 @@ use core_foundation::{
     string::CFString,
 };
 use std::cell::RefCell;
+use std::sync::Arc;
 use std::{mem::MaybeUninit, ops::Deref, os::raw::c_void, ptr};
 
 use coremidi_sys::{
-    MIDIClientCreate, MIDIClientCreateWithBlock, MIDIDestinationCreateWithBlock,
-    MIDIDestinationCreateWithProtocol, MIDIEventList, MIDIInputPortCreateWithBlock,
-    MIDIInputPortCreateWithProtocol, MIDINotification, MIDINotifyBlock, MIDIOutputPortCreate,
+    MIDIClientCreate, MIDIClientCreateWithBlock, MIDIDestinationCreateWithBlock, MIDIEventList,
+    MIDIInputPortCreateWithBlock, MIDINotification, MIDINotifyBlock, MIDIOutputPortCreate,
     MIDIPacketList, MIDIReadBlock, MIDIReceiveBlock, MIDISourceCreate,
 };
 
-use crate::ports::InputPortWithContext;
+use crate::availability::{MIDIDestinationCreateWithProtocol, MIDIInputPortCreateWithProtocol};
+#[cfg(feature = "metrics")]
+use crate::metrics::PortMetrics;
+use crate::ports::{Connection, InputPortWithContext};
 use crate::{
+    cache::EndpointCache,
     endpoints::{destinations::VirtualDestination, sources::VirtualSource},
-    notifications::Notification,
+    notifications::{Notification, PropertyWatchesClaim, PropertyWatchesDispatch},
     object::Object,
-    packets::PacketList,
+    packets::{PacketList, MAX_PACKET_LIST_SIZE},
     ports::{InputPort, OutputPort},
-    result_from_status, EventList, Protocol,
+    realtime::RtSafe,
+    result_from_status,
+    server::report_client_status,
+    transforms::{transform_ump_data, MessageFilter},
+    EventBuffer, EventList, MidiError, Protocol,
 };
 
 pub enum NotifyCallback {
@@ -65,9 +73,19 @@ where
 #[derive(Debug)]
 pub struct Client {
     object: Object,
+    endpoint_cache: Option<Arc<EndpointCache>>,
+    property_namespace: String,
+    // The name given when creating the client, to create it again
+    name: String,
+    // Releases the forwarding of the notifications to the property watchers when dropped
+    _property_watches: Option<PropertyWatchesClaim>,
 }
 
 impl Client {
+    /// The namespace used to prefix the keys of the [app properties](crate::Object::set_app_property)
+    /// until another one is [set](Client::set_property_namespace).
+    pub const DEFAULT_PROPERTY_NAMESPACE: &'static str = "com.github.chris-zen.coremidi";
+
     /// Creates a new CoreMIDI client with support for notifications.
     /// See [MIDIClientCreateWithBlock](https://developer.apple.com/documentation/coremidi/1495330-midiclientcreatewithblock).
     ///
@@ -82,9 +100,15 @@ impl Client {
     where
         F: Into<NotifyCallback>,
     {
+        #[cfg(all(feature = "ios", target_os = "ios"))]
+        crate::ios::keep_process_client();
         let client_name = CFString::new(name);
         let mut client_ref = MaybeUninit::uninit();
-        let notify_block = Self::notify_block(callback.into());
+        let endpoint_cache = Arc::new(EndpointCache::new());
+        let property_watches = PropertyWatchesDispatch::new();
+        let property_watches_claim = property_watches.claim_handle();
+        let notify_block =
+            Self::notify_block(callback.into(), endpoint_cache.clone(), property_watches);
         let status = unsafe {
             MIDIClientCreateWithBlock(
                 client_name.as_concrete_TypeRef(),
@@ -92,18 +116,35 @@ impl Client {
                 notify_block.deref() as *const _ as MIDINotifyBlock,
             )
         };
+        report_client_status(status);
         result_from_status(status, || {
             let client_ref = unsafe { client_ref.assume_init() };
             Client {
                 object: Object(client_ref),
+                endpoint_cache: Some(endpoint_cache),
+                property_namespace: Self::DEFAULT_PROPERTY_NAMESPACE.to_string(),
+                name: name.to_string(),
+                _property_watches: Some(property_watches_claim),
             }
         })
     }
 
+    /// Creates a new CoreMIDI client with support for notifications, like [Client::new_with_notifications],
+    /// but telling why it failed with a [MidiError] (see [MidiError::from_client_status]).
+    ///
+    pub fn new_with_notifications_checked<F>(name: &str, callback: F) -> Result<Client, MidiError>
+    where
+        F: Into<NotifyCallback>,
+    {
+        Self::new_with_notifications(name, callback).map_err(MidiError::from_client_status)
+    }
+
     /// Creates a new CoreMIDI client.
     /// See [MIDIClientCreate](https://developer.apple.com/documentation/coremidi/1495360-midiclientcreate).
     ///
     pub fn new(name: &str) -> Result<Client, OSStatus> {
+        #[cfg(all(feature = "ios", target_os = "ios"))]
+        crate::ios::keep_process_client();
         let client_name = CFString::new(name);
         let mut client_ref = MaybeUninit::uninit();
         let status = unsafe {
@@ -114,14 +155,61 @@ impl Client {
                 client_ref.as_mut_ptr(),
             )
         };
+        report_client_status(status);
         result_from_status(status, || {
             let client_ref = unsafe { client_ref.assume_init() };
             Client {
                 object: Object(client_ref),
+                endpoint_cache: None,
+                property_namespace: Self::DEFAULT_PROPERTY_NAMESPACE.to_string(),
+                name: name.to_string(),
+                _property_watches: None,
             }
         })
     }
 
+    /// Creates a new CoreMIDI client like [Client::new], but telling why it failed with a [MidiError]
+    /// (see [MidiError::from_client_status]).
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Client, MidiError};
+    /// match Client::new_checked("example-client") {
+    ///     Ok(client) => println!("Created {:?}", client.name()),
+    ///     Err(MidiError::PermissionDenied(_)) => println!("Missing permissions"),
+    ///     Err(error) => println!("{}", error),
+    /// }
+    /// ```
+    ///
+    pub fn new_checked(name: &str) -> Result<Client, MidiError> {
+        Self::new(name).map_err(MidiError::from_client_status)
+    }
+
+    /// Creates a new CoreMIDI client with the same name and [property namespace](Client::property_namespace),
+    /// to continue after the MIDI server was lost (see [MidiError::ServerUnavailable](crate::MidiError::ServerUnavailable)).
+    ///
+    /// This client is not disposed, as disposing the last client can stop the MIDI server again,
+    /// but its ports and virtual endpoints don't work anymore and need to be created again from the new one.
+    ///
+    /// The new client doesn't receive notifications, as the callback given to [Client::new_with_notifications]
+    /// can't be taken from this one. Create it with [Client::new_with_notifications] instead, registering
+    /// the callback again, to keep receiving them (and to keep the [property watchers](crate::Object::watch_property) working).
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Client, MidiError};
+    /// let mut client = Client::new("example-client").unwrap();
+    /// if let Err(status) = client.output_port("example-port") {
+    ///     if MidiError::from(status).is_server_unavailable() {
+    ///         client = client.recreate().unwrap();
+    ///     }
+    /// }
+    /// ```
+    ///
+    pub fn recreate(&self) -> Result<Client, OSStatus> {
+        let mut client = Client::new(&self.name)?;
+        client.property_namespace = self.property_namespace.clone();
+        Ok(client)
+    }
+
     /// Creates an output port through which the client may send outgoing MIDI messages to any MIDI destination.
     /// See [MIDIOutputPortCreate](https://developer.apple.com/documentation/coremidi/1495166-midioutputportcreate).
     ///
@@ -141,6 +229,13 @@ impl Client {
         })
     }
 
+    /// Creates an output port like [Client::output_port], but telling why it failed with a [MidiError]
+    /// (see [MidiError::from_port_status]).
+    ///
+    pub fn output_port_checked(&self, name: &str) -> Result<OutputPort, MidiError> {
+        self.output_port(name).map_err(MidiError::from_port_status)
+    }
+
     /// Creates an input port through which the client may receive incoming MIDI 1.0 messages from any MIDI source.
     /// See [MIDIInputPortCreate](https://developer.apple.com/documentation/coremidi/1495225-midiinputportcreate).
     ///
@@ -152,6 +247,13 @@ impl Client {
     {
         let port_name = CFString::new(name);
         let mut port_ref = MaybeUninit::uninit();
+        #[cfg(feature = "metrics")]
+        let (metrics, callback) = PortMetrics::wrap_packets(callback);
+        #[cfg(not(feature = "metrics"))]
+        let callback = {
+            let mut callback = callback;
+            move |packet_list: &PacketList, _: Option<&()>| (callback)(packet_list)
+        };
         let read_block = Self::read_block(callback);
         let status = unsafe {
             MIDIInputPortCreateWithBlock(
@@ -163,10 +265,24 @@ impl Client {
         };
         result_from_status(status, || {
             let port_ref = unsafe { port_ref.assume_init() };
-            InputPort::new(port_ref)
+            let port = InputPort::new(port_ref);
+            #[cfg(feature = "metrics")]
+            let port = port.with_metrics(metrics);
+            port
         })
     }
 
+    /// Creates an input port like [Client::input_port], but telling why it failed with a [MidiError]
+    /// (see [MidiError::from_port_status]).
+    ///
+    pub fn input_port_checked<F>(&self, name: &str, callback: F) -> Result<InputPort, MidiError>
+    where
+        F: FnMut(&PacketList) + Send + 'static,
+    {
+        self.input_port(name, callback)
+            .map_err(MidiError::from_port_status)
+    }
+
     /// Creates an input port through which the client may receive incoming MIDI messages from any MIDI source.
     /// It allows to choose which MIDI [Protocol] to use.
     /// See [MIDIInputPortCreateWithProtocol](https://developer.apple.com/documentation/coremidi/3566488-midiinputportcreatewithprotocol).
@@ -182,7 +298,27 @@ impl Client {
     {
         let port_name = CFString::new(name);
         let mut port_ref = MaybeUninit::uninit();
-        let receive_block = Self::receive_block::<T, _>(callback);
+        #[cfg(feature = "metrics")]
+        let metrics = Arc::new(PortMetrics::default());
+        #[cfg(feature = "metrics")]
+        let callback = {
+            let metrics = metrics.clone();
+            let mut callback = callback;
+            move |event_list: &EventList, connection: &mut Connection<T>| {
+                let context = &mut connection.context;
+                metrics.receive_events(event_list, connection.metrics.as_deref(), |event_list| {
+                    callback(event_list, context)
+                })
+            }
+        };
+        #[cfg(not(feature = "metrics"))]
+        let callback = {
+            let mut callback = callback;
+            move |event_list: &EventList, connection: &mut Connection<T>| {
+                (callback)(event_list, &mut connection.context)
+            }
+        };
+        let receive_block = Self::receive_block::<Connection<T>, _>(callback);
         let status = unsafe {
             MIDIInputPortCreateWithProtocol(
                 self.object.0,
@@ -194,7 +330,78 @@ impl Client {
         };
         result_from_status(status, || {
             let port_ref = unsafe { port_ref.assume_init() };
-            InputPortWithContext::<T>::new(port_ref)
+            let port = InputPortWithContext::<T>::new(port_ref);
+            #[cfg(feature = "metrics")]
+            let port = port.with_metrics(metrics);
+            port
+        })
+    }
+
+    /// Creates an input port like [Client::input_port_with_protocol], but telling why it failed with a [MidiError]
+    /// (see [MidiError::from_port_status]).
+    ///
+    pub fn input_port_with_protocol_checked<T, F>(
+        &self,
+        name: &str,
+        protocol: Protocol,
+        callback: F,
+    ) -> Result<InputPortWithContext<T>, MidiError>
+    where
+        F: FnMut(&EventList, &mut T) + Send + 'static,
+    {
+        self.input_port_with_protocol(name, protocol, callback)
+            .map_err(MidiError::from_port_status)
+    }
+
+    /// Creates an input port like [Client::input_port_with_protocol], but dropping the messages
+    /// rejected by the filter before the callback is called.
+    ///
+    /// The callback is not called at all when all the messages of an event list are dropped.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Client, MessageFilter, MessageKind, Protocol};
+    /// let client = Client::new("example-client").unwrap();
+    /// let filter = MessageFilter::new()
+    ///     .with_dropped(MessageKind::Clock)
+    ///     .with_dropped(MessageKind::ActiveSensing);
+    /// let input_port = client.input_port_with_filter("example-port", Protocol::Midi10, filter, |event_list, _: &mut ()| {
+    ///     println!("{:?}", event_list);
+    /// }).unwrap();
+    /// ```
+    ///
+    pub fn input_port_with_filter<T, F>(
+        &self,
+        name: &str,
+        protocol: Protocol,
+        mut filter: MessageFilter,
+        mut callback: F,
+    ) -> Result<InputPortWithContext<T>, OSStatus>
+    where
+        F: FnMut(&EventList, &mut T) + Send + 'static,
+    {
+        // Allocated upfront so that the callback doesn't allocate on the real-time thread:
+        // a received event packet has at most 64 words, and the filtered list is never larger than the received one,
+        // which is sized like the largest packet list.
+        let mut buffer = EventBuffer::with_capacity(MAX_PACKET_LIST_SIZE, protocol);
+        let mut words = Vec::with_capacity(64);
+        self.input_port_with_protocol(name, protocol, move |event_list, context: &mut T| {
+            if event_list
+                .iter()
+                .all(|packet| filter.keeps_all_ump(packet.data()))
+            {
+                return callback(event_list, context);
+            }
+            buffer.clear();
+            for packet in event_list.iter() {
+                words.clear();
+                transform_ump_data(&mut filter, packet.data(), &mut words);
+                if !words.is_empty() {
+                    buffer.push(packet.timestamp(), &words);
+                }
+            }
+            if !buffer.is_empty() {
+                callback(&buffer, context);
+            }
         })
     }
 
@@ -225,14 +432,14 @@ impl Client {
     pub fn virtual_destination<F>(
         &self,
         name: &str,
-        callback: F,
+        mut callback: F,
     ) -> Result<VirtualDestination, OSStatus>
     where
         F: FnMut(&PacketList) + Send + 'static,
     {
         let virtual_destination_name = CFString::new(name);
         let mut virtual_destination = MaybeUninit::uninit();
-        let read_block = Self::read_block(callback);
+        let read_block = Self::read_block::<(), _>(move |packet_list, _| (callback)(packet_list));
         let status = unsafe {
             MIDIDestinationCreateWithBlock(
                 self.object.0,
@@ -279,10 +486,46 @@ impl Client {
         })
     }
 
-    fn notify_block(callback: NotifyCallback) -> RcBlock<(*const MIDINotification,), ()> {
+    /// The cache for endpoint display names and unique ids owned by this client.
+    ///
+    /// It is only available for clients created with [Client::new_with_notifications],
+    /// as it relies on the notifications to invalidate its entries.
+    ///
+    pub fn endpoint_cache(&self) -> Option<&EndpointCache> {
+        self.endpoint_cache.as_deref()
+    }
+
+    /// Sets the namespace used to prefix the keys of the properties set with
+    /// [Object::set_app_property](crate::Object::set_app_property).
+    ///
+    /// CoreMIDI recommends using a reverse-DNS name (e.g. `com.example.my-app`) for custom properties,
+    /// so they don't collide with the ones from other applications.
+    /// By default it is [Client::DEFAULT_PROPERTY_NAMESPACE].
+    ///
+    pub fn set_property_namespace(&mut self, namespace: &str) {
+        self.property_namespace = namespace.trim_end_matches('.').to_string();
+    }
+
+    /// Get the namespace used to prefix the keys of the app properties.
+    ///
+    pub fn property_namespace(&self) -> &str {
+        &self.property_namespace
+    }
+
+    pub(crate) fn app_property_name(&self, key: &str) -> String {
+        format!("{}.{}", self.property_namespace, key)
+    }
+
+    fn notify_block(
+        callback: NotifyCallback,
+        endpoint_cache: Arc<EndpointCache>,
+        property_watches: PropertyWatchesDispatch,
+    ) -> RcBlock<(*const MIDINotification,), ()> {
         let notify_block = block::ConcreteBlock::new(move |message: *const MIDINotification| {
             let message = unsafe { &*message };
             if let Ok(notification) = Notification::try_from(message) {
+                endpoint_cache.handle_notification(&notification);
+                property_watches.dispatch(&notification);
                 match &callback {
                     NotifyCallback::ByReference(f) => (f.borrow_mut())(&notification),
                     NotifyCallback::ByOwnership(f) => (f.borrow_mut())(notification),
@@ -292,15 +535,14 @@ impl Client {
         notify_block.copy()
     }
 
-    fn read_block<F>(callback: F) -> RcBlock<(*const MIDIPacketList, *mut c_void), ()>
+    fn read_block<T, F>(callback: F) -> RcBlock<(*const MIDIPacketList, *mut c_void), ()>
     where
-        F: FnMut(&PacketList) + Send + 'static,
+        F: FnMut(&PacketList, Option<&T>) + Send + 'static,
     {
-        let callback = RefCell::new(callback);
+        let callback = RtSafe::new(callback);
         let read_block = block::ConcreteBlock::new(
-            move |pktlist: *const MIDIPacketList, _src_conn_ref_con: *mut c_void| {
-                let packet_list = unsafe { &*(pktlist as *const PacketList) };
-                (callback.borrow_mut())(packet_list);
+            move |pktlist: *const MIDIPacketList, src_conn_ref_con: *mut c_void| unsafe {
+                callback.read::<T>(pktlist, src_conn_ref_con)
             },
         );
         read_block.copy()
@@ -310,12 +552,10 @@ impl Client {
     where
         F: FnMut(&EventList, &mut T) + Send + 'static,
     {
-        let callback = RefCell::new(callback);
+        let callback = RtSafe::new(callback);
         let receive_block = block::ConcreteBlock::new(
-            move |evtlist: *const MIDIEventList, src_conn_ref_con: *mut c_void| {
-                let event_list = unsafe { &*(evtlist as *const EventList) };
-                let context = unsafe { &mut *(src_conn_ref_con as *mut T) };
-                (callback.borrow_mut())(event_list, context);
+            move |evtlist: *const MIDIEventList, src_conn_ref_con: *mut c_void| unsafe {
+                callback.receive::<T>(evtlist, src_conn_ref_con)
             },
         );
         receive_block.copy()
@@ -342,3 +582,26 @@ impl Deref for Client {
 //         unsafe { MIDIClientDispose(self.object.0) };
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use crate::Client;
+
+    #[test]
+    fn default_property_namespace() {
+        let client = Client::new("Test Client").unwrap();
+
+        assert_eq!(client.property_namespace(), "com.github.chris-zen.coremidi");
+    }
+
+    #[test]
+    fn recreate_client_with_its_name_and_property_namespace() {
+        let mut client = Client::new("Test Client").unwrap();
+        client.set_property_namespace("com.example.test");
+
+        let client = client.recreate().unwrap();
+
+        assert_eq!(client.name().as_deref(), Some("Test Client"));
+        assert_eq!(client.property_namespace(), "com.example.test");
+    }
+}
@@ -0,0 +1,275 @@
+use std::time::Duration;
+
+use crate::{duration_from_host_time, host_time_now, EventList, PacketList, Timestamp};
+
+/// The number of MIDI clocks per quarter note (beat).
+///
+pub const CLOCKS_PER_BEAT: u32 = 24;
+
+const TIMING_CLOCK: u8 = 0xf8;
+const START: u8 = 0xfa;
+const CONTINUE: u8 = 0xfb;
+const STOP: u8 = 0xfc;
+
+// How much a new interval moves the estimate, the lower the smoother (and slower to follow tempo changes).
+const SMOOTHING: f64 = 0.1;
+// The intervals further away than this ratio from the estimate are considered jitter and skipped,
+// unless there are too many of them in a row, meaning that the tempo actually changed.
+const MAX_DEVIATION: f64 = 0.5;
+const MAX_OUTLIERS: u32 = 6;
+
+type BeatCallback = Box<dyn FnMut(u64, Timestamp) + Send>;
+
+/// Follows the MIDI clock (0xF8) sent by an external device, estimating its tempo.
+///
+/// It is fed from the callback of an input port, and keeps a smoothed estimation of the tempo
+/// filtering the jitter of the clock messages. The Start, Continue and Stop messages are followed too,
+/// and an optional callback is called on every beat (every [CLOCKS_PER_BEAT] clocks) with the number of
+/// the beat since the start, and the timestamp of the clock starting it.
+///
+/// ```rust,no_run
+/// use std::sync::{Arc, Mutex};
+/// use coremidi::{ClockReceiver, Client, Source};
+/// let receiver = Arc::new(Mutex::new(ClockReceiver::new().with_beat_callback(|beat, _timestamp| {
+///     println!("Beat {}", beat);
+/// })));
+/// let callback_receiver = receiver.clone();
+/// let client = Client::new("example-client").unwrap();
+/// let input_port = client.input_port("example-port", move |packet_list| {
+///     callback_receiver.lock().unwrap().process_packets(packet_list);
+/// }).unwrap();
+/// input_port.connect_source(&Source::from_index(0).unwrap()).unwrap();
+/// // Later on:
+/// println!("{:?} BPM", receiver.lock().unwrap().bpm());
+/// ```
+///
+pub struct ClockReceiver {
+    running: bool,
+    last_clock: Option<Timestamp>,
+    // The smoothed interval between clocks, in nanoseconds
+    interval: Option<f64>,
+    outliers: u32,
+    clocks: u64,
+    beat_callback: Option<BeatCallback>,
+}
+
+impl ClockReceiver {
+    pub fn new() -> Self {
+        Self {
+            running: false,
+            last_clock: None,
+            interval: None,
+            outliers: 0,
+            clocks: 0,
+            beat_callback: None,
+        }
+    }
+
+    /// Call `callback` on every beat, with the number of the beat since the start and its timestamp.
+    ///
+    pub fn with_beat_callback<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(u64, Timestamp) + Send + 'static,
+    {
+        self.beat_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Process the realtime messages in a list of MIDI 1.0 packets, ignoring the rest of the data.
+    ///
+    pub fn process_packets(&mut self, packet_list: &PacketList) {
+        for packet in packet_list.iter() {
+            let timestamp = packet.timestamp();
+            // Realtime messages can appear anywhere, even in the middle of other messages
+            for status in packet.data().iter().filter(|byte| **byte >= TIMING_CLOCK) {
+                self.process(timestamp, *status);
+            }
+        }
+    }
+
+    /// Process the system realtime messages in a list of Universal MIDI Packets, ignoring the rest.
+    ///
+    pub fn process_events(&mut self, event_list: &EventList) {
+        for packet in event_list.iter() {
+            let timestamp = packet.timestamp();
+            for word in packet.data() {
+                if word >> 28 == 0x1 {
+                    self.process(timestamp, (word >> 16) as u8);
+                }
+            }
+        }
+    }
+
+    /// Process a single message given its status byte. A timestamp of 0 means now.
+    ///
+    pub fn process(&mut self, timestamp: Timestamp, status: u8) {
+        let timestamp = if timestamp == 0 {
+            host_time_now()
+        } else {
+            timestamp
+        };
+        match status {
+            TIMING_CLOCK => self.clock(timestamp),
+            START => {
+                self.running = true;
+                self.clocks = 0;
+            }
+            CONTINUE => self.running = true,
+            STOP => self.running = false,
+            _ => {}
+        }
+    }
+
+    /// The estimated tempo in beats per minute, once at least two clocks have been received.
+    ///
+    pub fn bpm(&self) -> Option<f64> {
+        self.interval
+            .map(|interval| 60.0e9 / (interval * CLOCKS_PER_BEAT as f64))
+    }
+
+    /// The estimated duration of a beat.
+    ///
+    pub fn beat_duration(&self) -> Option<Duration> {
+        self.interval
+            .map(|interval| Duration::from_nanos((interval * CLOCKS_PER_BEAT as f64) as u64))
+    }
+
+    /// Whether the clock has been started (or continued) and not stopped.
+    ///
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// The position within the current beat, from 0 to [CLOCKS_PER_BEAT] - 1.
+    ///
+    pub fn phase(&self) -> u32 {
+        (self.clocks % CLOCKS_PER_BEAT as u64) as u32
+    }
+
+    /// The number of beats since the start.
+    ///
+    pub fn beats(&self) -> u64 {
+        self.clocks / CLOCKS_PER_BEAT as u64
+    }
+
+    fn clock(&mut self, timestamp: Timestamp) {
+        if let Some(last_clock) = self.last_clock {
+            let interval = duration_from_host_time(timestamp.saturating_sub(last_clock));
+            self.update_interval(interval.as_nanos() as f64);
+        }
+        self.last_clock = Some(timestamp);
+
+        if self.running {
+            if self.phase() == 0 {
+                let beat = self.beats();
+                if let Some(callback) = self.beat_callback.as_mut() {
+                    callback(beat, timestamp);
+                }
+            }
+            self.clocks += 1;
+        }
+    }
+
+    fn update_interval(&mut self, interval: f64) {
+        if interval <= 0.0 {
+            return;
+        }
+        match self.interval {
+            Some(estimate) => {
+                let deviation = (interval - estimate).abs() / estimate;
+                if deviation > MAX_DEVIATION && self.outliers < MAX_OUTLIERS {
+                    self.outliers += 1;
+                } else if deviation > MAX_DEVIATION {
+                    self.outliers = 0;
+                    self.interval = Some(interval);
+                } else {
+                    self.outliers = 0;
+                    self.interval = Some(estimate + SMOOTHING * (interval - estimate));
+                }
+            }
+            None => self.interval = Some(interval),
+        }
+    }
+}
+
+impl Default for ClockReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::clock::{ClockReceiver, CLOCKS_PER_BEAT};
+    use crate::{host_time_from_duration, EventBuffer, PacketBuffer, Protocol};
+
+    // The interval between clocks at 120 BPM
+    fn interval() -> u64 {
+        host_time_from_duration(Duration::from_nanos(500_000_000 / CLOCKS_PER_BEAT as u64))
+    }
+
+    #[test]
+    fn estimate_tempo_with_jitter() {
+        let mut receiver = ClockReceiver::new();
+        let mut timestamp = 1_000;
+        for clock in 0..96u64 {
+            let jitter = if clock % 2 == 0 { 0 } else { interval() / 10 };
+            receiver.process(timestamp + jitter, 0xf8);
+            timestamp += interval();
+        }
+
+        let bpm = receiver.bpm().unwrap();
+        assert!((bpm - 120.0).abs() < 1.0, "{}", bpm);
+    }
+
+    #[test]
+    fn skip_outliers() {
+        let mut receiver = ClockReceiver::new();
+        let mut timestamp = 1_000;
+        for clock in 0..48 {
+            // A late clock
+            let delay = if clock == 30 { interval() * 3 } else { 0 };
+            timestamp += interval() + delay;
+            receiver.process(timestamp, 0xf8);
+        }
+
+        let bpm = receiver.bpm().unwrap();
+        assert!((bpm - 120.0).abs() < 1.0, "{}", bpm);
+    }
+
+    #[test]
+    fn beats_from_packets() {
+        let beats = Arc::new(Mutex::new(Vec::new()));
+        let callback_beats = beats.clone();
+        let mut receiver = ClockReceiver::new()
+            .with_beat_callback(move |beat, _| callback_beats.lock().unwrap().push(beat));
+
+        let mut packets = PacketBuffer::new(1, &[0xfa]);
+        for clock in 0..50 {
+            packets.push_data(1 + clock * interval(), &[0xf8]);
+        }
+        receiver.process_packets(&packets);
+
+        assert!(receiver.is_running());
+        assert_eq!(receiver.beats(), 2);
+        assert_eq!(receiver.phase(), 2);
+        assert_eq!(*beats.lock().unwrap(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn start_and_stop_from_events() {
+        let mut receiver = ClockReceiver::new();
+        let events = EventBuffer::new(Protocol::Midi20)
+            .with_packet(1, &[0x10fa0000])
+            .with_packet(2, &[0x10f80000])
+            .with_packet(3, &[0x10fc0000]);
+
+        receiver.process_events(&events);
+
+        assert!(!receiver.is_running());
+        assert_eq!(receiver.phase(), 1);
+    }
+}
@@ -0,0 +1,469 @@
+use std::time::Duration;
+
+use crate::transforms::for_each_midi1_message;
+use crate::{duration_from_host_time, host_time_now, PacketList, Timestamp};
+
+const DATA_ENTRY_MSB: u8 = 6;
+const DATA_ENTRY_LSB: u8 = 38;
+const DATA_INCREMENT: u8 = 96;
+const DATA_DECREMENT: u8 = 97;
+const NRPN_LSB: u8 = 98;
+const NRPN_MSB: u8 = 99;
+const RPN_LSB: u8 = 100;
+const RPN_MSB: u8 = 101;
+
+/// The number of a multi-message parameter, selected with CC 101/100 or CC 99/98.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParameterNumber {
+    Registered(u16),
+    NonRegistered(u16),
+}
+
+/// A change of the value of a parameter, as reassembled by a [ParameterReceiver].
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParameterChange {
+    pub channel: u8,
+    pub parameter: ParameterNumber,
+    /// The 14 bits value.
+    pub value: u16,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ChannelParameter {
+    registered: bool,
+    msb: Option<u8>,
+    lsb: Option<u8>,
+    value: u16,
+}
+
+impl ChannelParameter {
+    fn select(&mut self, registered: bool, msb: Option<u8>, lsb: Option<u8>) {
+        if self.registered != registered {
+            self.msb = None;
+            self.lsb = None;
+        }
+        self.registered = registered;
+        self.msb = msb.or(self.msb);
+        self.lsb = lsb.or(self.lsb);
+        self.value = 0;
+    }
+
+    fn parameter(&self) -> Option<ParameterNumber> {
+        let number = ((self.msb? as u16) << 7) | self.lsb? as u16;
+        match (self.registered, number) {
+            // The null RPN deselects the parameter
+            (true, 0x3fff) => None,
+            (true, number) => Some(ParameterNumber::Registered(number)),
+            (false, number) => Some(ParameterNumber::NonRegistered(number)),
+        }
+    }
+}
+
+/// Reassembles the values of the Registered and Non-Registered Parameter Numbers
+/// from the Control Change messages received.
+///
+/// The parameter is selected per channel with CC 101/100 (RPN) or 99/98 (NRPN), and then its value
+/// is set with CC 6 (MSB) and optionally CC 38 (LSB), or changed with CC 96/97 (increment/decrement).
+/// Every one of these messages produces a [ParameterChange] with the value so far.
+///
+/// ```
+/// use coremidi::{PacketBuffer, ParameterNumber, ParameterReceiver};
+/// let mut receiver = ParameterReceiver::new();
+/// let mut packets = PacketBuffer::with_capacity(0);
+/// packets.push_nrpn(0, 0, 0x0102, 1000);
+/// let mut changes = Vec::new();
+/// receiver.process_packets(&packets, |change| changes.push(change));
+/// let last = changes.last().unwrap();
+/// assert_eq!(last.parameter, ParameterNumber::NonRegistered(0x0102));
+/// assert_eq!(last.value, 1000);
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct ParameterReceiver {
+    channels: [ChannelParameter; 16],
+}
+
+impl ParameterReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process all the messages of a list of MIDI 1.0 packets, calling `on_change` for every parameter change.
+    ///
+    pub fn process_packets<F>(&mut self, packet_list: &PacketList, mut on_change: F)
+    where
+        F: FnMut(ParameterChange),
+    {
+        for packet in packet_list.iter() {
+            for_each_midi1_message(packet.data(), |message| {
+                if let Some(change) = self.process(message) {
+                    on_change(change);
+                }
+            });
+        }
+    }
+
+    /// Process a single MIDI 1.0 message, starting with its status byte,
+    /// returning the parameter change it produced if any.
+    ///
+    pub fn process(&mut self, message: &[u8]) -> Option<ParameterChange> {
+        let (status, controller, value) = match message {
+            [status, controller, value] if status & 0xf0 == 0xb0 => (*status, *controller, *value),
+            _ => return None,
+        };
+        let channel = status & 0x0f;
+        let state = &mut self.channels[channel as usize];
+        match controller {
+            NRPN_MSB => state.select(false, Some(value), None),
+            NRPN_LSB => state.select(false, None, Some(value)),
+            RPN_MSB => state.select(true, Some(value), None),
+            RPN_LSB => state.select(true, None, Some(value)),
+            DATA_ENTRY_MSB => state.value = (value as u16) << 7,
+            DATA_ENTRY_LSB => state.value = (state.value & !0x7f) | value as u16,
+            DATA_INCREMENT => state.value = (state.value + 1).min(0x3fff),
+            DATA_DECREMENT => state.value = state.value.saturating_sub(1),
+            _ => return None,
+        }
+        match controller {
+            DATA_ENTRY_MSB | DATA_ENTRY_LSB | DATA_INCREMENT | DATA_DECREMENT => {
+                state.parameter().map(|parameter| ParameterChange {
+                    channel,
+                    parameter,
+                    value: state.value,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The parameter currently selected in a channel.
+    ///
+    pub fn parameter(&self, channel: u8) -> Option<ParameterNumber> {
+        self.channels[(channel & 0x0f) as usize].parameter()
+    }
+}
+
+/// A change of the 14 bits value of a controller from 0 to 31, as paired by a [ControllerPairReceiver].
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControllerChange {
+    pub channel: u8,
+    /// The controller of the MSB, from 0 to 31.
+    pub controller: u8,
+    pub value: u16,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ControllerPair {
+    msb: u8,
+    // When the MSB was received, while waiting for its LSB
+    pending_since: Option<Timestamp>,
+}
+
+/// Pairs the MSB and LSB Control Change messages of the controllers 0 to 31 (with 32 to 63)
+/// into 14 bits values.
+///
+/// A value is produced when the LSB arrives after its MSB. As some devices only send the MSB,
+/// when the LSB doesn't arrive within the timeout, the orphaned MSB is produced alone (with an LSB of 0).
+/// Sending only the LSB updates the value using the last MSB received.
+///
+/// The timeout is checked when processing the following messages, and can be checked at any time with
+/// [poll](ControllerPairReceiver::poll).
+///
+/// ```
+/// use coremidi::{ControllerChange, ControllerPairReceiver, PacketBuffer};
+/// let mut receiver = ControllerPairReceiver::new();
+/// let mut packets = PacketBuffer::with_capacity(0);
+/// packets.push_cc_14bit(1, 0, 7, 1000);
+/// let mut changes = Vec::new();
+/// receiver.process_packets(&packets, |change| changes.push(change));
+/// assert_eq!(changes, vec![ControllerChange { channel: 0, controller: 7, value: 1000 }]);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct ControllerPairReceiver {
+    controllers: [[ControllerPair; 32]; 16],
+    pending: usize,
+    timeout: Duration,
+}
+
+impl ControllerPairReceiver {
+    /// The default time to wait for the LSB after the MSB.
+    ///
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(10);
+
+    pub fn new() -> Self {
+        Self {
+            controllers: [[ControllerPair::default(); 32]; 16],
+            pending: 0,
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Process all the messages of a list of MIDI 1.0 packets, calling `on_change` for every value produced.
+    ///
+    pub fn process_packets<F>(&mut self, packet_list: &PacketList, mut on_change: F)
+    where
+        F: FnMut(ControllerChange),
+    {
+        for packet in packet_list.iter() {
+            let timestamp = packet.timestamp();
+            for_each_midi1_message(packet.data(), |message| {
+                self.process(timestamp, message, &mut on_change)
+            });
+        }
+    }
+
+    /// Process a single MIDI 1.0 message, starting with its status byte. A timestamp of 0 means now.
+    ///
+    pub fn process<F>(&mut self, timestamp: Timestamp, message: &[u8], mut on_change: F)
+    where
+        F: FnMut(ControllerChange),
+    {
+        let timestamp = if timestamp == 0 {
+            host_time_now()
+        } else {
+            timestamp
+        };
+        self.poll(timestamp, &mut on_change);
+
+        let (status, controller, value) = match message {
+            [status, controller, value] if status & 0xf0 == 0xb0 => (*status, *controller, *value),
+            _ => return,
+        };
+        let channel = status & 0x0f;
+        let controllers = &mut self.controllers[channel as usize];
+        match controller {
+            0..=31 => {
+                let pair = &mut controllers[controller as usize];
+                if pair.pending_since.take().is_some() {
+                    // The previous MSB didn't get its LSB
+                    on_change(ControllerChange {
+                        channel,
+                        controller,
+                        value: (pair.msb as u16) << 7,
+                    });
+                    self.pending -= 1;
+                }
+                pair.msb = value;
+                pair.pending_since = Some(timestamp);
+                self.pending += 1;
+            }
+            32..=63 => {
+                let controller = controller - 32;
+                let pair = &mut controllers[controller as usize];
+                if pair.pending_since.take().is_some() {
+                    self.pending -= 1;
+                }
+                on_change(ControllerChange {
+                    channel,
+                    controller,
+                    value: ((pair.msb as u16) << 7) | value as u16,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Produce the orphaned MSBs that have been waiting for their LSB longer than the timeout at host time `now`.
+    ///
+    pub fn poll<F>(&mut self, now: Timestamp, mut on_change: F)
+    where
+        F: FnMut(ControllerChange),
+    {
+        if self.pending == 0 {
+            return;
+        }
+        for (channel, controllers) in self.controllers.iter_mut().enumerate() {
+            for (controller, pair) in controllers.iter_mut().enumerate() {
+                let expired = matches!(pair.pending_since, Some(since)
+                    if duration_from_host_time(now.saturating_sub(since)) > self.timeout);
+                if expired {
+                    pair.pending_since = None;
+                    self.pending -= 1;
+                    on_change(ControllerChange {
+                        channel: channel as u8,
+                        controller: controller as u8,
+                        value: (pair.msb as u16) << 7,
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl Default for ControllerPairReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The Channel Mode messages (CC 120 to 127), changing how a device responds to a channel.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelMode {
+    AllSoundOff,
+    ResetAllControllers,
+    /// Whether the keyboard of the device plays its own sound generator.
+    LocalControl(bool),
+    AllNotesOff,
+    OmniOff,
+    OmniOn,
+    /// Mono mode with a number of channels, where 0 means as many as voices the device has.
+    Mono(u8),
+    Poly,
+}
+
+impl ChannelMode {
+    pub fn controller(&self) -> u8 {
+        match self {
+            ChannelMode::AllSoundOff => 120,
+            ChannelMode::ResetAllControllers => 121,
+            ChannelMode::LocalControl(_) => 122,
+            ChannelMode::AllNotesOff => 123,
+            ChannelMode::OmniOff => 124,
+            ChannelMode::OmniOn => 125,
+            ChannelMode::Mono(_) => 126,
+            ChannelMode::Poly => 127,
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match self {
+            ChannelMode::LocalControl(true) => 127,
+            ChannelMode::Mono(channels) => (*channels).min(16),
+            _ => 0,
+        }
+    }
+
+    /// The Channel Mode message of a Control Change, if it is one.
+    ///
+    pub fn from_cc(controller: u8, value: u8) -> Option<Self> {
+        let mode = match controller {
+            120 => ChannelMode::AllSoundOff,
+            121 => ChannelMode::ResetAllControllers,
+            122 => ChannelMode::LocalControl(value >= 64),
+            123 => ChannelMode::AllNotesOff,
+            124 => ChannelMode::OmniOff,
+            125 => ChannelMode::OmniOn,
+            126 => ChannelMode::Mono(value),
+            127 => ChannelMode::Poly,
+            _ => return None,
+        };
+        Some(mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::controllers::{
+        ChannelMode, ControllerChange, ControllerPairReceiver, ParameterChange, ParameterNumber,
+        ParameterReceiver,
+    };
+    use crate::{host_time_from_duration, PacketBuffer};
+
+    fn changes(receiver: &mut ParameterReceiver, packets: &PacketBuffer) -> Vec<ParameterChange> {
+        let mut changes = Vec::new();
+        receiver.process_packets(packets, |change| changes.push(change));
+        changes
+    }
+
+    #[test]
+    fn reassemble_rpn_with_running_status() {
+        let mut receiver = ParameterReceiver::new();
+        // Pitch bend sensitivity, with running status and a data increment
+        let packets = PacketBuffer::new(0, &[0xb1, 101, 0, 100, 0, 6, 2, 38, 50, 96, 0]);
+
+        let parameter = ParameterNumber::Registered(0);
+        assert_eq!(
+            changes(&mut receiver, &packets),
+            vec![
+                ParameterChange {
+                    channel: 1,
+                    parameter,
+                    value: 2 << 7
+                },
+                ParameterChange {
+                    channel: 1,
+                    parameter,
+                    value: (2 << 7) | 50
+                },
+                ParameterChange {
+                    channel: 1,
+                    parameter,
+                    value: (2 << 7) | 51
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ignore_data_entry_without_parameter() {
+        let mut receiver = ParameterReceiver::new();
+        let mut packets = PacketBuffer::new(0, &[0xb0, 6, 10]);
+        packets.push_rpn(1, 0, 0x3fff, 0).push_cc(2, 0, 6, 10);
+
+        assert_eq!(changes(&mut receiver, &packets), vec![]);
+        assert_eq!(receiver.parameter(0), None);
+    }
+
+    #[test]
+    fn pair_controllers_with_orphaned_msb() {
+        let ms = host_time_from_duration(Duration::from_millis(1));
+        let mut receiver = ControllerPairReceiver::new().with_timeout(Duration::from_millis(5));
+        let mut changes = Vec::new();
+        let mut on_change = |change| changes.push(change);
+
+        receiver.process(ms, &[0xb2, 1, 0x10], &mut on_change);
+        receiver.process(2 * ms, &[0xb2, 33, 0x01], &mut on_change);
+        // An MSB without LSB, and then an LSB alone
+        receiver.process(3 * ms, &[0xb2, 1, 0x20], &mut on_change);
+        receiver.poll(10 * ms, &mut on_change);
+        receiver.process(11 * ms, &[0xb2, 33, 0x02], &mut on_change);
+
+        let change = |value| ControllerChange {
+            channel: 2,
+            controller: 1,
+            value,
+        };
+        assert_eq!(
+            changes,
+            vec![
+                change(0x10 << 7 | 0x01),
+                change(0x20 << 7),
+                change(0x20 << 7 | 0x02)
+            ]
+        );
+    }
+
+    #[test]
+    fn channel_modes() {
+        let modes = [
+            ChannelMode::AllSoundOff,
+            ChannelMode::ResetAllControllers,
+            ChannelMode::LocalControl(true),
+            ChannelMode::LocalControl(false),
+            ChannelMode::AllNotesOff,
+            ChannelMode::OmniOff,
+            ChannelMode::OmniOn,
+            ChannelMode::Mono(4),
+            ChannelMode::Poly,
+        ];
+        for mode in modes {
+            assert_eq!(
+                ChannelMode::from_cc(mode.controller(), mode.value()),
+                Some(mode)
+            );
+        }
+        assert_eq!(ChannelMode::from_cc(64, 127), None);
+    }
+}
@@ -1,7 +1,36 @@
-use coremidi_sys::MIDIObjectRef;
+use core_foundation::{
+    base::{OSStatus, TCFType},
+    string::CFString,
+};
+use coremidi_sys::{
+    ItemCount, MIDIDeviceGetEntity, MIDIDeviceGetNumberOfEntities, MIDIDeviceRef, MIDIGetDevice,
+    MIDIGetExternalDevice, MIDIGetNumberOfDevices, MIDIGetNumberOfExternalDevices, MIDIObjectRef,
+};
+use std::mem::{self, MaybeUninit};
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::ptr;
 
+use crate::availability::MIDIDeviceNewEntity;
+use crate::entity::Entity;
+use crate::info::DeviceInfo;
 use crate::object::Object;
+use crate::properties::{Properties, PropertyGetter, PropertySetter};
+use crate::sys::{
+    MIDIDeviceAddEntity, MIDIDeviceCreate, MIDIDeviceDispose, MIDIExternalDeviceCreate,
+    MIDISetupAddDevice, MIDISetupAddExternalDevice, MIDISetupRemoveDevice,
+    MIDISetupRemoveExternalDevice,
+};
+use crate::{
+    result_from_status, unit_result_from_status, Destination, Destinations, Protocol, Source,
+    Sources,
+};
+
+/// The status returned when a file does not exist (`fnfErr`).
+const FILE_NOT_FOUND: OSStatus = -43;
+
+/// The status returned when a parameter is not valid (`paramErr`).
+const INVALID_PARAMETER: OSStatus = -50;
 
 /// A [MIDI object](https://developer.apple.com/documentation/coremidi/midideviceref).
 ///
@@ -18,6 +47,249 @@ impl Device {
             object: Object(object_ref),
         }
     }
+
+    /// Create a device from a raw device reference, like the ones held by other libraries using CoreMIDI.
+    ///
+    /// # Safety
+    ///
+    /// The reference needs to be a valid `MIDIDeviceRef`, of a device or an external device.
+    /// The device doesn't own it, so it is not disposed nor removed from the setup when dropped.
+    ///
+    pub unsafe fn from_raw(device_ref: MIDIDeviceRef) -> Device {
+        Self::new(device_ref)
+    }
+
+    /// Create a new device that is not owned by any driver.
+    /// See [MIDIDeviceCreate](https://developer.apple.com/documentation/coremidi/mididevicecreate(_:_:_:_:_:)).
+    ///
+    /// The device is not visible to other clients until it is added to the setup with [OwnedDevice::add_to_setup].
+    ///
+    /// ```rust,no_run
+    /// use coremidi::Device;
+    /// let mut device = Device::create("example-device", "example-manufacturer", "example-model").unwrap();
+    /// device.add_entity("example-entity", false, 1, 1).unwrap();
+    /// device.add_to_setup().unwrap();
+    /// ```
+    ///
+    pub fn create(name: &str, manufacturer: &str, model: &str) -> Result<OwnedDevice, OSStatus> {
+        let name = CFString::new(name);
+        let manufacturer = CFString::new(manufacturer);
+        let model = CFString::new(model);
+        let mut device_ref = MaybeUninit::uninit();
+        let status = unsafe {
+            MIDIDeviceCreate(
+                ptr::null_mut(),
+                name.as_concrete_TypeRef(),
+                manufacturer.as_concrete_TypeRef(),
+                model.as_concrete_TypeRef(),
+                device_ref.as_mut_ptr(),
+            )
+        };
+        result_from_status(status, || OwnedDevice {
+            device: Device::new(unsafe { device_ref.assume_init() }),
+            external: false,
+            in_setup: false,
+        })
+    }
+
+    /// Create a new external device, representing an instrument connected to the ports of other devices.
+    /// See [MIDIExternalDeviceCreate](https://developer.apple.com/documentation/coremidi/midiexternaldevicecreate(_:_:_:_:)).
+    ///
+    /// Once added to the setup, other objects can be [connected](Object::connect_to) to it,
+    /// like "Add Device" does in the Audio MIDI Setup.
+    ///
+    pub fn create_external(
+        name: &str,
+        manufacturer: &str,
+        model: &str,
+    ) -> Result<OwnedDevice, OSStatus> {
+        let name = CFString::new(name);
+        let manufacturer = CFString::new(manufacturer);
+        let model = CFString::new(model);
+        let mut device_ref = MaybeUninit::uninit();
+        let status = unsafe {
+            MIDIExternalDeviceCreate(
+                name.as_concrete_TypeRef(),
+                manufacturer.as_concrete_TypeRef(),
+                model.as_concrete_TypeRef(),
+                device_ref.as_mut_ptr(),
+            )
+        };
+        result_from_status(status, || OwnedDevice {
+            device: Device::new(unsafe { device_ref.assume_init() }),
+            external: true,
+            in_setup: false,
+        })
+    }
+
+    /// Add an entity to the device, with a number of sources and destinations.
+    /// See [MIDIDeviceAddEntity](https://developer.apple.com/documentation/coremidi/midideviceaddentity(_:_:_:_:_:_:)).
+    ///
+    /// An embedded entity represents endpoints inside the device itself, rather than connectors to external devices.
+    ///
+    pub fn add_entity(
+        &self,
+        name: &str,
+        embedded: bool,
+        num_sources: usize,
+        num_destinations: usize,
+    ) -> Result<Entity, OSStatus> {
+        let name = CFString::new(name);
+        let mut entity_ref = MaybeUninit::uninit();
+        let status = unsafe {
+            MIDIDeviceAddEntity(
+                self.object.0,
+                name.as_concrete_TypeRef(),
+                embedded as u8,
+                num_sources as ItemCount,
+                num_destinations as ItemCount,
+                entity_ref.as_mut_ptr(),
+            )
+        };
+        result_from_status(status, || Entity::new(unsafe { entity_ref.assume_init() }))
+    }
+
+    /// Add an entity to the device like [Device::add_entity], declaring the MIDI [Protocol] used by its endpoints.
+    /// See [MIDIDeviceNewEntity](https://developer.apple.com/documentation/coremidi/mididevicenewentity(_:_:_:_:_:_:_:)).
+    ///
+    /// It allows to create entities capable of MIDI 2.0, and it requires macOS 11.0 or later.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Device, Protocol};
+    /// let mut device = Device::create("example-device", "example-manufacturer", "example-model").unwrap();
+    /// device.new_entity("example-entity", Protocol::Midi20, true, 1, 1).unwrap();
+    /// device.add_to_setup().unwrap();
+    /// ```
+    ///
+    pub fn new_entity(
+        &self,
+        name: &str,
+        protocol: Protocol,
+        embedded: bool,
+        num_sources: usize,
+        num_destinations: usize,
+    ) -> Result<Entity, OSStatus> {
+        let name = CFString::new(name);
+        let mut entity_ref = MaybeUninit::uninit();
+        let status = unsafe {
+            MIDIDeviceNewEntity(
+                self.object.0,
+                name.as_concrete_TypeRef(),
+                protocol.into(),
+                embedded as u8,
+                num_sources as ItemCount,
+                num_destinations as ItemCount,
+                entity_ref.as_mut_ptr(),
+            )
+        };
+        result_from_status(status, || Entity::new(unsafe { entity_ref.assume_init() }))
+    }
+
+    /// Remove the device from the current MIDI setup.
+    /// See [MIDISetupRemoveDevice](https://developer.apple.com/documentation/coremidi/midisetupremovedevice(_:)).
+    ///
+    /// It allows to clean up, for example at startup, the devices created and persisted in a previous run.
+    ///
+    pub fn remove_from_setup(&self) -> Result<(), OSStatus> {
+        unit_result_from_status(unsafe { MIDISetupRemoveDevice(self.object.0) })
+    }
+
+    /// Remove the external device from the current MIDI setup.
+    /// See [MIDISetupRemoveExternalDevice](https://developer.apple.com/documentation/coremidi/midisetupremoveexternaldevice(_:)).
+    ///
+    pub fn remove_external_from_setup(&self) -> Result<(), OSStatus> {
+        unit_result_from_status(unsafe { MIDISetupRemoveExternalDevice(self.object.0) })
+    }
+
+    /// Get the path to the image file for the device icon, if it has any.
+    /// See [kMIDIPropertyImage](https://developer.apple.com/documentation/coremidi/kMIDIPropertyImage)
+    ///
+    pub fn icon_path(&self) -> Option<PathBuf> {
+        Properties::image()
+            .value_from(self)
+            .ok()
+            .filter(|path: &String| !path.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// Set the image file for the device icon, which is shown in the Audio MIDI Setup.
+    /// See [kMIDIPropertyImage](https://developer.apple.com/documentation/coremidi/kMIDIPropertyImage)
+    ///
+    /// The path is made absolute, as required by CoreMIDI. It fails with `fnfErr` (-43) when the file does not exist,
+    /// and with `paramErr` (-50) when the path is not valid UTF-8.
+    ///
+    /// ```rust,no_run
+    /// let device = coremidi::Device::create("example-device", "example-manufacturer", "example-model").unwrap();
+    /// device.set_icon("resources/example-device.tiff").unwrap();
+    /// ```
+    ///
+    pub fn set_icon<P: AsRef<Path>>(&self, path: P) -> Result<(), OSStatus> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(FILE_NOT_FOUND);
+        }
+        let path = path.canonicalize().map_err(|_| FILE_NOT_FOUND)?;
+        let path = path.to_str().ok_or(INVALID_PARAMETER)?;
+        Properties::image().set_value(self, path)
+    }
+
+    /// Get the entities owned by the device.
+    /// See [MIDIDeviceGetEntity](https://developer.apple.com/documentation/coremidi/mididevicegetentity(_:_:)).
+    ///
+    pub fn entities(&self) -> Vec<Entity> {
+        let count = unsafe { MIDIDeviceGetNumberOfEntities(self.object.0) } as usize;
+        (0..count)
+            .filter_map(|index| self.entity_at(index))
+            .collect()
+    }
+
+    /// Get the entity at a given index, if there is any.
+    /// See [MIDIDeviceGetEntity](https://developer.apple.com/documentation/coremidi/mididevicegetentity(_:_:)).
+    ///
+    pub fn entity_at(&self, index: usize) -> Option<Entity> {
+        let entity_ref = unsafe { MIDIDeviceGetEntity(self.object.0, index as ItemCount) };
+        match entity_ref {
+            0 => None,
+            _ => Some(Entity::new(entity_ref)),
+        }
+    }
+
+    /// Check whether the device is offline, like the ones no longer present that the Audio MIDI Setup keeps around.
+    /// See [kMIDIPropertyOffline](https://developer.apple.com/documentation/coremidi/kMIDIPropertyOffline)
+    ///
+    pub fn is_offline(&self) -> bool {
+        Properties::offline().value_from(self).unwrap_or(false)
+    }
+
+    /// Get the sources of all the entities owned by the device.
+    ///
+    /// ```rust,no_run
+    /// for device in coremidi::Devices {
+    ///     println!("{:?}: {} sources", device.name(), device.sources().len());
+    /// }
+    /// ```
+    ///
+    pub fn sources(&self) -> Vec<Source> {
+        self.entities()
+            .iter()
+            .flat_map(|entity| entity.sources())
+            .collect()
+    }
+
+    /// Get the destinations of all the entities owned by the device.
+    ///
+    pub fn destinations(&self) -> Vec<Destination> {
+        self.entities()
+            .iter()
+            .flat_map(|entity| entity.destinations())
+            .collect()
+    }
+
+    /// Take a snapshot of the device properties, including its entities and their endpoints.
+    ///
+    pub fn info(&self) -> DeviceInfo {
+        DeviceInfo::from(self)
+    }
 }
 
 impl Clone for Device {
@@ -39,3 +311,289 @@ impl Deref for Device {
         &self.object
     }
 }
+
+/// The endpoints of the system grouped by the device that owns them,
+/// so a multi-port interface shows up once with all its inputs and outputs.
+///
+/// ```rust,no_run
+/// for ports in coremidi::DevicePorts::all() {
+///     let name = ports.device.as_ref().and_then(|device| device.name());
+///     println!("{:?}: {} inputs, {} outputs", name, ports.inputs.len(), ports.outputs.len());
+/// }
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct DevicePorts {
+    /// The device owning the endpoints, or `None` for the virtual endpoints.
+    pub device: Option<Device>,
+    pub inputs: Vec<Source>,
+    pub outputs: Vec<Destination>,
+}
+
+impl DevicePorts {
+    /// Group all the sources and destinations in the system by their device.
+    ///
+    /// The devices are listed in the order their first endpoint is found,
+    /// followed by a single group with the virtual endpoints, if there are any.
+    ///
+    pub fn all() -> Vec<DevicePorts> {
+        let mut groups: Vec<DevicePorts> = Vec::new();
+        for source in Sources {
+            let device = source.entity().and_then(|entity| entity.device());
+            Self::group_for(&mut groups, device).inputs.push(source);
+        }
+        for destination in Destinations {
+            let device = destination.entity().and_then(|entity| entity.device());
+            Self::group_for(&mut groups, device)
+                .outputs
+                .push(destination);
+        }
+        // Move the virtual endpoints to the end
+        groups.sort_by_key(|group| group.device.is_none());
+        groups
+    }
+
+    fn group_for(groups: &mut Vec<DevicePorts>, device: Option<Device>) -> &mut DevicePorts {
+        let index = match groups.iter().position(|group| group.device == device) {
+            Some(index) => index,
+            None => {
+                groups.push(DevicePorts {
+                    device,
+                    inputs: Vec::new(),
+                    outputs: Vec::new(),
+                });
+                groups.len() - 1
+            }
+        };
+        &mut groups[index]
+    }
+}
+
+/// A device created with [Device::create].
+///
+/// The device is removed from the setup (or disposed if it was never added) when dropped,
+/// unless it is explicitly [persisted](OwnedDevice::persist).
+///
+#[derive(Debug)]
+pub struct OwnedDevice {
+    device: Device,
+    external: bool,
+    in_setup: bool,
+}
+
+impl OwnedDevice {
+    /// Add the device to the current MIDI setup, making it visible to the rest of the clients.
+    /// See [MIDISetupAddDevice](https://developer.apple.com/documentation/coremidi/midisetupadddevice(_:))
+    /// and [MIDISetupAddExternalDevice](https://developer.apple.com/documentation/coremidi/midisetupaddexternaldevice(_:)).
+    ///
+    pub fn add_to_setup(&mut self) -> Result<(), OSStatus> {
+        let status = unsafe {
+            if self.external {
+                MIDISetupAddExternalDevice(self.device.object.0)
+            } else {
+                MIDISetupAddDevice(self.device.object.0)
+            }
+        };
+        unit_result_from_status(status)?;
+        self.in_setup = true;
+        Ok(())
+    }
+
+    /// Whether the device was added to the current MIDI setup.
+    ///
+    pub fn is_in_setup(&self) -> bool {
+        self.in_setup
+    }
+
+    /// Remove the device from the setup if it was added, or dispose it otherwise.
+    /// See [MIDIDeviceDispose](https://developer.apple.com/documentation/coremidi/mididevicedispose(_:)).
+    ///
+    pub fn remove(self) -> Result<(), OSStatus> {
+        let status = self.release();
+        mem::forget(self);
+        unit_result_from_status(status)
+    }
+
+    /// Keep the device in the setup after it is dropped, and even after the process exits.
+    ///
+    pub fn persist(self) -> Device {
+        let device = self.device.clone();
+        mem::forget(self);
+        device
+    }
+
+    /// Whether the device is an external device.
+    ///
+    pub fn is_external(&self) -> bool {
+        self.external
+    }
+
+    fn release(&self) -> OSStatus {
+        unsafe {
+            match (self.in_setup, self.external) {
+                (true, true) => MIDISetupRemoveExternalDevice(self.device.object.0),
+                (true, false) => MIDISetupRemoveDevice(self.device.object.0),
+                (false, _) => MIDIDeviceDispose(self.device.object.0),
+            }
+        }
+    }
+}
+
+impl Drop for OwnedDevice {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+impl Deref for OwnedDevice {
+    type Target = Device;
+
+    fn deref(&self) -> &Device {
+        &self.device
+    }
+}
+
+/// Devices available in the system, as added by the drivers.
+///
+/// The devices in the system can be iterated as:
+///
+/// ```rust,no_run
+/// for device in coremidi::Devices {
+///   println!("{:?}", device.name());
+/// }
+/// ```
+///
+pub struct Devices;
+
+impl Devices {
+    /// Get the number of devices in the system.
+    /// See [MIDIGetNumberOfDevices](https://developer.apple.com/documentation/coremidi/1495164-midigetnumberofdevices).
+    ///
+    pub fn count() -> usize {
+        unsafe { MIDIGetNumberOfDevices() as usize }
+    }
+
+    /// Iterate over the devices in the system that are online, skipping the offline ones.
+    ///
+    /// ```rust,no_run
+    /// for device in coremidi::Devices::online() {
+    ///   println!("{:?}", device.name());
+    /// }
+    /// ```
+    ///
+    pub fn online() -> impl Iterator<Item = Device> {
+        Devices.into_iter().filter(|device| !device.is_offline())
+    }
+}
+
+impl IntoIterator for Devices {
+    type Item = Device;
+    type IntoIter = DevicesIterator;
+
+    fn into_iter(self) -> Self::IntoIter {
+        DevicesIterator {
+            index: 0,
+            count: Self::count(),
+            external: false,
+        }
+    }
+}
+
+/// External devices available in the system, as added by the user in the MIDI Studio setup.
+///
+/// ```rust,no_run
+/// for device in coremidi::ExternalDevices {
+///   println!("{:?}", device.name());
+/// }
+/// ```
+///
+pub struct ExternalDevices;
+
+impl ExternalDevices {
+    /// Get the number of external devices in the system.
+    /// See [MIDIGetNumberOfExternalDevices](https://developer.apple.com/documentation/coremidi/1495303-midigetnumberofexternaldevices).
+    ///
+    pub fn count() -> usize {
+        unsafe { MIDIGetNumberOfExternalDevices() as usize }
+    }
+}
+
+impl IntoIterator for ExternalDevices {
+    type Item = Device;
+    type IntoIter = DevicesIterator;
+
+    fn into_iter(self) -> Self::IntoIter {
+        DevicesIterator {
+            index: 0,
+            count: Self::count(),
+            external: true,
+        }
+    }
+}
+
+pub struct DevicesIterator {
+    index: usize,
+    count: usize,
+    external: bool,
+}
+
+impl Iterator for DevicesIterator {
+    type Item = Device;
+
+    fn next(&mut self) -> Option<Device> {
+        if self.index < self.count {
+            let index = self.index as ItemCount;
+            let device_ref = unsafe {
+                if self.external {
+                    MIDIGetExternalDevice(index)
+                } else {
+                    MIDIGetDevice(index)
+                }
+            };
+            self.index += 1;
+            match device_ref {
+                0 => None,
+                _ => Some(Device::new(device_ref)),
+            }
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::device::{Device, DevicePorts, FILE_NOT_FOUND};
+    use crate::{Destination, Source};
+
+    #[test]
+    fn set_icon_not_found() {
+        let device = Device::new(0);
+
+        assert_eq!(
+            device.set_icon("/this/file/does/not/exist.tiff"),
+            Err(FILE_NOT_FOUND)
+        );
+    }
+
+    #[test]
+    fn device_ports_grouping() {
+        let mut groups = Vec::new();
+
+        DevicePorts::group_for(&mut groups, Some(Device::new(1)))
+            .inputs
+            .push(Source::new(10));
+        DevicePorts::group_for(&mut groups, None)
+            .inputs
+            .push(Source::new(20));
+        DevicePorts::group_for(&mut groups, Some(Device::new(1)))
+            .outputs
+            .push(Destination::new(11));
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].device, Some(Device::new(1)));
+        assert_eq!(groups[0].inputs, vec![Source::new(10)]);
+        assert_eq!(groups[0].outputs, vec![Destination::new(11)]);
+        assert_eq!(groups[1].device, None);
+    }
+}
@@ -0,0 +1,490 @@
+/*!
+Support for implementing [CoreMIDI drivers](https://developer.apple.com/documentation/coremidi/midi_drivers) in Rust.
+
+A driver is a CFPlugIn bundle loaded by the MIDI server, which calls the factory function declared
+in the bundle `Info.plist` to create an instance of the driver. That function only needs to hand over
+an implementation of the [Driver] trait to [new_driver_instance]:
+
+```rust,no_run
+use coremidi::driver::{new_driver_instance, DeviceList, Driver, DriverContext, RefCons};
+use coremidi::PacketList;
+use core_foundation_sys::{base::{CFAllocatorRef, OSStatus}, uuid::CFUUIDRef};
+use std::os::raw::c_void;
+
+struct ExampleDriver;
+
+impl Driver for ExampleDriver {
+    fn find_devices(&mut self, context: &DriverContext, devices: &DeviceList) -> Result<(), OSStatus> {
+        let device = context.create_device("example-device", "example-manufacturer", "example-model")?;
+        device.add_entity("example-entity", true, 1, 1)?;
+        devices.add(&device)
+    }
+
+    fn send(&mut self, _packet_list: &PacketList, _ref_cons: RefCons) -> Result<(), OSStatus> {
+        Ok(())
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn NewExampleDriver(_allocator: CFAllocatorRef, type_id: CFUUIDRef) -> *mut c_void {
+    unsafe { new_driver_instance(type_id, "D2A9F2E4-5E8B-4C0A-9A59-5B7E7D1C1A01", ExampleDriver) }
+}
+```
+
+Only the second version of the driver interface is supported, which exchanges the MIDI data as [PacketList]s.
+
+A panic in any of the methods of the [Driver] is caught before it reaches the MIDI server,
+and reported to it as a `kMIDIUnknownError` status.
+*/
+
+use core_foundation::{
+    base::{OSStatus, TCFType},
+    string::CFString,
+};
+use core_foundation_sys::{
+    base::{kCFAllocatorDefault, CFRelease},
+    plugin::{CFPlugInAddInstanceForFactory, CFPlugInRemoveInstanceForFactory},
+    uuid::{CFUUIDBytes, CFUUIDCreateFromString, CFUUIDGetUUIDBytes, CFUUIDRef},
+};
+use std::mem::MaybeUninit;
+use std::os::raw::c_void;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use coremidi_sys::{kMIDIUnknownError, ItemCount, MIDIDeviceRef, MIDIEndpointRef, MIDIPacketList};
+
+use crate::sys::{
+    MIDIDeviceCreate, MIDIDeviceListAddDevice, MIDIDeviceListDispose, MIDIDeviceListGetDevice,
+    MIDIDeviceListGetNumberOfDevices, MIDIDeviceListRef, MIDIDriverEnableMonitoring,
+    MIDIDriverInterface, MIDIDriverRef, MIDIEndpointGetRefCons, MIDIEndpointSetRefCons,
+    MIDIGetDriverDeviceList,
+};
+use crate::{
+    result_from_status, unit_result_from_status, Destination, Device, Endpoint, PacketList, Source,
+};
+
+/// The type id of the CoreMIDI drivers (`kMIDIDriverTypeID`).
+const DRIVER_TYPE_ID: [u8; 16] = [
+    0xEC, 0xDE, 0x95, 0x74, 0x0F, 0xE4, 0x11, 0xD4, 0xBB, 0x1A, 0x00, 0x50, 0xE4, 0xCE, 0xA5, 0x26,
+];
+
+/// The id of the second version of the driver interface (`kMIDIDriverInterface2ID`).
+const DRIVER_INTERFACE_2_ID: [u8; 16] = [
+    0x43, 0xC9, 0x8C, 0x3C, 0x30, 0x6C, 0x11, 0xD5, 0xAF, 0x73, 0x00, 0x30, 0x65, 0xA8, 0x30, 0x1E,
+];
+
+/// The id of the COM `IUnknown` interface.
+const IUNKNOWN_ID: [u8; 16] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+
+const S_OK: i32 = 0;
+const E_NOINTERFACE: i32 = 0x80000004_u32 as i32;
+
+/// The reference constants of a destination owned by a driver, which identify it when sending data.
+/// See [set_ref_cons].
+///
+pub type RefCons = (usize, usize);
+
+/// The implementation of a CoreMIDI driver.
+/// See [MIDIDriverInterface](https://developer.apple.com/documentation/coremidi/mididriverinterface).
+///
+/// All the methods are called from the MIDI server, one at a time.
+///
+pub trait Driver: Send {
+    /// Find the devices handled by the driver, adding them to the list.
+    ///
+    fn find_devices(
+        &mut self,
+        context: &DriverContext,
+        devices: &DeviceList,
+    ) -> Result<(), OSStatus>;
+
+    /// Start communicating with the devices in the list.
+    ///
+    fn start(&mut self, _context: &DriverContext, _devices: &DeviceList) -> Result<(), OSStatus> {
+        Ok(())
+    }
+
+    /// Stop communicating with the devices.
+    ///
+    fn stop(&mut self) -> Result<(), OSStatus> {
+        Ok(())
+    }
+
+    /// Configure a device. Reserved for future use by CoreMIDI.
+    ///
+    fn configure(&mut self, _device: &Device) -> Result<(), OSStatus> {
+        Ok(())
+    }
+
+    /// Send the data to the destination identified by its reference constants.
+    ///
+    fn send(&mut self, packet_list: &PacketList, ref_cons: RefCons) -> Result<(), OSStatus>;
+
+    /// Enable or disable a source, depending on whether there are clients listening to it.
+    ///
+    fn enable_source(&mut self, _source: &Source, _enabled: bool) -> Result<(), OSStatus> {
+        Ok(())
+    }
+
+    /// Stop any data pending to be sent to a destination, or to all of them when there is none.
+    ///
+    fn flush(
+        &mut self,
+        _destination: Option<&Destination>,
+        _ref_cons: RefCons,
+    ) -> Result<(), OSStatus> {
+        Ok(())
+    }
+
+    /// Receive a copy of the data sent to any destination, once [DriverContext::enable_monitoring] is called.
+    ///
+    fn monitor(
+        &mut self,
+        _destination: &Destination,
+        _packet_list: &PacketList,
+    ) -> Result<(), OSStatus> {
+        Ok(())
+    }
+}
+
+/// Access to the MIDI server from a driver.
+///
+pub struct DriverContext {
+    driver_ref: MIDIDriverRef,
+}
+
+impl DriverContext {
+    /// Create a new device owned by the driver.
+    /// See [MIDIDeviceCreate](https://developer.apple.com/documentation/coremidi/mididevicecreate(_:_:_:_:_:)).
+    ///
+    pub fn create_device(
+        &self,
+        name: &str,
+        manufacturer: &str,
+        model: &str,
+    ) -> Result<Device, OSStatus> {
+        let name = CFString::new(name);
+        let manufacturer = CFString::new(manufacturer);
+        let model = CFString::new(model);
+        let mut device_ref = MaybeUninit::uninit();
+        let status = unsafe {
+            MIDIDeviceCreate(
+                self.driver_ref,
+                name.as_concrete_TypeRef(),
+                manufacturer.as_concrete_TypeRef(),
+                model.as_concrete_TypeRef(),
+                device_ref.as_mut_ptr(),
+            )
+        };
+        result_from_status(status, || Device::new(unsafe { device_ref.assume_init() }))
+    }
+
+    /// Get the devices in the current setup owned by the driver.
+    /// See [MIDIGetDriverDeviceList](https://developer.apple.com/documentation/coremidi/midigetdriverdevicelist(_:)).
+    ///
+    pub fn device_list(&self) -> DeviceList {
+        DeviceList {
+            list_ref: unsafe { MIDIGetDriverDeviceList(self.driver_ref) },
+            owned: true,
+        }
+    }
+
+    /// Enable or disable receiving the data sent to any destination through [Driver::monitor].
+    /// See [MIDIDriverEnableMonitoring](https://developer.apple.com/documentation/coremidi/mididriverenablemonitoring(_:_:)).
+    ///
+    pub fn enable_monitoring(&self, enabled: bool) -> Result<(), OSStatus> {
+        unit_result_from_status(unsafe {
+            MIDIDriverEnableMonitoring(self.driver_ref, enabled as u8)
+        })
+    }
+}
+
+/// A [list of devices](https://developer.apple.com/documentation/coremidi/mididevicelistref) handled by a driver.
+///
+pub struct DeviceList {
+    list_ref: MIDIDeviceListRef,
+    owned: bool,
+}
+
+impl DeviceList {
+    /// The number of devices in the list.
+    ///
+    pub fn len(&self) -> usize {
+        unsafe { MIDIDeviceListGetNumberOfDevices(self.list_ref) as usize }
+    }
+
+    /// Whether the list is empty.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The devices in the list.
+    ///
+    pub fn devices(&self) -> Vec<Device> {
+        (0..self.len())
+            .map(|index| unsafe { MIDIDeviceListGetDevice(self.list_ref, index as ItemCount) })
+            .filter(|device_ref| *device_ref != 0)
+            .map(Device::new)
+            .collect()
+    }
+
+    /// Add a device to the list.
+    /// See [MIDIDeviceListAddDevice](https://developer.apple.com/documentation/coremidi/mididevicelistadddevice(_:_:)).
+    ///
+    pub fn add(&self, device: &Device) -> Result<(), OSStatus> {
+        unit_result_from_status(unsafe { MIDIDeviceListAddDevice(self.list_ref, device.object.0) })
+    }
+}
+
+impl Drop for DeviceList {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { MIDIDeviceListDispose(self.list_ref) };
+        }
+    }
+}
+
+/// Set the reference constants of a destination owned by the driver, to identify it in [Driver::send].
+/// See [MIDIEndpointSetRefCons](https://developer.apple.com/documentation/coremidi/midiendpointsetrefcons(_:_:_:)).
+///
+pub fn set_ref_cons(destination: &Destination, ref_cons: RefCons) -> Result<(), OSStatus> {
+    let (ref1, ref2) = ref_cons;
+    unit_result_from_status(unsafe {
+        MIDIEndpointSetRefCons(
+            destination.endpoint.object.0,
+            ref1 as *mut c_void,
+            ref2 as *mut c_void,
+        )
+    })
+}
+
+/// Get the reference constants of an endpoint owned by the driver.
+/// See [MIDIEndpointGetRefCons](https://developer.apple.com/documentation/coremidi/midiendpointgetrefcons(_:_:_:)).
+///
+pub fn ref_cons(endpoint: &Endpoint) -> Result<RefCons, OSStatus> {
+    let mut ref1 = ptr::null_mut();
+    let mut ref2 = ptr::null_mut();
+    let status = unsafe { MIDIEndpointGetRefCons(endpoint.object.0, &mut ref1, &mut ref2) };
+    result_from_status(status, || (ref1 as usize, ref2 as usize))
+}
+
+/// Create an instance of a driver, to be returned from the factory function of the plug-in.
+///
+/// It returns a null pointer when the type requested by the MIDI server is not a CoreMIDI driver,
+/// as expected from a CFPlugIn factory function.
+/// The factory id is the one declared for the factory function in the `Info.plist` of the plug-in.
+///
+/// # Safety
+///
+/// The type id needs to be either null or a valid `CFUUIDRef`, like the one received by the factory function.
+///
+pub unsafe fn new_driver_instance<D: Driver + 'static>(
+    type_id: CFUUIDRef,
+    factory_id: &str,
+    driver: D,
+) -> *mut c_void {
+    if type_id.is_null() || uuid_bytes(unsafe { CFUUIDGetUUIDBytes(type_id) }) != DRIVER_TYPE_ID {
+        return ptr::null_mut();
+    }
+    let factory_id = CFString::new(factory_id);
+    let factory_id =
+        unsafe { CFUUIDCreateFromString(kCFAllocatorDefault, factory_id.as_concrete_TypeRef()) };
+    if factory_id.is_null() {
+        return ptr::null_mut();
+    }
+    unsafe { CFPlugInAddInstanceForFactory(factory_id) };
+    let instance = Box::new(DriverInstance {
+        interface: ptr::null(),
+        vtable: MIDIDriverInterface {
+            _reserved: ptr::null_mut(),
+            QueryInterface: query_interface::<D>,
+            AddRef: add_ref::<D>,
+            Release: release::<D>,
+            FindDevices: find_devices::<D>,
+            Start: start::<D>,
+            Stop: stop::<D>,
+            Configure: configure::<D>,
+            Send: send::<D>,
+            EnableSource: enable_source::<D>,
+            Flush: flush::<D>,
+            Monitor: monitor::<D>,
+        },
+        ref_count: AtomicU32::new(1),
+        factory_id,
+        driver: Mutex::new(driver),
+    });
+    let instance = Box::into_raw(instance);
+    unsafe { (*instance).interface = &(*instance).vtable };
+    instance as *mut c_void
+}
+
+/// The COM object handed over to the MIDI server. A pointer to it is a `MIDIDriverRef`,
+/// so it needs to start with the pointer to the interface.
+#[repr(C)]
+struct DriverInstance<D> {
+    interface: *const MIDIDriverInterface,
+    vtable: MIDIDriverInterface,
+    ref_count: AtomicU32,
+    factory_id: CFUUIDRef,
+    driver: Mutex<D>,
+}
+
+fn uuid_bytes(uuid: CFUUIDBytes) -> [u8; 16] {
+    [
+        uuid.byte0,
+        uuid.byte1,
+        uuid.byte2,
+        uuid.byte3,
+        uuid.byte4,
+        uuid.byte5,
+        uuid.byte6,
+        uuid.byte7,
+        uuid.byte8,
+        uuid.byte9,
+        uuid.byte10,
+        uuid.byte11,
+        uuid.byte12,
+        uuid.byte13,
+        uuid.byte14,
+        uuid.byte15,
+    ]
+}
+
+fn instance<'a, D>(this: *mut c_void) -> &'a DriverInstance<D> {
+    unsafe { &*(this as *const DriverInstance<D>) }
+}
+
+fn with_driver<D, F>(this: MIDIDriverRef, f: F) -> OSStatus
+where
+    F: FnOnce(&mut D, &DriverContext) -> Result<(), OSStatus>,
+{
+    let context = DriverContext { driver_ref: this };
+    let mut driver = instance::<D>(this)
+        .driver
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match catch_unwind(AssertUnwindSafe(|| f(&mut driver, &context))) {
+        Ok(Ok(())) => 0,
+        Ok(Err(status)) => status,
+        Err(_) => kMIDIUnknownError as OSStatus,
+    }
+}
+
+fn borrowed_device_list(list_ref: MIDIDeviceListRef) -> DeviceList {
+    DeviceList {
+        list_ref,
+        owned: false,
+    }
+}
+
+extern "C" fn query_interface<D>(
+    this: *mut c_void,
+    iid: CFUUIDBytes,
+    ppv: *mut *mut c_void,
+) -> i32 {
+    // Only the second version of the driver interface is implemented, so asking for the first one
+    // (`kMIDIDriverInterfaceID`) fails like for any other unknown interface.
+    let iid = uuid_bytes(iid);
+    if iid == IUNKNOWN_ID || iid == DRIVER_INTERFACE_2_ID {
+        add_ref::<D>(this);
+        unsafe { *ppv = this };
+        S_OK
+    } else {
+        unsafe { *ppv = ptr::null_mut() };
+        E_NOINTERFACE
+    }
+}
+
+extern "C" fn add_ref<D>(this: *mut c_void) -> u32 {
+    instance::<D>(this).ref_count.fetch_add(1, Ordering::AcqRel) + 1
+}
+
+extern "C" fn release<D>(this: *mut c_void) -> u32 {
+    let ref_count = instance::<D>(this).ref_count.fetch_sub(1, Ordering::AcqRel) - 1;
+    if ref_count == 0 {
+        let instance = unsafe { Box::from_raw(this as *mut DriverInstance<D>) };
+        unsafe {
+            CFPlugInRemoveInstanceForFactory(instance.factory_id);
+            CFRelease(instance.factory_id as *const c_void);
+        }
+    }
+    ref_count
+}
+
+extern "C" fn find_devices<D: Driver>(
+    this: MIDIDriverRef,
+    list_ref: MIDIDeviceListRef,
+) -> OSStatus {
+    with_driver(this, |driver: &mut D, context| {
+        driver.find_devices(context, &borrowed_device_list(list_ref))
+    })
+}
+
+extern "C" fn start<D: Driver>(this: MIDIDriverRef, list_ref: MIDIDeviceListRef) -> OSStatus {
+    with_driver(this, |driver: &mut D, context| {
+        driver.start(context, &borrowed_device_list(list_ref))
+    })
+}
+
+extern "C" fn stop<D: Driver>(this: MIDIDriverRef) -> OSStatus {
+    with_driver(this, |driver: &mut D, _| driver.stop())
+}
+
+extern "C" fn configure<D: Driver>(this: MIDIDriverRef, device_ref: MIDIDeviceRef) -> OSStatus {
+    with_driver(this, |driver: &mut D, _| {
+        driver.configure(&Device::new(device_ref))
+    })
+}
+
+extern "C" fn send<D: Driver>(
+    this: MIDIDriverRef,
+    packet_list: *const MIDIPacketList,
+    ref1: *mut c_void,
+    ref2: *mut c_void,
+) -> OSStatus {
+    let packet_list = unsafe { &*(packet_list as *const PacketList) };
+    with_driver(this, |driver: &mut D, _| {
+        driver.send(packet_list, (ref1 as usize, ref2 as usize))
+    })
+}
+
+extern "C" fn enable_source<D: Driver>(
+    this: MIDIDriverRef,
+    source_ref: MIDIEndpointRef,
+    enabled: u8,
+) -> OSStatus {
+    with_driver(this, |driver: &mut D, _| {
+        driver.enable_source(&Source::new(source_ref), enabled != 0)
+    })
+}
+
+extern "C" fn flush<D: Driver>(
+    this: MIDIDriverRef,
+    destination_ref: MIDIEndpointRef,
+    ref1: *mut c_void,
+    ref2: *mut c_void,
+) -> OSStatus {
+    let destination = match destination_ref {
+        0 => None,
+        _ => Some(Destination::new(destination_ref)),
+    };
+    with_driver(this, |driver: &mut D, _| {
+        driver.flush(destination.as_ref(), (ref1 as usize, ref2 as usize))
+    })
+}
+
+extern "C" fn monitor<D: Driver>(
+    this: MIDIDriverRef,
+    destination_ref: MIDIEndpointRef,
+    packet_list: *const MIDIPacketList,
+) -> OSStatus {
+    let packet_list = unsafe { &*(packet_list as *const PacketList) };
+    with_driver(this, |driver: &mut D, _| {
+        driver.monitor(&Destination::new(destination_ref), packet_list)
+    })
+}
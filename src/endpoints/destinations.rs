@@ -1,12 +1,13 @@
 use std::ops::Deref;
 
 use coremidi_sys::{
-    ItemCount, MIDIEndpointDispose, MIDIEndpointRef, MIDIGetDestination,
-    MIDIGetNumberOfDestinations,
+    kMIDIObjectType_Destination, ItemCount, MIDIEndpointDispose, MIDIEndpointRef,
+    MIDIGetDestination, MIDIGetNumberOfDestinations, MIDIObjectFindByUniqueID, MIDIObjectRef,
+    MIDIObjectType,
 };
 
 use crate::endpoints::endpoint::Endpoint;
-use crate::Object;
+use crate::{Object, UniqueId};
 
 /// A [MIDI source](https://developer.apple.com/documentation/coremidi/midiendpointref) owned by an entity.
 ///
@@ -29,6 +30,18 @@ impl Destination {
         }
     }
 
+    /// Create a destination from a raw endpoint reference, like the ones held by other libraries using CoreMIDI.
+    ///
+    /// # Safety
+    ///
+    /// The reference needs to be a valid `MIDIEndpointRef` of a destination (not of a source).
+    /// The destination doesn't own it, so it is not disposed when dropped, and it stops working when the endpoint
+    /// is disposed by its owner or removed from the system.
+    ///
+    pub unsafe fn from_raw(endpoint_ref: MIDIEndpointRef) -> Destination {
+        Self::new(endpoint_ref)
+    }
+
     /// Create a destination endpoint from its index.
     /// See [MIDIGetDestination](https://developer.apple.com/documentation/coremidi/1495108-midigetdestination)
     ///
@@ -39,6 +52,13 @@ impl Destination {
             _ => Some(Self::new(endpoint_ref)),
         }
     }
+
+    /// Create a destination from its unique id.
+    /// See [MIDIObjectFindByUniqueID](https://developer.apple.com/documentation/coremidi/1495191-midiobjectfindbyuniqueid).
+    ///
+    pub fn from_unique_id(unique_id: UniqueId) -> Option<Destination> {
+        Destinations::find_by_unique_id(unique_id)
+    }
 }
 
 impl Clone for Destination {
@@ -92,6 +112,65 @@ impl Destinations {
     pub fn count() -> usize {
         unsafe { MIDIGetNumberOfDestinations() as usize }
     }
+
+    /// Iterate over the destinations in the system that are online, skipping the offline ones.
+    ///
+    /// ```rust,no_run
+    /// for destination in coremidi::Destinations::online() {
+    ///   println!("{}", destination.display_name().unwrap());
+    /// }
+    /// ```
+    ///
+    pub fn online() -> impl Iterator<Item = Destination> {
+        Destinations
+            .into_iter()
+            .filter(|destination| destination.is_online())
+    }
+
+    /// Find the first destination whose name or display name is exactly `name`.
+    ///
+    /// The display name usually includes the name of the device, like "IAC Driver Bus 1",
+    /// while the name is only the one of the endpoint, like "Bus 1".
+    ///
+    /// ```rust,no_run
+    /// let destination = coremidi::Destinations::find_by_name("IAC Driver Bus 1");
+    /// ```
+    ///
+    pub fn find_by_name(name: &str) -> Option<Destination> {
+        Destinations.into_iter().find(|destination| {
+            destination.name().as_deref() == Some(name)
+                || destination.display_name().as_deref() == Some(name)
+        })
+    }
+
+    /// Find the first destination whose name or display name matches `name`, ignoring the case.
+    /// See [Destinations::find_by_name].
+    ///
+    pub fn find_by_name_ignore_case(name: &str) -> Option<Destination> {
+        let name = name.to_lowercase();
+        let is_match = |candidate: Option<String>| {
+            candidate
+                .map(|candidate| candidate.to_lowercase() == name)
+                .unwrap_or(false)
+        };
+        Destinations.into_iter().find(|destination| {
+            is_match(destination.name()) || is_match(destination.display_name())
+        })
+    }
+
+    /// Find a destination based on its unique id.
+    /// See [MIDIObjectFindByUniqueID](https://developer.apple.com/documentation/coremidi/1495191-midiobjectfindbyuniqueid).
+    ///
+    fn find_by_unique_id(unique_id: UniqueId) -> Option<Destination> {
+        let mut obj_ref: MIDIObjectRef = 0;
+        let mut obj_type: MIDIObjectType = 0;
+        let status = unsafe { MIDIObjectFindByUniqueID(unique_id, &mut obj_ref, &mut obj_type) };
+        if status != 0 || obj_type != kMIDIObjectType_Destination {
+            None
+        } else {
+            Some(Destination::new(obj_ref as MIDIEndpointRef))
+        }
+    }
 }
 
 impl IntoIterator for Destinations {
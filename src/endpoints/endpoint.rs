@@ -1,9 +1,13 @@
 use core_foundation_sys::base::OSStatus;
+use std::mem::MaybeUninit;
 use std::ops::Deref;
 
-use coremidi_sys::{MIDIEndpointRef, MIDIFlushOutput};
+use coremidi_sys::{MIDIEndpointGetEntity, MIDIEndpointRef, MIDIFlushOutput, SInt32};
 
+use crate::entity::Entity;
+use crate::info::EndpointInfo;
 use crate::object::Object;
+use crate::properties::{Properties, PropertyGetter, PropertySetter};
 
 /// A MIDI source or source, owned by an entity.
 /// See [MIDIEndpointRef](https://developer.apple.com/documentation/coremidi/midiendpointref).
@@ -16,6 +20,10 @@ pub struct Endpoint {
 }
 
 impl Endpoint {
+    /// The maximum sysex speed (in bytes per second) assumed by CoreMIDI when it is not set,
+    /// which corresponds to the bandwidth of a standard MIDI 1.0 cable.
+    pub const DEFAULT_MAX_SYSEX_SPEED: u32 = 3125;
+
     pub(crate) fn new(endpoint_ref: MIDIEndpointRef) -> Self {
         Self {
             object: Object(endpoint_ref),
@@ -33,6 +41,72 @@ impl Endpoint {
             Err(status)
         }
     }
+
+    /// Get the entity that owns the endpoint, if any (virtual endpoints don't have one).
+    /// See [MIDIEndpointGetEntity](https://developer.apple.com/documentation/coremidi/midiendpointgetentity(_:_:)).
+    ///
+    pub fn entity(&self) -> Option<Entity> {
+        let mut entity_ref = MaybeUninit::uninit();
+        let status = unsafe { MIDIEndpointGetEntity(self.object.0, entity_ref.as_mut_ptr()) };
+        match status {
+            0 => match unsafe { entity_ref.assume_init() } {
+                0 => None,
+                entity_ref => Some(Entity::new(entity_ref)),
+            },
+            _ => None,
+        }
+    }
+
+    /// Check whether the endpoint is a virtual one created by an application,
+    /// rather than one provided by a driver for some hardware.
+    ///
+    /// Virtual endpoints either don't belong to any entity, or belong to a device without a
+    /// [driver owner](https://developer.apple.com/documentation/coremidi/kmidipropertydriverowner).
+    ///
+    pub fn is_virtual(&self) -> bool {
+        if self.entity().is_none() {
+            return true;
+        }
+        let driver_owner: Option<String> = Properties::driver_owner()
+            .try_value_from(self)
+            .ok()
+            .flatten();
+        !matches!(driver_owner, Some(owner) if !owner.is_empty())
+    }
+
+    /// Check whether the endpoint is currently online.
+    /// See [kMIDIPropertyOffline](https://developer.apple.com/documentation/coremidi/kMIDIPropertyOffline)
+    ///
+    pub fn is_online(&self) -> bool {
+        !Properties::offline().value_from(self).unwrap_or(false)
+    }
+
+    /// Take a snapshot of the endpoint properties.
+    ///
+    pub fn info(&self) -> EndpointInfo {
+        EndpointInfo::from(self)
+    }
+
+    /// Get the maximum rate, in bytes per second, at which sysex messages may be sent to this endpoint.
+    /// It defaults to [Endpoint::DEFAULT_MAX_SYSEX_SPEED] when the property is not set.
+    /// See [kMIDIPropertyMaxSysExSpeed](https://developer.apple.com/documentation/coremidi/kmidipropertymaxsysexspeed)
+    ///
+    pub fn max_sysex_speed(&self) -> u32 {
+        Properties::max_sysex_speed()
+            .value_from(self)
+            .ok()
+            .filter(|speed: &SInt32| *speed > 0)
+            .map(|speed| speed as u32)
+            .unwrap_or(Self::DEFAULT_MAX_SYSEX_SPEED)
+    }
+
+    /// Set the maximum rate, in bytes per second, at which sysex messages may be sent to this endpoint.
+    /// See [kMIDIPropertyMaxSysExSpeed](https://developer.apple.com/documentation/coremidi/kmidipropertymaxsysexspeed)
+    ///
+    pub fn set_max_sysex_speed(&self, bytes_per_second: u32) -> Result<(), OSStatus> {
+        let speed = bytes_per_second.min(SInt32::MAX as u32) as SInt32;
+        Properties::max_sysex_speed().set_value(self, speed)
+    }
 }
 
 impl AsRef<Object> for Endpoint {
@@ -48,3 +122,46 @@ impl Deref for Endpoint {
         &self.object
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Client, Endpoint, Properties, Protocol};
+
+    #[test]
+    fn max_sysex_speed_roundtrip() {
+        let client = Client::new("Test Client").unwrap();
+        let dest = client
+            .virtual_destination_with_protocol("A", Protocol::Midi10, |_| ())
+            .unwrap();
+
+        assert_eq!(dest.max_sysex_speed(), Endpoint::DEFAULT_MAX_SYSEX_SPEED);
+
+        dest.set_max_sysex_speed(6250).unwrap();
+
+        assert_eq!(dest.max_sysex_speed(), 6250);
+    }
+
+    #[test]
+    fn is_online() {
+        let client = Client::new("Test Client").unwrap();
+        let dest = client
+            .virtual_destination_with_protocol("A", Protocol::Midi10, |_| ())
+            .unwrap();
+
+        assert!(dest.is_online());
+
+        dest.set_property(&Properties::offline(), true).unwrap();
+
+        assert!(!dest.is_online());
+    }
+
+    #[test]
+    fn is_virtual() {
+        let client = Client::new("Test Client").unwrap();
+        let dest = client
+            .virtual_destination_with_protocol("A", Protocol::Midi10, |_| ())
+            .unwrap();
+
+        assert!(dest.is_virtual());
+    }
+}
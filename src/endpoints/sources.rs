@@ -4,12 +4,11 @@ use std::ops::Deref;
 use coremidi_sys::{
     kMIDIObjectType_Source, ItemCount, MIDIEndpointDispose, MIDIEndpointRef,
     MIDIGetNumberOfSources, MIDIGetSource, MIDIObjectFindByUniqueID, MIDIObjectRef, MIDIObjectType,
-    MIDIReceived, MIDIReceivedEventList, MIDIUniqueID,
 };
 
 use crate::endpoints::endpoint::Endpoint;
 use crate::ports::Packets;
-use crate::Object;
+use crate::{Object, UniqueId};
 
 /// A [MIDI source](https://developer.apple.com/documentation/coremidi/midiendpointref) owned by an entity.
 ///
@@ -32,6 +31,18 @@ impl Source {
         }
     }
 
+    /// Create a source from a raw endpoint reference, like the ones held by other libraries using CoreMIDI.
+    ///
+    /// # Safety
+    ///
+    /// The reference needs to be a valid `MIDIEndpointRef` of a source (not of a destination).
+    /// The source doesn't own it, so it is not disposed when dropped, and it stops working when the endpoint
+    /// is disposed by its owner or removed from the system.
+    ///
+    pub unsafe fn from_raw(endpoint_ref: MIDIEndpointRef) -> Source {
+        Self::new(endpoint_ref)
+    }
+
     /// Create a source endpoint from its index.
     /// See [MIDIGetSource](https://developer.apple.com/documentation/coremidi/1495168-midigetsource)
     ///
@@ -53,7 +64,7 @@ impl Source {
     /// Create a source from it's unique id.
     /// See [MIDIObjectFindByUniqueID](https://developer.apple.com/documentation/coremidi/1495191-midiobjectfindbyuniqueid).
     ///
-    pub fn from_unique_id(unique_id: u32) -> Option<Source> {
+    pub fn from_unique_id(unique_id: UniqueId) -> Option<Source> {
         Sources::find_by_unique_id(unique_id)
     }
 }
@@ -110,15 +121,55 @@ impl Sources {
         unsafe { MIDIGetNumberOfSources() as usize }
     }
 
+    /// Iterate over the sources in the system that are online, skipping the offline ones.
+    ///
+    /// ```rust,no_run
+    /// for source in coremidi::Sources::online() {
+    ///   println!("{}", source.display_name().unwrap());
+    /// }
+    /// ```
+    ///
+    pub fn online() -> impl Iterator<Item = Source> {
+        Sources.into_iter().filter(|source| source.is_online())
+    }
+
+    /// Find the first source whose name or display name is exactly `name`.
+    ///
+    /// The display name usually includes the name of the device, like "IAC Driver Bus 1",
+    /// while the name is only the one of the endpoint, like "Bus 1".
+    ///
+    /// ```rust,no_run
+    /// let source = coremidi::Sources::find_by_name("IAC Driver Bus 1");
+    /// ```
+    ///
+    pub fn find_by_name(name: &str) -> Option<Source> {
+        Sources.into_iter().find(|source| {
+            source.name().as_deref() == Some(name) || source.display_name().as_deref() == Some(name)
+        })
+    }
+
+    /// Find the first source whose name or display name matches `name`, ignoring the case.
+    /// See [Sources::find_by_name].
+    ///
+    pub fn find_by_name_ignore_case(name: &str) -> Option<Source> {
+        let name = name.to_lowercase();
+        let is_match = |candidate: Option<String>| {
+            candidate
+                .map(|candidate| candidate.to_lowercase() == name)
+                .unwrap_or(false)
+        };
+        Sources
+            .into_iter()
+            .find(|source| is_match(source.name()) || is_match(source.display_name()))
+    }
+
     /// Find a source based on its unique id.
     /// See [MIDIObjectFindByUniqueID](https://developer.apple.com/documentation/coremidi/1495191-midiobjectfindbyuniqueid).
     ///
-    fn find_by_unique_id(unique_id: u32) -> Option<Source> {
+    fn find_by_unique_id(unique_id: UniqueId) -> Option<Source> {
         let mut obj_ref: MIDIObjectRef = 0;
         let mut obj_type: MIDIObjectType = 0;
-        let status = unsafe {
-            MIDIObjectFindByUniqueID(unique_id as MIDIUniqueID, &mut obj_ref, &mut obj_type)
-        };
+        let status = unsafe { MIDIObjectFindByUniqueID(unique_id, &mut obj_ref, &mut obj_type) };
         if status != 0 || obj_type != kMIDIObjectType_Source {
             None
         } else {
@@ -182,21 +233,11 @@ impl VirtualSource {
     /// Distributes incoming MIDI from a source to the client input ports which are connected to that source.
     /// See [MIDIReceived](https://developer.apple.com/documentation/coremidi/1495276-midireceived)
     ///
-    pub fn received<'a, P>(&self, packets: P) -> Result<(), OSStatus>
+    pub fn received<P>(&self, packets: P) -> Result<(), OSStatus>
     where
-        P: Into<Packets<'a>>,
+        P: Packets,
     {
-        let status = match packets.into() {
-            Packets::BorrowedPacketList(packet_list) => unsafe {
-                MIDIReceived(self.endpoint.object.0, packet_list.as_ptr())
-            },
-            Packets::BorrowedEventList(event_list) => unsafe {
-                MIDIReceivedEventList(self.endpoint.object.0, event_list.as_ptr())
-            },
-            Packets::OwnedEventBuffer(event_buffer) => unsafe {
-                MIDIReceivedEventList(self.endpoint.object.0, event_buffer.as_ptr())
-            },
-        };
+        let status = packets.received(self.endpoint.object.0);
 
         if status == 0 {
             Ok(())
@@ -1,7 +1,16 @@
-use coremidi_sys::MIDIObjectRef;
+use core_foundation::base::OSStatus;
+use coremidi_sys::{
+    kMIDIObjectNotFound, MIDIEntityGetDestination, MIDIEntityGetDevice,
+    MIDIEntityGetNumberOfDestinations, MIDIEntityGetNumberOfSources, MIDIEntityGetSource,
+    MIDIObjectRef, SInt32,
+};
+use std::convert::TryFrom;
+use std::mem::MaybeUninit;
 use std::ops::Deref;
 
-use crate::object::Object;
+use crate::object::{Object, UniqueId};
+use crate::properties::{Properties, PropertyGetter, PropertySetter};
+use crate::{Destination, Device, Endpoint, Source};
 
 /// A [MIDI object](https://developer.apple.com/documentation/coremidi/midientityref).
 ///
@@ -18,6 +27,89 @@ impl Entity {
             object: Object(object_ref),
         }
     }
+
+    /// Get the sources owned by the entity.
+    /// See [MIDIEntityGetSource](https://developer.apple.com/documentation/coremidi/midientitygetsource(_:_:)).
+    ///
+    pub fn sources(&self) -> Vec<Source> {
+        let count = unsafe { MIDIEntityGetNumberOfSources(self.object.0) };
+        (0..count)
+            .map(|index| unsafe { MIDIEntityGetSource(self.object.0, index) })
+            .filter(|endpoint_ref| *endpoint_ref != 0)
+            .map(Source::new)
+            .collect()
+    }
+
+    /// Get the destinations owned by the entity.
+    /// See [MIDIEntityGetDestination](https://developer.apple.com/documentation/coremidi/midientitygetdestination(_:_:)).
+    ///
+    pub fn destinations(&self) -> Vec<Destination> {
+        let count = unsafe { MIDIEntityGetNumberOfDestinations(self.object.0) };
+        (0..count)
+            .map(|index| unsafe { MIDIEntityGetDestination(self.object.0, index) })
+            .filter(|endpoint_ref| *endpoint_ref != 0)
+            .map(Destination::new)
+            .collect()
+    }
+
+    /// Get the device that owns the entity.
+    /// See [MIDIEntityGetDevice](https://developer.apple.com/documentation/coremidi/midientitygetdevice(_:_:)).
+    ///
+    pub fn device(&self) -> Option<Device> {
+        let mut device_ref = MaybeUninit::uninit();
+        let status = unsafe { MIDIEntityGetDevice(self.object.0, device_ref.as_mut_ptr()) };
+        match status {
+            0 => match unsafe { device_ref.assume_init() } {
+                0 => None,
+                device_ref => Some(Device::new(device_ref)),
+            },
+            _ => None,
+        }
+    }
+
+    /// Check whether the endpoints of the entity are inside the device itself,
+    /// rather than connectors to external devices.
+    /// See [kMIDIPropertyIsEmbeddedEntity](https://developer.apple.com/documentation/coremidi/kmidipropertyisembeddedentity).
+    ///
+    pub fn is_embedded(&self) -> bool {
+        Properties::is_embedded_entity()
+            .value_from(self)
+            .unwrap_or(false)
+    }
+
+    /// Set whether the endpoints of the entity are inside the device itself.
+    ///
+    pub fn set_embedded(&self, embedded: bool) -> Result<(), OSStatus> {
+        Properties::is_embedded_entity().set_value(self, embedded)
+    }
+
+    /// Get the maximum rate, in bytes per second, at which the entity can receive system exclusive messages.
+    /// It defaults to [Endpoint::DEFAULT_MAX_SYSEX_SPEED] when the property is not set.
+    /// See [kMIDIPropertyMaxSysExSpeed](https://developer.apple.com/documentation/coremidi/kmidipropertymaxsysexspeed).
+    ///
+    pub fn max_sysex_speed(&self) -> u32 {
+        Properties::max_sysex_speed()
+            .value_from(self)
+            .ok()
+            .filter(|speed: &SInt32| *speed > 0)
+            .map(|speed| speed as u32)
+            .unwrap_or(Endpoint::DEFAULT_MAX_SYSEX_SPEED)
+    }
+
+    /// Set the maximum rate, in bytes per second, at which the entity can receive system exclusive messages.
+    ///
+    pub fn set_max_sysex_speed(&self, bytes_per_second: u32) -> Result<(), OSStatus> {
+        let speed = bytes_per_second.min(SInt32::MAX as u32) as SInt32;
+        Properties::max_sysex_speed().set_value(self, speed)
+    }
+
+    /// Set the unique ids of the external devices, entities or endpoints the entity is connected to,
+    /// replacing any previous connection. An empty list removes all the connections.
+    /// See [Properties::connection_unique_ids].
+    ///
+    pub fn set_connection_unique_ids(&self, unique_ids: &[UniqueId]) -> Result<(), OSStatus> {
+        Properties::connection_unique_ids().set_value(self, unique_ids)
+    }
 }
 
 impl Clone for Entity {
@@ -26,6 +118,30 @@ impl Clone for Entity {
     }
 }
 
+impl From<Entity> for Object {
+    fn from(entity: Entity) -> Self {
+        entity.object
+    }
+}
+
+/// Convert an object into an entity, checking that it really is an entity (or an external one).
+///
+/// It fails with [kMIDIObjectNotFound](https://developer.apple.com/documentation/coremidi/kmidiobjectnotfound)
+/// when the object is not an entity.
+///
+impl TryFrom<Object> for Entity {
+    type Error = OSStatus;
+
+    fn try_from(object: Object) -> Result<Self, Self::Error> {
+        match object.object_type()? {
+            coremidi_sys::kMIDIObjectType_Entity | coremidi_sys::kMIDIObjectType_ExternalEntity => {
+                Ok(Self { object })
+            }
+            _ => Err(kMIDIObjectNotFound),
+        }
+    }
+}
+
 impl AsRef<Object> for Entity {
     fn as_ref(&self) -> &Object {
         &self.object
@@ -39,3 +155,18 @@ impl Deref for Entity {
         &self.object
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use crate::{Client, Entity, Object};
+
+    #[test]
+    fn try_from_object_not_entity() {
+        let client = Client::new("Test Client").unwrap();
+        let source = client.virtual_source("A").unwrap();
+
+        assert!(Entity::try_from(Object(source.object.0)).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use core_foundation::base::OSStatus;
+use coremidi_sys::{kMIDIMessageSendErr, kMIDINotPermitted, kMIDIServerStartErr};
+use std::{env, fmt};
+
+/// The status returned by `MIDIClientCreate` on iOS when the MIDI server is gone (`paramErr`),
+/// for example after disposing the last client of the system.
+const CLIENT_SERVER_GONE: OSStatus = -50;
+
+// The statuses telling that the MIDI server could not be started or that the connection with it was lost
+const SERVER_UNAVAILABLE: [OSStatus; 2] = [kMIDIServerStartErr, kMIDIMessageSendErr];
+
+// Set by macOS in the environment of the processes running in the App Sandbox
+const SANDBOX_CONTAINER_VAR: &str = "APP_SANDBOX_CONTAINER_ID";
+
+#[cfg(target_os = "ios")]
+const PERMISSION_HINT: &str = "add `audio` to the `UIBackgroundModes` of the Info.plist \
+    to create virtual endpoints and keep using MIDI in the background";
+
+#[cfg(not(target_os = "ios"))]
+const PERMISSION_HINT: &str = "sandboxed applications need the `com.apple.security.device.usb` entitlement \
+    for USB devices, `com.apple.security.device.bluetooth` for Bluetooth ones, and \
+    `com.apple.security.network.client` and `com.apple.security.network.server` for network sessions";
+
+/// The status returned by the functions that are not available in the running system (`unimpErr`),
+/// like the MIDI 2.0 ones before macOS 11 (see [is_midi2_available](crate::is_midi2_available)).
+///
+pub const UNSUPPORTED_STATUS: OSStatus = -4;
+
+/// The kind of an error returned by this library, that can be told from its `OSStatus`.
+///
+/// The functions return the `OSStatus` of CoreMIDI as their error, which can be converted into a [MidiError]
+/// to handle the situations that need a special treatment.
+/// More kinds might be told apart in the future, so matching it needs a wildcard arm.
+///
+/// The constructors of clients and ports also have `_checked` variants (like [Client::new_checked](crate::Client::new_checked)),
+/// which return a [MidiError] already. Moving to them only needs to change the error handling,
+/// and the `OSStatus` is still available through [MidiError::status].
+///
+/// ```
+/// use coremidi::{MidiError, UNSUPPORTED_STATUS};
+/// assert_eq!(MidiError::from(UNSUPPORTED_STATUS), MidiError::Unsupported);
+/// assert_eq!(MidiError::from(-10835), MidiError::Status(-10835));
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MidiError {
+    /// The function is not available in the running system.
+    Unsupported,
+    /// The MIDI server could not be started, or the connection with it was lost, with the status telling why.
+    /// A new client can be created with [Client::recreate](crate::Client::recreate)
+    /// (see also [set_server_lost_callback](crate::set_server_lost_callback)).
+    ServerUnavailable(OSStatus),
+    /// The process is not permitted to do it, usually because of the App Sandbox or missing entitlements,
+    /// with the status telling why. See [MidiError::hint] for how to fix it.
+    PermissionDenied(OSStatus),
+    /// Any other error, with its status.
+    Status(OSStatus),
+}
+
+impl MidiError {
+    pub fn status(&self) -> OSStatus {
+        match self {
+            MidiError::Unsupported => UNSUPPORTED_STATUS,
+            MidiError::ServerUnavailable(status)
+            | MidiError::PermissionDenied(status)
+            | MidiError::Status(status) => *status,
+        }
+    }
+
+    /// The kind of an error returned when creating a [Client](crate::Client), which tells
+    /// a few more situations where the MIDI server is unavailable.
+    ///
+    /// In a sandboxed process the MIDI server failing to start means that the sandbox
+    /// did not allow to connect with it, which is told as [MidiError::PermissionDenied].
+    ///
+    /// Whether the process is sandboxed is only a guess from the `APP_SANDBOX_CONTAINER_ID` variable
+    /// that macOS sets in the environment of sandboxed applications. It is wrong when the variable
+    /// is set or removed by someone else, like a process launched from a sandboxed one.
+    ///
+    pub fn from_client_status(status: OSStatus) -> Self {
+        Self::from_client_status_in(status, is_sandboxed())
+    }
+
+    /// The kind of an error returned when creating a port, which tells the sandbox apart
+    /// as [MidiError::from_client_status] does.
+    ///
+    pub fn from_port_status(status: OSStatus) -> Self {
+        Self::from_port_status_in(status, is_sandboxed())
+    }
+
+    fn from_client_status_in(status: OSStatus, sandboxed: bool) -> Self {
+        match status {
+            CLIENT_SERVER_GONE => MidiError::ServerUnavailable(status),
+            status => Self::from_port_status_in(status, sandboxed),
+        }
+    }
+
+    fn from_port_status_in(status: OSStatus, sandboxed: bool) -> Self {
+        match status {
+            status if status == kMIDIServerStartErr && sandboxed => {
+                MidiError::PermissionDenied(status)
+            }
+            status => MidiError::from(status),
+        }
+    }
+
+    /// How to fix the error, when known.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::MidiError;
+    /// if let Err(status) = coremidi::Client::new("example-client") {
+    ///     let error = MidiError::from_client_status(status);
+    ///     println!("{}: {}", error, error.hint().unwrap_or("no hint"));
+    /// }
+    /// ```
+    ///
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            MidiError::Unsupported => Some("use the MIDI 1.0 functions instead"),
+            MidiError::ServerUnavailable(_) => {
+                Some("create the client again with `Client::recreate`")
+            }
+            MidiError::PermissionDenied(_) => Some(PERMISSION_HINT),
+            MidiError::Status(_) => None,
+        }
+    }
+
+    /// Whether the MIDI server is unavailable, and the client needs to be created again.
+    ///
+    pub fn is_server_unavailable(&self) -> bool {
+        matches!(self, MidiError::ServerUnavailable(_))
+    }
+}
+
+impl From<OSStatus> for MidiError {
+    fn from(status: OSStatus) -> Self {
+        match status {
+            UNSUPPORTED_STATUS => MidiError::Unsupported,
+            status if status == kMIDINotPermitted => MidiError::PermissionDenied(status),
+            status if SERVER_UNAVAILABLE.contains(&status) => MidiError::ServerUnavailable(status),
+            status => MidiError::Status(status),
+        }
+    }
+}
+
+impl fmt::Display for MidiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MidiError::Unsupported => write!(
+                f,
+                "not available in this system (MIDI 2.0 needs macOS 11, iOS 14 or later)"
+            ),
+            MidiError::ServerUnavailable(status) => write!(
+                f,
+                "the MIDI server is unavailable, the client needs to be created again (OSStatus {})",
+                status
+            ),
+            MidiError::PermissionDenied(status) => write!(
+                f,
+                "not permitted{} (OSStatus {})",
+                if is_sandboxed() {
+                    " by the App Sandbox"
+                } else {
+                    ""
+                },
+                status
+            ),
+            MidiError::Status(status) => write!(f, "CoreMIDI error (OSStatus {})", status),
+        }
+    }
+}
+
+impl std::error::Error for MidiError {}
+
+/// Whether this process runs in the App Sandbox, guessed from its environment (see [MidiError::from_client_status]).
+fn is_sandboxed() -> bool {
+    env::var_os(SANDBOX_CONTAINER_VAR).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{MidiError, UNSUPPORTED_STATUS};
+
+    #[test]
+    fn server_unavailable() {
+        assert_eq!(
+            MidiError::from(-10839),
+            MidiError::ServerUnavailable(-10839)
+        );
+        assert!(MidiError::from(-10838).is_server_unavailable());
+        assert_eq!(MidiError::from(-10830), MidiError::Status(-10830));
+        assert_eq!(MidiError::from(-50), MidiError::Status(-50));
+        assert_eq!(
+            MidiError::from_client_status(-50),
+            MidiError::ServerUnavailable(-50)
+        );
+        assert_eq!(
+            MidiError::from_client_status(UNSUPPORTED_STATUS),
+            MidiError::Unsupported
+        );
+        assert_eq!(MidiError::ServerUnavailable(-50).status(), -50);
+    }
+
+    #[test]
+    fn permission_denied() {
+        assert_eq!(MidiError::from(-10844), MidiError::PermissionDenied(-10844));
+        assert_eq!(
+            MidiError::from_client_status_in(-10839, true),
+            MidiError::PermissionDenied(-10839)
+        );
+        assert_eq!(
+            MidiError::from_client_status_in(-10839, false),
+            MidiError::ServerUnavailable(-10839)
+        );
+        assert_eq!(
+            MidiError::from_port_status_in(-10839, true),
+            MidiError::PermissionDenied(-10839)
+        );
+        assert_eq!(
+            MidiError::from_port_status_in(-50, true),
+            MidiError::Status(-50)
+        );
+        assert!(MidiError::PermissionDenied(-10844).hint().is_some());
+        assert_eq!(MidiError::Status(-10835).hint(), None);
+    }
+}
@@ -1,14 +1,15 @@
 use std::fmt::Formatter;
 use std::marker::PhantomData;
-use std::mem::size_of;
+use std::mem::{size_of, MaybeUninit};
 use std::ops::Deref;
 use std::slice;
+use std::time::Duration;
 
-use coremidi_sys::{
-    MIDIEventList, MIDIEventListAdd, MIDIEventListInit, MIDIEventPacket, MIDIEventPacketNext,
-};
+use coremidi_sys::{MIDIEventList, MIDIEventPacket, MIDIEventPacketNext, MIDIPacketList};
 
+use crate::availability::{MIDIEventListAdd, MIDIEventListInit};
 use crate::protocol::Protocol;
+use crate::time::durations_from_host_times;
 
 pub type Timestamp = u64;
 
@@ -36,6 +37,7 @@ impl EventList {
 
     /// Get an iterator for the packets in the list.
     ///
+    #[inline]
     pub fn iter(&self) -> EventListIter {
         EventListIter {
             count: self.len(),
@@ -44,6 +46,13 @@ impl EventList {
         }
     }
 
+    /// Get the timestamps of the packets converted into durations (see [duration_from_host_time](crate::duration_from_host_time)),
+    /// reading the host timebase only once for the whole list.
+    ///
+    pub fn durations(&self) -> impl Iterator<Item = Duration> + '_ {
+        durations_from_host_times(self.iter().map(|packet| packet.timestamp()))
+    }
+
     /// For internal usage only.
     /// Requires this instance to actually point to a valid MIDIEventList
     pub(crate) unsafe fn as_ptr(&self) -> *const MIDIEventList {
@@ -75,6 +84,7 @@ pub struct EventListIter<'a> {
 impl<'a> Iterator for EventListIter<'a> {
     type Item = &'a EventPacket;
 
+    #[inline]
     fn next(&mut self) -> Option<&'a EventPacket> {
         if self.count > 0 {
             let packet = unsafe { &*(self.packet_ptr as *const EventPacket) };
@@ -90,6 +100,7 @@ impl<'a> Iterator for EventListIter<'a> {
 pub struct EventPacket(MIDIEventPacket);
 
 impl EventPacket {
+    #[inline]
     pub fn timestamp(&self) -> Timestamp {
         self.0.timeStamp as Timestamp
     }
@@ -97,6 +108,7 @@ impl EventPacket {
     /// Get the packet data. This method just gives raw MIDI words. You would need another
     /// library to decode them and work with higher level events.
     ///
+    #[inline]
     pub fn data(&self) -> &[u32] {
         let data_ptr = self.0.words.as_ptr();
         let data_len = self.0.wordCount as usize;
@@ -114,25 +126,72 @@ impl std::fmt::Debug for EventPacket {
     }
 }
 
+/// The number of 32 bits words that an [EventBuffer] or a [PacketBuffer](crate::PacketBuffer)
+/// store inline by default, before moving the data to the heap.
+/// It is the least they can store inline (see [MIN_STORAGE_WORDS]), enough for an event list
+/// with a single packet of 64 words, or a packet list with a single packet of 256 bytes.
+///
+pub const DEFAULT_INLINE_WORDS: usize = MIN_STORAGE_WORDS;
+
+/// The number of 32 bits words that the storage of an [EventBuffer] or a [PacketBuffer](crate::PacketBuffer)
+/// holds at least, so that it always covers a whole `MIDIEventList` or `MIDIPacketList`
+/// and can be referenced as an [EventList] or a [PacketList](crate::PacketList).
+///
+pub const MIN_STORAGE_WORDS: usize = {
+    let event_list_size = size_of::<MIDIEventList>();
+    let packet_list_size = size_of::<MIDIPacketList>();
+    let size = if event_list_size > packet_list_size {
+        event_list_size
+    } else {
+        packet_list_size
+    };
+    (size - 1) / 4 + 1
+};
+
+/// A mutable `EventList` builder.
+///
+/// Up to `N` words are stored inline, without allocating, and the buffer moves to the heap when it needs more room.
+/// `N` can't be less than [MIN_STORAGE_WORDS], which is checked at compile time.
+/// Use a larger `N` when building event lists with several packets on a real-time thread:
+///
+/// ```
+/// use coremidi::{EventBuffer, Protocol};
+/// let mut buffer = EventBuffer::<128>::with_inline_storage(Protocol::Midi10);
+/// buffer.push(0, &[0x2090407f]).push(10, &[0x2080407f]).push(20, &[0x2090417f]);
+/// assert_eq!(buffer.capacity(), 128 * 4);
+/// ```
+///
 #[derive(Clone)]
-pub struct EventBuffer {
-    storage: Storage,
+pub struct EventBuffer<const N: usize = DEFAULT_INLINE_WORDS> {
+    storage: Storage<N>,
     current_packet_offset: usize,
 }
 
 impl EventBuffer {
-    const PACKET_HEADER_SIZE: usize = 8 +     // MIDIEventPacket::timestamp: MIDITimeStamp/UInt64
-                                      4; // MIDIEventPacket::wordCount: UInt32
-
     /// Create an empty `EventBuffer` for a given [Protocol] without allocating.
     ///
     pub fn new(protocol: Protocol) -> Self {
-        Self::with_capacity(Storage::INLINE_SIZE, protocol)
+        Self::with_inline_storage(protocol)
     }
 
     /// Create an empty `EventBuffer` of a given capacity for a given [Protocol].
     ///
     pub fn with_capacity(capacity: usize, protocol: Protocol) -> Self {
+        Self::init(capacity, protocol)
+    }
+}
+
+impl<const N: usize> EventBuffer<N> {
+    const PACKET_HEADER_SIZE: usize = 8 +     // MIDIEventPacket::timestamp: MIDITimeStamp/UInt64
+                                      4; // MIDIEventPacket::wordCount: UInt32
+
+    /// Create an empty `EventBuffer` for a given [Protocol] using only its inline storage of `N` words.
+    ///
+    pub fn with_inline_storage(protocol: Protocol) -> Self {
+        Self::init(Storage::<N>::INLINE_SIZE, protocol)
+    }
+
+    fn init(capacity: usize, protocol: Protocol) -> Self {
         let mut storage = Storage::with_capacity(capacity);
         let event_list_ptr = unsafe { storage.as_mut_ptr::<MIDIEventList>() };
         let current_packet_ptr = unsafe { MIDIEventListInit(event_list_ptr, protocol.into()) };
@@ -202,8 +261,9 @@ impl EventBuffer {
     ///     vec![(0, vec![0x40903c00, 0xffff0000])],
     /// )
     /// ```
+    #[inline]
     pub fn push(&mut self, timestamp: Timestamp, data: &[u32]) -> &mut Self {
-        self.ensure_capacity(data.len());
+        let capacity = self.ensure_capacity(data.len());
 
         let packet_list_ptr = unsafe { self.storage.as_mut_ptr::<MIDIEventList>() };
         let current_packet_ptr = unsafe {
@@ -212,7 +272,7 @@ impl EventBuffer {
         let current_packet_ptr = unsafe {
             MIDIEventListAdd(
                 packet_list_ptr,
-                self.storage.capacity() as u64,
+                capacity as u64,
                 current_packet_ptr,
                 timestamp,
                 data.len() as u64,
@@ -227,6 +287,19 @@ impl EventBuffer {
         self
     }
 
+    /// Reserve capacity for at least `additional` more words to be pushed into the buffer,
+    /// in a new packet, without reallocating.
+    ///
+    /// ```
+    /// use coremidi::{EventBuffer, Protocol};
+    /// let mut buffer = EventBuffer::new(Protocol::Midi10);
+    /// buffer.reserve(64);
+    /// assert!(buffer.capacity() >= 64 * 4);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        self.ensure_capacity(additional);
+    }
+
     /// Clears the buffer, removing all packets.
     /// Note that this method has no effect on the allocated capacity of the buffer.
     pub fn clear(&mut self) {
@@ -238,13 +311,15 @@ impl EventBuffer {
         };
     }
 
-    fn ensure_capacity(&mut self, data_len: usize) {
+    /// Returns the capacity of the storage after making room for the data.
+    #[inline]
+    fn ensure_capacity(&mut self, data_len: usize) -> usize {
         let next_capacity =
             self.aligned_bytes_len() + Self::PACKET_HEADER_SIZE + data_len * size_of::<u32>();
 
         unsafe {
             // We ensure capacity for the worst case as if there was no merge with the current packet
-            self.storage.ensure_capacity(next_capacity);
+            self.storage.ensure_capacity(next_capacity)
         }
     }
 
@@ -267,14 +342,14 @@ impl EventBuffer {
     }
 }
 
-impl AsRef<EventList> for EventBuffer {
+impl<const N: usize> AsRef<EventList> for EventBuffer<N> {
     #[inline]
     fn as_ref(&self) -> &EventList {
         unsafe { &*self.storage.as_ptr::<EventList>() }
     }
 }
 
-impl Deref for EventBuffer {
+impl<const N: usize> Deref for EventBuffer<N> {
     type Target = EventList;
 
     #[inline]
@@ -284,32 +359,37 @@ impl Deref for EventBuffer {
 }
 
 #[derive(Clone)]
-pub(crate) enum Storage {
+pub(crate) enum Storage<const N: usize> {
     /// Inline stores the data directly on the stack, if it is small enough.
     /// NOTE: using u32 ensures correct alignment (required on ARM)
-    Inline([u32; Storage::INLINE_SIZE / 4]),
-    /// External is used whenever the size of the data exceeds INLINE_PACKET_BUFFER_SIZE.
-    /// This means that the size of the contained vector is always greater than INLINE_PACKET_BUFFER_SIZE.
-    External(Vec<u32>),
+    Inline([MaybeUninit<u32>; N]),
+    /// External is used whenever the size of the data exceeds INLINE_SIZE.
+    /// This means that the size of the contained slice is always greater than INLINE_SIZE.
+    External(Box<[MaybeUninit<u32>]>),
 }
 
-impl Storage {
-    pub(crate) const INLINE_SIZE: usize = 8 // MIDIEventList header
-        + 12 // MIDIEventPacket header
-        + 4 * 4; // 4 words
+impl<const N: usize> Storage<N> {
+    pub(crate) const INLINE_SIZE: usize = N * 4;
 
+    /// The inline storage needs to cover a whole MIDIEventList or MIDIPacketList (see [MIN_STORAGE_WORDS]),
+    /// checked at compile time when a storage of a given size is created.
+    /// The external one is always larger than the inline one.
+    const VALID_INLINE_SIZE: () = assert!(
+        N >= MIN_STORAGE_WORDS,
+        "The inline storage needs to cover a whole event or packet list"
+    );
+
+    /// The words are left uninitialized, they are only written by the lists being built on top,
+    /// so only the part of the storage used by the list can be read.
     #[inline]
-    #[allow(clippy::uninit_vec)]
     pub(crate) fn with_capacity(capacity: usize) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::VALID_INLINE_SIZE;
         if capacity <= Self::INLINE_SIZE {
-            Self::Inline([0; Self::INLINE_SIZE / 4])
+            Self::Inline([MaybeUninit::uninit(); N])
         } else {
             let u32_len = ((capacity - 1) / 4) + 1;
-            let mut buffer = Vec::with_capacity(u32_len);
-            unsafe {
-                buffer.set_len(u32_len);
-            }
-            Storage::External(buffer)
+            Storage::External(Self::uninit_words(u32_len))
         }
     }
 
@@ -317,80 +397,86 @@ impl Storage {
     pub(crate) fn capacity(&self) -> usize {
         match *self {
             Storage::Inline(ref inline) => inline.len() * 4,
-            Storage::External(ref vec) => vec.len() * 4,
+            Storage::External(ref words) => words.len() * 4,
         }
     }
 
-    #[inline]
-    pub(crate) fn get_slice<T>(&self) -> &[T] {
-        unsafe {
-            match *self {
-                Storage::Inline(ref inline) => slice::from_raw_parts(
-                    inline.as_ptr() as *const T,
-                    inline.len() * size_of::<u32>() / size_of::<T>(),
-                ),
-                Storage::External(ref vec) => {
-                    slice::from_raw_parts(vec.as_ptr() as *const T, vec.len() * 4 / size_of::<T>())
-                }
-            }
-        }
+    /// Get the first `len` bytes of the storage.
+    ///
+    /// # Safety
+    ///
+    /// They need to be initialized, and `len` must be a multiple of the size of `T`.
+    #[cfg(test)]
+    pub(crate) unsafe fn get_slice<T>(&self, len: usize) -> &[T] {
+        debug_assert!(len <= self.capacity());
+        slice::from_raw_parts(self.as_ptr::<T>(), len / size_of::<T>())
     }
 
-    /// Call this only with larger length values (won't make the buffer smaller)
-    #[allow(clippy::uninit_vec)]
-    pub(crate) unsafe fn ensure_capacity(&mut self, capacity: usize) {
-        if capacity < Self::INLINE_SIZE || capacity < self.get_slice::<u8>().len() {
-            return;
+    /// Make sure that there is room for at least `capacity` bytes (it won't make the buffer smaller).
+    /// When it needs to grow, it at least doubles the current capacity, so that pushing
+    /// many events only needs a few reallocations. It returns the resulting capacity.
+    #[inline]
+    pub(crate) unsafe fn ensure_capacity(&mut self, capacity: usize) -> usize {
+        let current_capacity = self.capacity();
+        if capacity > current_capacity {
+            let new_capacity = std::cmp::max(capacity, current_capacity * 2);
+            self.grow(new_capacity);
+            self.capacity()
+        } else {
+            current_capacity
         }
+    }
 
-        let vec_capacity = ((capacity - 1) / 4) + 1;
-        let vec: Option<Vec<u32>> = match *self {
-            Storage::Inline(ref inline) => {
-                let mut v = Vec::with_capacity(vec_capacity);
-                v.extend_from_slice(inline);
-                v.set_len(vec_capacity);
-                Some(v)
-            }
-            Storage::External(ref mut vec) => {
-                let current_len = vec.len();
-                vec.reserve(vec_capacity - current_len);
-                vec.set_len(vec_capacity);
-                None
-            }
+    /// The current words are moved into the new storage as they are, initialized or not,
+    /// and the new words are left uninitialized.
+    #[cold]
+    #[inline(never)]
+    unsafe fn grow(&mut self, capacity: usize) {
+        let u32_len = ((capacity - 1) / 4) + 1;
+        let mut words = Self::uninit_words(u32_len);
+        let current_words: &[MaybeUninit<u32>] = match *self {
+            Storage::Inline(ref inline) => inline,
+            Storage::External(ref words) => words,
         };
+        words[..current_words.len()].copy_from_slice(current_words);
+        *self = Storage::External(words);
+    }
 
-        // to prevent borrow-check errors, this must come after the `match`
-        if let Some(v) = vec {
-            *self = Storage::External(v);
-        }
+    fn uninit_words(len: usize) -> Box<[MaybeUninit<u32>]> {
+        let mut words = Vec::with_capacity(len);
+        // Uninitialized words are valid values of MaybeUninit
+        unsafe { words.set_len(len) };
+        words.into_boxed_slice()
     }
 
     #[inline]
     pub(crate) unsafe fn as_ptr<T>(&self) -> *const T {
         match *self {
             Storage::Inline(ref inline) => inline.as_ptr() as *const T,
-            Storage::External(ref vec) => vec.as_ptr() as *const T,
+            Storage::External(ref words) => words.as_ptr() as *const T,
         }
     }
 
     #[inline]
     pub(crate) unsafe fn as_mut_ptr<T>(&mut self) -> *mut T {
-        self.as_ptr::<T>() as *mut T
+        match *self {
+            Storage::Inline(ref mut inline) => inline.as_mut_ptr() as *mut T,
+            Storage::External(ref mut words) => words.as_mut_ptr() as *mut T,
+        }
     }
 }
 
-impl std::fmt::Debug for Storage {
+impl<const N: usize> std::fmt::Debug for Storage<N> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for b in self.get_slice::<u8>() {
-            write!(f, " {:02x}", *b)?;
-        }
-        Ok(())
+        f.debug_struct("Storage")
+            .field("capacity", &self.capacity())
+            .finish()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::events::{Storage, Timestamp};
+    use crate::events::{Storage, Timestamp, DEFAULT_INLINE_WORDS};
     use crate::protocol::Protocol;
     use crate::{EventBuffer, EventList};
     use coremidi_sys::{
@@ -444,7 +530,10 @@ mod tests {
     fn event_buffer_new() {
         let event_buffer = EventBuffer::new(Protocol::Midi20);
 
-        assert_eq!(event_buffer.capacity(), Storage::INLINE_SIZE);
+        assert_eq!(
+            event_buffer.capacity(),
+            Storage::<DEFAULT_INLINE_WORDS>::INLINE_SIZE
+        );
         assert_eq!(event_buffer.protocol(), Protocol::Midi20);
         assert_eq!(event_buffer.len(), 0);
     }
@@ -453,16 +542,38 @@ mod tests {
     fn event_buffer_with_capacity_inline() {
         let event_buffer = EventBuffer::with_capacity(2, Protocol::Midi20);
 
-        assert_eq!(event_buffer.capacity(), Storage::INLINE_SIZE);
+        assert_eq!(
+            event_buffer.capacity(),
+            Storage::<DEFAULT_INLINE_WORDS>::INLINE_SIZE
+        );
         assert_eq!(event_buffer.protocol(), Protocol::Midi20);
         assert_eq!(event_buffer.len(), 0);
     }
 
+    #[test]
+    fn event_buffer_with_larger_inline_storage() {
+        let mut event_buffer = EventBuffer::<128>::with_inline_storage(Protocol::Midi10);
+        event_buffer
+            .push(10, &[0x20903c7f])
+            .push(20, &[0x20803c00])
+            .push(30, &[0x20903e7f])
+            .push(40, &[0x20803e00]);
+
+        assert_eq!(event_buffer.len(), 4);
+        assert!(matches!(event_buffer.storage, Storage::Inline(_)));
+    }
+
     #[test]
     fn event_buffer_with_capacity_external() {
-        let event_buffer = EventBuffer::with_capacity(Storage::INLINE_SIZE * 2, Protocol::Midi20);
+        let event_buffer = EventBuffer::with_capacity(
+            Storage::<DEFAULT_INLINE_WORDS>::INLINE_SIZE * 2,
+            Protocol::Midi20,
+        );
 
-        assert_eq!(event_buffer.capacity(), Storage::INLINE_SIZE * 2);
+        assert_eq!(
+            event_buffer.capacity(),
+            Storage::<DEFAULT_INLINE_WORDS>::INLINE_SIZE * 2
+        );
     }
 
     #[test]
@@ -513,6 +624,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn event_buffer_grows_exponentially() {
+        let mut event_buffer = EventBuffer::new(Protocol::Midi20);
+        let mut reallocations = 0;
+        let mut capacity = event_buffer.capacity();
+        for timestamp in 0..1000 {
+            event_buffer.push(timestamp, &[0x20903c7f]);
+            if event_buffer.capacity() != capacity {
+                capacity = event_buffer.capacity();
+                reallocations += 1;
+            }
+        }
+
+        assert_eq!(event_buffer.len(), 1000);
+        assert!(reallocations <= 12);
+    }
+
     #[test]
     fn event_buffer_clear() {
         let mut event_buffer = EventBuffer::new(Protocol::Midi20).with_packet(10, &[1, 2]);
@@ -529,7 +657,10 @@ mod tests {
         event_buffer.clear();
 
         assert_eq!(event_buffer.len(), 0);
-        assert_eq!(event_buffer.capacity(), Storage::INLINE_SIZE);
+        assert_eq!(
+            event_buffer.capacity(),
+            Storage::<DEFAULT_INLINE_WORDS>::INLINE_SIZE
+        );
         assert_eq!(
             event_buffer
                 .iter()
@@ -538,4 +669,43 @@ mod tests {
             vec![]
         );
     }
+
+    // These only use the storage, with no calls to CoreMIDI
+    #[test]
+    fn storage_with_capacity() {
+        let storage = Storage::<DEFAULT_INLINE_WORDS>::with_capacity(
+            Storage::<DEFAULT_INLINE_WORDS>::INLINE_SIZE * 4,
+        );
+
+        assert!(matches!(storage, Storage::External(_)));
+        assert_eq!(
+            storage.capacity(),
+            Storage::<DEFAULT_INLINE_WORDS>::INLINE_SIZE * 4
+        );
+    }
+
+    #[test]
+    fn storage_grows_keeping_the_contents() {
+        let mut storage = Storage::<DEFAULT_INLINE_WORDS>::with_capacity(0);
+        unsafe {
+            *storage.as_mut_ptr::<u32>() = 0x20903c7f;
+            *storage.as_mut_ptr::<u32>().add(1) = 0x20803c00;
+        }
+
+        let capacity = unsafe { storage.ensure_capacity(1024) };
+        assert_eq!(capacity, 1024);
+        assert_eq!(
+            unsafe { storage.get_slice::<u32>(8) },
+            &[0x20903c7f, 0x20803c00]
+        );
+
+        unsafe { *storage.as_mut_ptr::<u32>().add(255) = 0x20903d7f };
+        let capacity = unsafe { storage.ensure_capacity(1028) };
+        assert_eq!(capacity, 2048);
+        assert_eq!(
+            unsafe { storage.get_slice::<u32>(8) },
+            &[0x20903c7f, 0x20803c00]
+        );
+        assert_eq!(unsafe { *storage.as_ptr::<u32>().add(255) }, 0x20903d7f);
+    }
 }
@@ -0,0 +1,149 @@
+use crate::endpoints::endpoint::Endpoint;
+use crate::object::UniqueId;
+use crate::properties::{Properties, PropertyGetter};
+use crate::{Device, Entity};
+
+/// A snapshot of the most relevant properties of an endpoint.
+///
+/// ```rust,no_run
+/// let source = coremidi::Source::from_index(0).unwrap();
+/// println!("{:?}", source.info());
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EndpointInfo {
+    pub name: Option<String>,
+    pub display_name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub unique_id: Option<UniqueId>,
+    pub offline: bool,
+}
+
+impl From<&Endpoint> for EndpointInfo {
+    fn from(endpoint: &Endpoint) -> Self {
+        Self {
+            name: endpoint.name(),
+            display_name: endpoint.display_name(),
+            manufacturer: Properties::manufacturer().value_from(endpoint).ok(),
+            model: Properties::model().value_from(endpoint).ok(),
+            unique_id: endpoint.unique_id(),
+            offline: !endpoint.is_online(),
+        }
+    }
+}
+
+/// A snapshot of the properties of an entity, including its endpoints.
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EntityInfo {
+    pub name: Option<String>,
+    pub unique_id: Option<UniqueId>,
+    pub sources: Vec<EndpointInfo>,
+    pub destinations: Vec<EndpointInfo>,
+}
+
+impl From<&Entity> for EntityInfo {
+    fn from(entity: &Entity) -> Self {
+        Self {
+            name: entity.name(),
+            unique_id: entity.unique_id(),
+            sources: entity
+                .sources()
+                .iter()
+                .map(|source| source.info())
+                .collect(),
+            destinations: entity
+                .destinations()
+                .iter()
+                .map(|destination| destination.info())
+                .collect(),
+        }
+    }
+}
+
+/// A snapshot of the properties of a device, including its entities and their endpoints.
+///
+/// ```rust,no_run
+/// let source = coremidi::Source::from_index(0).unwrap();
+/// if let Some(device) = source.entity().and_then(|entity| entity.device()) {
+///     println!("{:#?}", device.info());
+/// }
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceInfo {
+    pub name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub unique_id: Option<UniqueId>,
+    pub offline: bool,
+    pub entities: Vec<EntityInfo>,
+}
+
+impl From<&Device> for DeviceInfo {
+    fn from(device: &Device) -> Self {
+        Self {
+            name: device.name(),
+            manufacturer: Properties::manufacturer().value_from(device).ok(),
+            model: Properties::model().value_from(device).ok(),
+            unique_id: device.unique_id(),
+            offline: device.is_offline(),
+            entities: device.entities().iter().map(EntityInfo::from).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Client, Protocol};
+
+    #[test]
+    fn endpoint_info() {
+        let client = Client::new("Test Client").unwrap();
+        let dest = client
+            .virtual_destination_with_protocol("A", Protocol::Midi10, |_| ())
+            .unwrap();
+
+        let info = dest.info();
+
+        assert_eq!(info.name.as_deref(), Some("A"));
+        assert_eq!(info.unique_id, dest.unique_id());
+        assert!(!info.offline);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn device_info_serde() {
+        use crate::{DeviceInfo, EndpointInfo, EntityInfo};
+
+        let endpoint = EndpointInfo {
+            name: Some("Port 1".to_string()),
+            display_name: Some("Synth Port 1".to_string()),
+            manufacturer: None,
+            model: None,
+            unique_id: Some(-42),
+            offline: false,
+        };
+        let info = DeviceInfo {
+            name: Some("Synth".to_string()),
+            manufacturer: Some("Acme".to_string()),
+            model: None,
+            unique_id: Some(7),
+            offline: true,
+            entities: vec![EntityInfo {
+                name: None,
+                unique_id: Some(8),
+                sources: vec![endpoint.clone()],
+                destinations: vec![endpoint],
+            }],
+        };
+
+        let json = serde_json::to_string(&info).unwrap();
+
+        assert_eq!(serde_json::from_str::<DeviceInfo>(&json).unwrap(), info);
+    }
+}
@@ -0,0 +1,97 @@
+//! Adjustments for the applications running on iOS (and iPadOS).
+//!
+//! Disposing the last client of the application can stop the MIDI server, and then no other client
+//! can be created by the application. So the first time that the application creates a [Client](crate::Client),
+//! another one is created along with it, which lives as long as the process.
+
+use core_foundation::base::{OSStatus, TCFType};
+use core_foundation::string::CFString;
+use once_cell::sync::OnceCell;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::Mutex;
+
+use coremidi_sys::{MIDIClientCreate, MIDIClientRef};
+
+use crate::objc::NotificationObserver;
+use crate::restart;
+
+/// The client that keeps the MIDI server running for the application, never disposed.
+static PROCESS_CLIENT: OnceCell<MIDIClientRef> = OnceCell::new();
+
+/// Create the client living as long as the process, unless it already exists.
+/// It is tried again the next time when it fails, as the server might be available by then.
+///
+pub(crate) fn keep_process_client() {
+    let _ = PROCESS_CLIENT.get_or_try_init(|| {
+        let client_name = CFString::new("coremidi-process-client");
+        let mut client_ref = MaybeUninit::uninit();
+        let status = unsafe {
+            MIDIClientCreate(
+                client_name.as_concrete_TypeRef(),
+                None,
+                ptr::null_mut(),
+                client_ref.as_mut_ptr(),
+            )
+        };
+        match status {
+            0 => Ok(unsafe { client_ref.assume_init() }),
+            _ => Err(status),
+        }
+    });
+}
+
+// The value of UIApplicationWillEnterForegroundNotification
+const WILL_ENTER_FOREGROUND: &str = "UIApplicationWillEnterForegroundNotification";
+
+/// Restarts the MIDI I/O (see [restart]) every time the application comes back to the foreground,
+/// until dropped.
+///
+/// While the application is in the background, the system can stop its MIDI I/O
+/// (unless it has the `audio` background mode), and the ports may stay silent after resuming.
+///
+/// ```rust,no_run
+/// use coremidi::{Client, ForegroundRestart};
+/// let client = Client::new("example-client").unwrap();
+/// let _restart = ForegroundRestart::with_callback(|result| {
+///     if let Err(status) = result {
+///         println!("MIDI could not be restarted: {}", status);
+///     }
+/// });
+/// ```
+///
+pub struct ForegroundRestart {
+    _observer: NotificationObserver,
+}
+
+impl ForegroundRestart {
+    pub fn new() -> ForegroundRestart {
+        Self::with_callback(|_| {})
+    }
+
+    /// Call `callback` after every restart with its result, for example to connect the sources again.
+    /// It is called from the main thread.
+    ///
+    pub fn with_callback<F>(callback: F) -> ForegroundRestart
+    where
+        F: FnMut(Result<(), OSStatus>) + Send + 'static,
+    {
+        let callback = Mutex::new(callback);
+        let observer =
+            NotificationObserver::new(WILL_ENTER_FOREGROUND, ptr::null_mut(), move || {
+                let result = restart();
+                (callback
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()))(result);
+            });
+        ForegroundRestart {
+            _observer: observer,
+        }
+    }
+}
+
+impl Default for ForegroundRestart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
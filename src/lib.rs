@@ -40,38 +40,164 @@ For handling low level MIDI data you may look into:
 */
 
 mod any_object;
+mod availability;
+mod backend;
+#[cfg(feature = "bluetooth")]
+mod bluetooth;
+mod cache;
+mod capture;
 mod client;
+mod clock;
+mod controllers;
 mod device;
+#[cfg(all(feature = "driver", not(target_os = "ios")))]
+pub mod driver;
 mod endpoints;
 mod entity;
+mod error;
 mod events;
+mod info;
+#[cfg(all(feature = "ios", target_os = "ios"))]
+mod ios;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod mmc;
+mod monitor;
+mod mpe;
+mod msc;
+#[cfg(feature = "midi-msg")]
+mod msg;
+mod mtc;
+mod mts;
+mod network;
+mod notes;
 mod notifications;
+mod objc;
 mod object;
 mod packets;
+mod pool;
 mod ports;
 mod properties;
 mod protocol;
+mod queue;
+mod realtime;
+mod reconnect;
+mod recorder;
+mod registry;
+mod replay;
+mod resolver;
+mod router;
+mod scheduler;
+mod sds;
+mod sensing;
+mod server;
+#[cfg(feature = "midly")]
+mod smf;
+mod snapshot;
+mod sys;
+mod sysex;
+mod thru;
+mod time;
+mod transforms;
 
 use core_foundation_sys::base::OSStatus;
 
 use coremidi_sys::{MIDIFlushOutput, MIDIRestart};
 
 pub use crate::any_object::AnyObject;
+pub use crate::availability::is_midi2_available;
+pub use crate::backend::{
+    Backend, BackendEndpoint, BackendNotification, CoreMidiBackend, MockBackend, MockConnection,
+};
+#[cfg(feature = "bluetooth")]
+pub use crate::bluetooth::{
+    ble_midi_data, ble_midi_packets, BluetoothCentral, BluetoothCentralEvent, BluetoothDevice,
+    BluetoothPeripheral, BluetoothPeripheralEvent, BluetoothState, BLE_MIDI_CHARACTERISTIC_UUID,
+    BLE_MIDI_PACKET_SIZE, BLE_MIDI_SERVICE_UUID,
+};
+pub use crate::cache::EndpointCache;
+pub use crate::capture::{
+    capture_buffer, CaptureDrain, CaptureReader, CaptureWriter, CapturedPacket,
+};
 pub use crate::client::{Client, NotifyCallback};
-pub use crate::device::Device;
+pub use crate::clock::{ClockReceiver, CLOCKS_PER_BEAT};
+pub use crate::controllers::{
+    ChannelMode, ControllerChange, ControllerPairReceiver, ParameterChange, ParameterNumber,
+    ParameterReceiver,
+};
+pub use crate::device::{Device, DevicePorts, Devices, ExternalDevices, OwnedDevice};
 pub use crate::endpoints::destinations::{Destination, Destinations, VirtualDestination};
 pub use crate::endpoints::endpoint::Endpoint;
 pub use crate::endpoints::sources::{Source, Sources, VirtualSource};
 pub use crate::entity::Entity;
-pub use crate::events::{EventBuffer, EventList, EventListIter, EventPacket, Timestamp};
-pub use crate::notifications::{AddedRemovedInfo, IoErrorInfo, Notification, PropertyChangedInfo};
-pub use crate::object::Object;
-pub use crate::packets::{Packet, PacketBuffer, PacketList, PacketListIterator};
-pub use crate::ports::{InputPort, InputPortWithContext, OutputPort};
+pub use crate::error::{MidiError, UNSUPPORTED_STATUS};
+pub use crate::events::{
+    EventBuffer, EventList, EventListIter, EventPacket, Timestamp, DEFAULT_INLINE_WORDS,
+    MIN_STORAGE_WORDS,
+};
+pub use crate::info::{DeviceInfo, EndpointInfo, EntityInfo};
+#[cfg(all(feature = "ios", target_os = "ios"))]
+pub use crate::ios::ForegroundRestart;
+#[cfg(feature = "metrics")]
+pub use crate::metrics::{MetricsSnapshot, ReceivedSnapshot, LATENCY_BUCKETS_MICROS};
+pub use crate::mmc::{MachineControl, MachineControlCommand};
+pub use crate::monitor::Monitor;
+pub use crate::mpe::{MpeEvent, MpeNote, MpeReceiver, MpeZone};
+pub use crate::msc::{Cue, ShowControl, ShowControlFormat};
+#[cfg(feature = "midi-msg")]
+pub use crate::msg::MidiMsgs;
+pub use crate::mtc::{FrameRate, MtcDecoder, MtcGenerator, Timecode};
+pub use crate::mts::{NoteTuning, Tuning};
+pub use crate::network::{
+    DiscoveredHost, NetworkBrowser, NetworkBrowserEvent, NetworkConnection, NetworkConnectionEvent,
+    NetworkConnectionPolicy, NetworkHost, NetworkHostInfo, NetworkSession, NetworkSessionObserver,
+};
+pub use crate::notes::{NoteEvent, NoteTracker};
+pub use crate::notifications::{
+    AddedRemovedInfo, IoErrorInfo, Notification, PropertyChangedInfo, PropertyWatcher,
+};
+pub use crate::object::{Object, UniqueId};
+pub use crate::packets::{Packet, PacketBuffer, PacketList, PacketListIterator, SlicePacketBuffer};
+pub use crate::pool::{PacketBufferPool, PooledPacketBuffer};
+pub use crate::ports::{InputPort, InputPortWithContext, OutputPort, Packets};
+pub use crate::properties::keys as property_keys;
 pub use crate::properties::{
-    BooleanProperty, IntegerProperty, Properties, PropertyGetter, PropertySetter, StringProperty,
+    AnyPropertyValue, BooleanProperty, ConnectionUniqueIdsProperty, DataProperty, IntegerProperty,
+    IntoCFString, NamedProperty, Properties, PropertyGetter, PropertyHandle, PropertyKey,
+    PropertySetter, PropertyValue, StringProperty,
 };
 pub use crate::protocol::Protocol;
+pub use crate::queue::{
+    message_queue, MessageConsumer, MessageProducer, TimestampedMessage, MAX_MESSAGE_WORDS,
+};
+pub use crate::reconnect::ReconnectingInputPort;
+pub use crate::recorder::{RecordedPacket, Recorder};
+pub use crate::registry::{EndpointEvent, EndpointRegistry, RegisteredEndpoint};
+pub use crate::replay::{ReplayBackend, ReplayEvent, ReplayScript};
+pub use crate::resolver::EndpointDescriptor;
+pub use crate::router::{RouteTransform, Router};
+pub use crate::scheduler::Scheduler;
+pub use crate::sds::{
+    sample_dump_packets, SampleDumpHeader, SampleDumpMessage, SAMPLE_DUMP_PACKET_SIZE,
+};
+pub use crate::sensing::ActiveSensingWatchdog;
+pub use crate::server::{clear_server_lost_callback, set_server_lost_callback};
+#[cfg(feature = "midly")]
+pub use crate::smf::{tick_duration, track_to_packets, TrackRecorder};
+pub use crate::snapshot::{system_snapshot, SetupChange, SetupTracker, SystemSnapshot};
+pub use crate::sysex::{
+    ResetKind, SysexTransaction, GM2_SYSTEM_ON, GM_SYSTEM_ON, GS_RESET, XG_SYSTEM_ON,
+};
+pub use crate::thru::{
+    ThruConnection, ThruConnectionParams, ThruConnectionPersistence, THRU_CONNECTION_MAX_ENDPOINTS,
+};
+pub use crate::time::{
+    duration_from_host_time, durations_from_host_times, host_time_from_duration, host_time_now,
+};
+pub use crate::transforms::{
+    midi1_data_len, route_transform, transform_midi1_data, transform_ump_data, ump_message_len,
+    ChannelMap, KeyboardZone, MessageFilter, MessageKind, MessageTransform, VelocityCurve,
+};
 
 /// Unschedules previously-sent packets for all the endpoints.
 /// See [MIDIFlushOutput](https://developer.apple.com/documentation/coremidi/1495312-midiflushoutput).
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::iter;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{duration_from_host_time, host_time_now, EventList, PacketList, Timestamp, UniqueId};
+
+/// The upper bounds of the buckets of the latency histograms, in microseconds.
+/// There is an additional bucket for the latencies above the last bound.
+///
+pub const LATENCY_BUCKETS_MICROS: [u64; 12] = [
+    10, 20, 50, 100, 200, 500, 1_000, 2_000, 5_000, 10_000, 20_000, 50_000,
+];
+
+const NUM_LATENCY_BUCKETS: usize = LATENCY_BUCKETS_MICROS.len() + 1;
+
+/// The counters of the data received by a port, or by a port from one of its sources.
+#[derive(Debug, Default)]
+pub(crate) struct ReceivedMetrics {
+    packets: AtomicU64,
+    bytes: AtomicU64,
+    latencies: [AtomicU64; NUM_LATENCY_BUCKETS],
+    consumption_latencies: [AtomicU64; NUM_LATENCY_BUCKETS],
+}
+
+impl ReceivedMetrics {
+    pub(crate) fn record_events(&self, event_list: &EventList, now: Timestamp) {
+        for packet in event_list.iter() {
+            self.count(packet.data().len() * 4);
+            self.record_latency(packet.timestamp(), now);
+        }
+    }
+
+    pub(crate) fn record_packets(&self, packet_list: &PacketList, now: Timestamp) {
+        for packet in packet_list.iter() {
+            self.count(packet.data().len());
+            self.record_latency(packet.timestamp(), now);
+        }
+    }
+
+    /// Record the time spent by the callback consuming the data received at `since`.
+    pub(crate) fn record_consumption(&self, since: Timestamp, now: Timestamp) {
+        let latency = duration_from_host_time(now.saturating_sub(since));
+        self.consumption_latencies[latency_bucket(latency)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> ReceivedSnapshot {
+        ReceivedSnapshot {
+            packets_received: self.packets.load(Ordering::Relaxed),
+            bytes_received: self.bytes.load(Ordering::Relaxed),
+            latency_histogram: histogram(&self.latencies),
+            consumption_histogram: histogram(&self.consumption_latencies),
+        }
+    }
+
+    #[inline]
+    fn count(&self, len: usize) {
+        self.packets.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    /// Record the time since the packet was received by CoreMIDI, unless it has no timestamp.
+    #[inline]
+    fn record_latency(&self, timestamp: Timestamp, now: Timestamp) {
+        if timestamp == 0 || timestamp > now {
+            return;
+        }
+        let latency = duration_from_host_time(now - timestamp);
+        self.latencies[latency_bucket(latency)].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The counters of a port, updated from the send and receive paths without locking.
+#[derive(Debug, Default)]
+pub(crate) struct PortMetrics {
+    packets_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    received: ReceivedMetrics,
+    // Only locked when connecting a source and taking a snapshot, never from the receive paths
+    sources: Mutex<HashMap<UniqueId, Arc<ReceivedMetrics>>>,
+}
+
+impl PortMetrics {
+    /// Update the metrics of the port, and the ones of the source the events come from, around
+    /// the call to the receive callback.
+    ///
+    /// Unlike [PortMetrics::wrap_packets] it doesn't return the wrapped callback, as a closure returned
+    /// from here would need the contexts of the connections to be `'static` in older compilers.
+    #[inline]
+    pub(crate) fn receive_events<F>(
+        &self,
+        event_list: &EventList,
+        source: Option<&ReceivedMetrics>,
+        callback: F,
+    ) where
+        F: FnOnce(&EventList),
+    {
+        let received = self.received_by(source);
+        let now = host_time_now();
+        received
+            .clone()
+            .for_each(|metrics| metrics.record_events(event_list, now));
+        callback(event_list);
+        let consumed = host_time_now();
+        received.for_each(|metrics| metrics.record_consumption(now, consumed));
+    }
+
+    /// Wrap a read callback so that it updates the metrics of the port, and the ones of the source
+    /// the packets come from, around the call.
+    pub(crate) fn wrap_packets<F>(
+        mut callback: F,
+    ) -> (
+        Arc<Self>,
+        impl FnMut(&PacketList, Option<&ReceivedMetrics>) + Send + 'static,
+    )
+    where
+        F: FnMut(&PacketList) + Send + 'static,
+    {
+        let metrics = Arc::new(Self::default());
+        let callback_metrics = metrics.clone();
+        let callback = move |packet_list: &PacketList, source: Option<&ReceivedMetrics>| {
+            let received = callback_metrics.received_by(source);
+            let now = host_time_now();
+            received
+                .clone()
+                .for_each(|metrics| metrics.record_packets(packet_list, now));
+            callback(packet_list);
+            let consumed = host_time_now();
+            received.for_each(|metrics| metrics.record_consumption(now, consumed));
+        };
+        (metrics, callback)
+    }
+
+    /// Get the metrics of a source connected to the port, which are kept for the lifetime of the port.
+    pub(crate) fn source(&self, unique_id: UniqueId) -> Arc<ReceivedMetrics> {
+        let mut sources = self
+            .sources
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        sources.entry(unique_id).or_default().clone()
+    }
+
+    pub(crate) fn record_sent(&self, packets: usize, bytes: usize) {
+        self.packets_sent
+            .fetch_add(packets as u64, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        let received = self.received.snapshot();
+        let sources = self
+            .sources
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        MetricsSnapshot {
+            packets_sent: self.packets_sent.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            packets_received: received.packets_received,
+            bytes_received: received.bytes_received,
+            latency_histogram: received.latency_histogram,
+            consumption_histogram: received.consumption_histogram,
+            sources: sources
+                .iter()
+                .map(|(unique_id, metrics)| (*unique_id, metrics.snapshot()))
+                .collect(),
+        }
+    }
+
+    /// The metrics updated for the data received from a source: the ones of the port, and the ones of the source if known.
+    #[inline]
+    fn received_by<'a>(
+        &'a self,
+        source: Option<&'a ReceivedMetrics>,
+    ) -> impl Iterator<Item = &'a ReceivedMetrics> + Clone {
+        iter::once(&self.received).chain(source)
+    }
+}
+
+fn histogram(latencies: &[AtomicU64; NUM_LATENCY_BUCKETS]) -> Vec<u64> {
+    latencies
+        .iter()
+        .map(|count| count.load(Ordering::Relaxed))
+        .collect()
+}
+
+fn latency_bucket(latency: Duration) -> usize {
+    let micros = latency.as_micros();
+    LATENCY_BUCKETS_MICROS
+        .iter()
+        .position(|bound| micros <= *bound as u128)
+        .unwrap_or(LATENCY_BUCKETS_MICROS.len())
+}
+
+/// The values of the counters of a port at some point in time.
+///
+/// The latencies are the time between CoreMIDI receiving the packets (their timestamp),
+/// and the callback of the port being called with them.
+/// The consumption latencies are the time between the callback being called, and it returning
+/// after consuming the packets.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    pub packets_received: u64,
+    pub bytes_received: u64,
+    /// The number of packets for each bucket in [LATENCY_BUCKETS_MICROS], plus the ones above the last bound.
+    pub latency_histogram: Vec<u64>,
+    /// The number of calls to the callback for each bucket in [LATENCY_BUCKETS_MICROS], plus the ones above the last bound.
+    pub consumption_histogram: Vec<u64>,
+    /// The counters of the data received from each of the sources connected to the port, by their unique id.
+    pub sources: HashMap<UniqueId, ReceivedSnapshot>,
+}
+
+/// The values of the counters of the data received from a source at some point in time.
+/// See [MetricsSnapshot].
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReceivedSnapshot {
+    pub packets_received: u64,
+    pub bytes_received: u64,
+    pub latency_histogram: Vec<u64>,
+    pub consumption_histogram: Vec<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::metrics::{latency_bucket, PortMetrics, LATENCY_BUCKETS_MICROS};
+    use crate::ports::Connection;
+    use crate::{EventBuffer, EventList, PacketBuffer, Protocol};
+
+    #[test]
+    fn latency_buckets() {
+        assert_eq!(latency_bucket(Duration::from_micros(0)), 0);
+        assert_eq!(latency_bucket(Duration::from_micros(10)), 0);
+        assert_eq!(latency_bucket(Duration::from_micros(11)), 1);
+        assert_eq!(
+            latency_bucket(Duration::from_secs(1)),
+            LATENCY_BUCKETS_MICROS.len()
+        );
+    }
+
+    #[test]
+    fn count_packets_and_bytes() {
+        let metrics = PortMetrics::default();
+        let events = EventBuffer::new(Protocol::Midi20)
+            .with_packet(0, &[0x40903c00, 0xffff0000])
+            .with_packet(1, &[0x20803c00]);
+
+        metrics.record_sent(1, 3);
+        metrics.received.record_events(&events, 0);
+        let snapshot = metrics.snapshot();
+
+        assert_eq!(snapshot.packets_sent, 1);
+        assert_eq!(snapshot.bytes_sent, 3);
+        assert_eq!(snapshot.packets_received, 2);
+        assert_eq!(snapshot.bytes_received, 12);
+        assert_eq!(snapshot.latency_histogram.iter().sum::<u64>(), 0);
+    }
+
+    #[test]
+    fn count_by_source() {
+        let (metrics, mut callback) = PortMetrics::wrap_packets(|_| {});
+        let source = metrics.source(7);
+        let packets = PacketBuffer::new(0, &[0x90, 0x40, 0x7f]);
+
+        callback(&packets, Some(&source));
+        callback(&packets, None);
+        let snapshot = metrics.snapshot();
+
+        assert_eq!(snapshot.packets_received, 2);
+        assert_eq!(snapshot.bytes_received, 6);
+        assert_eq!(snapshot.consumption_histogram.iter().sum::<u64>(), 2);
+        assert_eq!(snapshot.sources.len(), 1);
+        let source = &snapshot.sources[&7];
+        assert_eq!(source.packets_received, 1);
+        assert_eq!(source.bytes_received, 3);
+        assert_eq!(source.consumption_histogram.iter().sum::<u64>(), 1);
+    }
+
+    #[test]
+    fn count_by_connection() {
+        let metrics = PortMetrics::default();
+        let callback = |event_list: &EventList, connection: &mut Connection<u32>| {
+            let context = &mut connection.context;
+            metrics.receive_events(event_list, connection.metrics.as_deref(), |_| *context += 1)
+        };
+        let mut connection = Connection {
+            context: 0,
+            metrics: Some(metrics.source(7)),
+        };
+        let events = EventBuffer::new(Protocol::Midi10).with_packet(0, &[0x20903c7f]);
+
+        callback(&events, &mut connection);
+        let snapshot = metrics.snapshot();
+
+        assert_eq!(connection.context, 1);
+        assert_eq!(snapshot.packets_received, 1);
+        assert_eq!(snapshot.sources[&7].bytes_received, 4);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::Timecode;
+
+const UNIVERSAL_REAL_TIME: u8 = 0x7f;
+const MACHINE_CONTROL_COMMAND: u8 = 0x06;
+const LOCATE: u8 = 0x44;
+// The length of the LOCATE data, and its TARGET subcommand
+const LOCATE_TARGET: [u8; 2] = [0x06, 0x01];
+
+/// The transport commands of MIDI Machine Control.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MachineControlCommand {
+    Stop,
+    Play,
+    DeferredPlay,
+    FastForward,
+    Rewind,
+    /// Start recording (punch in).
+    RecordStrobe,
+    /// Stop recording (punch out).
+    RecordExit,
+    RecordPause,
+    Pause,
+    Eject,
+    Chase,
+    Reset,
+    /// Move to a position.
+    Locate(Timecode),
+}
+
+impl MachineControlCommand {
+    fn code(&self) -> u8 {
+        match self {
+            MachineControlCommand::Stop => 0x01,
+            MachineControlCommand::Play => 0x02,
+            MachineControlCommand::DeferredPlay => 0x03,
+            MachineControlCommand::FastForward => 0x04,
+            MachineControlCommand::Rewind => 0x05,
+            MachineControlCommand::RecordStrobe => 0x06,
+            MachineControlCommand::RecordExit => 0x07,
+            MachineControlCommand::RecordPause => 0x08,
+            MachineControlCommand::Pause => 0x09,
+            MachineControlCommand::Eject => 0x0a,
+            MachineControlCommand::Chase => 0x0b,
+            MachineControlCommand::Reset => 0x0d,
+            MachineControlCommand::Locate(_) => LOCATE,
+        }
+    }
+
+    fn from_code(code: u8, data: &[u8]) -> Option<Self> {
+        let command = match code {
+            0x01 => MachineControlCommand::Stop,
+            0x02 => MachineControlCommand::Play,
+            0x03 => MachineControlCommand::DeferredPlay,
+            0x04 => MachineControlCommand::FastForward,
+            0x05 => MachineControlCommand::Rewind,
+            0x06 => MachineControlCommand::RecordStrobe,
+            0x07 => MachineControlCommand::RecordExit,
+            0x08 => MachineControlCommand::RecordPause,
+            0x09 => MachineControlCommand::Pause,
+            0x0a => MachineControlCommand::Eject,
+            0x0b => MachineControlCommand::Chase,
+            0x0d => MachineControlCommand::Reset,
+            LOCATE if data.starts_with(&LOCATE_TARGET) => {
+                MachineControlCommand::Locate(Timecode::from_standard_time(&data[2..])?)
+            }
+            _ => return None,
+        };
+        Some(command)
+    }
+
+    /// Build the SysEx data of the command for a device ID (or [MachineControl::ALL_DEVICES]).
+    ///
+    pub fn to_sysex(&self, device_id: u8) -> Vec<u8> {
+        let mut sysex = vec![
+            0xf0,
+            UNIVERSAL_REAL_TIME,
+            device_id & 0x7f,
+            MACHINE_CONTROL_COMMAND,
+            self.code(),
+        ];
+        if let MachineControlCommand::Locate(position) = self {
+            sysex.extend_from_slice(&LOCATE_TARGET);
+            sysex.extend_from_slice(&position.standard_time());
+        }
+        sysex.push(0xf7);
+        sysex
+    }
+
+    /// Parse the SysEx data of a command (including the 0xF0 and 0xF7 bytes), returning the device ID and the command.
+    ///
+    pub fn parse(sysex: &[u8]) -> Option<(u8, MachineControlCommand)> {
+        match sysex {
+            [0xf0, UNIVERSAL_REAL_TIME, device_id, MACHINE_CONTROL_COMMAND, code, data @ .., 0xf7] => {
+                Some((*device_id, Self::from_code(*code, data)?))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Builds and parses the MIDI Machine Control commands for a specific device ID
+/// (see [Properties::supports_mmc](crate::Properties::supports_mmc)).
+///
+/// ```
+/// use coremidi::{FrameRate, MachineControl, MachineControlCommand, Timecode};
+/// let machine_control = MachineControl::new(0x10);
+/// assert_eq!(machine_control.play(), vec![0xf0, 0x7f, 0x10, 0x06, 0x02, 0xf7]);
+///
+/// let locate = machine_control.locate(&Timecode::new(0, 1, 0, 0, FrameRate::Fps30));
+/// assert!(matches!(machine_control.parse(&locate), Some(MachineControlCommand::Locate(_))));
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MachineControl {
+    device_id: u8,
+}
+
+impl MachineControl {
+    /// The device ID addressing all the devices.
+    ///
+    pub const ALL_DEVICES: u8 = 0x7f;
+
+    pub fn new(device_id: u8) -> Self {
+        Self {
+            device_id: device_id & 0x7f,
+        }
+    }
+
+    pub fn device_id(&self) -> u8 {
+        self.device_id
+    }
+
+    pub fn command(&self, command: MachineControlCommand) -> Vec<u8> {
+        command.to_sysex(self.device_id)
+    }
+
+    pub fn play(&self) -> Vec<u8> {
+        self.command(MachineControlCommand::Play)
+    }
+
+    pub fn stop(&self) -> Vec<u8> {
+        self.command(MachineControlCommand::Stop)
+    }
+
+    pub fn record(&self) -> Vec<u8> {
+        self.command(MachineControlCommand::RecordStrobe)
+    }
+
+    pub fn locate(&self, position: &Timecode) -> Vec<u8> {
+        self.command(MachineControlCommand::Locate(*position))
+    }
+
+    /// Parse a command addressed to this device ID (or to all the devices).
+    ///
+    pub fn parse(&self, sysex: &[u8]) -> Option<MachineControlCommand> {
+        match MachineControlCommand::parse(sysex)? {
+            (device_id, command)
+                if device_id == self.device_id || device_id == Self::ALL_DEVICES =>
+            {
+                Some(command)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mmc::{MachineControl, MachineControlCommand};
+    use crate::{FrameRate, Timecode};
+
+    #[test]
+    fn locate() {
+        let position = Timecode::new(1, 2, 3, 4, FrameRate::Fps2997Drop);
+        let sysex = MachineControlCommand::Locate(position).to_sysex(0x7f);
+
+        assert_eq!(
+            sysex,
+            vec![0xf0, 0x7f, 0x7f, 0x06, 0x44, 0x06, 0x01, 0x41, 0x02, 0x03, 0x04, 0x00, 0xf7]
+        );
+        assert_eq!(
+            MachineControlCommand::parse(&sysex),
+            Some((0x7f, MachineControlCommand::Locate(position)))
+        );
+    }
+
+    #[test]
+    fn parse_only_for_the_device() {
+        let machine_control = MachineControl::new(1);
+
+        assert_eq!(
+            machine_control.parse(&MachineControl::new(1).stop()),
+            Some(MachineControlCommand::Stop)
+        );
+        assert_eq!(
+            machine_control.parse(&MachineControl::new(0x7f).record()),
+            Some(MachineControlCommand::RecordStrobe)
+        );
+        assert_eq!(machine_control.parse(&MachineControl::new(2).play()), None);
+        assert_eq!(
+            machine_control.parse(&[0xf0, 0x7f, 0x01, 0x06, 0x0c, 0xf7]),
+            None
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use core_foundation::base::OSStatus;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{
+    AnyObject, Client, EventList, InputPortWithContext, Notification, Protocol, Source, Sources,
+};
+
+type MonitorPort = Arc<Mutex<Option<InputPortWithContext<Source>>>>;
+
+fn lock_port(port: &MonitorPort) -> MutexGuard<'_, Option<InputPortWithContext<Source>>> {
+    port.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn handle_notification(port: &MonitorPort, notification: &Notification) {
+    let mut guard = lock_port(port);
+    let input_port = match guard.as_mut() {
+        Some(input_port) => input_port,
+        None => return,
+    };
+    // There is nobody to report the errors to from the notifications thread
+    match notification {
+        Notification::ObjectAdded(info) => {
+            if let AnyObject::Source(source) = &info.child {
+                let _ = input_port.connect_source(source, source.clone());
+            }
+        }
+        Notification::ObjectRemoved(info) => {
+            if let AnyObject::Source(source) = &info.child {
+                let _ = input_port.disconnect_source(source);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A utility receiving the MIDI data from all the sources in the system.
+///
+/// It owns a client and an input port connected to every source available when it is created,
+/// and to the ones appearing afterwards. The callback receives the data along with the source it comes from.
+///
+/// The sources are tracked through notifications, so the run loop that was current when the monitor
+/// was created needs to be running for it to pick up the new sources (see [Client::new_with_notifications]).
+///
+/// ```rust,no_run
+/// use coremidi::{Monitor, Protocol};
+/// let monitor = Monitor::new("example-monitor", Protocol::Midi10, |source, event_list| {
+///     println!("{:?}: {:?}", source.display_name(), event_list);
+/// }).unwrap();
+/// ```
+///
+pub struct Monitor {
+    port: MonitorPort,
+    // The client must be dropped after the port, as it owns it
+    _client: Client,
+}
+
+impl Monitor {
+    /// Create a monitor with its own client and input port, both named `name`.
+    ///
+    pub fn new<F>(name: &str, protocol: Protocol, mut callback: F) -> Result<Monitor, OSStatus>
+    where
+        F: FnMut(&Source, &EventList) + Send + 'static,
+    {
+        let port: MonitorPort = Arc::new(Mutex::new(None));
+        let notifications_port = port.clone();
+        let client = Client::new_with_notifications(name, move |notification: &Notification| {
+            handle_notification(&notifications_port, notification)
+        })?;
+        let mut input_port = client.input_port_with_protocol(
+            name,
+            protocol,
+            move |event_list, source: &mut Source| callback(source, event_list),
+        )?;
+        for source in Sources {
+            input_port.connect_source(&source, source.clone())?;
+        }
+        *lock_port(&port) = Some(input_port);
+        Ok(Monitor {
+            port,
+            _client: client,
+        })
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        lock_port(&self.port).take();
+    }
+}
@@ -0,0 +1,310 @@
+use crate::transforms::for_each_midi1_message;
+use crate::{PacketList, ParameterNumber, ParameterReceiver};
+
+// The Registered Parameter Number of the MPE Configuration Message
+pub(crate) const MPE_CONFIGURATION: u16 = 6;
+const TIMBRE: u8 = 74;
+
+/// One of the two zones of MIDI Polyphonic Expression.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MpeZone {
+    /// The zone with the master channel 0 (channel 1 for humans), and the member channels above it.
+    Lower,
+    /// The zone with the master channel 15 (channel 16 for humans), and the member channels below it.
+    Upper,
+}
+
+impl MpeZone {
+    pub fn master_channel(&self) -> u8 {
+        match self {
+            MpeZone::Lower => 0,
+            MpeZone::Upper => 15,
+        }
+    }
+
+    /// The member channels of the zone when it has `member_channels` of them.
+    ///
+    pub fn member_channels(&self, member_channels: u8) -> std::ops::RangeInclusive<u8> {
+        let member_channels = member_channels.min(15);
+        match self {
+            MpeZone::Lower => 1..=member_channels,
+            MpeZone::Upper => (15 - member_channels)..=14,
+        }
+    }
+}
+
+/// A note played in a member channel of an MPE zone.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MpeNote {
+    pub zone: MpeZone,
+    pub channel: u8,
+    pub note: u8,
+}
+
+/// The per-note events demultiplexed by an [MpeReceiver].
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpeEvent {
+    NoteOn {
+        note: MpeNote,
+        velocity: u8,
+    },
+    NoteOff {
+        note: MpeNote,
+        velocity: u8,
+    },
+    /// The 14 bits pitch bend of the note, where 0x2000 is the center.
+    PitchBend {
+        note: MpeNote,
+        value: u16,
+    },
+    /// The pressure of the note, from Channel Pressure.
+    Pressure {
+        note: MpeNote,
+        pressure: u8,
+    },
+    /// The third dimension of control of the note (CC 74).
+    Timbre {
+        note: MpeNote,
+        value: u8,
+    },
+}
+
+/// Demultiplexes the streams of MIDI Polyphonic Expression into per-note events.
+///
+/// The member channel messages (notes, pitch bend, channel pressure and CC 74) are turned into [MpeEvent]s
+/// for every note playing in the channel. The zones are configured explicitly, or from the MPE Configuration
+/// Messages received. The messages of the master channels and of the channels not in a zone are ignored,
+/// so they can be handled as usual.
+///
+/// ```
+/// use coremidi::{MpeEvent, MpeReceiver, MpeZone, PacketBuffer};
+/// let mut receiver = MpeReceiver::new();
+/// let mut packets = PacketBuffer::with_capacity(0);
+/// packets
+///     .push_mpe_configuration(0, MpeZone::Lower, 15)
+///     .push_note_on(0, 3, 60, 100)
+///     .push_pitch_bend(0, 3, 0x3000);
+/// let mut events = Vec::new();
+/// receiver.process_packets(&packets, |event| events.push(event));
+/// assert!(matches!(events[1], MpeEvent::PitchBend { note, value: 0x3000 } if note.note == 60));
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct MpeReceiver {
+    lower_members: u8,
+    upper_members: u8,
+    // The notes playing in every channel, as bit sets
+    notes: [u128; 16],
+    parameters: ParameterReceiver,
+}
+
+impl MpeReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configure a zone with a number of member channels, where 0 disables it.
+    ///
+    pub fn with_zone(mut self, zone: MpeZone, member_channels: u8) -> Self {
+        self.set_zone(zone, member_channels);
+        self
+    }
+
+    /// Configure a zone, as the MPE Configuration Message does. The other zone shrinks if they overlap.
+    ///
+    pub fn set_zone(&mut self, zone: MpeZone, member_channels: u8) {
+        let member_channels = member_channels.min(15);
+        let (members, other_members) = match zone {
+            MpeZone::Lower => (&mut self.lower_members, &mut self.upper_members),
+            MpeZone::Upper => (&mut self.upper_members, &mut self.lower_members),
+        };
+        *members = member_channels;
+        *other_members = (*other_members).min(14u8.saturating_sub(member_channels));
+    }
+
+    /// The number of member channels of a zone.
+    ///
+    pub fn zone_members(&self, zone: MpeZone) -> u8 {
+        match zone {
+            MpeZone::Lower => self.lower_members,
+            MpeZone::Upper => self.upper_members,
+        }
+    }
+
+    /// The zone that a channel is a member of.
+    ///
+    pub fn zone_of(&self, channel: u8) -> Option<MpeZone> {
+        [MpeZone::Lower, MpeZone::Upper]
+            .iter()
+            .copied()
+            .find(|zone| {
+                let members = self.zone_members(*zone);
+                members > 0 && zone.member_channels(members).contains(&channel)
+            })
+    }
+
+    /// Process all the messages of a list of MIDI 1.0 packets, calling `on_event` for every per-note event.
+    ///
+    pub fn process_packets<F>(&mut self, packet_list: &PacketList, mut on_event: F)
+    where
+        F: FnMut(MpeEvent),
+    {
+        for packet in packet_list.iter() {
+            for_each_midi1_message(packet.data(), |message| {
+                self.process(message, &mut on_event)
+            });
+        }
+    }
+
+    /// Process a single MIDI 1.0 message, starting with its status byte.
+    ///
+    pub fn process<F>(&mut self, message: &[u8], mut on_event: F)
+    where
+        F: FnMut(MpeEvent),
+    {
+        if let Some(change) = self.parameters.process(message) {
+            if change.parameter == ParameterNumber::Registered(MPE_CONFIGURATION) {
+                match change.channel {
+                    0 => self.set_zone(MpeZone::Lower, (change.value >> 7) as u8),
+                    15 => self.set_zone(MpeZone::Upper, (change.value >> 7) as u8),
+                    _ => {}
+                }
+            }
+            return;
+        }
+
+        let status = match message.first() {
+            Some(status) if *status < 0xf0 => *status,
+            _ => return,
+        };
+        let channel = status & 0x0f;
+        let zone = match self.zone_of(channel) {
+            Some(zone) => zone,
+            None => return,
+        };
+        let note_of = |note| MpeNote {
+            zone,
+            channel,
+            note,
+        };
+        let notes = &mut self.notes[channel as usize];
+        match (status & 0xf0, &message[1..]) {
+            (0x90, [note, velocity]) if *velocity > 0 => {
+                *notes |= 1 << (note & 0x7f);
+                on_event(MpeEvent::NoteOn {
+                    note: note_of(*note),
+                    velocity: *velocity,
+                });
+            }
+            (0x80, [note, velocity]) | (0x90, [note, velocity]) => {
+                *notes &= !(1 << (note & 0x7f));
+                on_event(MpeEvent::NoteOff {
+                    note: note_of(*note),
+                    velocity: *velocity,
+                });
+            }
+            (0xe0, [lsb, msb]) => {
+                let value = ((*msb as u16) << 7) | *lsb as u16;
+                for_each_note(*notes, |note| {
+                    on_event(MpeEvent::PitchBend {
+                        note: note_of(note),
+                        value,
+                    })
+                });
+            }
+            (0xd0, [pressure]) => for_each_note(*notes, |note| {
+                on_event(MpeEvent::Pressure {
+                    note: note_of(note),
+                    pressure: *pressure,
+                })
+            }),
+            (0xb0, [TIMBRE, value]) => for_each_note(*notes, |note| {
+                on_event(MpeEvent::Timbre {
+                    note: note_of(note),
+                    value: *value,
+                })
+            }),
+            _ => {}
+        }
+    }
+}
+
+fn for_each_note<F>(notes: u128, mut f: F)
+where
+    F: FnMut(u8),
+{
+    let mut remaining = notes;
+    while remaining != 0 {
+        let note = remaining.trailing_zeros();
+        f(note as u8);
+        remaining &= remaining - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mpe::{MpeEvent, MpeNote, MpeReceiver, MpeZone};
+    use crate::PacketBuffer;
+
+    #[test]
+    fn zones_shrink_when_overlapping() {
+        let mut receiver = MpeReceiver::new().with_zone(MpeZone::Lower, 10);
+        receiver.set_zone(MpeZone::Upper, 7);
+
+        assert_eq!(receiver.zone_members(MpeZone::Lower), 7);
+        assert_eq!(receiver.zone_of(1), Some(MpeZone::Lower));
+        assert_eq!(receiver.zone_of(8), Some(MpeZone::Upper));
+        assert_eq!(receiver.zone_of(0), None);
+        assert_eq!(receiver.zone_of(15), None);
+    }
+
+    #[test]
+    fn per_note_events() {
+        let mut receiver = MpeReceiver::new().with_zone(MpeZone::Upper, 3);
+        let mut packets = PacketBuffer::with_capacity(0);
+        packets
+            .push_note_on(0, 14, 60, 100)
+            .push_note_on(0, 13, 64, 90)
+            .push_channel_pressure(0, 14, 30)
+            .push_cc(0, 13, 74, 20)
+            // Not in the zone
+            .push_pitch_bend(0, 1, 0)
+            .push_note_on(0, 14, 60, 0);
+        let mut events = Vec::new();
+        receiver.process_packets(&packets, |event| events.push(event));
+
+        let note = |channel, note| MpeNote {
+            zone: MpeZone::Upper,
+            channel,
+            note,
+        };
+        assert_eq!(
+            events,
+            vec![
+                MpeEvent::NoteOn {
+                    note: note(14, 60),
+                    velocity: 100
+                },
+                MpeEvent::NoteOn {
+                    note: note(13, 64),
+                    velocity: 90
+                },
+                MpeEvent::Pressure {
+                    note: note(14, 60),
+                    pressure: 30
+                },
+                MpeEvent::Timbre {
+                    note: note(13, 64),
+                    value: 20
+                },
+                MpeEvent::NoteOff {
+                    note: note(14, 60),
+                    velocity: 0
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use crate::Timecode;
+
+const UNIVERSAL_REAL_TIME: u8 = 0x7f;
+const SHOW_CONTROL: u8 = 0x02;
+
+/// The command formats of MIDI Show Control, telling the kind of equipment addressed by a message.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShowControlFormat {
+    Lighting,
+    MovingLights,
+    Sound,
+    Machinery,
+    Video,
+    Projection,
+    Pyro,
+    AllTypes,
+    /// Any other command format from the specification.
+    Other(u8),
+}
+
+impl ShowControlFormat {
+    pub fn code(&self) -> u8 {
+        match self {
+            ShowControlFormat::Lighting => 0x01,
+            ShowControlFormat::MovingLights => 0x02,
+            ShowControlFormat::Sound => 0x10,
+            ShowControlFormat::Machinery => 0x20,
+            ShowControlFormat::Video => 0x30,
+            ShowControlFormat::Projection => 0x40,
+            ShowControlFormat::Pyro => 0x61,
+            ShowControlFormat::AllTypes => 0x7f,
+            ShowControlFormat::Other(code) => code & 0x7f,
+        }
+    }
+}
+
+/// The cue addressed by a MIDI Show Control command, with its number, and optionally its list and path.
+/// They are made of ASCII digits and a decimal point, like "12.5".
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cue<'a> {
+    pub number: &'a str,
+    pub list: Option<&'a str>,
+    pub path: Option<&'a str>,
+}
+
+impl<'a> Cue<'a> {
+    pub fn new(number: &'a str) -> Self {
+        Self {
+            number,
+            list: None,
+            path: None,
+        }
+    }
+
+    pub fn with_list(mut self, list: &'a str) -> Self {
+        self.list = Some(list);
+        self
+    }
+
+    pub fn with_path(mut self, path: &'a str) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    fn write(&self, sysex: &mut Vec<u8>) {
+        sysex.extend(self.number.bytes().map(|byte| byte & 0x7f));
+        // The path needs the list before it, even if empty
+        if self.list.is_some() || self.path.is_some() {
+            sysex.push(0);
+            sysex.extend(self.list.unwrap_or("").bytes().map(|byte| byte & 0x7f));
+        }
+        if let Some(path) = self.path {
+            sysex.push(0);
+            sysex.extend(path.bytes().map(|byte| byte & 0x7f));
+        }
+    }
+}
+
+/// Builds the SysEx messages of MIDI Show Control, for lighting, sound and show control equipment
+/// (see [Properties::supports_show_control](crate::Properties::supports_show_control)).
+///
+/// ```
+/// use coremidi::{Cue, ShowControl, ShowControlFormat};
+/// let show_control = ShowControl::new(0x01, ShowControlFormat::Lighting);
+/// assert_eq!(
+///     show_control.go(Some(Cue::new("23.5").with_list("2"))),
+///     vec![0xf0, 0x7f, 0x01, 0x02, 0x01, 0x01, b'2', b'3', b'.', b'5', 0x00, b'2', 0xf7]
+/// );
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShowControl {
+    device_id: u8,
+    format: ShowControlFormat,
+}
+
+impl ShowControl {
+    /// The device ID addressing all the devices.
+    ///
+    pub const ALL_DEVICES: u8 = 0x7f;
+
+    /// Build messages for a device ID (from 0 to 111, a group from 112 to 126, or [ShowControl::ALL_DEVICES])
+    /// and a command format.
+    ///
+    pub fn new(device_id: u8, format: ShowControlFormat) -> Self {
+        Self {
+            device_id: device_id & 0x7f,
+            format,
+        }
+    }
+
+    /// Start the transition to a cue, or to the next one when not given.
+    ///
+    pub fn go(&self, cue: Option<Cue>) -> Vec<u8> {
+        self.cue_command(0x01, cue)
+    }
+
+    /// Stop the transition to a cue, or all of them when not given.
+    ///
+    pub fn stop(&self, cue: Option<Cue>) -> Vec<u8> {
+        self.cue_command(0x02, cue)
+    }
+
+    /// Resume the transition to a cue, or all of them when not given.
+    ///
+    pub fn resume(&self, cue: Option<Cue>) -> Vec<u8> {
+        self.cue_command(0x03, cue)
+    }
+
+    /// Start the transition to a cue at a given time, or to the next one when not given.
+    ///
+    pub fn timed_go(&self, time: &Timecode, cue: Option<Cue>) -> Vec<u8> {
+        self.command(0x04, |sysex| {
+            sysex.extend_from_slice(&time.standard_time());
+            if let Some(cue) = cue {
+                cue.write(sysex);
+            }
+        })
+    }
+
+    /// Load a cue, so it is ready to go.
+    ///
+    pub fn load(&self, cue: Cue) -> Vec<u8> {
+        self.cue_command(0x05, Some(cue))
+    }
+
+    /// Fire a macro, from 0 to 127.
+    ///
+    pub fn fire(&self, macro_number: u8) -> Vec<u8> {
+        self.command(0x07, |sysex| sysex.push(macro_number & 0x7f))
+    }
+
+    /// Turn off all the outputs, keeping their state to restore them.
+    ///
+    pub fn all_off(&self) -> Vec<u8> {
+        self.command(0x08, |_| {})
+    }
+
+    /// Restore the outputs turned off with [all_off](ShowControl::all_off).
+    ///
+    pub fn restore(&self) -> Vec<u8> {
+        self.command(0x09, |_| {})
+    }
+
+    /// Stop everything and go back to the top of the cue lists.
+    ///
+    pub fn reset(&self) -> Vec<u8> {
+        self.command(0x0a, |_| {})
+    }
+
+    fn cue_command(&self, command: u8, cue: Option<Cue>) -> Vec<u8> {
+        self.command(command, |sysex| {
+            if let Some(cue) = cue {
+                cue.write(sysex);
+            }
+        })
+    }
+
+    fn command<F>(&self, command: u8, write_data: F) -> Vec<u8>
+    where
+        F: FnOnce(&mut Vec<u8>),
+    {
+        let mut sysex = vec![
+            0xf0,
+            UNIVERSAL_REAL_TIME,
+            self.device_id,
+            SHOW_CONTROL,
+            self.format.code(),
+            command,
+        ];
+        write_data(&mut sysex);
+        sysex.push(0xf7);
+        sysex
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::msc::{Cue, ShowControl, ShowControlFormat};
+    use crate::{FrameRate, Timecode};
+
+    #[test]
+    fn timed_go_with_cue_path() {
+        let show_control = ShowControl::new(ShowControl::ALL_DEVICES, ShowControlFormat::Sound);
+        let time = Timecode::new(1, 2, 3, 4, FrameRate::Fps25);
+        let cue = Cue::new("1").with_path("3");
+
+        assert_eq!(
+            show_control.timed_go(&time, Some(cue)),
+            vec![
+                0xf0, 0x7f, 0x7f, 0x02, 0x10, 0x04, 0x21, 0x02, 0x03, 0x04, 0x00, b'1', 0x00, 0x00,
+                b'3', 0xf7
+            ]
+        );
+    }
+
+    #[test]
+    fn commands_without_data() {
+        let show_control = ShowControl::new(0x05, ShowControlFormat::Other(0x11));
+
+        assert_eq!(
+            show_control.stop(None),
+            vec![0xf0, 0x7f, 0x05, 0x02, 0x11, 0x02, 0xf7]
+        );
+        assert_eq!(
+            show_control.all_off(),
+            vec![0xf0, 0x7f, 0x05, 0x02, 0x11, 0x08, 0xf7]
+        );
+    }
+}
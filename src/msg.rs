@@ -0,0 +1,168 @@
+use std::convert::TryFrom;
+
+use midi_msg::{MidiMsg, ParseError, ReceiverContext};
+
+use crate::{Packet, PacketBuffer, Timestamp};
+
+/// Parses the first message of the packet.
+///
+/// SysEx messages split across several packets can't be parsed from a single one,
+/// and the rest of the messages of the packet are available through [Packet::midi_msgs].
+///
+/// ```
+/// use std::convert::TryFrom;
+/// use midi_msg::{Channel, ChannelVoiceMsg, MidiMsg};
+/// let packets = coremidi::PacketBuffer::new(0, &[0x90, 0x40, 0x7f]);
+/// let msg = MidiMsg::try_from(packets.iter().next().unwrap()).unwrap();
+/// assert_eq!(msg, MidiMsg::ChannelVoice {
+///     channel: Channel::Ch1,
+///     msg: ChannelVoiceMsg::NoteOn { note: 0x40, velocity: 0x7f },
+/// });
+/// ```
+///
+impl TryFrom<&Packet> for MidiMsg {
+    type Error = ParseError;
+
+    fn try_from(packet: &Packet) -> Result<Self, Self::Error> {
+        MidiMsg::from_midi(packet.data()).map(|(msg, _)| msg)
+    }
+}
+
+impl Packet {
+    /// Parse all the messages of the packet with [midi_msg](https://crates.io/crates/midi-msg),
+    /// stopping after the first one that fails.
+    ///
+    /// ```
+    /// use midi_msg::MidiMsg;
+    /// let mut chord = coremidi::PacketBuffer::new(0, &[0x90, 0x3c, 0x7f]);
+    /// chord.push_data(0, &[0x90, 0x40, 0x7f]);
+    /// let packet = chord.iter().next().unwrap();
+    /// assert_eq!(packet.midi_msgs().filter(|msg| msg.is_ok()).count(), 2);
+    /// ```
+    ///
+    pub fn midi_msgs(&self) -> MidiMsgs<'_> {
+        MidiMsgs {
+            data: self.data(),
+            context: ReceiverContext::default(),
+        }
+    }
+}
+
+/// The messages of a [Packet] parsed with [midi_msg](https://crates.io/crates/midi-msg).
+///
+pub struct MidiMsgs<'a> {
+    data: &'a [u8],
+    context: ReceiverContext,
+}
+
+impl<'a> Iterator for MidiMsgs<'a> {
+    type Item = Result<MidiMsg, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        match MidiMsg::from_midi_with_context(self.data, &mut self.context) {
+            Ok((msg, len)) => {
+                self.data = &self.data[len.min(self.data.len())..];
+                Some(Ok(msg))
+            }
+            Err(err) => {
+                self.data = &[];
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<const N: usize> PacketBuffer<N> {
+    /// Add a message built with [midi_msg](https://crates.io/crates/midi-msg) at the given timestamp.
+    ///
+    /// ```
+    /// use midi_msg::{Channel, ChannelVoiceMsg, MidiMsg};
+    /// let mut packets = coremidi::PacketBuffer::with_capacity(16);
+    /// packets.push_midi_msg(0, &MidiMsg::ChannelVoice {
+    ///     channel: Channel::Ch2,
+    ///     msg: ChannelVoiceMsg::NoteOff { note: 0x40, velocity: 0 },
+    /// });
+    /// assert_eq!(packets.iter().next().unwrap().data(), &[0x81, 0x40, 0x00]);
+    /// ```
+    ///
+    pub fn push_midi_msg(&mut self, timestamp: Timestamp, msg: &MidiMsg) -> &mut Self {
+        self.push_data(timestamp, &msg.to_midi())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use midi_msg::{
+        Channel, ChannelVoiceMsg, ControlChange, MidiMsg, SystemExclusiveMsg, SystemRealTimeMsg,
+    };
+
+    use crate::PacketBuffer;
+
+    fn round_trip(msgs: &[MidiMsg]) -> Vec<MidiMsg> {
+        let mut packets = PacketBuffer::with_capacity(256);
+        for msg in msgs {
+            packets.push_midi_msg(0, msg);
+        }
+        packets
+            .iter()
+            .flat_map(|packet| packet.midi_msgs())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn channel_messages_round_trip() {
+        let msgs = vec![
+            MidiMsg::ChannelVoice {
+                channel: Channel::Ch1,
+                msg: ChannelVoiceMsg::NoteOn {
+                    note: 60,
+                    velocity: 100,
+                },
+            },
+            MidiMsg::ChannelVoice {
+                channel: Channel::Ch16,
+                msg: ChannelVoiceMsg::ControlChange {
+                    control: ControlChange::CC {
+                        control: 20,
+                        value: 64,
+                    },
+                },
+            },
+            MidiMsg::ChannelVoice {
+                channel: Channel::Ch3,
+                msg: ChannelVoiceMsg::PitchBend { bend: 0x2000 },
+            },
+            MidiMsg::SystemRealTime {
+                msg: SystemRealTimeMsg::TimingClock,
+            },
+        ];
+        assert_eq!(round_trip(&msgs), msgs);
+    }
+
+    #[test]
+    fn sysex_round_trip() {
+        let msg = MidiMsg::SystemExclusive {
+            msg: SystemExclusiveMsg::Commercial {
+                id: 0x41.into(),
+                data: vec![0x10, 0x42, 0x12],
+            },
+        };
+        let packets = PacketBuffer::new(0, &msg.to_midi());
+        let packet = packets.iter().next().unwrap();
+        assert_eq!(MidiMsg::try_from(packet).unwrap(), msg);
+    }
+
+    #[test]
+    fn invalid_packet() {
+        let packets = PacketBuffer::new(0, &[0x40, 0x7f]);
+        let packet = packets.iter().next().unwrap();
+        assert!(MidiMsg::try_from(packet).is_err());
+        assert_eq!(packet.midi_msgs().count(), 1);
+    }
+}
@@ -0,0 +1,562 @@
+use std::time::Duration;
+
+use crate::{
+    duration_from_host_time, host_time_from_duration, host_time_now, PacketBuffer, PacketList,
+    Timestamp,
+};
+
+const QUARTER_FRAME: u8 = 0xf1;
+const FULL_FRAME_HEADER: [u8; 5] = [0xf0, 0x7f, 0x7f, 0x01, 0x01];
+
+/// The frame rates supported by MIDI Time Code.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameRate {
+    Fps24,
+    Fps25,
+    /// 29.97 frames per second, with drop-frame numbering.
+    Fps2997Drop,
+    Fps30,
+}
+
+impl FrameRate {
+    /// The number of frames counted in a second (30 for 29.97 drop-frame).
+    ///
+    pub fn frames_per_second(&self) -> u32 {
+        match self {
+            FrameRate::Fps24 => 24,
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps2997Drop | FrameRate::Fps30 => 30,
+        }
+    }
+
+    /// The real duration of a frame.
+    ///
+    pub fn frame_duration(&self) -> Duration {
+        match self {
+            FrameRate::Fps2997Drop => Duration::from_nanos(1_001_000_000 / 30),
+            _ => Duration::from_nanos(1_000_000_000 / self.frames_per_second() as u64),
+        }
+    }
+
+    /// The code used for the rate in the MTC messages.
+    ///
+    pub fn code(&self) -> u8 {
+        match self {
+            FrameRate::Fps24 => 0,
+            FrameRate::Fps25 => 1,
+            FrameRate::Fps2997Drop => 2,
+            FrameRate::Fps30 => 3,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Self {
+        match code & 0x03 {
+            0 => FrameRate::Fps24,
+            1 => FrameRate::Fps25,
+            2 => FrameRate::Fps2997Drop,
+            _ => FrameRate::Fps30,
+        }
+    }
+}
+
+/// A SMPTE timecode, as transported by MIDI Time Code.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub rate: FrameRate,
+}
+
+impl Timecode {
+    pub fn new(hours: u8, minutes: u8, seconds: u8, frames: u8, rate: FrameRate) -> Self {
+        Self {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            rate,
+        }
+    }
+
+    /// The number of frames since 00:00:00:00, taking into account the frames skipped by drop-frame.
+    ///
+    pub fn to_frames(&self) -> u64 {
+        let fps = self.rate.frames_per_second() as u64;
+        let seconds = self.hours as u64 * 3600 + self.minutes as u64 * 60 + self.seconds as u64;
+        let frames = seconds * fps + self.frames as u64;
+        if self.rate == FrameRate::Fps2997Drop {
+            // Two frames are skipped every minute, except for every tenth minute
+            let minutes = self.hours as u64 * 60 + self.minutes as u64;
+            frames - 2 * (minutes - minutes / 10)
+        } else {
+            frames
+        }
+    }
+
+    /// The timecode for a number of frames since 00:00:00:00, wrapping around after 24 hours.
+    ///
+    pub fn from_frames(frames: u64, rate: FrameRate) -> Self {
+        let fps = rate.frames_per_second() as u64;
+        let frames = if rate == FrameRate::Fps2997Drop {
+            const FRAMES_PER_10_MINUTES: u64 = 17982;
+            const FRAMES_PER_MINUTE: u64 = 1798;
+            let tens = frames / FRAMES_PER_10_MINUTES;
+            let rest = frames % FRAMES_PER_10_MINUTES;
+            let dropped = if rest > 1 {
+                18 * tens + 2 * ((rest - 2) / FRAMES_PER_MINUTE)
+            } else {
+                18 * tens
+            };
+            frames + dropped
+        } else {
+            frames
+        };
+        let seconds = frames / fps;
+        Self {
+            hours: ((seconds / 3600) % 24) as u8,
+            minutes: ((seconds / 60) % 60) as u8,
+            seconds: (seconds % 60) as u8,
+            frames: (frames % fps) as u8,
+            rate,
+        }
+    }
+
+    /// The full frame SysEx message used to locate a receiver to this timecode.
+    ///
+    /// ```
+    /// use coremidi::{FrameRate, Timecode};
+    /// let timecode = Timecode::new(1, 2, 3, 4, FrameRate::Fps25);
+    /// assert_eq!(timecode.full_frame(), [0xf0, 0x7f, 0x7f, 0x01, 0x01, 0x21, 2, 3, 4, 0xf7]);
+    /// ```
+    pub fn full_frame(&self) -> [u8; 10] {
+        [
+            FULL_FRAME_HEADER[0],
+            FULL_FRAME_HEADER[1],
+            FULL_FRAME_HEADER[2],
+            FULL_FRAME_HEADER[3],
+            FULL_FRAME_HEADER[4],
+            (self.rate.code() << 5) | (self.hours & 0x1f),
+            self.minutes & 0x3f,
+            self.seconds & 0x3f,
+            self.frames & 0x1f,
+            0xf7,
+        ]
+    }
+
+    /// The "standard time code" used by the MIDI Show Control and Machine Control messages:
+    /// hours with the frame rate, minutes, seconds, frames, and subframes (always 0).
+    ///
+    pub(crate) fn standard_time(&self) -> [u8; 5] {
+        [
+            (self.rate.code() << 5) | (self.hours & 0x1f),
+            self.minutes & 0x3f,
+            self.seconds & 0x3f,
+            self.frames & 0x1f,
+            0,
+        ]
+    }
+
+    /// Decode the "standard time code" of the MIDI Show Control and Machine Control messages.
+    ///
+    pub(crate) fn from_standard_time(data: &[u8]) -> Option<Timecode> {
+        match data {
+            [hours, minutes, seconds, frames, ..] => Some(Timecode::new(
+                hours & 0x1f,
+                minutes & 0x3f,
+                seconds & 0x3f,
+                frames & 0x1f,
+                FrameRate::from_code((hours >> 5) & 0x03),
+            )),
+            _ => None,
+        }
+    }
+
+    /// The data of the quarter frame message number `piece` (0 to 7) describing this timecode.
+    ///
+    pub fn quarter_frame(&self, piece: u8) -> u8 {
+        let piece = piece & 0x07;
+        let nibble = match piece {
+            0 => self.frames & 0x0f,
+            1 => (self.frames >> 4) & 0x01,
+            2 => self.seconds & 0x0f,
+            3 => (self.seconds >> 4) & 0x03,
+            4 => self.minutes & 0x0f,
+            5 => (self.minutes >> 4) & 0x03,
+            6 => self.hours & 0x0f,
+            _ => (self.rate.code() << 1) | ((self.hours >> 4) & 0x01),
+        };
+        (piece << 4) | nibble
+    }
+}
+
+/// Generates MIDI Time Code quarter frame messages locked to the host time.
+///
+/// The generator is located at a timecode and the host time when it should be reached,
+/// and then produces the quarter frames (four per frame) with their timestamps, into a [PacketBuffer]
+/// that can be sent through an [OutputPort](crate::OutputPort). Every sequence of eight quarter frames
+/// describes the timecode of the frame when the sequence started.
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use coremidi::{host_time_from_duration, host_time_now, Client, Destination, FrameRate, MtcGenerator, PacketBuffer, Timecode};
+/// let client = Client::new("example-client").unwrap();
+/// let output_port = client.output_port("example-port").unwrap();
+/// let destination = Destination::from_index(0).unwrap();
+///
+/// let start = Timecode::new(1, 0, 0, 0, FrameRate::Fps25);
+/// let mut generator = MtcGenerator::new(start, host_time_now());
+/// output_port.send(&destination, &PacketBuffer::new(0, &start.full_frame())).unwrap();
+/// loop {
+///     // Schedule the quarter frames of the next 100 ms
+///     let mut packets = PacketBuffer::with_capacity(0);
+///     generator.generate(host_time_now() + host_time_from_duration(Duration::from_millis(100)), &mut packets);
+///     output_port.send(&destination, &packets).unwrap();
+///     std::thread::sleep(Duration::from_millis(50));
+/// }
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct MtcGenerator {
+    start: Timecode,
+    start_frames: u64,
+    start_time: Timestamp,
+    next_quarter: u64,
+}
+
+impl MtcGenerator {
+    /// Create a generator that reaches `timecode` at the host time `at`.
+    ///
+    pub fn new(timecode: Timecode, at: Timestamp) -> Self {
+        Self {
+            start: timecode,
+            start_frames: timecode.to_frames(),
+            start_time: at,
+            next_quarter: 0,
+        }
+    }
+
+    /// Move the generator to `timecode`, to be reached at the host time `at`.
+    /// The receivers should be sent the [Timecode::full_frame] message too.
+    ///
+    pub fn locate(&mut self, timecode: Timecode, at: Timestamp) {
+        *self = Self::new(timecode, at);
+    }
+
+    pub fn rate(&self) -> FrameRate {
+        self.start.rate
+    }
+
+    /// The timecode of the frame for the next quarter frame to be generated.
+    ///
+    pub fn timecode(&self) -> Timecode {
+        Timecode::from_frames(self.start_frames + self.next_quarter / 4, self.start.rate)
+    }
+
+    /// Add the quarter frames with timestamps up to `until` into `packets`, returning how many were added.
+    ///
+    pub fn generate(&mut self, until: Timestamp, packets: &mut PacketBuffer) -> usize {
+        let mut count = 0;
+        loop {
+            let timestamp = self.quarter_timestamp(self.next_quarter);
+            if timestamp > until {
+                return count;
+            }
+            let piece = (self.next_quarter % 8) as u8;
+            // All the pieces of a sequence describe the frame where it started
+            let sequence_frame = self.start_frames + (self.next_quarter - piece as u64) / 4;
+            let timecode = Timecode::from_frames(sequence_frame, self.start.rate);
+            packets.push_data(timestamp, &[QUARTER_FRAME, timecode.quarter_frame(piece)]);
+            self.next_quarter += 1;
+            count += 1;
+        }
+    }
+
+    fn quarter_timestamp(&self, quarter: u64) -> Timestamp {
+        let nanos = self.start.rate.frame_duration().as_nanos() as u64 * quarter / 4;
+        self.start_time + host_time_from_duration(Duration::from_nanos(nanos))
+    }
+}
+
+/// Decodes incoming MIDI Time Code into a running timecode.
+///
+/// It is a small state machine fed from the callback of an input port, with the quarter frame messages
+/// and the full frame SysEx messages. The timecode is known once a whole sequence of eight quarter frames
+/// has been received in order (including the frame rate), and then it advances with every frame.
+/// When no quarter frames arrive for longer than the dropout timeout, or they arrive out of order,
+/// the timecode is lost until the next complete sequence (or full frame message).
+///
+/// ```
+/// use coremidi::{FrameRate, MtcDecoder, MtcGenerator, PacketBuffer, Timecode};
+/// let mut generator = MtcGenerator::new(Timecode::new(1, 0, 0, 0, FrameRate::Fps25), 1);
+/// let mut packets = PacketBuffer::with_capacity(0);
+/// generator.generate(1, &mut packets);
+/// let mut decoder = MtcDecoder::new();
+/// decoder.process_packets(&packets);
+/// assert_eq!(decoder.timecode(), None); // It needs a whole sequence
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct MtcDecoder {
+    pieces: [u8; 8],
+    // The number of consecutive pieces received in order, up to 8
+    received: usize,
+    last_piece: Option<u8>,
+    last_timestamp: Option<Timestamp>,
+    frames: Option<u64>,
+    rate: Option<FrameRate>,
+    dropout_timeout: Duration,
+}
+
+impl MtcDecoder {
+    /// The default time without quarter frames after which the timecode is considered lost.
+    ///
+    pub const DEFAULT_DROPOUT_TIMEOUT: Duration = Duration::from_millis(200);
+
+    pub fn new() -> Self {
+        Self {
+            pieces: [0; 8],
+            received: 0,
+            last_piece: None,
+            last_timestamp: None,
+            frames: None,
+            rate: None,
+            dropout_timeout: Self::DEFAULT_DROPOUT_TIMEOUT,
+        }
+    }
+
+    pub fn with_dropout_timeout(mut self, timeout: Duration) -> Self {
+        self.dropout_timeout = timeout;
+        self
+    }
+
+    /// The current timecode, if known.
+    ///
+    pub fn timecode(&self) -> Option<Timecode> {
+        match (self.frames, self.rate) {
+            (Some(frames), Some(rate)) => Some(Timecode::from_frames(frames, rate)),
+            _ => None,
+        }
+    }
+
+    /// The frame rate detected from the last messages received.
+    ///
+    pub fn rate(&self) -> Option<FrameRate> {
+        self.rate
+    }
+
+    /// Process the quarter frames and full frame messages of a list of packets,
+    /// returning the timecode if it changed.
+    ///
+    pub fn process_packets(&mut self, packet_list: &PacketList) -> Option<Timecode> {
+        let mut changed = None;
+        for packet in packet_list.iter() {
+            let data = packet.data();
+            let mut index = 0;
+            while index < data.len() {
+                let rest = &data[index..];
+                if rest[0] == QUARTER_FRAME && rest.len() >= 2 {
+                    changed = self
+                        .process_quarter_frame(packet.timestamp(), rest[1])
+                        .or(changed);
+                    index += 2;
+                } else if rest.len() >= 10 && rest[..5] == FULL_FRAME_HEADER {
+                    changed = self
+                        .process_full_frame(packet.timestamp(), &rest[..10])
+                        .or(changed);
+                    index += 10;
+                } else {
+                    index += 1;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Process the data byte of a quarter frame message, returning the timecode if it changed.
+    /// A timestamp of 0 means now.
+    ///
+    pub fn process_quarter_frame(&mut self, timestamp: Timestamp, data: u8) -> Option<Timecode> {
+        let timestamp = if timestamp == 0 {
+            host_time_now()
+        } else {
+            timestamp
+        };
+        let piece = (data >> 4) & 0x07;
+        let in_order = matches!(self.last_piece, Some(last_piece) if (last_piece + 1) % 8 == piece);
+        let timed_out = matches!(self.last_timestamp, Some(last_timestamp)
+            if duration_from_host_time(timestamp.saturating_sub(last_timestamp)) > self.dropout_timeout);
+        if !in_order || timed_out {
+            self.received = 0;
+            self.frames = None;
+        }
+        self.last_piece = Some(piece);
+        self.last_timestamp = Some(timestamp);
+        self.pieces[piece as usize] = data & 0x0f;
+        self.received = (self.received + 1).min(8);
+
+        match piece {
+            7 if self.received == 8 => {
+                let timecode = self.decode();
+                self.rate = Some(timecode.rate);
+                // The sequence describes the frame when it started, two frames ago
+                self.frames = Some(timecode.to_frames() + 2);
+                self.timecode()
+            }
+            3 if self.frames.is_some() => {
+                self.frames = self.frames.map(|frames| frames + 1);
+                self.timecode()
+            }
+            _ => None,
+        }
+    }
+
+    /// Process a full frame SysEx message, locating the timecode to it.
+    /// It returns the timecode if the message is valid.
+    ///
+    pub fn process_full_frame(&mut self, timestamp: Timestamp, data: &[u8]) -> Option<Timecode> {
+        if data.len() < 10 || data[..5] != FULL_FRAME_HEADER {
+            return None;
+        }
+        let rate = FrameRate::from_code(data[5] >> 5);
+        let timecode = Timecode::new(data[5] & 0x1f, data[6], data[7], data[8], rate);
+        self.received = 0;
+        self.last_piece = None;
+        self.last_timestamp = Some(timestamp);
+        self.rate = Some(rate);
+        self.frames = Some(timecode.to_frames());
+        self.timecode()
+    }
+
+    fn decode(&self) -> Timecode {
+        let pieces = &self.pieces;
+        Timecode::new(
+            pieces[6] | ((pieces[7] & 0x01) << 4),
+            pieces[4] | ((pieces[5] & 0x03) << 4),
+            pieces[2] | ((pieces[3] & 0x03) << 4),
+            pieces[0] | ((pieces[1] & 0x01) << 4),
+            FrameRate::from_code(pieces[7] >> 1),
+        )
+    }
+}
+
+impl Default for MtcDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::mtc::{FrameRate, MtcDecoder, MtcGenerator, Timecode};
+    use crate::{host_time_from_duration, PacketBuffer};
+
+    #[test]
+    fn drop_frame_numbering() {
+        let rate = FrameRate::Fps2997Drop;
+        // The frames 00 and 01 are skipped at the start of every minute but the tenth ones
+        assert_eq!(Timecode::new(0, 1, 0, 2, rate).to_frames(), 1800);
+        assert_eq!(
+            Timecode::from_frames(1800, rate),
+            Timecode::new(0, 1, 0, 2, rate)
+        );
+        assert_eq!(
+            Timecode::from_frames(1799, rate),
+            Timecode::new(0, 0, 59, 29, rate)
+        );
+        assert_eq!(
+            Timecode::from_frames(17982, rate),
+            Timecode::new(0, 10, 0, 0, rate)
+        );
+
+        for frames in (0..200_000).step_by(7) {
+            assert_eq!(Timecode::from_frames(frames, rate).to_frames(), frames);
+        }
+    }
+
+    #[test]
+    fn quarter_frames() {
+        let timecode = Timecode::new(0x17, 0x2a, 0x3b, 0x18, FrameRate::Fps30);
+        let pieces: Vec<u8> = (0..8).map(|piece| timecode.quarter_frame(piece)).collect();
+        assert_eq!(pieces, vec![0x08, 0x11, 0x2b, 0x33, 0x4a, 0x52, 0x67, 0x77]);
+    }
+
+    #[test]
+    fn generate_locked_to_host_time() {
+        let rate = FrameRate::Fps25;
+        let start = Timecode::new(1, 0, 0, 0, rate);
+        let mut generator = MtcGenerator::new(start, 1000);
+        let mut packets = PacketBuffer::with_capacity(0);
+
+        // 4 quarter frames per frame of 40 ms, including the first one at the start
+        let until = 1000 + host_time_from_duration(Duration::from_millis(80));
+        assert_eq!(generator.generate(until, &mut packets), 9);
+        assert_eq!(generator.timecode(), Timecode::new(1, 0, 0, 2, rate));
+
+        let messages: Vec<Vec<u8>> = packets
+            .iter()
+            .map(|packet| packet.data().to_vec())
+            .collect();
+        assert_eq!(messages[0], vec![0xf1, 0x00]);
+        assert_eq!(messages[7], vec![0xf1, 0x72]);
+        // The second sequence starts two frames later
+        assert_eq!(messages[8], vec![0xf1, 0x02]);
+        assert_eq!(packets.iter().last().unwrap().timestamp(), until);
+    }
+
+    #[test]
+    fn decode_generated_timecode() {
+        let rate = FrameRate::Fps2997Drop;
+        let mut generator = MtcGenerator::new(Timecode::new(0, 0, 59, 28, rate), 1000);
+        let mut decoder = MtcDecoder::new();
+        let mut timecodes = Vec::new();
+        for _ in 0..16 {
+            let mut packets = PacketBuffer::with_capacity(0);
+            generator.generate(generator_next(&generator), &mut packets);
+            if let Some(timecode) = decoder.process_packets(&packets) {
+                timecodes.push(timecode);
+            }
+        }
+
+        assert_eq!(decoder.rate(), Some(rate));
+        assert_eq!(
+            timecodes,
+            vec![
+                Timecode::new(0, 1, 0, 2, rate),
+                Timecode::new(0, 1, 0, 3, rate),
+                Timecode::new(0, 1, 0, 4, rate),
+            ]
+        );
+    }
+
+    // The timestamp of the next quarter frame, to generate them one by one
+    fn generator_next(generator: &MtcGenerator) -> u64 {
+        generator.quarter_timestamp(generator.next_quarter)
+    }
+
+    #[test]
+    fn dropout_and_full_frame() {
+        let rate = FrameRate::Fps25;
+        let mut decoder =
+            MtcDecoder::new().with_dropout_timeout(std::time::Duration::from_millis(50));
+        let quarter = host_time_from_duration(Duration::from_millis(10));
+        let timecode = Timecode::new(1, 2, 3, 4, rate);
+        for piece in 0..8 {
+            decoder
+                .process_quarter_frame(1 + piece as u64 * quarter, timecode.quarter_frame(piece));
+        }
+        assert_eq!(decoder.timecode(), Some(Timecode::new(1, 2, 3, 6, rate)));
+
+        // The next quarter frame arrives too late
+        decoder.process_quarter_frame(1 + 20 * quarter, timecode.quarter_frame(0));
+        assert_eq!(decoder.timecode(), None);
+
+        let located = decoder.process_full_frame(1 + 21 * quarter, &timecode.full_frame());
+        assert_eq!(located, Some(timecode));
+    }
+}
@@ -0,0 +1,207 @@
+const UNIVERSAL_NON_REAL_TIME: u8 = 0x7e;
+const UNIVERSAL_REAL_TIME: u8 = 0x7f;
+const MIDI_TUNING_STANDARD: u8 = 0x08;
+const BULK_DUMP_REQUEST: u8 = 0x00;
+const BULK_DUMP: u8 = 0x01;
+const NOTE_CHANGE: u8 = 0x02;
+const BANK_BULK_DUMP_REQUEST: u8 = 0x03;
+const BANK_BULK_DUMP: u8 = 0x04;
+const BANK_NOTE_CHANGE: u8 = 0x07;
+const NAME_LENGTH: usize = 16;
+
+/// The frequency of a note in the MIDI Tuning Standard, as an equal tempered semitone (with 69 as A4 at 440 Hz)
+/// and a 14 bits fraction of a semitone above it.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NoteTuning {
+    pub semitone: u8,
+    /// The fraction of a semitone in units of 100/16384 cents.
+    pub fraction: u16,
+}
+
+impl NoteTuning {
+    /// The special value asking to keep the current tuning of a note.
+    ///
+    pub const NO_CHANGE: NoteTuning = NoteTuning {
+        semitone: 0x7f,
+        fraction: 0x3fff,
+    };
+
+    pub fn new(semitone: u8, fraction: u16) -> Self {
+        Self {
+            semitone: semitone & 0x7f,
+            fraction: fraction & 0x3fff,
+        }
+    }
+
+    /// The tuning closest to a frequency in Hz, limited to the range of the standard (8.18 Hz to 12543.85 Hz).
+    ///
+    pub fn from_frequency(frequency: f64) -> Self {
+        let semitones =
+            (69.0 + 12.0 * (frequency / 440.0).log2()).clamp(0.0, 127.0 + 16382.0 / 16384.0);
+        let fraction = ((semitones - semitones.floor()) * 16384.0).round() as u16;
+        // The fraction can round up to the next semitone
+        let semitone = semitones.floor() as u8 + (fraction >> 14) as u8;
+        Self::new(semitone, fraction)
+    }
+
+    pub fn frequency(&self) -> f64 {
+        let semitones = self.semitone as f64 + self.fraction as f64 / 16384.0;
+        440.0 * ((semitones - 69.0) / 12.0).exp2()
+    }
+
+    fn write(&self, sysex: &mut Vec<u8>) {
+        sysex.extend_from_slice(&[
+            self.semitone & 0x7f,
+            ((self.fraction >> 7) & 0x7f) as u8,
+            (self.fraction & 0x7f) as u8,
+        ]);
+    }
+}
+
+/// Builds the SysEx messages of the MIDI Tuning Standard, to retune the notes of a tuning program
+/// (optionally within a tuning bank) of a device.
+///
+/// ```
+/// use coremidi::{NoteTuning, Tuning};
+/// let tuning = Tuning::new(0x7f, 3);
+/// // Tune A4 a quarter tone up
+/// assert_eq!(
+///     tuning.note_changes(&[(69, NoteTuning::new(69, 0x2000))]),
+///     vec![0xf0, 0x7f, 0x7f, 0x08, 0x02, 0x03, 0x01, 69, 69, 0x40, 0x00, 0xf7]
+/// );
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tuning {
+    device_id: u8,
+    bank: Option<u8>,
+    program: u8,
+}
+
+impl Tuning {
+    /// The device ID addressing all the devices.
+    ///
+    pub const ALL_DEVICES: u8 = 0x7f;
+
+    /// Build messages for a device ID (or [Tuning::ALL_DEVICES]) and a tuning program from 0 to 127.
+    ///
+    pub fn new(device_id: u8, program: u8) -> Self {
+        Self {
+            device_id: device_id & 0x7f,
+            bank: None,
+            program: program & 0x7f,
+        }
+    }
+
+    /// Address the tuning program within a tuning bank from 0 to 127.
+    ///
+    pub fn with_bank(mut self, bank: u8) -> Self {
+        self.bank = Some(bank & 0x7f);
+        self
+    }
+
+    /// Ask the device to send the bulk dump of the tuning program.
+    ///
+    pub fn bulk_dump_request(&self) -> Vec<u8> {
+        let command = self
+            .bank
+            .map_or(BULK_DUMP_REQUEST, |_| BANK_BULK_DUMP_REQUEST);
+        self.message(UNIVERSAL_NON_REAL_TIME, command, |_| {})
+    }
+
+    /// The bulk dump of the tuning program, with the tuning of the 128 notes and a name of up to 16 ASCII characters.
+    ///
+    pub fn bulk_dump(&self, name: &str, notes: &[NoteTuning; 128]) -> Vec<u8> {
+        let command = self.bank.map_or(BULK_DUMP, |_| BANK_BULK_DUMP);
+        let mut sysex = self.message(UNIVERSAL_NON_REAL_TIME, command, |sysex| {
+            sysex.extend(
+                name.bytes()
+                    .chain(std::iter::repeat(b' '))
+                    .take(NAME_LENGTH)
+                    .map(|byte| byte & 0x7f),
+            );
+            for note in notes.iter() {
+                note.write(sysex);
+            }
+        });
+        let checksum = sysex[1..sysex.len() - 1]
+            .iter()
+            .fold(0, |sum, byte| sum ^ byte)
+            & 0x7f;
+        sysex.insert(sysex.len() - 1, checksum);
+        sysex
+    }
+
+    /// Change the tuning of some notes in real time, as pairs of note number and tuning.
+    /// The notes already sounding are retuned too. Up to 127 changes are sent, the rest are ignored.
+    ///
+    pub fn note_changes(&self, changes: &[(u8, NoteTuning)]) -> Vec<u8> {
+        let command = self.bank.map_or(NOTE_CHANGE, |_| BANK_NOTE_CHANGE);
+        self.message(UNIVERSAL_REAL_TIME, command, |sysex| {
+            let changes = &changes[..changes.len().min(127)];
+            sysex.push(changes.len() as u8);
+            for (note, tuning) in changes {
+                sysex.push(note & 0x7f);
+                tuning.write(sysex);
+            }
+        })
+    }
+
+    fn message<F>(&self, universal: u8, command: u8, write_data: F) -> Vec<u8>
+    where
+        F: FnOnce(&mut Vec<u8>),
+    {
+        let mut sysex = vec![
+            0xf0,
+            universal,
+            self.device_id,
+            MIDI_TUNING_STANDARD,
+            command,
+        ];
+        sysex.extend(self.bank);
+        sysex.push(self.program);
+        write_data(&mut sysex);
+        sysex.push(0xf7);
+        sysex
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mts::{NoteTuning, Tuning};
+
+    #[test]
+    fn tuning_from_frequency() {
+        assert_eq!(NoteTuning::from_frequency(440.0), NoteTuning::new(69, 0));
+        assert_eq!(
+            NoteTuning::from_frequency(440.0 * (50.0f64 / 1200.0).exp2()),
+            NoteTuning::new(69, 0x2000)
+        );
+        assert_eq!(
+            NoteTuning::from_frequency(440.0 * (99.9999f64 / 1200.0).exp2()),
+            NoteTuning::new(70, 0)
+        );
+        assert_eq!(NoteTuning::from_frequency(1.0), NoteTuning::new(0, 0));
+        assert!((NoteTuning::new(60, 0).frequency() - 261.6256).abs() < 0.0001);
+    }
+
+    #[test]
+    fn bank_bulk_dump() {
+        let tuning = Tuning::new(0x10, 2).with_bank(1);
+        let notes = [NoteTuning::NO_CHANGE; 128];
+        let sysex = tuning.bulk_dump("Pythagorean", &notes);
+
+        assert_eq!(sysex.len(), 409);
+        assert_eq!(&sysex[..7], &[0xf0, 0x7e, 0x10, 0x08, 0x04, 0x01, 0x02]);
+        assert_eq!(&sysex[7..23], b"Pythagorean     ");
+        assert_eq!(&sysex[23..26], &[0x7f, 0x7f, 0x7f]);
+        let checksum = sysex[1..407].iter().fold(0, |sum, byte| sum ^ byte);
+        assert_eq!(sysex[407..], [checksum, 0xf7]);
+
+        assert_eq!(
+            tuning.bulk_dump_request(),
+            vec![0xf0, 0x7e, 0x10, 0x08, 0x03, 0x01, 0x02, 0xf7]
+        );
+    }
+}
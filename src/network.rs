@@ -0,0 +1,693 @@
+use core_foundation::{base::OSStatus, string::CFString};
+use coremidi_sys::MIDIEndpointRef;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::objc::{
+    class, ns_string, selector, send, send1, send3, set_objects, string_from, with_pool,
+    NotificationObserver, ObjcObject,
+};
+use crate::sys::{
+    kDNSServiceFlagsAdd, poll, pollfd, DNSServiceBrowse, DNSServiceErrorType, DNSServiceFlags,
+    DNSServiceProcessResult, DNSServiceRef, DNSServiceRefDeallocate, DNSServiceRefSockFD, ObjcId,
+    POLLIN,
+};
+use crate::{Destination, Source};
+
+/// Who can connect to the network session.
+/// See [MIDINetworkConnectionPolicy](https://developer.apple.com/documentation/coremidi/midinetworkconnectionpolicy).
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NetworkConnectionPolicy {
+    NoOne,
+    HostsInContactList,
+    Anyone,
+}
+
+impl NetworkConnectionPolicy {
+    fn code(&self) -> usize {
+        match self {
+            NetworkConnectionPolicy::NoOne => 0,
+            NetworkConnectionPolicy::HostsInContactList => 1,
+            NetworkConnectionPolicy::Anyone => 2,
+        }
+    }
+
+    fn from_code(code: usize) -> Self {
+        match code {
+            1 => NetworkConnectionPolicy::HostsInContactList,
+            2 => NetworkConnectionPolicy::Anyone,
+            _ => NetworkConnectionPolicy::NoOne,
+        }
+    }
+}
+
+/// The description of a [NetworkHost], that can be sent to other threads.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NetworkHostInfo {
+    pub name: Option<String>,
+    pub address: Option<String>,
+    pub port: u16,
+    pub net_service_name: Option<String>,
+    pub net_service_domain: Option<String>,
+}
+
+/// A [MIDI network host](https://developer.apple.com/documentation/coremidi/midinetworkhost),
+/// a peer that the network session can connect to.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkHost {
+    object: ObjcObject,
+}
+
+impl NetworkHost {
+    /// A host with an address (like an IP address or a host name) and a port.
+    ///
+    pub fn new(name: &str, address: &str, port: u16) -> NetworkHost {
+        let (name, address) = (CFString::new(name), CFString::new(address));
+        with_pool(|| unsafe {
+            let host: ObjcId = send3(
+                class!("MIDINetworkHost"),
+                selector!("hostWithName:address:port:"),
+                ns_string(&name),
+                ns_string(&address),
+                port as usize,
+            );
+            NetworkHost {
+                object: ObjcObject::retain(host).expect("MIDINetworkHost"),
+            }
+        })
+    }
+
+    /// A host found through Bonjour, with the name and domain of its service.
+    ///
+    pub fn with_net_service(name: &str, service_name: &str, service_domain: &str) -> NetworkHost {
+        let name = CFString::new(name);
+        let (service_name, service_domain) =
+            (CFString::new(service_name), CFString::new(service_domain));
+        with_pool(|| unsafe {
+            let host: ObjcId = send3(
+                class!("MIDINetworkHost"),
+                selector!("hostWithName:netServiceName:netServiceDomain:"),
+                ns_string(&name),
+                ns_string(&service_name),
+                ns_string(&service_domain),
+            );
+            NetworkHost {
+                object: ObjcObject::retain(host).expect("MIDINetworkHost"),
+            }
+        })
+    }
+
+    pub fn name(&self) -> Option<String> {
+        with_pool(|| unsafe { string_from(send(self.object.0, selector!("name"))) })
+    }
+
+    pub fn address(&self) -> Option<String> {
+        with_pool(|| unsafe { string_from(send(self.object.0, selector!("address"))) })
+    }
+
+    pub fn port(&self) -> u16 {
+        with_pool(|| unsafe { send::<usize>(self.object.0, selector!("port")) as u16 })
+    }
+
+    pub fn net_service_name(&self) -> Option<String> {
+        with_pool(|| unsafe { string_from(send(self.object.0, selector!("netServiceName"))) })
+    }
+
+    pub fn net_service_domain(&self) -> Option<String> {
+        with_pool(|| unsafe { string_from(send(self.object.0, selector!("netServiceDomain"))) })
+    }
+
+    pub fn info(&self) -> NetworkHostInfo {
+        NetworkHostInfo {
+            name: self.name(),
+            address: self.address(),
+            port: self.port(),
+            net_service_name: self.net_service_name(),
+            net_service_domain: self.net_service_domain(),
+        }
+    }
+
+    /// Whether both hosts have the same address and port, or the same Bonjour service.
+    ///
+    pub fn has_same_address_as(&self, other: &NetworkHost) -> bool {
+        with_pool(|| unsafe {
+            send1::<ObjcId, i8>(
+                self.object.0,
+                selector!("hasSameAddressAs:"),
+                other.object.0,
+            ) != 0
+        })
+    }
+}
+
+/// A [MIDI network connection](https://developer.apple.com/documentation/coremidi/midinetworkconnection)
+/// between the network session and a host.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkConnection {
+    object: ObjcObject,
+}
+
+impl NetworkConnection {
+    /// A connection to a host, that is established when added to the session.
+    ///
+    pub fn new(host: &NetworkHost) -> NetworkConnection {
+        with_pool(|| unsafe {
+            let connection: ObjcId = send1(
+                class!("MIDINetworkConnection"),
+                selector!("connectionWithHost:"),
+                host.object.0,
+            );
+            NetworkConnection {
+                object: ObjcObject::retain(connection).expect("MIDINetworkConnection"),
+            }
+        })
+    }
+
+    pub fn host(&self) -> NetworkHost {
+        with_pool(|| unsafe {
+            let host: ObjcId = send(self.object.0, selector!("host"));
+            NetworkHost {
+                object: ObjcObject::retain(host).expect("MIDINetworkHost"),
+            }
+        })
+    }
+}
+
+/// A change of the connections of the [NetworkSession], as reported to [NetworkSession::watch_connections].
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NetworkConnectionEvent {
+    Connected(NetworkHostInfo),
+    Disconnected(NetworkHostInfo),
+}
+
+/// The events telling how the connected hosts changed from `previous` to `current`.
+fn connection_events(
+    previous: &HashSet<NetworkHostInfo>,
+    current: &HashSet<NetworkHostInfo>,
+) -> Vec<NetworkConnectionEvent> {
+    let disconnected = previous
+        .difference(current)
+        .cloned()
+        .map(NetworkConnectionEvent::Disconnected);
+    let connected = current
+        .difference(previous)
+        .cloned()
+        .map(NetworkConnectionEvent::Connected);
+    disconnected.chain(connected).collect()
+}
+
+// The value of MIDINetworkNotificationSessionDidChange
+const SESSION_DID_CHANGE: &str = "MIDINetworkNotificationSessionDidChange";
+
+struct ConnectionWatch {
+    connected: HashSet<NetworkHostInfo>,
+    callback: Box<dyn FnMut(NetworkConnectionEvent) + Send>,
+}
+
+/// Keeps calling the callback given to [NetworkSession::watch_connections] until dropped.
+///
+pub struct NetworkSessionObserver {
+    _observer: NotificationObserver,
+}
+
+/// The [MIDI network session](https://developer.apple.com/documentation/coremidi/midinetworksession)
+/// of the system, sending and receiving MIDI over the network with RTP-MIDI (Apple Network MIDI).
+///
+/// The session has a source and a destination like any other endpoint, which send and receive
+/// the MIDI messages of all the connected hosts.
+///
+/// ```rust,no_run
+/// use coremidi::{NetworkConnection, NetworkConnectionPolicy, NetworkHost, NetworkSession};
+/// let session = NetworkSession::default_session();
+/// session.set_enabled(true);
+/// session.set_connection_policy(NetworkConnectionPolicy::HostsInContactList);
+///
+/// let host = NetworkHost::new("studio", "192.168.1.20", 5004);
+/// session.add_contact(&host);
+/// session.add_connection(&NetworkConnection::new(&host));
+/// println!("Sending to {:?}", session.destination().display_name());
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkSession {
+    object: ObjcObject,
+}
+
+impl NetworkSession {
+    /// The network session of the system, which is shared by all the applications.
+    ///
+    pub fn default_session() -> NetworkSession {
+        with_pool(|| unsafe {
+            let session: ObjcId = send(class!("MIDINetworkSession"), selector!("defaultSession"));
+            NetworkSession {
+                object: ObjcObject::retain(session).expect("MIDINetworkSession"),
+            }
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        with_pool(|| unsafe { send::<i8>(self.object.0, selector!("isEnabled")) != 0 })
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        with_pool(|| unsafe {
+            send1::<i8, ()>(self.object.0, selector!("setEnabled:"), enabled as i8)
+        })
+    }
+
+    /// The UDP port of the session.
+    ///
+    pub fn network_port(&self) -> u16 {
+        with_pool(|| unsafe { send::<usize>(self.object.0, selector!("networkPort")) as u16 })
+    }
+
+    /// The name of the session, as seen by the other hosts in the network.
+    ///
+    pub fn network_name(&self) -> Option<String> {
+        with_pool(|| unsafe { string_from(send(self.object.0, selector!("networkName"))) })
+    }
+
+    /// The name of the session endpoints in this computer.
+    ///
+    pub fn local_name(&self) -> Option<String> {
+        with_pool(|| unsafe { string_from(send(self.object.0, selector!("localName"))) })
+    }
+
+    pub fn connection_policy(&self) -> NetworkConnectionPolicy {
+        with_pool(|| unsafe {
+            NetworkConnectionPolicy::from_code(send(self.object.0, selector!("connectionPolicy")))
+        })
+    }
+
+    pub fn set_connection_policy(&self, policy: NetworkConnectionPolicy) {
+        with_pool(|| unsafe {
+            send1::<usize, ()>(
+                self.object.0,
+                selector!("setConnectionPolicy:"),
+                policy.code(),
+            )
+        })
+    }
+
+    /// The hosts that the session knows of, which can connect when the policy is
+    /// [HostsInContactList](NetworkConnectionPolicy::HostsInContactList).
+    ///
+    pub fn contacts(&self) -> Vec<NetworkHost> {
+        with_pool(|| unsafe {
+            set_objects(send(self.object.0, selector!("contacts")))
+                .into_iter()
+                .map(|object| NetworkHost { object })
+                .collect()
+        })
+    }
+
+    /// Add a host to the contacts, returning whether it was added.
+    ///
+    pub fn add_contact(&self, host: &NetworkHost) -> bool {
+        with_pool(|| unsafe {
+            send1::<ObjcId, i8>(self.object.0, selector!("addContact:"), host.object.0) != 0
+        })
+    }
+
+    /// Remove a host from the contacts, returning whether it was removed.
+    ///
+    pub fn remove_contact(&self, host: &NetworkHost) -> bool {
+        with_pool(|| unsafe {
+            send1::<ObjcId, i8>(self.object.0, selector!("removeContact:"), host.object.0) != 0
+        })
+    }
+
+    /// The connections of the session to other hosts.
+    ///
+    pub fn connections(&self) -> Vec<NetworkConnection> {
+        with_pool(|| unsafe {
+            set_objects(send(self.object.0, selector!("connections")))
+                .into_iter()
+                .map(|object| NetworkConnection { object })
+                .collect()
+        })
+    }
+
+    /// Connect to a host, returning whether the connection was added.
+    ///
+    pub fn add_connection(&self, connection: &NetworkConnection) -> bool {
+        with_pool(|| unsafe {
+            send1::<ObjcId, i8>(
+                self.object.0,
+                selector!("addConnection:"),
+                connection.object.0,
+            ) != 0
+        })
+    }
+
+    /// Disconnect from a host, returning whether the connection was removed.
+    ///
+    pub fn remove_connection(&self, connection: &NetworkConnection) -> bool {
+        with_pool(|| unsafe {
+            send1::<ObjcId, i8>(
+                self.object.0,
+                selector!("removeConnection:"),
+                connection.object.0,
+            ) != 0
+        })
+    }
+
+    /// Connect to a host, returning whether the connection was added.
+    ///
+    pub fn connect(&self, host: &NetworkHost) -> bool {
+        self.add_connection(&NetworkConnection::new(host))
+    }
+
+    /// Disconnect from a host (or from any other with the same address), returning whether it was connected.
+    ///
+    pub fn disconnect(&self, host: &NetworkHost) -> bool {
+        let mut removed = false;
+        for connection in self.connections() {
+            if connection.host().has_same_address_as(host) {
+                removed |= self.remove_connection(&connection);
+            }
+        }
+        removed
+    }
+
+    fn connected_hosts(&self) -> HashSet<NetworkHostInfo> {
+        self.connections()
+            .iter()
+            .map(|connection| connection.host().info())
+            .collect()
+    }
+
+    /// Call `callback` whenever a host connects to or disconnects from the session, until the observer
+    /// returned is dropped. The callback is called from the thread where the session changed.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{NetworkConnectionEvent, NetworkSession};
+    /// let session = NetworkSession::default_session();
+    /// let _observer = session.watch_connections(|event| match event {
+    ///     NetworkConnectionEvent::Connected(host) => println!("{:?} connected", host.name),
+    ///     NetworkConnectionEvent::Disconnected(host) => println!("{:?} disconnected", host.name),
+    /// });
+    /// ```
+    pub fn watch_connections<F>(&self, callback: F) -> NetworkSessionObserver
+    where
+        F: FnMut(NetworkConnectionEvent) + Send + 'static,
+    {
+        let watch = Mutex::new(ConnectionWatch {
+            connected: self.connected_hosts(),
+            callback: Box::new(callback),
+        });
+        let session = self.clone();
+        let observer = NotificationObserver::new(SESSION_DID_CHANGE, self.object.0, move || {
+            let connected = session.connected_hosts();
+            let mut watch = watch
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            for event in connection_events(&watch.connected, &connected) {
+                (watch.callback)(event);
+            }
+            watch.connected = connected;
+        });
+        NetworkSessionObserver {
+            _observer: observer,
+        }
+    }
+
+    /// The source receiving the MIDI messages from all the connected hosts.
+    ///
+    pub fn source(&self) -> Source {
+        with_pool(|| unsafe {
+            Source::from_raw(send::<MIDIEndpointRef>(
+                self.object.0,
+                selector!("sourceEndpoint"),
+            ))
+        })
+    }
+
+    /// The destination sending MIDI messages to all the connected hosts.
+    ///
+    pub fn destination(&self) -> Destination {
+        with_pool(|| unsafe {
+            Destination::from_raw(send::<MIDIEndpointRef>(
+                self.object.0,
+                selector!("destinationEndpoint"),
+            ))
+        })
+    }
+}
+
+/// A network MIDI host advertised through Bonjour, as found by a [NetworkBrowser].
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DiscoveredHost {
+    /// The name of the Bonjour service.
+    pub name: String,
+    pub domain: String,
+}
+
+impl DiscoveredHost {
+    /// The host to add to the contacts, or to connect to, of the [NetworkSession].
+    ///
+    pub fn host(&self) -> NetworkHost {
+        NetworkHost::with_net_service(&self.name, &self.name, &self.domain)
+    }
+}
+
+/// A change of the hosts advertised in the network, as reported by a [NetworkBrowser].
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NetworkBrowserEvent {
+    HostFound(DiscoveredHost),
+    HostLost(DiscoveredHost),
+}
+
+/// The hosts found, counting the network interfaces they were found through,
+/// so every host is only reported once.
+#[derive(Debug, Default)]
+struct DiscoveredHosts {
+    interfaces: HashMap<DiscoveredHost, usize>,
+}
+
+impl DiscoveredHosts {
+    fn update(&mut self, host: DiscoveredHost, added: bool) -> Option<NetworkBrowserEvent> {
+        let count = self.interfaces.entry(host.clone()).or_insert(0);
+        if added {
+            *count += 1;
+            Some(NetworkBrowserEvent::HostFound(host)).filter(|_| *count == 1)
+        } else if *count > 0 {
+            *count -= 1;
+            if *count == 0 {
+                self.interfaces.remove(&host);
+                Some(NetworkBrowserEvent::HostLost(host))
+            } else {
+                None
+            }
+        } else {
+            self.interfaces.remove(&host);
+            None
+        }
+    }
+}
+
+struct BrowserContext {
+    hosts: DiscoveredHosts,
+    callback: Box<dyn FnMut(NetworkBrowserEvent) + Send>,
+}
+
+extern "C" fn browse_reply(
+    _service: DNSServiceRef,
+    flags: DNSServiceFlags,
+    _interface_index: u32,
+    error: DNSServiceErrorType,
+    service_name: *const c_char,
+    _service_type: *const c_char,
+    domain: *const c_char,
+    context: *mut c_void,
+) {
+    if error != 0 || service_name.is_null() || domain.is_null() {
+        return;
+    }
+    let context = unsafe { &mut *(context as *mut BrowserContext) };
+    let host = unsafe {
+        DiscoveredHost {
+            name: CStr::from_ptr(service_name).to_string_lossy().into_owned(),
+            domain: CStr::from_ptr(domain).to_string_lossy().into_owned(),
+        }
+    };
+    if let Some(event) = context.hosts.update(host, flags & kDNSServiceFlagsAdd != 0) {
+        (context.callback)(event);
+    }
+}
+
+struct BrowseService(DNSServiceRef);
+
+// The service is only used from the browser thread once it is created
+unsafe impl Send for BrowseService {}
+
+/// Discovers the network MIDI hosts (RTP-MIDI sessions) advertised through Bonjour.
+///
+/// The browsing happens in a background thread, which calls the callback whenever a host appears or disappears,
+/// until the browser is dropped. The hosts found can be connected with [NetworkSession::connect].
+///
+/// ```rust,no_run
+/// use coremidi::{NetworkBrowser, NetworkBrowserEvent, NetworkSession};
+/// let browser = NetworkBrowser::new(|event| {
+///     if let NetworkBrowserEvent::HostFound(found) = event {
+///         NetworkSession::default_session().connect(&found.host());
+///     }
+/// })
+/// .unwrap();
+/// ```
+///
+pub struct NetworkBrowser {
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl NetworkBrowser {
+    /// The Bonjour service type of the network MIDI sessions.
+    ///
+    pub const SERVICE_TYPE: &'static str = "_apple-midi._udp";
+
+    /// Start browsing. The error is the one from DNS Service Discovery (`DNSServiceErrorType`).
+    ///
+    pub fn new<F>(callback: F) -> Result<NetworkBrowser, OSStatus>
+    where
+        F: FnMut(NetworkBrowserEvent) + Send + 'static,
+    {
+        let context = Box::into_raw(Box::new(BrowserContext {
+            hosts: DiscoveredHosts::default(),
+            callback: Box::new(callback),
+        }));
+        let service_type = CString::new(Self::SERVICE_TYPE).unwrap_or_default();
+        let mut service = ptr::null_mut();
+        let status = unsafe {
+            DNSServiceBrowse(
+                &mut service,
+                0,
+                0,
+                service_type.as_ptr(),
+                ptr::null(),
+                browse_reply,
+                context as *mut c_void,
+            )
+        };
+        if status != 0 {
+            drop(unsafe { Box::from_raw(context) });
+            return Err(status);
+        }
+        let service = BrowseService(service);
+        let context = context as usize;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread_stopped = stopped.clone();
+        let thread = thread::spawn(move || {
+            let service = service;
+            run_browser(&service, &thread_stopped);
+            unsafe {
+                DNSServiceRefDeallocate(service.0);
+                drop(Box::from_raw(context as *mut BrowserContext));
+            }
+        });
+        Ok(NetworkBrowser {
+            stopped,
+            thread: Some(thread),
+        })
+    }
+}
+
+fn run_browser(service: &BrowseService, stopped: &AtomicBool) {
+    let fd = unsafe { DNSServiceRefSockFD(service.0) };
+    while !stopped.load(Ordering::Relaxed) {
+        let mut fds = pollfd {
+            fd,
+            events: POLLIN,
+            revents: 0,
+        };
+        // Wake up from time to time to check whether the browser was dropped
+        let ready = unsafe { poll(&mut fds, 1, 100) };
+        if ready > 0 && unsafe { DNSServiceProcessResult(service.0) } != 0 {
+            break;
+        }
+    }
+}
+
+impl Drop for NetworkBrowser {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::network::{
+        connection_events, DiscoveredHost, DiscoveredHosts, NetworkBrowserEvent,
+        NetworkConnectionEvent, NetworkConnectionPolicy, NetworkHostInfo,
+    };
+
+    #[test]
+    fn hosts_found_through_several_interfaces() {
+        let mut hosts = DiscoveredHosts::default();
+        let studio = DiscoveredHost {
+            name: "studio".to_string(),
+            domain: "local.".to_string(),
+        };
+
+        assert_eq!(
+            hosts.update(studio.clone(), true),
+            Some(NetworkBrowserEvent::HostFound(studio.clone()))
+        );
+        assert_eq!(hosts.update(studio.clone(), true), None);
+        assert_eq!(hosts.update(studio.clone(), false), None);
+        assert_eq!(
+            hosts.update(studio.clone(), false),
+            Some(NetworkBrowserEvent::HostLost(studio.clone()))
+        );
+        assert_eq!(hosts.update(studio, false), None);
+    }
+
+    #[test]
+    fn connection_changes() {
+        let host = |name: &str| NetworkHostInfo {
+            name: Some(name.to_string()),
+            address: None,
+            port: 5004,
+            net_service_name: Some(name.to_string()),
+            net_service_domain: Some("local.".to_string()),
+        };
+        let previous: HashSet<_> = vec![host("a"), host("b")].into_iter().collect();
+        let current: HashSet<_> = vec![host("b"), host("c")].into_iter().collect();
+
+        assert_eq!(
+            connection_events(&previous, &current),
+            vec![
+                NetworkConnectionEvent::Disconnected(host("a")),
+                NetworkConnectionEvent::Connected(host("c")),
+            ]
+        );
+    }
+
+    #[test]
+    fn connection_policy_codes() {
+        for policy in [
+            NetworkConnectionPolicy::NoOne,
+            NetworkConnectionPolicy::HostsInContactList,
+            NetworkConnectionPolicy::Anyone,
+        ] {
+            assert_eq!(NetworkConnectionPolicy::from_code(policy.code()), policy);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::transforms::for_each_midi1_message;
+use crate::{duration_from_host_time, host_time_now, PacketList, Timestamp};
+
+const ALL_SOUND_OFF: u8 = 120;
+const ALL_NOTES_OFF: u8 = 123;
+
+/// A note played from its Note On to its Note Off, as paired by a [NoteTracker].
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteEvent {
+    pub channel: u8,
+    pub note: u8,
+    /// The velocity of the Note On.
+    pub velocity: u8,
+    /// The host time of the Note On.
+    pub start: Timestamp,
+    pub duration: Duration,
+}
+
+/// Pairs the Note On messages received with their Note Off messages, producing a [NoteEvent]
+/// with the duration of every note once it ends.
+///
+/// A Note On with velocity 0 is taken as a Note Off. The notes whose Note Off is missing end when the same note
+/// is played again in the channel, with an All Notes Off or All Sound Off message for the channel,
+/// after the maximum duration (if any), or when calling [finish](NoteTracker::finish).
+///
+/// ```
+/// use coremidi::{NoteTracker, PacketBuffer};
+/// let mut tracker = NoteTracker::new();
+/// let mut packets = PacketBuffer::with_capacity(0);
+/// packets.push_note_on(1000, 0, 60, 100).push_note_on(2000, 0, 60, 0);
+/// let mut notes = Vec::new();
+/// tracker.process_packets(&packets, |note| notes.push(note));
+/// assert_eq!(notes.len(), 1);
+/// assert_eq!((notes[0].note, notes[0].velocity, notes[0].start), (60, 100, 1000));
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct NoteTracker {
+    // The timestamp and velocity of the notes playing, by channel and note
+    playing: HashMap<(u8, u8), (Timestamp, u8)>,
+    max_duration: Option<Duration>,
+}
+
+impl NoteTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// End the notes still playing after `max_duration`, as if their Note Off was lost.
+    ///
+    pub fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// The number of notes playing, waiting for their Note Off.
+    ///
+    pub fn playing(&self) -> usize {
+        self.playing.len()
+    }
+
+    /// Process all the messages of a list of MIDI 1.0 packets, calling `on_note` for every note that ends.
+    ///
+    pub fn process_packets<F>(&mut self, packet_list: &PacketList, mut on_note: F)
+    where
+        F: FnMut(NoteEvent),
+    {
+        for packet in packet_list.iter() {
+            let timestamp = packet.timestamp();
+            for_each_midi1_message(packet.data(), |message| {
+                self.process(timestamp, message, &mut on_note)
+            });
+        }
+    }
+
+    /// Process a single MIDI 1.0 message, starting with its status byte. A timestamp of 0 means now.
+    ///
+    pub fn process<F>(&mut self, timestamp: Timestamp, message: &[u8], mut on_note: F)
+    where
+        F: FnMut(NoteEvent),
+    {
+        let timestamp = if timestamp == 0 {
+            host_time_now()
+        } else {
+            timestamp
+        };
+        self.poll(timestamp, &mut on_note);
+
+        let (status, data1, data2) = match message {
+            [status, data1, data2] if *status < 0xf0 => (*status, *data1, *data2),
+            _ => return,
+        };
+        let channel = status & 0x0f;
+        match (status & 0xf0, data2) {
+            (0x90, velocity) if velocity > 0 => {
+                // Playing the same note again ends the previous one
+                if let Some(started) = self.playing.insert((channel, data1), (timestamp, velocity))
+                {
+                    on_note(note_event(channel, data1, started, timestamp));
+                }
+            }
+            (0x80, _) | (0x90, _) => {
+                if let Some(started) = self.playing.remove(&(channel, data1)) {
+                    on_note(note_event(channel, data1, started, timestamp));
+                }
+            }
+            (0xb0, _) if data1 == ALL_SOUND_OFF || data1 == ALL_NOTES_OFF => {
+                self.end_notes(timestamp, |key_channel, _| key_channel == channel, on_note);
+            }
+            _ => {}
+        }
+    }
+
+    /// End the notes playing for longer than the maximum duration at the host time `now`.
+    ///
+    pub fn poll<F>(&mut self, now: Timestamp, on_note: F)
+    where
+        F: FnMut(NoteEvent),
+    {
+        if let Some(max_duration) = self.max_duration {
+            self.end_notes(
+                now,
+                |_, start| duration_from_host_time(now.saturating_sub(start)) > max_duration,
+                on_note,
+            );
+        }
+    }
+
+    /// End all the notes still playing at the host time `timestamp`, for example when the input is disconnected.
+    ///
+    pub fn finish<F>(&mut self, timestamp: Timestamp, on_note: F)
+    where
+        F: FnMut(NoteEvent),
+    {
+        self.end_notes(timestamp, |_, _| true, on_note);
+    }
+
+    fn end_notes<P, F>(&mut self, timestamp: Timestamp, predicate: P, mut on_note: F)
+    where
+        P: Fn(u8, Timestamp) -> bool,
+        F: FnMut(NoteEvent),
+    {
+        let mut ended: Vec<_> = self
+            .playing
+            .iter()
+            .filter(|((channel, _), (start, _))| predicate(*channel, *start))
+            .map(|(key, started)| (*key, *started))
+            .collect();
+        // Report them in the order they started
+        ended.sort_by_key(|((channel, note), (start, _))| (*start, *channel, *note));
+        for ((channel, note), started) in ended {
+            self.playing.remove(&(channel, note));
+            on_note(note_event(channel, note, started, timestamp));
+        }
+    }
+}
+
+fn note_event(channel: u8, note: u8, started: (Timestamp, u8), end: Timestamp) -> NoteEvent {
+    let (start, velocity) = started;
+    NoteEvent {
+        channel,
+        note,
+        velocity,
+        start,
+        duration: duration_from_host_time(end.saturating_sub(start)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::notes::NoteTracker;
+    use crate::{host_time_from_duration, PacketBuffer};
+
+    #[test]
+    fn notes_with_missing_offs() {
+        let ms = |millis| host_time_from_duration(Duration::from_millis(millis));
+        let mut tracker = NoteTracker::new();
+        let mut packets = PacketBuffer::with_capacity(0);
+        packets
+            .push_note_on(ms(10), 0, 60, 100)
+            .push_note_on(ms(20), 1, 60, 90)
+            .push_note_on(ms(30), 2, 62, 80)
+            // Played again without a Note Off
+            .push_note_on(ms(40), 0, 60, 70)
+            .push_cc(ms(50), 1, 123, 0)
+            .push_note_off(ms(60), 0, 60, 0)
+            // Not playing
+            .push_note_off(ms(70), 3, 60, 0);
+        let mut notes = Vec::new();
+        tracker.process_packets(&packets, |note| notes.push(note));
+        assert_eq!(tracker.playing(), 1);
+        tracker.finish(ms(100), |note| notes.push(note));
+
+        let summary: Vec<_> = notes
+            .iter()
+            .map(|note| (note.channel, note.velocity, note.duration.as_millis()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(0, 100, 30), (1, 90, 30), (0, 70, 20), (2, 80, 70)]
+        );
+    }
+
+    #[test]
+    fn max_duration() {
+        let ms = |millis| host_time_from_duration(Duration::from_millis(millis));
+        let mut tracker = NoteTracker::new().with_max_duration(Duration::from_millis(100));
+        let mut notes = Vec::new();
+        tracker.process(ms(10), &[0x90, 60, 100], |note| notes.push(note));
+        tracker.poll(ms(100), |note| notes.push(note));
+        assert!(notes.is_empty());
+
+        tracker.poll(ms(200), |note| notes.push(note));
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].duration.as_millis(), 190);
+        assert_eq!(tracker.playing(), 0);
+    }
+}
@@ -3,9 +3,14 @@
 use core_foundation::base::{OSStatus, TCFType};
 use core_foundation::string::{CFString, CFStringRef};
 
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
 use coremidi_sys::{
     MIDIIOErrorNotification, MIDINotification, MIDIObjectAddRemoveNotification,
-    MIDIObjectPropertyChangeNotification,
+    MIDIObjectPropertyChangeNotification, MIDIObjectRef,
 };
 
 use crate::any_object::AnyObject;
@@ -13,18 +18,21 @@ use crate::device::Device;
 use crate::object::Object;
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AddedRemovedInfo {
     pub parent: AnyObject,
     pub child: AnyObject,
 }
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PropertyChangedInfo {
     pub object: AnyObject,
     pub property_name: String,
 }
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IoErrorInfo {
     pub driver_device: Device,
     pub error_code: OSStatus,
@@ -34,6 +42,7 @@ pub struct IoErrorInfo {
 /// See [MIDINotification](https://developer.apple.com/documentation/coremidi/midinotification).
 ///
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Notification {
     SetupChanged,
     ObjectAdded(AddedRemovedInfo),
@@ -128,6 +137,154 @@ impl TryFrom<&MIDINotification> for Notification {
     }
 }
 
+type PropertyWatchCallback = Arc<Mutex<dyn FnMut(&PropertyChangedInfo) + Send + 'static>>;
+
+struct PropertyWatch {
+    object_ref: MIDIObjectRef,
+    property_name: String,
+    callback: PropertyWatchCallback,
+}
+
+static PROPERTY_WATCHES: Lazy<Mutex<HashMap<usize, PropertyWatch>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_PROPERTY_WATCH_ID: AtomicUsize = AtomicUsize::new(0);
+
+// The id of the notify block dispatching the notifications to the property watchers (0 until claimed, or once released)
+static PROPERTY_WATCHES_DISPATCHER: AtomicUsize = AtomicUsize::new(0);
+
+static NEXT_PROPERTY_WATCHES_DISPATCHER_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// A subscription to the changes of a property for a given object.
+/// See [Object::watch_property](crate::Object::watch_property).
+///
+/// The subscription is cancelled when this is dropped.
+///
+#[derive(Debug)]
+pub struct PropertyWatcher {
+    id: usize,
+}
+
+impl PropertyWatcher {
+    pub(crate) fn new<F>(object_ref: MIDIObjectRef, property_name: String, callback: F) -> Self
+    where
+        F: FnMut(&PropertyChangedInfo) + Send + 'static,
+    {
+        let id = NEXT_PROPERTY_WATCH_ID.fetch_add(1, Ordering::Relaxed);
+        let watch = PropertyWatch {
+            object_ref,
+            property_name,
+            callback: Arc::new(Mutex::new(callback)),
+        };
+        lock_property_watches().insert(id, watch);
+        Self { id }
+    }
+}
+
+impl Drop for PropertyWatcher {
+    fn drop(&mut self) {
+        lock_property_watches().remove(&self.id);
+    }
+}
+
+fn lock_property_watches() -> std::sync::MutexGuard<'static, HashMap<usize, PropertyWatch>> {
+    PROPERTY_WATCHES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Forwards the notifications received by the notify block of a client to the property watchers.
+///
+/// Every client receives the same notifications, so only the one that claimed it first forwards them,
+/// and the watchers are called once per change no matter how many clients there are.
+/// The claim is released when its client is dropped (see [PropertyWatchesClaim]),
+/// and then the next client receiving a notification takes over.
+pub(crate) struct PropertyWatchesDispatch {
+    id: usize,
+    released: Arc<AtomicBool>,
+}
+
+impl PropertyWatchesDispatch {
+    pub(crate) fn new() -> Self {
+        Self {
+            id: NEXT_PROPERTY_WATCHES_DISPATCHER_ID.fetch_add(1, Ordering::Relaxed),
+            released: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// The claim of this dispatch, to be kept by its client.
+    pub(crate) fn claim_handle(&self) -> PropertyWatchesClaim {
+        PropertyWatchesClaim {
+            id: self.id,
+            released: self.released.clone(),
+        }
+    }
+
+    pub(crate) fn dispatch(&self, notification: &Notification) {
+        if self.claim(&PROPERTY_WATCHES_DISPATCHER) {
+            dispatch_to_property_watchers(notification);
+        }
+    }
+
+    fn claim(&self, dispatcher: &AtomicUsize) -> bool {
+        let claimed =
+            match dispatcher.compare_exchange(0, self.id, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => true,
+                Err(current) => current == self.id,
+            };
+        if claimed && self.released.load(Ordering::SeqCst) {
+            // Released by its client meanwhile, so leave it to the next one
+            let _ = dispatcher.compare_exchange(self.id, 0, Ordering::SeqCst, Ordering::SeqCst);
+            return false;
+        }
+        claimed
+    }
+}
+
+/// Releases the claim of a [PropertyWatchesDispatch] when its client is dropped.
+///
+/// Clients are never disposed, so CoreMIDI keeps calling their notify blocks,
+/// but those of the dropped clients don't forward the notifications anymore.
+#[derive(Debug)]
+pub(crate) struct PropertyWatchesClaim {
+    id: usize,
+    released: Arc<AtomicBool>,
+}
+
+impl PropertyWatchesClaim {
+    fn release(&self, dispatcher: &AtomicUsize) {
+        self.released.store(true, Ordering::SeqCst);
+        let _ = dispatcher.compare_exchange(self.id, 0, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
+
+impl Drop for PropertyWatchesClaim {
+    fn drop(&mut self) {
+        self.release(&PROPERTY_WATCHES_DISPATCHER);
+    }
+}
+
+/// Forward a notification to the property watchers interested on it.
+///
+/// The callbacks are called without holding the lock of the watchers, so they can create or drop other watchers.
+fn dispatch_to_property_watchers(notification: &Notification) {
+    if let Notification::PropertyChanged(info) = notification {
+        let object_ref = info.object.as_ref().0;
+        let callbacks: Vec<PropertyWatchCallback> = lock_property_watches()
+            .values()
+            .filter(|watch| {
+                watch.object_ref == object_ref && watch.property_name == info.property_name
+            })
+            .map(|watch| watch.callback.clone())
+            .collect();
+        for callback in callbacks {
+            (callback
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()))(info);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -141,8 +298,13 @@ mod tests {
 
     use crate::any_object::AnyObject;
     use crate::device::Device;
-    use crate::notifications::{AddedRemovedInfo, IoErrorInfo, Notification, PropertyChangedInfo};
+    use crate::notifications::{
+        dispatch_to_property_watchers, AddedRemovedInfo, IoErrorInfo, Notification,
+        PropertyChangedInfo, PropertyWatchesDispatch,
+    };
     use crate::object::Object;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
     #[test]
     fn notification_from_error() {
@@ -356,4 +518,72 @@ mod tests {
 
         assert_eq!(notification.unwrap(), Notification::IoError(info));
     }
+
+    #[test]
+    fn property_watcher_filters_notifications() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let watcher_calls = calls.clone();
+        let watcher = Object(0x1234).watch_property_named("offline", move |info| {
+            assert_eq!(info.property_name, "offline");
+            watcher_calls.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let notify = |object_ref, property_name: &str| {
+            dispatch_to_property_watchers(&Notification::PropertyChanged(PropertyChangedInfo {
+                object: AnyObject::Destination(crate::Destination::new(object_ref)),
+                property_name: property_name.to_string(),
+            }))
+        };
+
+        notify(0x1234, "offline");
+        notify(0x1234, "name");
+        notify(0x4321, "offline");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        drop(watcher);
+        notify(0x1234, "offline");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn property_watcher_can_watch_from_its_callback() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let watcher_calls = calls.clone();
+        let _watcher = Object(0x5678).watch_property_named("name", move |_| {
+            let inner = Object(0x5678).watch_property_named("model", |_| {});
+            drop(inner);
+            watcher_calls.fetch_add(1, Ordering::SeqCst);
+        });
+
+        dispatch_to_property_watchers(&Notification::PropertyChanged(PropertyChangedInfo {
+            object: AnyObject::Destination(crate::Destination::new(0x5678)),
+            property_name: "name".to_string(),
+        }));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn property_watches_dispatched_by_one_client() {
+        let dispatcher = AtomicUsize::new(0);
+        let first = PropertyWatchesDispatch::new();
+        let second = PropertyWatchesDispatch::new();
+
+        assert!(first.claim(&dispatcher));
+        assert!(!second.claim(&dispatcher));
+        assert!(first.claim(&dispatcher));
+    }
+
+    #[test]
+    fn property_watches_dispatched_by_another_client_when_released() {
+        let dispatcher = AtomicUsize::new(0);
+        let first = PropertyWatchesDispatch::new();
+        let second = PropertyWatchesDispatch::new();
+        let first_claim = first.claim_handle();
+
+        assert!(first.claim(&dispatcher));
+        first_claim.release(&dispatcher);
+        assert!(second.claim(&dispatcher));
+        assert!(!first.claim(&dispatcher));
+        assert!(second.claim(&dispatcher));
+    }
 }
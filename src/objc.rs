@@ -0,0 +1,178 @@
+//! Helpers to call the Objective-C APIs of the system frameworks through the runtime,
+//! for the parts of CoreMIDI (and friends) without a C interface.
+
+use block::{ConcreteBlock, RcBlock};
+use core_foundation::{
+    base::TCFType,
+    string::{CFString, CFStringRef},
+};
+
+use crate::sys::{
+    objc_autoreleasePoolPop, objc_autoreleasePoolPush, objc_msgSend, objc_release, objc_retain,
+    ObjcId, ObjcSel,
+};
+
+macro_rules! selector {
+    ($name:literal) => {
+        $crate::sys::sel_registerName(concat!($name, "\0").as_ptr() as *const std::os::raw::c_char)
+    };
+}
+pub(crate) use selector;
+
+macro_rules! class {
+    ($name:literal) => {
+        $crate::sys::objc_getClass(concat!($name, "\0").as_ptr() as *const std::os::raw::c_char)
+    };
+}
+pub(crate) use class;
+
+/// Send a message without arguments.
+pub(crate) unsafe fn send<R>(receiver: ObjcId, selector: ObjcSel) -> R {
+    let send: unsafe extern "C" fn(ObjcId, ObjcSel) -> R =
+        std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    send(receiver, selector)
+}
+
+/// Send a message with one argument.
+pub(crate) unsafe fn send1<A, R>(receiver: ObjcId, selector: ObjcSel, a: A) -> R {
+    let send: unsafe extern "C" fn(ObjcId, ObjcSel, A) -> R =
+        std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    send(receiver, selector, a)
+}
+
+/// Send a message with two arguments.
+#[cfg(feature = "bluetooth")]
+pub(crate) unsafe fn send2<A, B, R>(receiver: ObjcId, selector: ObjcSel, a: A, b: B) -> R {
+    let send: unsafe extern "C" fn(ObjcId, ObjcSel, A, B) -> R =
+        std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    send(receiver, selector, a, b)
+}
+
+/// Send a message with three arguments.
+pub(crate) unsafe fn send3<A, B, C, R>(receiver: ObjcId, selector: ObjcSel, a: A, b: B, c: C) -> R {
+    let send: unsafe extern "C" fn(ObjcId, ObjcSel, A, B, C) -> R =
+        std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    send(receiver, selector, a, b, c)
+}
+
+/// Send a message with four arguments.
+pub(crate) unsafe fn send4<A, B, C, D, R>(
+    receiver: ObjcId,
+    selector: ObjcSel,
+    a: A,
+    b: B,
+    c: C,
+    d: D,
+) -> R {
+    let send: unsafe extern "C" fn(ObjcId, ObjcSel, A, B, C, D) -> R =
+        std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    send(receiver, selector, a, b, c, d)
+}
+
+/// Run `f` within an autorelease pool, for the objects returned by the methods called.
+pub(crate) fn with_pool<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    unsafe {
+        let pool = objc_autoreleasePoolPush();
+        let result = f();
+        objc_autoreleasePoolPop(pool);
+        result
+    }
+}
+
+/// A strong reference to an Objective-C object.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ObjcObject(pub(crate) ObjcId);
+
+impl ObjcObject {
+    /// Keep an object returned by a method (that is autoreleased).
+    pub(crate) unsafe fn retain(object: ObjcId) -> Option<Self> {
+        if object.is_null() {
+            None
+        } else {
+            Some(Self(objc_retain(object)))
+        }
+    }
+}
+
+impl Clone for ObjcObject {
+    fn clone(&self) -> Self {
+        Self(unsafe { objc_retain(self.0) })
+    }
+}
+
+impl Drop for ObjcObject {
+    fn drop(&mut self) {
+        unsafe { objc_release(self.0) }
+    }
+}
+
+pub(crate) unsafe fn ns_string(string: &CFString) -> ObjcId {
+    // NSString and CFString are toll-free bridged
+    string.as_concrete_TypeRef() as ObjcId
+}
+
+pub(crate) unsafe fn string_from(ns_string: ObjcId) -> Option<String> {
+    if ns_string.is_null() {
+        None
+    } else {
+        Some(CFString::wrap_under_get_rule(ns_string as CFStringRef).to_string())
+    }
+}
+
+/// The objects of an NSSet.
+pub(crate) unsafe fn set_objects(set: ObjcId) -> Vec<ObjcObject> {
+    if set.is_null() {
+        return Vec::new();
+    }
+    let array: ObjcId = send(set, selector!("allObjects"));
+    let count: usize = send(array, selector!("count"));
+    (0..count)
+        .filter_map(|index| ObjcObject::retain(send1(array, selector!("objectAtIndex:"), index)))
+        .collect()
+}
+
+/// An observer of the notifications posted to the default `NSNotificationCenter`, removed when dropped.
+pub(crate) struct NotificationObserver {
+    observer: ObjcObject,
+    // The notification center keeps its own copy, but just in case
+    _block: RcBlock<(ObjcId,), ()>,
+}
+
+impl NotificationObserver {
+    /// Call `f` for every notification with a name, posted by an object (or by any of them when null).
+    /// It is called from the thread posting the notification.
+    pub(crate) fn new<F>(name: &str, object: ObjcId, f: F) -> Self
+    where
+        F: Fn() + 'static,
+    {
+        let block = ConcreteBlock::new(move |_notification: ObjcId| f()).copy();
+        let name = CFString::new(name);
+        with_pool(|| unsafe {
+            let center: ObjcId = send(class!("NSNotificationCenter"), selector!("defaultCenter"));
+            let observer: ObjcId = send4(
+                center,
+                selector!("addObserverForName:object:queue:usingBlock:"),
+                ns_string(&name),
+                object,
+                std::ptr::null_mut::<std::os::raw::c_void>(),
+                &*block as *const _ as ObjcId,
+            );
+            NotificationObserver {
+                observer: ObjcObject::retain(observer).expect("NSNotificationCenter observer"),
+                _block: block,
+            }
+        })
+    }
+}
+
+impl Drop for NotificationObserver {
+    fn drop(&mut self) {
+        with_pool(|| unsafe {
+            let center: ObjcId = send(class!("NSNotificationCenter"), selector!("defaultCenter"));
+            send1::<ObjcId, ()>(center, selector!("removeObserver:"), self.observer.0);
+        })
+    }
+}
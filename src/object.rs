@@ -1,11 +1,27 @@
+use core_foundation::{base::TCFType, string::CFString};
 use core_foundation_sys::base::OSStatus;
 use std::fmt;
 
-use coremidi_sys::{MIDIObjectRef, SInt32};
+use coremidi_sys::{
+    kMIDIIDNotUnique, kMIDIObjectNotFound, MIDIObjectFindByUniqueID, MIDIObjectRef,
+    MIDIObjectRemoveProperty, MIDIObjectType, MIDIUniqueID,
+};
 
+use crate::notifications::{PropertyChangedInfo, PropertyWatcher};
 use crate::properties::{
-    BooleanProperty, IntegerProperty, Properties, PropertyGetter, PropertySetter, StringProperty,
+    none_if_unknown, AnyPropertyValue, BooleanProperty, IntegerProperty, NamedProperty, Properties,
+    PropertyGetter, PropertyHandle, PropertyKey, PropertySetter, PropertyValue, StringProperty,
 };
+use crate::{unit_result_from_status, Client};
+
+/// A [unique identifier](https://developer.apple.com/documentation/coremidi/midiuniqueid) for a MIDI object.
+///
+/// Unique ids are signed 32 bits integers, and they are persistent across system reboots.
+///
+pub type UniqueId = MIDIUniqueID;
+
+/// The maximum number of ids tried by [Object::set_unique_id_or_next].
+const MAX_UNIQUE_ID_ATTEMPTS: usize = 64;
 
 /// A [MIDI Object](https://developer.apple.com/documentation/coremidi/midiobjectref).
 ///
@@ -18,46 +34,193 @@ impl Object {
     /// Get the name for the object.
     ///
     pub fn name(&self) -> Option<String> {
-        Properties::name().value_from(self).ok()
+        Properties::name().try_value_from(self).ok().flatten()
     }
 
     /// Get the unique id for the object.
     ///
-    pub fn unique_id(&self) -> Option<u32> {
-        Properties::unique_id()
-            .value_from(self)
+    pub fn unique_id(&self) -> Option<UniqueId> {
+        Properties::unique_id().try_value_from(self).ok().flatten()
+    }
+
+    /// Set the unique id for the object.
+    ///
+    /// It fails with [kMIDIIDNotUnique](https://developer.apple.com/documentation/coremidi/kmidiidnotunique)
+    /// when the id is already used by another object.
+    ///
+    pub fn set_unique_id(&self, unique_id: UniqueId) -> Result<(), OSStatus> {
+        Properties::unique_id().set_value(self, unique_id)
+    }
+
+    /// Set the unique id for the object, trying the following ids when it is already in use.
+    ///
+    /// It returns the id that was finally assigned to the object.
+    ///
+    /// ```rust,no_run
+    /// let client = coremidi::Client::new("example-client").unwrap();
+    /// let source = client.virtual_source("example-source").unwrap();
+    /// let unique_id = source.set_unique_id_or_next(0x12345678).unwrap();
+    /// println!("Unique id: {:08x}", unique_id);
+    /// ```
+    ///
+    pub fn set_unique_id_or_next(&self, unique_id: UniqueId) -> Result<UniqueId, OSStatus> {
+        let mut candidate = unique_id;
+        let mut status = kMIDIIDNotUnique;
+        for _ in 0..MAX_UNIQUE_ID_ATTEMPTS {
+            match self.set_unique_id(candidate) {
+                Ok(()) => return Ok(candidate),
+                Err(err) if err == kMIDIIDNotUnique => status = err,
+                Err(err) => return Err(err),
+            }
+            candidate = next_unique_id(candidate);
+        }
+        Err(status)
+    }
+
+    /// Get the unique ids of the objects this object is connected to.
+    /// See [connection unique ids](Properties::connection_unique_ids).
+    ///
+    pub fn connection_unique_ids(&self) -> Vec<UniqueId> {
+        Properties::connection_unique_ids()
+            .try_value_from(self)
             .ok()
-            .map(|v: SInt32| v as u32)
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    /// Connect the object to another one, usually an external device or one of its entities or endpoints,
+    /// like it is done from the Audio MIDI Setup.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Device, Destination};
+    /// let mut synth = Device::create_external("example-synth", "example-manufacturer", "example-model").unwrap();
+    /// let entity = synth.add_entity("example-entity", false, 1, 1).unwrap();
+    /// synth.add_to_setup().unwrap();
+    /// let destination = Destination::from_index(0).unwrap();
+    /// destination.connect_to(&entity).unwrap();
+    /// ```
+    ///
+    pub fn connect_to(&self, other: &Object) -> Result<(), OSStatus> {
+        let unique_id = other.unique_id().ok_or(kMIDIObjectNotFound)?;
+        let mut unique_ids = self.connection_unique_ids();
+        if !unique_ids.contains(&unique_id) {
+            unique_ids.push(unique_id);
+            Properties::connection_unique_ids().set_value(self, unique_ids)?;
+        }
+        Ok(())
+    }
+
+    /// Disconnect the object from another one it was connected to with [Object::connect_to].
+    ///
+    pub fn disconnect_from(&self, other: &Object) -> Result<(), OSStatus> {
+        let unique_id = other.unique_id().ok_or(kMIDIObjectNotFound)?;
+        let mut unique_ids = self.connection_unique_ids();
+        let len = unique_ids.len();
+        unique_ids.retain(|id| *id != unique_id);
+        if unique_ids.len() != len {
+            Properties::connection_unique_ids().set_value(self, unique_ids)?;
+        }
+        Ok(())
+    }
+
+    /// Find out the type of the object, looking it up by its unique id.
+    /// See [MIDIObjectFindByUniqueID](https://developer.apple.com/documentation/coremidi/1495191-midiobjectfindbyuniqueid).
+    ///
+    pub(crate) fn object_type(&self) -> Result<MIDIObjectType, OSStatus> {
+        let unique_id = self.unique_id().ok_or(kMIDIObjectNotFound)?;
+        let mut object_ref: MIDIObjectRef = 0;
+        let mut object_type: MIDIObjectType = 0;
+        let status =
+            unsafe { MIDIObjectFindByUniqueID(unique_id, &mut object_ref, &mut object_type) };
+        match status {
+            0 if object_ref == self.0 => Ok(object_type),
+            0 => Err(kMIDIObjectNotFound),
+            _ => Err(status),
+        }
     }
 
     /// Get the display name for the object.
     ///
     pub fn display_name(&self) -> Option<String> {
-        Properties::display_name().value_from(self).ok()
+        Properties::display_name()
+            .try_value_from(self)
+            .ok()
+            .flatten()
+    }
+
+    /// Get a handle to the [name](Properties::name) property of the object.
+    ///
+    pub fn name_property(&self) -> PropertyHandle<'_, StringProperty, String> {
+        self.property_handle(Properties::name())
+    }
+
+    /// Get a handle to the [display name](Properties::display_name) property of the object.
+    ///
+    pub fn display_name_property(&self) -> PropertyHandle<'_, StringProperty, String> {
+        self.property_handle(Properties::display_name())
+    }
+
+    /// Get a handle to the [manufacturer](Properties::manufacturer) property of the object.
+    ///
+    pub fn manufacturer_property(&self) -> PropertyHandle<'_, StringProperty, String> {
+        self.property_handle(Properties::manufacturer())
+    }
+
+    /// Get a handle to the [model](Properties::model) property of the object.
+    ///
+    pub fn model_property(&self) -> PropertyHandle<'_, StringProperty, String> {
+        self.property_handle(Properties::model())
+    }
+
+    /// Get a handle to the [offline](Properties::offline) property of the object.
+    ///
+    pub fn offline_property(&self) -> PropertyHandle<'_, BooleanProperty, bool> {
+        self.property_handle(Properties::offline())
+    }
+
+    /// Get a handle to the [private](Properties::private) property of the object.
+    ///
+    pub fn private_property(&self) -> PropertyHandle<'_, BooleanProperty, bool> {
+        self.property_handle(Properties::private())
+    }
+
+    /// Get a handle to any property of the object, with the value type given by `T`.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::Properties;
+    /// let source = coremidi::Source::from_index(0).unwrap();
+    /// let speed = source.property_handle::<_, i32>(Properties::max_sysex_speed());
+    /// println!("{:?}", speed.get());
+    /// ```
+    ///
+    pub fn property_handle<P, T>(&self, property: P) -> PropertyHandle<'_, P, T> {
+        PropertyHandle::new(self, property)
     }
 
     /// Sets an object's string-type property.
     ///
     pub fn set_property_string(&self, name: &str, value: &str) -> Result<(), OSStatus> {
-        StringProperty::new(name).set_value(self, value)
+        StringProperty::interned(name).set_value(self, value)
     }
 
     /// Gets an object's string-type property.
     ///
+    #[deprecated(note = "use `Object::property::<String>(name)` instead")]
     pub fn get_property_string(&self, name: &str) -> Result<String, OSStatus> {
-        StringProperty::new(name).value_from(self)
+        self.property(name)
     }
 
     /// Sets an object's integer-type property.
     ///
     pub fn set_property_integer(&self, name: &str, value: i32) -> Result<(), OSStatus> {
-        IntegerProperty::new(name).set_value(self, value)
+        IntegerProperty::interned(name).set_value(self, value)
     }
 
     /// Gets an object's integer-type property.
     ///
+    #[deprecated(note = "use `Object::property::<i32>(name)` instead")]
     pub fn get_property_integer(&self, name: &str) -> Result<i32, OSStatus> {
-        IntegerProperty::new(name).value_from(self)
+        self.property(name)
     }
 
     /// Sets an object's boolean-type property.
@@ -65,15 +228,146 @@ impl Object {
     /// CoreMIDI treats booleans as integers (0/1) but this API uses native bool types
     ///
     pub fn set_property_boolean(&self, name: &str, value: bool) -> Result<(), OSStatus> {
-        BooleanProperty::new(name).set_value(self, value)
+        BooleanProperty::interned(name).set_value(self, value)
     }
 
     /// Gets an object's boolean-type property.
     ///
     /// CoreMIDI treats booleans as integers (0/1) but this API uses native bool types
     ///
+    #[deprecated(note = "use `Object::property::<bool>(name)` instead")]
     pub fn get_property_boolean(&self, name: &str) -> Result<bool, OSStatus> {
-        BooleanProperty::new(name).value_from(self)
+        self.property(name)
+    }
+
+    /// Gets an object's property by name, choosing the right CoreMIDI call from its type.
+    ///
+    /// The keys of the well-known CoreMIDI properties are reused, so reading them from many objects
+    /// doesn't create a new key every time.
+    ///
+    /// ```rust,no_run
+    /// let source = coremidi::Source::from_index(0).unwrap();
+    /// let name = source.property::<String>("name").unwrap();
+    /// let offline = source.property::<bool>("offline").unwrap();
+    /// ```
+    ///
+    pub fn property<T: PropertyValue>(&self, name: &str) -> Result<T, OSStatus> {
+        T::get_property(self, name)
+    }
+
+    /// Gets an object's property by name, or `None` when the object doesn't have it.
+    /// See [Object::property] for further details.
+    ///
+    pub fn try_property<T: PropertyValue>(&self, name: &str) -> Result<Option<T>, OSStatus> {
+        none_if_unknown(T::get_property(self, name))
+    }
+
+    /// Reads several properties in a single pass, in the same order as the keys.
+    ///
+    /// The keys can be built once and reused for many objects, which avoids creating
+    /// the same property names over and over when building large endpoint tables.
+    /// Properties that can't be read are returned as `None`.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Properties, PropertyKey, Sources};
+    /// let keys: Vec<PropertyKey> = vec![
+    ///     Properties::display_name().into(),
+    ///     Properties::unique_id().into(),
+    ///     Properties::offline().into(),
+    /// ];
+    /// for source in Sources {
+    ///     println!("{:?}", source.read_properties(&keys));
+    /// }
+    /// ```
+    ///
+    pub fn read_properties(&self, keys: &[PropertyKey]) -> Vec<Option<AnyPropertyValue>> {
+        keys.iter().map(|key| key.read(self).ok()).collect()
+    }
+
+    /// Sets an object's property by name, choosing the right CoreMIDI call from its type.
+    ///
+    pub fn set_property_value<T: PropertyValue>(
+        &self,
+        name: &str,
+        value: T,
+    ) -> Result<(), OSStatus> {
+        T::set_property(self, name, value)
+    }
+
+    /// Sets a custom property, prefixing its key with the [namespace](crate::Client::set_property_namespace)
+    /// configured on the client.
+    ///
+    /// ```rust,no_run
+    /// let mut client = coremidi::Client::new("example-client").unwrap();
+    /// client.set_property_namespace("com.example.my-app");
+    /// let source = coremidi::Source::from_index(0).unwrap();
+    /// // Sets the property `com.example.my-app.color`
+    /// source.set_app_property(&client, "color", "red".to_string()).unwrap();
+    /// ```
+    ///
+    pub fn set_app_property<T: PropertyValue>(
+        &self,
+        client: &Client,
+        key: &str,
+        value: T,
+    ) -> Result<(), OSStatus> {
+        self.set_property_value(&client.app_property_name(key), value)
+    }
+
+    /// Gets a custom property, prefixing its key with the [namespace](crate::Client::set_property_namespace)
+    /// configured on the client.
+    ///
+    pub fn get_app_property<T: PropertyValue>(
+        &self,
+        client: &Client,
+        key: &str,
+    ) -> Result<T, OSStatus> {
+        self.property(&client.app_property_name(key))
+    }
+
+    /// Removes an object's property.
+    /// See [MIDIObjectRemoveProperty](https://developer.apple.com/documentation/coremidi/1495167-midiobjectremoveproperty).
+    ///
+    pub fn remove_property(&self, name: &str) -> Result<(), OSStatus> {
+        let property_key = CFString::new(name);
+        let status =
+            unsafe { MIDIObjectRemoveProperty(self.0, property_key.as_concrete_TypeRef()) };
+        unit_result_from_status(status)
+    }
+
+    /// Calls the callback every time the property changes for this object.
+    ///
+    /// The changes are detected from the notifications received by a client created with
+    /// [Client::new_with_notifications](crate::Client::new_with_notifications) (the first one receiving any,
+    /// until it is dropped and another one takes over), so such a client must exist, and its run loop
+    /// must be running, for the callback to be called.
+    /// The subscription lasts until the returned [PropertyWatcher] is dropped.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Client, Destination, Notification, Properties};
+    /// let client = Client::new_with_notifications("example-client", |_: &Notification| ()).unwrap();
+    /// let destination = Destination::from_index(0).unwrap();
+    /// let _watcher = destination.watch_property(&Properties::offline(), |info| {
+    ///     println!("The offline property changed for {:?}", info.object);
+    /// });
+    /// ```
+    ///
+    pub fn watch_property<P, F>(&self, property: &P, callback: F) -> PropertyWatcher
+    where
+        P: NamedProperty,
+        F: FnMut(&PropertyChangedInfo) + Send + 'static,
+    {
+        self.watch_property_named(&property.name(), callback)
+    }
+
+    /// Calls the callback every time the property with the given name changes for this object.
+    /// See [Object::watch_property] for further details.
+    ///
+    pub fn watch_property_named<F>(&self, name: &str, callback: F) -> PropertyWatcher
+    where
+        F: FnMut(&PropertyChangedInfo) + Send + 'static,
+    {
+        PropertyWatcher::new(self.0, name.to_string(), callback)
     }
 
     pub fn set_property<T>(
@@ -89,8 +383,92 @@ impl Object {
     }
 }
 
+/// The id following a given one, skipping the zero value used as an invalid id.
+fn next_unique_id(unique_id: UniqueId) -> UniqueId {
+    match unique_id.wrapping_add(1) {
+        0 => 1,
+        next => next,
+    }
+}
+
 impl fmt::Debug for Object {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Object({:x})", self.0 as usize)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Client, Protocol};
+
+    #[test]
+    fn next_unique_id() {
+        assert_eq!(super::next_unique_id(1), 2);
+        assert_eq!(super::next_unique_id(-1), 1);
+        assert_eq!(super::next_unique_id(i32::MAX), i32::MIN);
+    }
+
+    #[test]
+    fn set_unique_id_or_next() {
+        let client = Client::new("Test Client").unwrap();
+        let source1 = client.virtual_source("A").unwrap();
+        let source2 = client.virtual_source("B").unwrap();
+
+        let unique_id = source1.unique_id().unwrap();
+
+        assert!(source2.set_unique_id(unique_id).is_err());
+        let assigned = source2.set_unique_id_or_next(unique_id).unwrap();
+        assert_ne!(assigned, unique_id);
+        assert_eq!(source2.unique_id(), Some(assigned));
+    }
+
+    #[test]
+    fn connect_to() {
+        let client = Client::new("Test Client").unwrap();
+        let source1 = client.virtual_source("A").unwrap();
+        let source2 = client.virtual_source("B").unwrap();
+        let source3 = client.virtual_source("C").unwrap();
+
+        source1.connect_to(&source2).unwrap();
+        source1.connect_to(&source3).unwrap();
+        source1.connect_to(&source2).unwrap();
+        assert_eq!(
+            source1.connection_unique_ids(),
+            vec![source2.unique_id().unwrap(), source3.unique_id().unwrap()]
+        );
+
+        source1.disconnect_from(&source2).unwrap();
+        assert_eq!(
+            source1.connection_unique_ids(),
+            vec![source3.unique_id().unwrap()]
+        );
+    }
+
+    #[test]
+    fn remove_property() {
+        let client = Client::new("Test Client").unwrap();
+        let dest = client
+            .virtual_destination_with_protocol("A", Protocol::Midi10, |_| ())
+            .unwrap();
+
+        dest.set_property_string("my-own-string-property", "my-value")
+            .unwrap();
+        dest.remove_property("my-own-string-property").unwrap();
+
+        assert!(dest.property::<String>("my-own-string-property").is_err());
+    }
+
+    #[test]
+    fn app_property() {
+        let mut client = Client::new("Test Client").unwrap();
+        client.set_property_namespace("com.example.test.");
+        let dest = client
+            .virtual_destination_with_protocol("A", Protocol::Midi10, |_| ())
+            .unwrap();
+
+        dest.set_app_property(&client, "level", 42).unwrap();
+
+        assert_eq!(dest.get_app_property::<i32>(&client, "level"), Ok(42));
+        assert_eq!(dest.property::<i32>("com.example.test.level"), Ok(42));
+    }
+}
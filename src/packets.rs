@@ -1,16 +1,25 @@
 use std::fmt;
 use std::marker::PhantomData;
 use std::ops::Deref;
+use std::ptr;
 use std::slice;
+use std::time::Duration;
+
+use once_cell::unsync::OnceCell;
 
 use coremidi_sys::{
     MIDIPacket, MIDIPacketList, MIDIPacketListAdd, MIDIPacketListInit, MIDIPacketNext,
 };
 
-use crate::events::Storage;
+use crate::events::{Storage, DEFAULT_INLINE_WORDS};
+use crate::time::durations_from_host_times;
+use crate::{ChannelMode, MpeZone};
 
 pub use crate::events::Timestamp;
 
+/// The maximum size of a packet list that can be sent at once (see [MIDISend](https://developer.apple.com/documentation/coremidi/1495289-midisend)).
+pub(crate) const MAX_PACKET_LIST_SIZE: usize = 65536;
+
 /// A [list of MIDI events](https://developer.apple.com/documentation/coremidi/midipacketlist) being received from, or being sent to, one endpoint.
 ///
 pub struct PacketList(MIDIPacketList);
@@ -38,6 +47,7 @@ impl PacketList {
 
     /// Get an iterator for the packets in the list.
     ///
+    #[inline]
     pub fn iter(&self) -> PacketListIterator {
         PacketListIterator {
             count: self.len(),
@@ -45,6 +55,89 @@ impl PacketList {
             _phantom: PhantomData,
         }
     }
+
+    /// Get the timestamps of the packets converted into durations (see [duration_from_host_time](crate::duration_from_host_time)),
+    /// reading the host timebase only once for the whole list.
+    ///
+    pub fn durations(&self) -> impl Iterator<Item = Duration> + '_ {
+        durations_from_host_times(self.iter().map(|packet| packet.timestamp()))
+    }
+
+    /// Get a view of the list that allows to access the packets by index.
+    ///
+    pub fn indexed(&self) -> IndexedPacketList<'_> {
+        IndexedPacketList::new(self)
+    }
+}
+
+/// A view of a [PacketList] giving random access to its packets.
+///
+/// The packets of a list need to be traversed to find one of them, as they have different sizes.
+/// This view records where each packet is on the first access, so inspecting a large list
+/// (for example with long sysex messages) several times doesn't need to traverse it again.
+///
+/// ```
+/// let buffer = coremidi::PacketBuffer::new(0, &[0x90, 0x40, 0x7f]);
+/// let packets = buffer.indexed();
+/// assert_eq!(packets.get(0).map(|packet| packet.data()), Some(&[0x90, 0x40, 0x7f][..]));
+/// assert!(packets.get(1).is_none());
+/// ```
+///
+pub struct IndexedPacketList<'a> {
+    list: &'a PacketList,
+    packets: OnceCell<Vec<*const MIDIPacket>>,
+}
+
+impl<'a> IndexedPacketList<'a> {
+    pub fn new(list: &'a PacketList) -> Self {
+        Self {
+            list,
+            packets: OnceCell::new(),
+        }
+    }
+
+    /// Check if the packet list is empty.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Get the number of packets in the list.
+    ///
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Get the packet at a given index.
+    ///
+    pub fn get(&self, index: usize) -> Option<&'a Packet> {
+        self.packets()
+            .get(index)
+            .map(|packet_ptr| unsafe { &*(*packet_ptr as *const Packet) })
+    }
+
+    /// Get an iterator for the packets in the list.
+    ///
+    pub fn iter(&self) -> impl Iterator<Item = &'a Packet> + '_ {
+        self.packets()
+            .iter()
+            .map(|packet_ptr| unsafe { &*(*packet_ptr as *const Packet) })
+    }
+
+    /// Get the underlying packet list.
+    ///
+    pub fn as_packet_list(&self) -> &'a PacketList {
+        self.list
+    }
+
+    fn packets(&self) -> &[*const MIDIPacket] {
+        self.packets.get_or_init(|| {
+            self.list
+                .iter()
+                .map(|packet| packet as *const Packet as *const MIDIPacket)
+                .collect()
+        })
+    }
 }
 
 impl fmt::Debug for PacketList {
@@ -86,10 +179,13 @@ pub struct PacketListIterator<'a> {
 impl<'a> Iterator for PacketListIterator<'a> {
     type Item = &'a Packet;
 
+    #[inline]
     fn next(&mut self) -> Option<&'a Packet> {
         if self.count > 0 {
             let packet = unsafe { &*(self.packet_ptr as *const Packet) };
             self.count -= 1;
+            // This is not an FFI call, coremidi-sys implements it as an inlined function,
+            // following the alignment rules of the official SDK (4 bytes on ARM, unaligned otherwise).
             self.packet_ptr = unsafe { MIDIPacketNext(self.packet_ptr) };
             Some(packet)
         } else {
@@ -106,6 +202,7 @@ pub struct Packet(MIDIPacket);
 impl Packet {
     /// Get the packet timestamp.
     ///
+    #[inline]
     pub fn timestamp(&self) -> Timestamp {
         self.0.timeStamp as Timestamp
     }
@@ -118,6 +215,7 @@ impl Packet {
     /// let data: Vec<u8> = packet_list.iter().map(|packet| packet.data().to_vec()).flatten().collect();
     /// assert_eq!(data, vec![0x90, 0x40, 0x7f])
     /// ```
+    #[inline]
     pub fn data(&self) -> &[u8] {
         let data_ptr = self.0.data.as_ptr();
         let data_len = self.0.length as usize;
@@ -166,16 +264,14 @@ impl fmt::Display for Packet {
 /// while a `PacketBuffer` is a mutable structure that allows to build a `PacketList` by adding packets.
 /// It dereferences to a `PacketList`, so it can be used whenever a `PacketList` is needed.
 ///
-pub struct PacketBuffer {
-    storage: Storage,
+/// Up to `N` words are stored inline (see [EventBuffer](crate::EventBuffer) and [DEFAULT_INLINE_WORDS]).
+///
+pub struct PacketBuffer<const N: usize = DEFAULT_INLINE_WORDS> {
+    storage: Storage<N>,
     current_packet_offset: usize,
 }
 
 impl PacketBuffer {
-    const PACKET_LIST_HEADER_SIZE: usize = 4; // MIDIPacketList::numPackets: UInt32
-    const PACKET_HEADER_SIZE: usize = 8 +     // MIDIPacket::timeStamp: MIDITimeStamp/UInt64
-            2; // MIDIPacket::length: UInt16
-
     /// Create a `PacketBuffer` with a single packet containing the provided timestamp and data.
     ///
     /// According to the official documentation for CoreMIDI, the timestamp represents
@@ -218,15 +314,37 @@ impl PacketBuffer {
     /// Create an empty `PacketBuffer` with no packets.
     ///
     /// Example on how to create an empty `PacketBuffer`
-    /// with a capacity for 128 bytes in total (including headers):
+    /// with a capacity for 1024 bytes in total (including headers):
     ///
     /// ```
-    /// let buffer = coremidi::PacketBuffer::with_capacity(128);
+    /// let buffer = coremidi::PacketBuffer::with_capacity(1024);
     /// assert_eq!(buffer.len(), 0);
-    /// assert_eq!(buffer.capacity(), 128);
+    /// assert_eq!(buffer.capacity(), 1024);
     /// ```
     pub fn with_capacity(capacity: usize) -> Self {
-        let capacity = std::cmp::max(capacity, Storage::INLINE_SIZE);
+        Self::init(capacity)
+    }
+}
+
+impl<const N: usize> PacketBuffer<N> {
+    const PACKET_LIST_HEADER_SIZE: usize = 4; // MIDIPacketList::numPackets: UInt32
+    const PACKET_HEADER_SIZE: usize = 8 +     // MIDIPacket::timeStamp: MIDITimeStamp/UInt64
+            2; // MIDIPacket::length: UInt16
+
+    /// Create an empty `PacketBuffer` using only its inline storage of `N` words.
+    ///
+    /// ```
+    /// let mut buffer = coremidi::PacketBuffer::<128>::with_inline_storage();
+    /// buffer.push_data(0, &[0x90, 0x3c, 0x7f]).push_data(10, &[0x80, 0x3c, 0x00]);
+    /// assert_eq!(buffer.len(), 2);
+    /// assert_eq!(buffer.capacity(), 128 * 4);
+    /// ```
+    pub fn with_inline_storage() -> Self {
+        Self::init(Storage::<N>::INLINE_SIZE)
+    }
+
+    fn init(capacity: usize) -> Self {
+        let capacity = std::cmp::max(capacity, Storage::<N>::INLINE_SIZE);
         let mut storage = Storage::with_capacity(capacity);
         let packet_list_ptr = unsafe { storage.as_mut_ptr::<MIDIPacketList>() };
         let current_packet_ptr = unsafe { MIDIPacketListInit(packet_list_ptr) };
@@ -262,9 +380,21 @@ impl PacketBuffer {
     /// let repr = format!("{}", &chord as &coremidi::PacketList);
     /// assert_eq!(repr, "PacketList(len=1)\n  0000000000000000: 90 3c 7f 90 40 7f");
     /// ```
+    #[inline]
     pub fn push_data(&mut self, timestamp: Timestamp, data: &[u8]) -> &mut Self {
-        self.ensure_capacity(data.len());
+        let capacity = self.ensure_capacity(data.len());
+
+        if !self.push_data_fast(timestamp, data) {
+            self.push_data_native(timestamp, data, capacity);
+        }
 
+        self
+    }
+
+    /// Add the data through `MIDIPacketListAdd`, for the cases not covered by [Self::push_data_fast].
+    #[cold]
+    #[inline(never)]
+    fn push_data_native(&mut self, timestamp: Timestamp, data: &[u8], capacity: usize) {
         let packet_list_ptr = unsafe { self.storage.as_mut_ptr::<MIDIPacketList>() };
         let current_packet_ptr = unsafe {
             self.storage.as_ptr::<u8>().add(self.current_packet_offset) as *mut MIDIPacket
@@ -273,7 +403,7 @@ impl PacketBuffer {
         let current_packet_ptr = unsafe {
             MIDIPacketListAdd(
                 packet_list_ptr,
-                self.storage.capacity() as u64,
+                capacity as u64,
                 current_packet_ptr,
                 timestamp,
                 data.len() as u64,
@@ -284,8 +414,330 @@ impl PacketBuffer {
         self.current_packet_offset = unsafe {
             (current_packet_ptr as *const u8).offset_from(packet_list_ptr as *const u8) as usize
         };
+    }
 
-        self
+    /// Add the data writing it directly into the buffer, for the common case of channel messages
+    /// with a timestamp that is not smaller than the previous one.
+    /// It does the same as `MIDIPacketListAdd` would do, but avoiding the FFI call and the byte by byte copies.
+    ///
+    /// It returns false, without modifying the buffer, when the native implementation needs to be used.
+    /// The capacity for the data must have been ensured already.
+    #[inline]
+    fn push_data_fast(&mut self, timestamp: Timestamp, data: &[u8]) -> bool {
+        if data.len() > u16::MAX as usize || !is_plain_message(data) {
+            return false;
+        }
+
+        let packet_list_ptr = unsafe { self.storage.as_mut_ptr::<MIDIPacketList>() };
+        let current_packet_ptr = unsafe {
+            (packet_list_ptr as *mut u8).add(self.current_packet_offset) as *mut MIDIPacket
+        };
+
+        let packet_ptr = if self.as_ref().is_empty() {
+            current_packet_ptr
+        } else {
+            let current_packet = unsafe { &*(current_packet_ptr as *const Packet) };
+            if timestamp < current_packet.timestamp() {
+                return false;
+            }
+            if timestamp == current_packet.timestamp() {
+                if !is_plain_message(current_packet.data()) {
+                    return false;
+                }
+                // Merge with the current packet
+                let length = current_packet.data().len();
+                if length + data.len() > u16::MAX as usize {
+                    return false;
+                }
+                unsafe {
+                    let data_ptr = ptr::addr_of_mut!((*current_packet_ptr).data) as *mut u8;
+                    ptr::copy_nonoverlapping(data.as_ptr(), data_ptr.add(length), data.len());
+                    ptr::addr_of_mut!((*current_packet_ptr).length)
+                        .write_unaligned((length + data.len()) as u16);
+                }
+                return true;
+            }
+            unsafe { MIDIPacketNext(current_packet_ptr) as *mut MIDIPacket }
+        };
+
+        unsafe {
+            ptr::addr_of_mut!((*packet_ptr).timeStamp).write_unaligned(timestamp);
+            ptr::addr_of_mut!((*packet_ptr).length).write_unaligned(data.len() as u16);
+            let data_ptr = ptr::addr_of_mut!((*packet_ptr).data) as *mut u8;
+            ptr::copy_nonoverlapping(data.as_ptr(), data_ptr, data.len());
+            let num_packets_ptr = ptr::addr_of_mut!((*packet_list_ptr).numPackets);
+            num_packets_ptr.write_unaligned(num_packets_ptr.read_unaligned() + 1);
+        }
+
+        self.current_packet_offset =
+            unsafe { (packet_ptr as *const u8).offset_from(packet_list_ptr as *const u8) as usize };
+
+        true
+    }
+
+    /// Add a Note On message. The channel goes from 0 to 15, and the note and velocity from 0 to 127
+    /// (the bits out of range are ignored, as for the rest of the typed messages).
+    ///
+    /// ```
+    /// let mut buffer = coremidi::PacketBuffer::with_capacity(0);
+    /// buffer.push_note_on(0, 1, 64, 127);
+    /// assert_eq!(buffer.iter().next().unwrap().data(), &[0x91, 64, 127]);
+    /// ```
+    pub fn push_note_on(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    ) -> &mut Self {
+        self.push_channel_message(timestamp, 0x90, channel, &[note, velocity])
+    }
+
+    /// Add a Note Off message.
+    ///
+    pub fn push_note_off(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    ) -> &mut Self {
+        self.push_channel_message(timestamp, 0x80, channel, &[note, velocity])
+    }
+
+    /// Add a Polyphonic Key Pressure (aftertouch) message.
+    ///
+    pub fn push_key_pressure(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        note: u8,
+        pressure: u8,
+    ) -> &mut Self {
+        self.push_channel_message(timestamp, 0xa0, channel, &[note, pressure])
+    }
+
+    /// Add a Control Change message.
+    ///
+    pub fn push_cc(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        controller: u8,
+        value: u8,
+    ) -> &mut Self {
+        self.push_channel_message(timestamp, 0xb0, channel, &[controller, value])
+    }
+
+    /// Add a Program Change message.
+    ///
+    pub fn push_program_change(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        program: u8,
+    ) -> &mut Self {
+        self.push_channel_message(timestamp, 0xc0, channel, &[program])
+    }
+
+    /// Add a Program Change message preceded by the Bank Select messages (CC 0 and CC 32) given,
+    /// all of them with the same timestamp.
+    ///
+    /// ```
+    /// let mut buffer = coremidi::PacketBuffer::with_capacity(0);
+    /// buffer.push_program(0, 0, Some(1), Some(2), 3);
+    /// assert_eq!(buffer.iter().next().unwrap().data(), &[0xb0, 0, 1, 0xb0, 32, 2, 0xc0, 3]);
+    /// ```
+    pub fn push_program(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        bank_msb: Option<u8>,
+        bank_lsb: Option<u8>,
+        program: u8,
+    ) -> &mut Self {
+        if let Some(bank_msb) = bank_msb {
+            self.push_cc(timestamp, channel, 0, bank_msb);
+        }
+        if let Some(bank_lsb) = bank_lsb {
+            self.push_cc(timestamp, channel, 32, bank_lsb);
+        }
+        self.push_program_change(timestamp, channel, program)
+    }
+
+    /// Add a Channel Pressure (aftertouch) message.
+    ///
+    pub fn push_channel_pressure(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        pressure: u8,
+    ) -> &mut Self {
+        self.push_channel_message(timestamp, 0xd0, channel, &[pressure])
+    }
+
+    /// Add a Pitch Bend message, with a 14 bits value where 0x2000 is the center.
+    ///
+    /// ```
+    /// let mut buffer = coremidi::PacketBuffer::with_capacity(0);
+    /// buffer.push_pitch_bend(0, 0, 0x2000);
+    /// assert_eq!(buffer.iter().next().unwrap().data(), &[0xe0, 0x00, 0x40]);
+    /// ```
+    pub fn push_pitch_bend(&mut self, timestamp: Timestamp, channel: u8, value: u16) -> &mut Self {
+        let lsb = (value & 0x7f) as u8;
+        let msb = ((value >> 7) & 0x7f) as u8;
+        self.push_channel_message(timestamp, 0xe0, channel, &[lsb, msb])
+    }
+
+    /// Add a 14 bits value for a controller from 0 to 31, as the pair of Control Change messages
+    /// for its MSB (`controller`) and its LSB (`controller + 32`).
+    ///
+    /// ```
+    /// let mut buffer = coremidi::PacketBuffer::with_capacity(0);
+    /// buffer.push_cc_14bit(0, 0, 7, 0x3fff);
+    /// assert_eq!(buffer.iter().next().unwrap().data(), &[0xb0, 7, 0x7f, 0xb0, 39, 0x7f]);
+    /// ```
+    pub fn push_cc_14bit(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        controller: u8,
+        value: u16,
+    ) -> &mut Self {
+        let controller = controller & 0x1f;
+        self.push_cc(timestamp, channel, controller, (value >> 7) as u8)
+            .push_cc(timestamp, channel, controller + 32, value as u8)
+    }
+
+    /// Add the Control Change sequence setting a Registered Parameter Number (CC 101 and 100)
+    /// and its 14 bits value (CC 6 and 38).
+    ///
+    /// ```
+    /// let mut buffer = coremidi::PacketBuffer::with_capacity(0);
+    /// // Pitch bend sensitivity of 12 semitones
+    /// buffer.push_rpn(0, 0, 0x0000, 12 << 7);
+    /// assert_eq!(
+    ///     buffer.iter().next().unwrap().data(),
+    ///     &[0xb0, 101, 0, 0xb0, 100, 0, 0xb0, 6, 12, 0xb0, 38, 0]
+    /// );
+    /// ```
+    pub fn push_rpn(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        parameter: u16,
+        value: u16,
+    ) -> &mut Self {
+        self.push_parameter(timestamp, channel, 101, parameter, value)
+    }
+
+    /// Add the Control Change sequence setting a Non-Registered Parameter Number (CC 99 and 98)
+    /// and its 14 bits value (CC 6 and 38).
+    ///
+    pub fn push_nrpn(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        parameter: u16,
+        value: u16,
+    ) -> &mut Self {
+        self.push_parameter(timestamp, channel, 99, parameter, value)
+    }
+
+    /// Add a Channel Mode message.
+    ///
+    /// ```
+    /// use coremidi::{ChannelMode, PacketBuffer};
+    /// let mut buffer = PacketBuffer::with_capacity(0);
+    /// buffer
+    ///     .push_channel_mode(0, 0, ChannelMode::LocalControl(false))
+    ///     .push_channel_mode(0, 0, ChannelMode::Mono(1));
+    /// assert_eq!(buffer.iter().next().unwrap().data(), &[0xb0, 122, 0, 0xb0, 126, 1]);
+    /// ```
+    pub fn push_channel_mode(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        mode: ChannelMode,
+    ) -> &mut Self {
+        self.push_cc(timestamp, channel, mode.controller(), mode.value())
+    }
+
+    /// Add the MPE Configuration Message (RPN 6 in the master channel) setting the number of member channels
+    /// of a zone, where 0 disables the zone.
+    ///
+    /// ```
+    /// use coremidi::{MpeZone, PacketBuffer};
+    /// let mut buffer = PacketBuffer::with_capacity(0);
+    /// buffer.push_mpe_configuration(0, MpeZone::Upper, 7);
+    /// assert_eq!(
+    ///     buffer.iter().next().unwrap().data(),
+    ///     &[0xbf, 101, 0, 0xbf, 100, 6, 0xbf, 6, 7, 0xbf, 38, 0]
+    /// );
+    /// ```
+    pub fn push_mpe_configuration(
+        &mut self,
+        timestamp: Timestamp,
+        zone: MpeZone,
+        member_channels: u8,
+    ) -> &mut Self {
+        let member_channels = member_channels.min(15) as u16;
+        self.push_rpn(
+            timestamp,
+            zone.master_channel(),
+            crate::mpe::MPE_CONFIGURATION,
+            member_channels << 7,
+        )
+    }
+
+    fn push_parameter(
+        &mut self,
+        timestamp: Timestamp,
+        channel: u8,
+        parameter_msb_controller: u8,
+        parameter: u16,
+        value: u16,
+    ) -> &mut Self {
+        self.push_cc(
+            timestamp,
+            channel,
+            parameter_msb_controller,
+            (parameter >> 7) as u8,
+        )
+        .push_cc(
+            timestamp,
+            channel,
+            parameter_msb_controller - 1,
+            parameter as u8,
+        )
+        .push_cc(timestamp, channel, 6, (value >> 7) as u8)
+        .push_cc(timestamp, channel, 38, value as u8)
+    }
+
+    fn push_channel_message(
+        &mut self,
+        timestamp: Timestamp,
+        status: u8,
+        channel: u8,
+        data: &[u8],
+    ) -> &mut Self {
+        let mut message = [status | (channel & 0x0f), 0, 0];
+        for (byte, value) in message[1..].iter_mut().zip(data) {
+            *byte = value & 0x7f;
+        }
+        self.push_data(timestamp, &message[..=data.len()])
+    }
+
+    /// Reserve capacity for at least `additional` more bytes to be pushed into the buffer,
+    /// in a new packet, without reallocating.
+    ///
+    /// ```
+    /// let mut buffer = coremidi::PacketBuffer::with_capacity(0);
+    /// buffer.reserve(1024);
+    /// assert!(buffer.capacity() >= 1024);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        self.ensure_capacity(additional);
     }
 
     /// Clears the buffer, removing all packets.
@@ -298,15 +750,23 @@ impl PacketBuffer {
         };
     }
 
-    fn ensure_capacity(&mut self, data_len: usize) {
-        let next_capacity = self.aligned_bytes_len() + Self::PACKET_HEADER_SIZE + data_len;
+    /// Returns the capacity of the storage after making room for the data.
+    #[inline]
+    fn ensure_capacity(&mut self, data_len: usize) -> usize {
+        let next_capacity = self.size_with(data_len);
 
         unsafe {
             // We ensure capacity for the worst case as if there was no merge with the current packet
-            self.storage.ensure_capacity(next_capacity);
+            self.storage.ensure_capacity(next_capacity)
         }
     }
 
+    /// The size of the list after adding `data_len` bytes in a new packet (the worst case).
+    #[inline]
+    pub(crate) fn size_with(&self, data_len: usize) -> usize {
+        self.aligned_bytes_len() + Self::PACKET_HEADER_SIZE + data_len
+    }
+
     #[inline]
     fn aligned_bytes_len(&self) -> usize {
         let storage_start_ptr = unsafe { self.storage.as_ptr::<u8>() };
@@ -325,14 +785,121 @@ impl PacketBuffer {
     }
 }
 
-impl AsRef<PacketList> for PacketBuffer {
+impl<const N: usize> AsRef<PacketList> for PacketBuffer<N> {
     #[inline]
     fn as_ref(&self) -> &PacketList {
         unsafe { &*self.storage.as_ptr::<PacketList>() }
     }
 }
 
-impl Deref for PacketBuffer {
+impl<const N: usize> Deref for PacketBuffer<N> {
+    type Target = PacketList;
+
+    #[inline]
+    fn deref(&self) -> &PacketList {
+        self.as_ref()
+    }
+}
+
+/// Whether the data starts with a channel message, and there are no system messages in it
+/// (sysex, system common or real time), which are the ones with special rules when merging packets.
+#[inline]
+fn is_plain_message(data: &[u8]) -> bool {
+    match data.first() {
+        Some(status) if *status >= 0x80 => data.iter().all(|b| *b < 0xf0),
+        _ => false,
+    }
+}
+
+/// A `PacketList` builder over memory provided by the caller, for example an array on the stack
+/// or a slab allocated up front, so it never allocates.
+///
+/// Unlike a [PacketBuffer], it can not grow, and adding data fails when there is no more room for it.
+///
+/// ```
+/// let mut words = [0u32; 8];
+/// let mut buffer = coremidi::SlicePacketBuffer::new(&mut words);
+/// assert!(buffer.push_data(0, &[0x90, 0x3c, 0x7f]));
+/// assert!(!buffer.push_data(1, &[0xf0; 32]));
+/// assert_eq!(buffer.len(), 1);
+/// ```
+///
+pub struct SlicePacketBuffer<'a> {
+    words: &'a mut [u32],
+    current_packet_offset: usize,
+}
+
+impl<'a> SlicePacketBuffer<'a> {
+    /// Create an empty buffer using `words` as its storage.
+    ///
+    /// Panics if there is no room for the header of the packet list (4 bytes).
+    ///
+    pub fn new(words: &'a mut [u32]) -> Self {
+        assert!(
+            words.len() * 4 >= PacketBuffer::<DEFAULT_INLINE_WORDS>::PACKET_LIST_HEADER_SIZE,
+            "The storage of a SlicePacketBuffer needs to hold the packet list header"
+        );
+        let mut buffer = Self {
+            words,
+            current_packet_offset: 0,
+        };
+        buffer.clear();
+        buffer
+    }
+
+    /// Get the capacity of the storage in bytes.
+    ///
+    pub fn capacity(&self) -> usize {
+        self.words.len() * 4
+    }
+
+    /// Add a new event containing the provided timestamp and data (see [PacketBuffer::push_data]).
+    ///
+    /// It returns `false`, leaving the buffer untouched, when there is not enough room for the data.
+    ///
+    pub fn push_data(&mut self, timestamp: Timestamp, data: &[u8]) -> bool {
+        let packet_list_ptr = self.words.as_mut_ptr() as *mut MIDIPacketList;
+        let current_packet_ptr = unsafe {
+            (packet_list_ptr as *mut u8).add(self.current_packet_offset) as *mut MIDIPacket
+        };
+        let current_packet_ptr = unsafe {
+            MIDIPacketListAdd(
+                packet_list_ptr,
+                self.capacity() as u64,
+                current_packet_ptr,
+                timestamp,
+                data.len() as u64,
+                data.as_ptr(),
+            )
+        };
+        if current_packet_ptr.is_null() {
+            return false;
+        }
+        self.current_packet_offset = unsafe {
+            (current_packet_ptr as *const u8).offset_from(packet_list_ptr as *const u8) as usize
+        };
+        true
+    }
+
+    /// Clears the buffer, removing all packets.
+    ///
+    pub fn clear(&mut self) {
+        let packet_list_ptr = self.words.as_mut_ptr() as *mut MIDIPacketList;
+        let current_packet_ptr = unsafe { MIDIPacketListInit(packet_list_ptr) };
+        self.current_packet_offset = unsafe {
+            (current_packet_ptr as *const u8).offset_from(packet_list_ptr as *const u8) as usize
+        };
+    }
+}
+
+impl<'a> AsRef<PacketList> for SlicePacketBuffer<'a> {
+    #[inline]
+    fn as_ref(&self) -> &PacketList {
+        unsafe { &*(self.words.as_ptr() as *const PacketList) }
+    }
+}
+
+impl<'a> Deref for SlicePacketBuffer<'a> {
     type Target = PacketList;
 
     #[inline]
@@ -374,27 +941,41 @@ mod tests {
         assert_eq!(packet_buf.len(), 4);
     }
 
+    #[test]
+    fn push_data_fast_too_long_for_a_packet() {
+        let mut packet_buf = PacketBuffer::with_capacity(0);
+        let mut data = vec![0x40; u16::MAX as usize + 1];
+        data[0] = 0x90;
+        packet_buf.ensure_capacity(data.len());
+
+        assert!(!packet_buf.push_data_fast(0, &data));
+        assert_eq!(packet_buf.len(), 0);
+    }
+
     #[test]
     fn packet_buffer_empty_with_capacity() {
-        let packet_buf = PacketBuffer::with_capacity(128);
-        assert_eq!(packet_buf.capacity(), 128);
+        let packet_buf = PacketBuffer::with_capacity(1024);
+        assert_eq!(packet_buf.capacity(), 1024);
         assert_eq!(packet_buf.len(), 0);
     }
 
     #[test]
     fn packet_buffer_with_capacity_zero() {
         let packet_buf = PacketBuffer::with_capacity(0);
-        assert_eq!(packet_buf.capacity(), Storage::INLINE_SIZE);
+        assert_eq!(
+            packet_buf.capacity(),
+            Storage::<DEFAULT_INLINE_WORDS>::INLINE_SIZE
+        );
         assert_eq!(packet_buf.len(), 0);
     }
 
     #[test]
     fn packet_buffer_with_capacity() {
-        let mut packet_buf = PacketBuffer::with_capacity(128);
+        let mut packet_buf = PacketBuffer::with_capacity(1024);
         packet_buf.push_data(43, &[0x91u8, 0x40, 0x7f]);
         packet_buf.push_data(44, &[0x80u8, 0x40, 0x7f]);
         packet_buf.push_data(45, &[0x81u8, 0x40, 0x7f]);
-        assert_eq!(packet_buf.capacity(), 128);
+        assert_eq!(packet_buf.capacity(), 1024);
         assert_eq!(packet_buf.len(), 3);
     }
 
@@ -410,6 +991,75 @@ mod tests {
         assert_eq!(packet_buf.len(), 0);
     }
 
+    #[test]
+    fn indexed_packet_list() {
+        let mut sysex = vec![0xF0];
+        sysex.resize(301, 0x01);
+        sysex.push(0xF7);
+        let mut packet_buf = PacketBuffer::new(42, &[0x90u8, 0x40, 0x7f]);
+        packet_buf.push_data(43, &sysex);
+        packet_buf.push_data(44, &[0x80u8, 0x40, 0x7f]);
+
+        let packets = packet_buf.indexed();
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets.get(1).map(|packet| packet.data()), Some(&sysex[..]));
+        assert_eq!(packets.get(2).map(|packet| packet.timestamp()), Some(44));
+        assert!(packets.get(3).is_none());
+        assert_eq!(
+            packets
+                .iter()
+                .map(|packet| packet.timestamp())
+                .collect::<Vec<_>>(),
+            vec![42, 43, 44]
+        );
+    }
+
+    #[test]
+    fn slice_packet_buffer_push_until_full() {
+        let mut words = [0u32; 8];
+        let mut buffer = SlicePacketBuffer::new(&mut words);
+        assert_eq!(buffer.capacity(), 32);
+        assert!(buffer.push_data(42, &[0x90u8, 0x40, 0x7f]));
+        assert!(buffer.push_data(43, &[0x80u8, 0x40, 0x00]));
+        assert!(!buffer.push_data(44, &[0x90u8, 0x41, 0x7f]));
+        assert_eq!(buffer.len(), 2);
+        buffer.clear();
+        assert_eq!(buffer.len(), 0);
+    }
+
+    #[test]
+    fn typed_channel_messages() {
+        let mut buffer = PacketBuffer::with_capacity(0);
+        buffer
+            .push_note_on(0, 0x13, 0x80 | 60, 100)
+            .push_note_off(0, 3, 60, 0)
+            .push_key_pressure(0, 3, 60, 10)
+            .push_cc(0, 3, 7, 127)
+            .push_program_change(0, 3, 5)
+            .push_channel_pressure(0, 3, 20)
+            .push_pitch_bend(0, 3, 0x3fff);
+
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(
+            buffer.iter().next().unwrap().data(),
+            &[
+                0x93, 60, 100, 0x83, 60, 0, 0xa3, 60, 10, 0xb3, 7, 127, 0xc3, 5, 0xd3, 20, 0xe3,
+                0x7f, 0x7f
+            ]
+        );
+    }
+
+    #[test]
+    fn parameter_numbers() {
+        let mut buffer = PacketBuffer::with_capacity(0);
+        buffer.push_nrpn(0, 2, 0x3fff, 0x2001);
+
+        assert_eq!(
+            buffer.iter().next().unwrap().data(),
+            &[0xb2, 99, 0x7f, 0xb2, 98, 0x7f, 0xb2, 6, 0x40, 0xb2, 38, 0x01]
+        );
+    }
+
     #[test]
     fn compare_equal_timestamps() {
         unsafe {
@@ -497,6 +1147,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn compare_plain_messages() {
+        unsafe {
+            compare_packet_list(vec![
+                (42, vec![0x90, 0x40, 0x7f]),
+                (42, vec![0xc0, 0x05]),
+                (43, vec![0xb0, 0x07, 0x64]),
+                (43, vec![0xf8]),
+                (44, vec![0x80, 0x40, 0x00]),
+            ])
+        }
+    }
+
+    #[test]
+    fn compare_plain_messages_around_sysex() {
+        unsafe {
+            compare_packet_list(vec![
+                (42, vec![0x90, 0x40, 0x7f]),
+                (42, vec![0xF0, 0x01, 0x01, 0xF7]),
+                (42, vec![0x90, 0x41, 0x7f]),
+                (42, vec![0x90, 0x42, 0x7f]),
+                (41, vec![0x80, 0x40, 0x00]),
+            ])
+        }
+    }
+
     /// Compares the results of building a PacketList using our PacketBuffer API
     /// and the native API (MIDIPacketListAdd, etc).
     unsafe fn compare_packet_list(packets: Vec<(MIDITimeStamp, Vec<u8>)>) {
@@ -526,27 +1202,12 @@ mod tests {
             packet_buf.push_data(pkt.0, &pkt.1);
         }
 
-        // print buffer contents for debugging purposes
-        let packet_buf_slice = packet_buf.storage.get_slice::<u8>();
-        println!(
-            "native: {:?}",
-            buffer[0..packet_buf_slice.len()]
-                .iter()
-                .map(|b| format!("{:02X}", b))
-                .collect::<Vec<String>>()
-                .join(" ")
-        );
-        println!(
-            "buffer: {:?}",
-            packet_buf_slice
-                .iter()
-                .map(|b| format!("{:02X}", b))
-                .collect::<Vec<String>>()
-                .join(" ")
-        );
-
         let list: &PacketList = &packet_buf;
 
+        // print the lists for debugging purposes
+        println!("native: {:?}", list_native);
+        println!("buffer: {:?}", list);
+
         // check if the contents match
         assert_eq!(
             list_native.len(),
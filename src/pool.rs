@@ -0,0 +1,129 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::PacketBuffer;
+
+/// A pool of [PacketBuffer]s that are recycled instead of being allocated for every send.
+///
+/// The buffers are created up front with the same capacity, and [acquire](PacketBufferPool::acquire)
+/// only allocates when all of them are in use. The buffers go back to the pool, cleared, when the guard is dropped.
+///
+/// ```
+/// let pool = coremidi::PacketBufferPool::new(4, 256);
+/// {
+///     let mut buffer = pool.acquire();
+///     buffer.push_data(0, &[0x90, 0x40, 0x7f]);
+///     assert_eq!(buffer.len(), 1);
+///     assert_eq!(pool.available(), 3);
+/// }
+/// assert_eq!(pool.available(), 4);
+/// ```
+///
+pub struct PacketBufferPool {
+    buffers: Mutex<Vec<PacketBuffer>>,
+    capacity: usize,
+}
+
+impl PacketBufferPool {
+    /// Create a pool with `count` empty buffers of `capacity` bytes each.
+    ///
+    pub fn new(count: usize, capacity: usize) -> Self {
+        let mut buffers = Vec::with_capacity(count);
+        buffers.extend((0..count).map(|_| PacketBuffer::with_capacity(capacity)));
+        Self {
+            buffers: Mutex::new(buffers),
+            capacity,
+        }
+    }
+
+    /// Take an empty buffer from the pool, or create a new one if there is none available.
+    ///
+    pub fn acquire(&self) -> PooledPacketBuffer<'_> {
+        let buffer = self
+            .lock()
+            .pop()
+            .unwrap_or_else(|| PacketBuffer::with_capacity(self.capacity));
+        PooledPacketBuffer {
+            pool: self,
+            buffer: Some(buffer),
+        }
+    }
+
+    /// The number of buffers ready to be acquired without allocating.
+    ///
+    pub fn available(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn release(&self, mut buffer: PacketBuffer) {
+        buffer.clear();
+        self.lock().push(buffer);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<PacketBuffer>> {
+        self.buffers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A [PacketBuffer] borrowed from a [PacketBufferPool], that goes back to the pool when dropped.
+///
+pub struct PooledPacketBuffer<'a> {
+    pool: &'a PacketBufferPool,
+    buffer: Option<PacketBuffer>,
+}
+
+impl<'a> Deref for PooledPacketBuffer<'a> {
+    type Target = PacketBuffer;
+
+    fn deref(&self) -> &PacketBuffer {
+        self.buffer.as_ref().unwrap()
+    }
+}
+
+impl<'a> DerefMut for PooledPacketBuffer<'a> {
+    fn deref_mut(&mut self) -> &mut PacketBuffer {
+        self.buffer.as_mut().unwrap()
+    }
+}
+
+impl<'a> Drop for PooledPacketBuffer<'a> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            self.pool.release(buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pool::PacketBufferPool;
+
+    #[test]
+    fn acquire_and_release() {
+        let pool = PacketBufferPool::new(1, 1024);
+        {
+            let mut buffer = pool.acquire();
+            buffer.push_data(0, &[0x90, 0x40, 0x7f]);
+            assert_eq!(pool.available(), 0);
+        }
+        assert_eq!(pool.available(), 1);
+
+        let buffer = pool.acquire();
+        assert_eq!(buffer.len(), 0);
+        assert_eq!(buffer.capacity(), 1024);
+    }
+
+    #[test]
+    fn acquire_when_exhausted() {
+        let pool = PacketBufferPool::new(0, 1024);
+        {
+            let first = pool.acquire();
+            let second = pool.acquire();
+            assert_eq!(first.capacity(), 1024);
+            assert_eq!(second.capacity(), 1024);
+        }
+        assert_eq!(pool.available(), 2);
+    }
+}
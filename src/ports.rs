@@ -1,53 +1,149 @@
 use core_foundation::base::OSStatus;
 use std::collections::HashMap;
 use std::ffi::c_void;
+use std::fmt;
 use std::ops::Deref;
 use std::ptr;
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
 
 use coremidi_sys::{
-    MIDIObjectRef, MIDIPortConnectSource, MIDIPortDisconnectSource, MIDIPortDispose, MIDIPortRef,
-    MIDISend, MIDISendEventList,
+    MIDIEndpointRef, MIDIObjectRef, MIDIPortConnectSource, MIDIPortDisconnectSource,
+    MIDIPortDispose, MIDIPortRef, MIDIReceived, MIDISend,
 };
 
+use crate::availability::{MIDIReceivedEventList, MIDISendEventList};
 use crate::endpoints::destinations::Destination;
 use crate::endpoints::sources::Source;
+#[cfg(feature = "metrics")]
+use crate::metrics::{MetricsSnapshot, PortMetrics, ReceivedMetrics};
 use crate::object::Object;
-use crate::packets::PacketList;
-use crate::{EventBuffer, EventList, PacketBuffer};
+use crate::packets::{PacketList, MAX_PACKET_LIST_SIZE};
+use crate::{
+    host_time_from_duration, host_time_now, BooleanProperty, ChannelMode, EventBuffer, EventList,
+    PacketBuffer, Properties, Protocol, ResetKind, Timestamp,
+};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// The lists of packets that can be sent through an [OutputPort], or received from a [VirtualSource](crate::VirtualSource).
+///
+/// It is implemented for [PacketList] and [EventList] (and their buffers), so whether to call
+/// the CoreMIDI functions for packet lists or event lists is decided at compile time.
+///
+pub trait Packets: private::Sealed {
+    #[doc(hidden)]
+    fn send(&self, port: MIDIPortRef, destination: MIDIEndpointRef) -> OSStatus;
 
-pub enum Packets<'a> {
-    BorrowedPacketList(&'a PacketList),
-    BorrowedEventList(&'a EventList),
-    OwnedEventBuffer(EventBuffer),
+    #[doc(hidden)]
+    fn received(&self, source: MIDIEndpointRef) -> OSStatus;
+
+    /// The number of packets and bytes in the list.
+    #[doc(hidden)]
+    #[cfg(feature = "metrics")]
+    fn count(&self) -> (usize, usize);
 }
 
-impl<'a> From<&'a PacketList> for Packets<'a> {
-    fn from(packet_list: &'a PacketList) -> Self {
-        Self::BorrowedPacketList(packet_list)
+impl private::Sealed for PacketList {}
+
+impl Packets for PacketList {
+    #[inline]
+    fn send(&self, port: MIDIPortRef, destination: MIDIEndpointRef) -> OSStatus {
+        unsafe { MIDISend(port, destination, self.as_ptr()) }
+    }
+
+    #[inline]
+    fn received(&self, source: MIDIEndpointRef) -> OSStatus {
+        unsafe { MIDIReceived(source, self.as_ptr()) }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count(&self) -> (usize, usize) {
+        self.iter().fold((0, 0), |(packets, bytes), packet| {
+            (packets + 1, bytes + packet.data().len())
+        })
     }
 }
 
-impl<'a> From<&'a PacketBuffer> for Packets<'a> {
-    fn from(packet_buffer: &'a PacketBuffer) -> Self {
-        Self::BorrowedPacketList(&*packet_buffer)
+impl private::Sealed for EventList {}
+
+impl Packets for EventList {
+    #[inline]
+    fn send(&self, port: MIDIPortRef, destination: MIDIEndpointRef) -> OSStatus {
+        unsafe { MIDISendEventList(port, destination, self.as_ptr()) }
+    }
+
+    #[inline]
+    fn received(&self, source: MIDIEndpointRef) -> OSStatus {
+        unsafe { MIDIReceivedEventList(source, self.as_ptr()) }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count(&self) -> (usize, usize) {
+        self.iter().fold((0, 0), |(packets, bytes), packet| {
+            (packets + 1, bytes + packet.data().len() * 4)
+        })
     }
 }
 
-impl<'a> From<&'a EventList> for Packets<'a> {
-    fn from(event_list: &'a EventList) -> Self {
-        Self::BorrowedEventList(event_list)
+impl<const N: usize> private::Sealed for PacketBuffer<N> {}
+
+impl<const N: usize> Packets for PacketBuffer<N> {
+    #[inline]
+    fn send(&self, port: MIDIPortRef, destination: MIDIEndpointRef) -> OSStatus {
+        (**self).send(port, destination)
+    }
+
+    #[inline]
+    fn received(&self, source: MIDIEndpointRef) -> OSStatus {
+        (**self).received(source)
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count(&self) -> (usize, usize) {
+        (**self).count()
     }
 }
 
-impl<'a> From<&'a EventBuffer> for Packets<'a> {
-    fn from(event_buffer: &'a EventBuffer) -> Self {
-        Self::BorrowedEventList(&*event_buffer)
+impl<const N: usize> private::Sealed for EventBuffer<N> {}
+
+impl<const N: usize> Packets for EventBuffer<N> {
+    #[inline]
+    fn send(&self, port: MIDIPortRef, destination: MIDIEndpointRef) -> OSStatus {
+        (**self).send(port, destination)
+    }
+
+    #[inline]
+    fn received(&self, source: MIDIEndpointRef) -> OSStatus {
+        (**self).received(source)
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count(&self) -> (usize, usize) {
+        (**self).count()
     }
 }
 
-impl<'a> From<EventBuffer> for Packets<'a> {
-    fn from(event_buffer: EventBuffer) -> Self {
-        Self::OwnedEventBuffer(event_buffer)
+impl<P: Packets + ?Sized> private::Sealed for &P {}
+
+impl<P: Packets + ?Sized> Packets for &P {
+    #[inline]
+    fn send(&self, port: MIDIPortRef, destination: MIDIEndpointRef) -> OSStatus {
+        (**self).send(port, destination)
+    }
+
+    #[inline]
+    fn received(&self, source: MIDIEndpointRef) -> OSStatus {
+        (**self).received(source)
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count(&self) -> (usize, usize) {
+        (**self).count()
     }
 }
 
@@ -95,15 +191,21 @@ impl Drop for Port {
 /// let events = EventBuffer::new(Protocol::Midi10).with_packet(0, &[0x2090407f]);
 /// output_port.send(&destination, &events).unwrap();
 /// ```
-#[derive(Debug)]
 pub struct OutputPort {
     pub(crate) port: Port,
+    // Reused by the methods building the packet lists themselves
+    buffer: Mutex<PacketBuffer>,
+    #[cfg(feature = "metrics")]
+    metrics: PortMetrics,
 }
 
 impl OutputPort {
     pub(crate) fn new(port_ref: MIDIPortRef) -> Self {
         Self {
             port: Port::new(port_ref),
+            buffer: Mutex::new(PacketBuffer::with_capacity(0)),
+            #[cfg(feature = "metrics")]
+            metrics: PortMetrics::default(),
         }
     }
 
@@ -111,39 +213,304 @@ impl OutputPort {
     /// See [MIDISendEventList](https://developer.apple.com/documentation/coremidi/3566494-midisendeventlist)
     /// See [MIDISend](https://developer.apple.com/documentation/coremidi/1495289-midisend).
     ///
-    pub fn send<'a, P>(&self, destination: &Destination, packets: P) -> Result<(), OSStatus>
+    pub fn send<P>(&self, destination: &Destination, packets: P) -> Result<(), OSStatus>
     where
-        P: Into<Packets<'a>>,
+        P: Packets,
     {
-        let status = match packets.into() {
-            Packets::BorrowedPacketList(packet_list) => unsafe {
-                MIDISend(
-                    self.port.object.0,
-                    destination.endpoint.object.0,
-                    packet_list.as_ptr(),
-                )
-            },
-            Packets::BorrowedEventList(event_list) => unsafe {
-                MIDISendEventList(
-                    self.port.object.0,
-                    destination.endpoint.object.0,
-                    event_list.as_ptr(),
-                )
-            },
-            Packets::OwnedEventBuffer(event_buffer) => unsafe {
-                MIDISendEventList(
-                    self.port.object.0,
-                    destination.endpoint.object.0,
-                    event_buffer.as_ptr(),
-                )
-            },
-        };
+        let status = packets.send(self.port.object.0, destination.endpoint.object.0);
         if status == 0 {
+            #[cfg(feature = "metrics")]
+            {
+                let (packets, bytes) = packets.count();
+                self.metrics.record_sent(packets, bytes);
+            }
             Ok(())
         } else {
             Err(status)
         }
     }
+
+    /// The number of packets and bytes sent through this port so far.
+    ///
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Send several lists of packets to a destination, coalescing them into as few calls to `MIDISend` as possible.
+    ///
+    /// The packets are sent in order, and a new call is made whenever the size limit of a packet list would be exceeded,
+    /// or the timestamps go backwards. This is useful when sending many tiny lists of events at once.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Client, Destination, PacketBuffer};
+    /// let client = Client::new("example-client").unwrap();
+    /// let output_port = client.output_port("example-port").unwrap();
+    /// let destination = Destination::from_index(0).unwrap();
+    /// let notes: Vec<PacketBuffer> = (0..100)
+    ///     .map(|timestamp| PacketBuffer::new(timestamp, &[0x90, 0x40, 0x7f]))
+    ///     .collect();
+    /// output_port.send_batch(&destination, notes.iter().map(|note| &**note)).unwrap();
+    /// ```
+    pub fn send_batch<'a, I>(
+        &self,
+        destination: &Destination,
+        packet_lists: I,
+    ) -> Result<(), OSStatus>
+    where
+        I: IntoIterator<Item = &'a PacketList>,
+    {
+        let packets = packet_lists.into_iter().flat_map(|packet_list| {
+            packet_list
+                .iter()
+                .map(|packet| (packet.timestamp(), packet.data()))
+        });
+        self.send_iter(destination, packets)
+    }
+
+    /// Send the events produced by an iterator, building the packet lists in a buffer owned by the port,
+    /// so there is no need to build a [PacketBuffer] just to send some events and forget about them.
+    ///
+    /// As with [OutputPort::send_batch], the events are sent in as few calls to `MIDISend` as possible.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Client, Destination};
+    /// let client = Client::new("example-client").unwrap();
+    /// let output_port = client.output_port("example-port").unwrap();
+    /// let destination = Destination::from_index(0).unwrap();
+    /// let chord = [[0x90, 0x3c, 0x7f], [0x90, 0x40, 0x7f], [0x90, 0x43, 0x7f]];
+    /// output_port.send_iter(&destination, chord.iter().map(|note| (0, &note[..]))).unwrap();
+    /// ```
+    pub fn send_iter<'a, I>(&self, destination: &Destination, events: I) -> Result<(), OSStatus>
+    where
+        I: IntoIterator<Item = (Timestamp, &'a [u8])>,
+    {
+        let mut buffer = self
+            .buffer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        coalesce_packets(&mut buffer, events, MAX_PACKET_LIST_SIZE, |packet_list| {
+            self.send(destination, packet_list)
+        })
+    }
+
+    /// Select a program in a bank: send the Bank Select (CC 0 and CC 32) and Program Change messages
+    /// in a single packet.
+    ///
+    /// The Bank Select messages that the destination tells it doesn't receive
+    /// (see [Properties::receives_bank_select_msb] and [Properties::receives_bank_select_lsb]) are not sent.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Client, Destination};
+    /// let client = Client::new("example-client").unwrap();
+    /// let output_port = client.output_port("example-port").unwrap();
+    /// let destination = Destination::from_index(0).unwrap();
+    /// output_port.send_program(&destination, 0, 0, 1, 42).unwrap();
+    /// ```
+    pub fn send_program(
+        &self,
+        destination: &Destination,
+        channel: u8,
+        bank_msb: u8,
+        bank_lsb: u8,
+        program: u8,
+    ) -> Result<(), OSStatus> {
+        // The properties are optional, so the Bank Select is sent unless told otherwise
+        let receives =
+            |property: BooleanProperty| destination.get_property(&property).unwrap_or(true);
+        let bank_msb = Some(bank_msb).filter(|_| receives(Properties::receives_bank_select_msb()));
+        let bank_lsb = Some(bank_lsb).filter(|_| receives(Properties::receives_bank_select_lsb()));
+        let mut buffer = self
+            .buffer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        buffer.clear();
+        buffer.push_program(0, channel, bank_msb, bank_lsb, program);
+        self.send(destination, &*buffer)
+    }
+
+    /// Play a note: send a Note On now, and its Note Off `duration` later.
+    ///
+    /// Both are sent at once, with the Note Off scheduled in host time, so there is no need
+    /// to wait (nor to keep a thread around) to stop the note.
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    /// use coremidi::{Client, Destination};
+    /// let client = Client::new("example-client").unwrap();
+    /// let output_port = client.output_port("example-port").unwrap();
+    /// let destination = Destination::from_index(0).unwrap();
+    /// output_port.play_note(&destination, 0, 64, 127, Duration::from_millis(500)).unwrap();
+    /// ```
+    pub fn play_note(
+        &self,
+        destination: &Destination,
+        channel: u8,
+        note: u8,
+        velocity: u8,
+        duration: Duration,
+    ) -> Result<(), OSStatus> {
+        let mut buffer = self
+            .buffer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = host_time_now();
+        buffer.clear();
+        buffer
+            .push_note_on(now, channel, note, velocity)
+            .push_note_off(now + host_time_from_duration(duration), channel, note, 0);
+        self.send(destination, &*buffer)
+    }
+
+    /// Send a Channel Mode message, like turning Local Control off or switching to Mono mode.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{ChannelMode, Client, Destination};
+    /// let client = Client::new("example-client").unwrap();
+    /// let output_port = client.output_port("example-port").unwrap();
+    /// let destination = Destination::from_index(0).unwrap();
+    /// output_port.send_channel_mode(&destination, 0, ChannelMode::OmniOff).unwrap();
+    /// output_port.send_channel_mode(&destination, 0, ChannelMode::Poly).unwrap();
+    /// ```
+    pub fn send_channel_mode(
+        &self,
+        destination: &Destination,
+        channel: u8,
+        mode: ChannelMode,
+    ) -> Result<(), OSStatus> {
+        let mut buffer = self
+            .buffer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        buffer.clear();
+        buffer.push_channel_mode(0, channel, mode);
+        self.send(destination, &*buffer)
+    }
+
+    /// Reset a sound module with one of the standard GM, GS or XG messages, as usually done before
+    /// setting it up for a song.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Client, Destination, ResetKind};
+    /// let client = Client::new("example-client").unwrap();
+    /// let output_port = client.output_port("example-port").unwrap();
+    /// let destination = Destination::from_index(0).unwrap();
+    /// output_port.send_reset(&destination, ResetKind::GeneralMidi).unwrap();
+    /// ```
+    pub fn send_reset(&self, destination: &Destination, kind: ResetKind) -> Result<(), OSStatus> {
+        let mut buffer = self
+            .buffer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        buffer.clear();
+        buffer.push_data(0, kind.sysex());
+        self.send(destination, &*buffer)
+    }
+
+    /// Silence a destination (the panic button): send All Sound Off, Sustain off and All Notes Off
+    /// on all the 16 channels.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Client, Destination};
+    /// let client = Client::new("example-client").unwrap();
+    /// let output_port = client.output_port("example-port").unwrap();
+    /// let destination = Destination::from_index(0).unwrap();
+    /// output_port.panic(&destination).unwrap();
+    /// ```
+    pub fn panic(&self, destination: &Destination) -> Result<(), OSStatus> {
+        let mut buffer = self
+            .buffer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        buffer.clear();
+        push_panic_packets(&mut buffer);
+        self.send(destination, &*buffer)
+    }
+
+    /// Like [OutputPort::panic], but sending the messages as Universal MIDI Packets of a protocol,
+    /// for the destinations using the MIDI 2.0 protocol.
+    ///
+    pub fn panic_with_protocol(
+        &self,
+        destination: &Destination,
+        protocol: Protocol,
+    ) -> Result<(), OSStatus> {
+        self.send(destination, panic_events(protocol))
+    }
+}
+
+// In this order, so the notes held by the sustain pedal are released too
+const PANIC_CONTROLLERS: [u8; 3] = [120, 64, 123];
+
+fn push_panic_packets(buffer: &mut PacketBuffer) {
+    for channel in 0..16 {
+        for controller in PANIC_CONTROLLERS {
+            buffer.push_cc(0, channel, controller, 0);
+        }
+    }
+}
+
+fn panic_events(protocol: Protocol) -> EventBuffer {
+    let midi20 = protocol == Protocol::Midi20;
+    let mut events = EventBuffer::new(if midi20 {
+        Protocol::Midi20
+    } else {
+        Protocol::Midi10
+    });
+    let mut words = Vec::with_capacity(PANIC_CONTROLLERS.len() * 2);
+    for channel in 0..16u32 {
+        words.clear();
+        for controller in PANIC_CONTROLLERS {
+            let message = ((0xb0 | channel) << 16) | ((controller as u32) << 8);
+            if midi20 {
+                // The value of MIDI 2.0 Control Changes goes in the second word
+                words.extend_from_slice(&[0x4000_0000 | message, 0]);
+            } else {
+                words.push(0x2000_0000 | message);
+            }
+        }
+        events.push(0, &words);
+    }
+    events
+}
+
+impl fmt::Debug for OutputPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutputPort")
+            .field("port", &self.port)
+            .finish()
+    }
+}
+
+/// Join the events into as few packet lists as possible of at most `max_size` bytes,
+/// keeping the timestamps in order, and call `send` with each of them.
+fn coalesce_packets<'a, I, F>(
+    buffer: &mut PacketBuffer,
+    events: I,
+    max_size: usize,
+    mut send: F,
+) -> Result<(), OSStatus>
+where
+    I: IntoIterator<Item = (Timestamp, &'a [u8])>,
+    F: FnMut(&PacketList) -> Result<(), OSStatus>,
+{
+    // It might have been left with some packets after an error
+    buffer.clear();
+    let mut last_timestamp = 0;
+    for (timestamp, data) in events {
+        let exceeds_size = buffer.size_with(data.len()) > max_size;
+        if !buffer.is_empty() && (timestamp < last_timestamp || exceeds_size) {
+            send(buffer)?;
+            buffer.clear();
+        }
+        buffer.push_data(timestamp, data);
+        last_timestamp = timestamp;
+    }
+    if !buffer.is_empty() {
+        send(buffer)?;
+        buffer.clear();
+    }
+    Ok(())
 }
 
 impl Deref for OutputPort {
@@ -157,18 +524,42 @@ impl Deref for OutputPort {
 #[derive(Debug)]
 pub struct InputPort {
     pub(crate) port: Port,
+    #[cfg(feature = "metrics")]
+    metrics: Arc<PortMetrics>,
 }
 
 impl InputPort {
     pub(crate) fn new(port_ref: MIDIPortRef) -> Self {
         Self {
             port: Port::new(port_ref),
+            #[cfg(feature = "metrics")]
+            metrics: Arc::default(),
         }
     }
 
+    /// Attach the metrics updated by the callback of the port.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn with_metrics(self, metrics: Arc<PortMetrics>) -> Self {
+        Self { metrics, ..self }
+    }
+
+    /// The number of packets and bytes received through this port so far, and their latencies.
+    ///
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
     pub fn connect_source(&self, source: &Source) -> Result<(), OSStatus> {
-        let status =
-            unsafe { MIDIPortConnectSource(self.object.0, source.object.0, ptr::null_mut()) };
+        // The metrics of the source are kept by the port metrics, so they outlive the connection
+        #[cfg(feature = "metrics")]
+        let ref_con = source
+            .unique_id()
+            .map(|unique_id| Arc::as_ptr(&self.metrics.source(unique_id)) as *mut c_void)
+            .unwrap_or(ptr::null_mut());
+        #[cfg(not(feature = "metrics"))]
+        let ref_con = ptr::null_mut();
+        let status = unsafe { MIDIPortConnectSource(self.object.0, source.object.0, ref_con) };
         if status == 0 {
             Ok(())
         } else {
@@ -199,9 +590,9 @@ impl Deref for InputPort {
 /// A simple example to create an input port:
 ///
 /// ```rust,no_run
-/// use coremidi::{Client, Protocol, Source};
+/// use coremidi::{Client, Protocol, Source, UniqueId};
 /// let client = Client::new("example-client").unwrap();
-/// let mut input_port = client.input_port_with_protocol("example-port", Protocol::Midi10, |event_list, context: &mut u32| println!("{:08x}: {:?}", context, event_list)).unwrap();
+/// let mut input_port = client.input_port_with_protocol("example-port", Protocol::Midi10, |event_list, context: &mut UniqueId| println!("{:08x}: {:?}", context, event_list)).unwrap();
 /// let source = Source::from_index(0).unwrap();
 /// let context = source.unique_id().unwrap_or(0);
 /// input_port.connect_source(&source, context);
@@ -209,7 +600,9 @@ impl Deref for InputPort {
 #[derive(Debug)]
 pub struct InputPortWithContext<T> {
     pub(crate) port: Port,
-    pub(crate) contexts: HashMap<MIDIObjectRef, Box<T>>,
+    pub(crate) contexts: HashMap<MIDIObjectRef, Box<Connection<T>>>,
+    #[cfg(feature = "metrics")]
+    metrics: Arc<PortMetrics>,
 }
 
 impl<T> InputPortWithContext<T> {
@@ -217,17 +610,42 @@ impl<T> InputPortWithContext<T> {
         Self {
             port: Port::new(port_ref),
             contexts: HashMap::new(),
+            #[cfg(feature = "metrics")]
+            metrics: Arc::default(),
         }
     }
 
+    /// Attach the metrics updated by the callback of the port.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn with_metrics(self, metrics: Arc<PortMetrics>) -> Self {
+        Self { metrics, ..self }
+    }
+
+    /// The number of events and bytes received through this port so far, and their latencies.
+    ///
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
     pub fn connect_source(&mut self, source: &Source, context: T) -> Result<(), OSStatus> {
-        let mut context = Box::new(context);
-        let context_ptr = context.as_mut() as *mut T;
+        let mut connection = Box::new(Connection {
+            context,
+            #[cfg(feature = "metrics")]
+            metrics: source
+                .unique_id()
+                .map(|unique_id| self.metrics.source(unique_id)),
+        });
+        let connection_ptr = connection.as_mut() as *mut Connection<T>;
         let status = unsafe {
-            MIDIPortConnectSource(self.object.0, source.object.0, context_ptr as *mut c_void)
+            MIDIPortConnectSource(
+                self.object.0,
+                source.object.0,
+                connection_ptr as *mut c_void,
+            )
         };
         if status == 0 {
-            self.contexts.insert(source.object.0, context);
+            self.contexts.insert(source.object.0, connection);
             Ok(())
         } else {
             Err(status)
@@ -245,6 +663,14 @@ impl<T> InputPortWithContext<T> {
     }
 }
 
+/// A source connected to an [InputPortWithContext], which CoreMIDI hands over to its callback.
+#[derive(Debug)]
+pub(crate) struct Connection<T> {
+    pub(crate) context: T,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: Option<Arc<ReceivedMetrics>>,
+}
+
 impl<T> Deref for InputPortWithContext<T> {
     type Target = Port;
 
@@ -252,3 +678,109 @@ impl<T> Deref for InputPortWithContext<T> {
         &self.port
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ports::{coalesce_packets, panic_events, push_panic_packets};
+    use crate::{PacketBuffer, PacketList, Protocol, Timestamp};
+
+    fn sent_timestamps(lists: &[PacketBuffer], max_size: usize) -> Vec<Vec<Timestamp>> {
+        let mut sent = Vec::new();
+        let events = lists.iter().flat_map(|list| {
+            list.iter()
+                .map(|packet| (packet.timestamp(), packet.data()))
+        });
+        coalesce_packets(
+            &mut PacketBuffer::with_capacity(0),
+            events,
+            max_size,
+            |packet_list| {
+                sent.push(
+                    packet_list
+                        .iter()
+                        .map(|packet| packet.timestamp())
+                        .collect(),
+                );
+                Ok(())
+            },
+        )
+        .unwrap();
+        sent
+    }
+
+    #[test]
+    fn coalesce_in_a_single_list() {
+        let lists: Vec<PacketBuffer> = (0..10)
+            .map(|timestamp| PacketBuffer::new(timestamp, &[0x90, 0x40, 0x7f]))
+            .collect();
+
+        assert_eq!(
+            sent_timestamps(&lists, 65536),
+            vec![(0..10).collect::<Vec<Timestamp>>()]
+        );
+    }
+
+    #[test]
+    fn coalesce_respecting_the_size_limit() {
+        let lists: Vec<PacketBuffer> = (0..4)
+            .map(|timestamp| PacketBuffer::new(timestamp, &[0x90, 0x40, 0x7f]))
+            .collect();
+
+        // The list header (4 bytes) and two packets with 3 bytes (13 bytes each, 16 when aligned)
+        assert_eq!(sent_timestamps(&lists, 36), vec![vec![0, 1], vec![2, 3]]);
+    }
+
+    #[test]
+    fn coalesce_with_timestamps_going_backwards() {
+        let lists = vec![
+            PacketBuffer::new(10, &[0x90, 0x40, 0x7f]),
+            PacketBuffer::new(20, &[0x90, 0x41, 0x7f]),
+            PacketBuffer::new(5, &[0x80, 0x40, 0x00]),
+        ];
+
+        assert_eq!(sent_timestamps(&lists, 65536), vec![vec![10, 20], vec![5]]);
+    }
+
+    #[test]
+    fn coalesce_stops_on_error() {
+        let events: [(Timestamp, &[u8]); 2] = [(10, &[0x90, 0x40, 0x7f]), (5, &[0x80, 0x40, 0x00])];
+        let mut buffer = PacketBuffer::with_capacity(0);
+        let mut calls = 0;
+        let result = coalesce_packets(&mut buffer, events, 65536, |_: &PacketList| {
+            calls += 1;
+            Err(-1)
+        });
+
+        assert_eq!(result, Err(-1));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn panic_messages() {
+        let mut packets = PacketBuffer::with_capacity(0);
+        push_panic_packets(&mut packets);
+        let data: Vec<u8> = packets
+            .iter()
+            .flat_map(|packet| packet.data().to_vec())
+            .collect();
+        assert_eq!(data.len(), 16 * 3 * 3);
+        assert_eq!(&data[..9], &[0xb0, 120, 0, 0xb0, 64, 0, 0xb0, 123, 0]);
+
+        let words = |protocol| -> Vec<u32> {
+            panic_events(protocol)
+                .iter()
+                .flat_map(|packet| packet.data().to_vec())
+                .collect()
+        };
+        let midi20 = words(Protocol::Midi20);
+        assert_eq!(midi20.len(), 16 * 3 * 2);
+        assert_eq!(
+            &midi20[90..],
+            &[0x40bf7800, 0, 0x40bf4000, 0, 0x40bf7b00, 0]
+        );
+        assert_eq!(
+            &words(Protocol::Midi10)[..3],
+            &[0x20b07800, 0x20b04000, 0x20b07b00]
+        );
+    }
+}
@@ -1,18 +1,31 @@
+//! The properties of the MIDI objects.
+//!
+//! Properties are cheap to clone, so they can be created once and reused.
+//! A property created with `new` owns its key, so it stays valid independently of the name passed in,
+//! while a property created with `interned` reuses the CoreMIDI constant key when the name is one of the
+//! well-known properties, and only creates a new key for custom names.
+//! The most common properties are also cached in [property_keys](crate::property_keys).
+
 use core_foundation::{
     base::{CFGetRetainCount, CFIndex, CFTypeRef, OSStatus, TCFType},
+    data::CFData,
     string::{CFString, CFStringRef},
 };
+use once_cell::sync::Lazy;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 
 use coremidi_sys::{
     kMIDIPropertyAdvanceScheduleTimeMuSec, kMIDIPropertyCanRoute, kMIDIPropertyConnectionUniqueID,
     kMIDIPropertyDeviceID, kMIDIPropertyDisplayName, kMIDIPropertyDriverDeviceEditorApp,
-    kMIDIPropertyDriverOwner, kMIDIPropertyDriverVersion, kMIDIPropertyIsBroadcast,
-    kMIDIPropertyIsDrumMachine, kMIDIPropertyIsEffectUnit, kMIDIPropertyIsEmbeddedEntity,
-    kMIDIPropertyIsMixer, kMIDIPropertyIsSampler, kMIDIPropertyManufacturer,
-    kMIDIPropertyMaxReceiveChannels, kMIDIPropertyMaxSysExSpeed, kMIDIPropertyMaxTransmitChannels,
-    kMIDIPropertyModel, kMIDIPropertyName, kMIDIPropertyOffline, kMIDIPropertyPanDisruptsStereo,
-    kMIDIPropertyPrivate, kMIDIPropertyProtocolID, kMIDIPropertyReceiveChannels,
+    kMIDIPropertyDriverOwner, kMIDIPropertyDriverVersion, kMIDIPropertyImage,
+    kMIDIPropertyIsBroadcast, kMIDIPropertyIsDrumMachine, kMIDIPropertyIsEffectUnit,
+    kMIDIPropertyIsEmbeddedEntity, kMIDIPropertyIsMixer, kMIDIPropertyIsSampler,
+    kMIDIPropertyManufacturer, kMIDIPropertyMaxReceiveChannels, kMIDIPropertyMaxSysExSpeed,
+    kMIDIPropertyMaxTransmitChannels, kMIDIPropertyModel, kMIDIPropertyName, kMIDIPropertyOffline,
+    kMIDIPropertyPanDisruptsStereo, kMIDIPropertyPrivate, kMIDIPropertyReceiveChannels,
     kMIDIPropertyReceivesBankSelectLSB, kMIDIPropertyReceivesBankSelectMSB,
     kMIDIPropertyReceivesClock, kMIDIPropertyReceivesMTC, kMIDIPropertyReceivesNotes,
     kMIDIPropertyReceivesProgramChanges, kMIDIPropertySingleRealtimeEntity,
@@ -20,28 +33,133 @@ use coremidi_sys::{
     kMIDIPropertyTransmitChannels, kMIDIPropertyTransmitsBankSelectLSB,
     kMIDIPropertyTransmitsBankSelectMSB, kMIDIPropertyTransmitsClock, kMIDIPropertyTransmitsMTC,
     kMIDIPropertyTransmitsNotes, kMIDIPropertyTransmitsProgramChanges, kMIDIPropertyUniqueID,
-    MIDIObjectGetIntegerProperty, MIDIObjectGetStringProperty, MIDIObjectSetIntegerProperty,
-    MIDIObjectSetStringProperty, SInt32,
+    kMIDIUnknownProperty, kMIDIWrongPropertyType, MIDIObjectGetDataProperty,
+    MIDIObjectGetIntegerProperty, MIDIObjectGetStringProperty, MIDIObjectSetDataProperty,
+    MIDIObjectSetIntegerProperty, MIDIObjectSetStringProperty, SInt32,
 };
 
-use crate::{object::Object, result_from_status, unit_result_from_status};
+use crate::{
+    availability::property_protocol_id,
+    object::{Object, UniqueId},
+    result_from_status, unit_result_from_status,
+};
 
 pub trait PropertyGetter<T> {
     fn value_from(&self, object: &Object) -> Result<T, OSStatus>;
+
+    /// Get the value of the property, or `None` when the object doesn't have it.
+    ///
+    /// Errors other than [kMIDIUnknownProperty](https://developer.apple.com/documentation/coremidi/kmidiunknownproperty)
+    /// are still returned as `Err`.
+    ///
+    fn try_value_from(&self, object: &Object) -> Result<Option<T>, OSStatus> {
+        none_if_unknown(self.value_from(object))
+    }
 }
 
 pub trait PropertySetter<T> {
     fn set_value(&self, object: &Object, value: T) -> Result<(), OSStatus>;
 }
 
+/// Turn an unknown property error into `None`, keeping any other error.
+pub(crate) fn none_if_unknown<T>(result: Result<T, OSStatus>) -> Result<Option<T>, OSStatus> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(status) if status == kMIDIUnknownProperty => Ok(None),
+        Err(status) => Err(status),
+    }
+}
+
+/// A property that can be identified by the name of its key.
+pub trait NamedProperty {
+    fn name(&self) -> String;
+}
+
+/// The keys of the properties defined by CoreMIDI, by name.
+///
+/// They are CoreMIDI constants living for the rest of the program, so they can be reused by any property.
+static WELL_KNOWN_KEYS: Lazy<HashMap<String, PropertyKeyStorage>> = Lazy::new(|| {
+    let constants = unsafe {
+        [
+            kMIDIPropertyName,
+            kMIDIPropertyManufacturer,
+            kMIDIPropertyModel,
+            kMIDIPropertyUniqueID,
+            kMIDIPropertyDeviceID,
+            kMIDIPropertyReceiveChannels,
+            kMIDIPropertyTransmitChannels,
+            kMIDIPropertyMaxSysExSpeed,
+            kMIDIPropertyAdvanceScheduleTimeMuSec,
+            kMIDIPropertyIsEmbeddedEntity,
+            kMIDIPropertyIsBroadcast,
+            kMIDIPropertySingleRealtimeEntity,
+            kMIDIPropertyConnectionUniqueID,
+            kMIDIPropertyOffline,
+            kMIDIPropertyPrivate,
+            kMIDIPropertyDriverOwner,
+            kMIDIPropertyImage,
+            kMIDIPropertyDriverVersion,
+            kMIDIPropertySupportsGeneralMIDI,
+            kMIDIPropertySupportsMMC,
+            kMIDIPropertyCanRoute,
+            kMIDIPropertyReceivesClock,
+            kMIDIPropertyReceivesMTC,
+            kMIDIPropertyReceivesNotes,
+            kMIDIPropertyReceivesProgramChanges,
+            kMIDIPropertyReceivesBankSelectMSB,
+            kMIDIPropertyReceivesBankSelectLSB,
+            kMIDIPropertyTransmitsClock,
+            kMIDIPropertyTransmitsMTC,
+            kMIDIPropertyTransmitsNotes,
+            kMIDIPropertyTransmitsProgramChanges,
+            kMIDIPropertyTransmitsBankSelectMSB,
+            kMIDIPropertyTransmitsBankSelectLSB,
+            kMIDIPropertyPanDisruptsStereo,
+            kMIDIPropertyIsSampler,
+            kMIDIPropertyIsDrumMachine,
+            kMIDIPropertyIsMixer,
+            kMIDIPropertyIsEffectUnit,
+            kMIDIPropertyMaxReceiveChannels,
+            kMIDIPropertyMaxTransmitChannels,
+            kMIDIPropertyDriverDeviceEditorApp,
+            kMIDIPropertySupportsShowControl,
+            kMIDIPropertyDisplayName,
+        ]
+    };
+    constants
+        .iter()
+        .copied()
+        .chain(property_protocol_id())
+        .map(|constant| {
+            let key = PropertyKeyStorage::Constant(constant);
+            (key.name(), key)
+        })
+        .collect()
+});
+
+/// Return the key for a property name, reusing the CoreMIDI constant for the well-known properties,
+/// or creating a new one otherwise.
+fn intern_key(name: &str) -> PropertyKeyStorage {
+    WELL_KNOWN_KEYS
+        .get(name)
+        .cloned()
+        .unwrap_or_else(|| PropertyKeyStorage::Owned(CFString::new(name)))
+}
+
 /// Because Property structs can be constructed from strings that have been
 /// passed in from the user or are constants CFStringRefs from CoreMidi, we
 /// need to abstract over how we store their keys.
+#[derive(Clone)]
 enum PropertyKeyStorage {
     Owned(CFString),
     Constant(CFStringRef),
 }
 
+// The keys are immutable CFStrings (either owned or CoreMIDI constants),
+// which are safe to share between threads.
+unsafe impl Send for PropertyKeyStorage {}
+unsafe impl Sync for PropertyKeyStorage {}
+
 impl PropertyKeyStorage {
     /// Return a raw CFStringRef pointing to this property key
     fn as_string_ref(&self) -> CFStringRef {
@@ -51,6 +169,12 @@ impl PropertyKeyStorage {
         }
     }
 
+    /// Return the name of this property key
+    fn name(&self) -> String {
+        let key: CFString = unsafe { TCFType::wrap_under_get_rule(self.as_string_ref()) };
+        key.to_string()
+    }
+
     /// For checking the retain count when debugging
     #[allow(dead_code)]
     fn retain_count(&self) -> CFIndex {
@@ -65,25 +189,32 @@ impl PropertyKeyStorage {
 
 /// A MIDI object property which value is an String
 ///
+#[derive(Clone)]
 pub struct StringProperty(PropertyKeyStorage);
 
 impl StringProperty {
+    /// Create a property for a custom key.
+    ///
     pub fn new(name: &str) -> Self {
         StringProperty(PropertyKeyStorage::Owned(CFString::new(name)))
     }
 
+    /// Create a property for a key, reusing the CoreMIDI constant when it's one of the well-known properties
+    /// (like `name`), and creating a new key otherwise.
+    ///
+    pub fn interned(name: &str) -> Self {
+        StringProperty(intern_key(name))
+    }
+
     /// Note: Should only be used internally with predefined CoreMidi constants,
     /// since it does not bump the retain count of the CFStringRef.
     fn from_constant_string_ref(string_ref: CFStringRef) -> Self {
         StringProperty(PropertyKeyStorage::Constant(string_ref))
     }
-}
 
-impl<T> PropertyGetter<T> for StringProperty
-where
-    T: From<String>,
-{
-    fn value_from(&self, object: &Object) -> Result<T, OSStatus> {
+    /// Get the value of the property as a `CFString`, without converting it into a Rust `String`.
+    ///
+    pub fn cf_value_from(&self, object: &Object) -> Result<CFString, OSStatus> {
         let property_key = self.0.as_string_ref();
         let mut string_ref = MaybeUninit::uninit();
         let status =
@@ -91,37 +222,122 @@ where
         result_from_status(status, || {
             let string_ref = unsafe { string_ref.assume_init() };
             if string_ref.is_null() {
-                return "".to_string().into();
+                return CFString::from_static_string("");
             };
-            let cf_string: CFString = unsafe { TCFType::wrap_under_create_rule(string_ref) };
-            cf_string.to_string().into()
+            unsafe { TCFType::wrap_under_create_rule(string_ref) }
+        })
+    }
+
+    /// Call a closure with the value of the property borrowed as a `&str`.
+    ///
+    /// Whenever CoreFoundation can provide direct access to the UTF-8 contents of the string,
+    /// no allocation happens, which makes it well suited for comparing names in hot paths:
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Properties, Source};
+    /// let source = Source::from_index(0).unwrap();
+    /// let is_iac = Properties::name()
+    ///     .with_value(&source, |name| name.starts_with("IAC"))
+    ///     .unwrap_or(false);
+    /// ```
+    ///
+    pub fn with_value<F, R>(&self, object: &Object, f: F) -> Result<R, OSStatus>
+    where
+        F: FnOnce(&str) -> R,
+    {
+        self.cf_value_from(object).map(|cf_string| {
+            let value: Cow<str> = (&cf_string).into();
+            f(&value)
         })
     }
 }
 
-impl<'a, T> PropertySetter<T> for StringProperty
+impl<T> PropertyGetter<T> for StringProperty
+where
+    T: From<String>,
+{
+    fn value_from(&self, object: &Object) -> Result<T, OSStatus> {
+        self.cf_value_from(object)
+            .map(|cf_string| cf_string.to_string().into())
+    }
+}
+
+impl<T> PropertySetter<T> for StringProperty
 where
-    T: Into<String>,
+    T: IntoCFString,
 {
     fn set_value(&self, object: &Object, value: T) -> Result<(), OSStatus> {
         let property_key = self.0.as_string_ref();
-        let value: String = value.into();
-        let string = CFString::new(&value);
+        let string = value.into_cf_string();
         let string_ref = string.as_concrete_TypeRef();
         let status = unsafe { MIDIObjectSetStringProperty(object.0, property_key, string_ref) };
         unit_result_from_status(status)
     }
 }
 
+/// A value that can be converted into a `CFString` to be set into a [StringProperty].
+///
+/// Values that already are a `CFString` are passed to CoreMIDI without re-encoding them.
+///
+pub trait IntoCFString {
+    fn into_cf_string(self) -> CFString;
+}
+
+impl IntoCFString for &str {
+    fn into_cf_string(self) -> CFString {
+        CFString::new(self)
+    }
+}
+
+impl IntoCFString for String {
+    fn into_cf_string(self) -> CFString {
+        CFString::new(&self)
+    }
+}
+
+impl IntoCFString for &String {
+    fn into_cf_string(self) -> CFString {
+        CFString::new(self)
+    }
+}
+
+impl IntoCFString for Cow<'_, str> {
+    fn into_cf_string(self) -> CFString {
+        CFString::new(&self)
+    }
+}
+
+impl IntoCFString for CFString {
+    fn into_cf_string(self) -> CFString {
+        self
+    }
+}
+
+impl IntoCFString for &CFString {
+    fn into_cf_string(self) -> CFString {
+        self.clone()
+    }
+}
+
 /// A MIDI object property which value is an Integer
 ///
+#[derive(Clone)]
 pub struct IntegerProperty(PropertyKeyStorage);
 
 impl IntegerProperty {
+    /// Create a property for a custom key.
+    ///
     pub fn new(name: &str) -> Self {
         IntegerProperty(PropertyKeyStorage::Owned(CFString::new(name)))
     }
 
+    /// Create a property for a key, reusing the CoreMIDI constant when it's one of the well-known properties
+    /// (like `name`), and creating a new key otherwise.
+    ///
+    pub fn interned(name: &str) -> Self {
+        IntegerProperty(intern_key(name))
+    }
+
     /// Note: Should only be used internally with predefined CoreMidi constants,
     /// since it does not bump the retain count of the CFStringRef.
     fn from_constant_string_ref(string_ref: CFStringRef) -> Self {
@@ -158,13 +374,25 @@ where
 
 /// A MIDI object property which value is a Boolean
 ///
+#[derive(Clone)]
 pub struct BooleanProperty(IntegerProperty);
 
 impl BooleanProperty {
+    /// Create a property for a custom key.
+    ///
+    /// CoreMIDI stores booleans as integers, so this is backed by an [IntegerProperty].
+    ///
     pub fn new(name: &str) -> Self {
         BooleanProperty(IntegerProperty::new(name))
     }
 
+    /// Create a property for a key, reusing the CoreMIDI constant when it's one of the well-known properties
+    /// (like `name`), and creating a new key otherwise.
+    ///
+    pub fn interned(name: &str) -> Self {
+        BooleanProperty(IntegerProperty::interned(name))
+    }
+
     /// Note: Should only be used internally with predefined CoreMidi constants,
     /// since it does not bump the retain count of the CFStringRef.
     fn from_constant_string_ref(string_ref: CFStringRef) -> Self {
@@ -193,6 +421,364 @@ where
     }
 }
 
+/// A MIDI object property which value is raw data
+///
+#[derive(Clone)]
+pub struct DataProperty(PropertyKeyStorage);
+
+impl DataProperty {
+    /// Create a property for a custom key.
+    ///
+    pub fn new(name: &str) -> Self {
+        DataProperty(PropertyKeyStorage::Owned(CFString::new(name)))
+    }
+
+    /// Create a property for a key, reusing the CoreMIDI constant when it's one of the well-known properties
+    /// (like `name`), and creating a new key otherwise.
+    ///
+    pub fn interned(name: &str) -> Self {
+        DataProperty(intern_key(name))
+    }
+}
+
+impl<T> PropertyGetter<T> for DataProperty
+where
+    T: From<Vec<u8>>,
+{
+    fn value_from(&self, object: &Object) -> Result<T, OSStatus> {
+        let property_key = self.0.as_string_ref();
+        let mut data_ref = MaybeUninit::uninit();
+        let status =
+            unsafe { MIDIObjectGetDataProperty(object.0, property_key, data_ref.as_mut_ptr()) };
+        result_from_status(status, || {
+            let data_ref = unsafe { data_ref.assume_init() };
+            if data_ref.is_null() {
+                return Vec::new().into();
+            };
+            let cf_data: CFData = unsafe { TCFType::wrap_under_create_rule(data_ref) };
+            cf_data.bytes().to_vec().into()
+        })
+    }
+}
+
+impl<T> PropertySetter<T> for DataProperty
+where
+    T: Into<Vec<u8>>,
+{
+    fn set_value(&self, object: &Object, value: T) -> Result<(), OSStatus> {
+        let property_key = self.0.as_string_ref();
+        let value: Vec<u8> = value.into();
+        let data = CFData::from_buffer(&value);
+        let status = unsafe {
+            MIDIObjectSetDataProperty(object.0, property_key, data.as_concrete_TypeRef())
+        };
+        unit_result_from_status(status)
+    }
+}
+
+/// The [connection unique ids](https://developer.apple.com/documentation/coremidi/kMIDIPropertyConnectionUniqueID)
+/// of an object, which identify the external devices or endpoints it is connected to.
+///
+/// CoreMIDI stores a single connection as an integer, and several connections as data
+/// containing an array of big-endian ids, so this property reads and writes both forms.
+/// Setting an empty list of ids removes the property.
+///
+#[derive(Clone)]
+pub struct ConnectionUniqueIdsProperty {
+    integer: IntegerProperty,
+    data: DataProperty,
+}
+
+impl ConnectionUniqueIdsProperty {
+    fn new() -> Self {
+        let string_ref = unsafe { kMIDIPropertyConnectionUniqueID };
+        Self {
+            integer: IntegerProperty::from_constant_string_ref(string_ref),
+            data: DataProperty(PropertyKeyStorage::Constant(string_ref)),
+        }
+    }
+}
+
+impl PropertyGetter<Vec<UniqueId>> for ConnectionUniqueIdsProperty {
+    fn value_from(&self, object: &Object) -> Result<Vec<UniqueId>, OSStatus> {
+        match self.integer.value_from(object) {
+            Ok(unique_id) => Ok(vec![unique_id]),
+            Err(status) if status == kMIDIWrongPropertyType => self
+                .data
+                .value_from(object)
+                .map(|data: Vec<u8>| unique_ids_from_data(&data)),
+            Err(status) => Err(status),
+        }
+    }
+}
+
+impl<T> PropertySetter<T> for ConnectionUniqueIdsProperty
+where
+    T: AsRef<[UniqueId]>,
+{
+    fn set_value(&self, object: &Object, value: T) -> Result<(), OSStatus> {
+        match value.as_ref() {
+            [] => object.remove_property(&self.integer.name()),
+            [unique_id] => self.integer.set_value(object, *unique_id),
+            unique_ids => self.data.set_value(object, unique_ids_to_data(unique_ids)),
+        }
+    }
+}
+
+impl NamedProperty for ConnectionUniqueIdsProperty {
+    fn name(&self) -> String {
+        self.integer.name()
+    }
+}
+
+fn unique_ids_from_data(data: &[u8]) -> Vec<UniqueId> {
+    data.chunks_exact(4)
+        .map(|chunk| UniqueId::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+fn unique_ids_to_data(unique_ids: &[UniqueId]) -> Vec<u8> {
+    unique_ids
+        .iter()
+        .flat_map(|unique_id| unique_id.to_be_bytes())
+        .collect()
+}
+
+/// A type that can be stored into a MIDI object property identified by its name.
+///
+/// It allows selecting the right CoreMIDI call for a property at compile time,
+/// and it is implemented for `String`, `i32`, `bool` and `Vec<u8>`.
+/// Custom types can be supported by delegating into any of those:
+///
+/// ```rust,no_run
+/// use coremidi::{Object, PropertyValue};
+///
+/// struct Channels(u16);
+///
+/// impl PropertyValue for Channels {
+///     fn get_property(object: &Object, name: &str) -> Result<Self, i32> {
+///         i32::get_property(object, name).map(|mask| Channels(mask as u16))
+///     }
+///
+///     fn set_property(object: &Object, name: &str, value: Self) -> Result<(), i32> {
+///         i32::set_property(object, name, value.0 as i32)
+///     }
+/// }
+///
+/// let source = coremidi::Source::from_index(0).unwrap();
+/// let channels = source.property::<Channels>("receiveChannels").unwrap();
+/// ```
+///
+pub trait PropertyValue: Sized {
+    fn get_property(object: &Object, name: &str) -> Result<Self, OSStatus>;
+    fn set_property(object: &Object, name: &str, value: Self) -> Result<(), OSStatus>;
+}
+
+impl PropertyValue for String {
+    fn get_property(object: &Object, name: &str) -> Result<Self, OSStatus> {
+        StringProperty::interned(name).value_from(object)
+    }
+
+    fn set_property(object: &Object, name: &str, value: Self) -> Result<(), OSStatus> {
+        StringProperty::interned(name).set_value(object, value)
+    }
+}
+
+impl PropertyValue for i32 {
+    fn get_property(object: &Object, name: &str) -> Result<Self, OSStatus> {
+        IntegerProperty::interned(name).value_from(object)
+    }
+
+    fn set_property(object: &Object, name: &str, value: Self) -> Result<(), OSStatus> {
+        IntegerProperty::interned(name).set_value(object, value)
+    }
+}
+
+impl PropertyValue for bool {
+    fn get_property(object: &Object, name: &str) -> Result<Self, OSStatus> {
+        BooleanProperty::interned(name).value_from(object)
+    }
+
+    fn set_property(object: &Object, name: &str, value: Self) -> Result<(), OSStatus> {
+        BooleanProperty::interned(name).set_value(object, value)
+    }
+}
+
+impl PropertyValue for Vec<u8> {
+    fn get_property(object: &Object, name: &str) -> Result<Self, OSStatus> {
+        DataProperty::interned(name).value_from(object)
+    }
+
+    fn set_property(object: &Object, name: &str, value: Self) -> Result<(), OSStatus> {
+        DataProperty::interned(name).set_value(object, value)
+    }
+}
+
+impl NamedProperty for StringProperty {
+    fn name(&self) -> String {
+        self.0.name()
+    }
+}
+
+impl NamedProperty for IntegerProperty {
+    fn name(&self) -> String {
+        self.0.name()
+    }
+}
+
+impl NamedProperty for DataProperty {
+    fn name(&self) -> String {
+        self.0.name()
+    }
+}
+
+impl NamedProperty for BooleanProperty {
+    fn name(&self) -> String {
+        self.0.name()
+    }
+}
+
+/// A property of any of the supported types, to be read with [Object::read_properties](crate::Object::read_properties).
+///
+#[derive(Clone)]
+pub enum PropertyKey {
+    String(StringProperty),
+    Integer(IntegerProperty),
+    Boolean(BooleanProperty),
+    Data(DataProperty),
+}
+
+impl PropertyKey {
+    pub(crate) fn read(&self, object: &Object) -> Result<AnyPropertyValue, OSStatus> {
+        match self {
+            Self::String(property) => property.value_from(object).map(AnyPropertyValue::String),
+            Self::Integer(property) => property.value_from(object).map(AnyPropertyValue::Integer),
+            Self::Boolean(property) => property.value_from(object).map(AnyPropertyValue::Boolean),
+            Self::Data(property) => property.value_from(object).map(AnyPropertyValue::Data),
+        }
+    }
+}
+
+impl From<StringProperty> for PropertyKey {
+    fn from(property: StringProperty) -> Self {
+        Self::String(property)
+    }
+}
+
+impl From<IntegerProperty> for PropertyKey {
+    fn from(property: IntegerProperty) -> Self {
+        Self::Integer(property)
+    }
+}
+
+impl From<BooleanProperty> for PropertyKey {
+    fn from(property: BooleanProperty) -> Self {
+        Self::Boolean(property)
+    }
+}
+
+impl From<DataProperty> for PropertyKey {
+    fn from(property: DataProperty) -> Self {
+        Self::Data(property)
+    }
+}
+
+impl NamedProperty for PropertyKey {
+    fn name(&self) -> String {
+        match self {
+            Self::String(property) => property.name(),
+            Self::Integer(property) => property.name(),
+            Self::Boolean(property) => property.name(),
+            Self::Data(property) => property.name(),
+        }
+    }
+}
+
+/// The value of a property read through a [PropertyKey].
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnyPropertyValue {
+    String(String),
+    Integer(i32),
+    Boolean(bool),
+    Data(Vec<u8>),
+}
+
+/// A property bound to an object, to read or write its value without repeating the object.
+///
+/// ```rust,no_run
+/// let source = coremidi::Source::from_index(0).unwrap();
+/// let name = source.name_property().get().unwrap();
+/// source.private_property().set(true).unwrap();
+/// ```
+///
+pub struct PropertyHandle<'a, P, T> {
+    object: &'a Object,
+    property: P,
+    value_type: PhantomData<T>,
+}
+
+impl<'a, P, T> PropertyHandle<'a, P, T> {
+    pub(crate) fn new(object: &'a Object, property: P) -> Self {
+        Self {
+            object,
+            property,
+            value_type: PhantomData,
+        }
+    }
+
+    /// Get the value of the property.
+    ///
+    pub fn get(&self) -> Result<T, OSStatus>
+    where
+        P: PropertyGetter<T>,
+    {
+        self.property.value_from(self.object)
+    }
+
+    /// Get the value of the property, or `None` when the object doesn't have it.
+    ///
+    pub fn try_get(&self) -> Result<Option<T>, OSStatus>
+    where
+        P: PropertyGetter<T>,
+    {
+        self.property.try_value_from(self.object)
+    }
+
+    /// Set the value of the property.
+    ///
+    pub fn set(&self, value: T) -> Result<(), OSStatus>
+    where
+        P: PropertySetter<T>,
+    {
+        self.property.set_value(self.object, value)
+    }
+}
+
+/// Cached instances of the most common properties, to read them in tight loops without creating them every time.
+///
+/// ```rust,no_run
+/// use coremidi::{property_keys, PropertyGetter, Sources};
+/// for source in Sources {
+///     let name: String = property_keys::NAME.value_from(&source).unwrap();
+///     let offline: bool = property_keys::OFFLINE.value_from(&source).unwrap();
+/// }
+/// ```
+///
+pub mod keys {
+    use once_cell::sync::Lazy;
+
+    use super::{BooleanProperty, IntegerProperty, Properties, StringProperty};
+
+    pub static NAME: Lazy<StringProperty> = Lazy::new(Properties::name);
+    pub static DISPLAY_NAME: Lazy<StringProperty> = Lazy::new(Properties::display_name);
+    pub static MANUFACTURER: Lazy<StringProperty> = Lazy::new(Properties::manufacturer);
+    pub static MODEL: Lazy<StringProperty> = Lazy::new(Properties::model);
+    pub static UNIQUE_ID: Lazy<IntegerProperty> = Lazy::new(Properties::unique_id);
+    pub static OFFLINE: Lazy<BooleanProperty> = Lazy::new(Properties::offline);
+    pub static PRIVATE: Lazy<BooleanProperty> = Lazy::new(Properties::private);
+}
+
 /// The set of properties that might be available for MIDI objects.
 ///
 pub struct Properties;
@@ -263,6 +849,13 @@ impl Properties {
         IntegerProperty::from_constant_string_ref(unsafe { kMIDIPropertyConnectionUniqueID })
     }
 
+    /// See [kMIDIPropertyConnectionUniqueID](https://developer.apple.com/documentation/coremidi/kMIDIPropertyConnectionUniqueID)
+    ///
+    /// Unlike [Properties::connection_unique_id], it supports objects with several connections.
+    pub fn connection_unique_ids() -> ConnectionUniqueIdsProperty {
+        ConnectionUniqueIdsProperty::new()
+    }
+
     /// See [kMIDIPropertyOffline](https://developer.apple.com/documentation/coremidi/kMIDIPropertyOffline)
     pub fn offline() -> BooleanProperty {
         BooleanProperty::from_constant_string_ref(unsafe { kMIDIPropertyOffline })
@@ -281,8 +874,10 @@ impl Properties {
     // /// See [kMIDIPropertyNameConfiguration](https://developer.apple.com/documentation/coremidi/kMIDIPropertyNameConfiguration)
     // pub fn name_configuration() -> Property { unsafe { Property(kMIDIPropertyNameConfiguration) } }
 
-    // /// See [kMIDIPropertyImage](https://developer.apple.com/documentation/coremidi/kMIDIPropertyImage)
-    // pub fn image() -> Property { unsafe { Property(kMIDIPropertyImage) } }
+    /// See [kMIDIPropertyImage](https://developer.apple.com/documentation/coremidi/kMIDIPropertyImage)
+    pub fn image() -> StringProperty {
+        StringProperty::from_constant_string_ref(unsafe { kMIDIPropertyImage })
+    }
 
     /// See [kMIDIPropertyDriverVersion](https://developer.apple.com/documentation/coremidi/kMIDIPropertyDriverVersion)
     pub fn driver_version() -> IntegerProperty {
@@ -416,7 +1011,11 @@ impl Properties {
 
     /// See [kMIDIPropertyProtocolID](https://developer.apple.com/documentation/coremidi/kmidipropertyprotocolid)
     pub fn protocol_id() -> IntegerProperty {
-        IntegerProperty::from_constant_string_ref(unsafe { kMIDIPropertyProtocolID })
+        match property_protocol_id() {
+            Some(key) => IntegerProperty::from_constant_string_ref(key),
+            // Unknown to the systems without MIDI 2.0 anyway
+            None => IntegerProperty::new("protocol"),
+        }
     }
 }
 
@@ -474,6 +1073,93 @@ mod tests {
             check_get_original(&property, &dest);
             check_roundtrip(&property, &dest);
         }
+
+        #[test]
+        fn test_set_cf_string() {
+            let (_client, dest) = setup();
+            let property = Properties::name();
+
+            property
+                .set_value(&dest, &CFString::new(NAME_MODIFIED))
+                .unwrap();
+            let name: String = property.value_from(&dest).unwrap();
+
+            assert_eq!(name, NAME_MODIFIED);
+        }
+
+        #[test]
+        fn test_handle() {
+            let (_client, dest) = setup();
+            let property = dest.name_property();
+
+            assert_eq!(property.get(), Ok(NAME_ORIG.to_string()));
+            property.set(NAME_MODIFIED.to_string()).unwrap();
+            assert_eq!(property.get(), Ok(NAME_MODIFIED.to_string()));
+        }
+
+        #[test]
+        fn test_interned() {
+            let (_client, dest) = setup();
+            let property = StringProperty::interned("name");
+
+            check_get_original(&property, &dest);
+            check_roundtrip(&property, &dest);
+        }
+
+        #[test]
+        fn test_cached() {
+            let (_client, dest) = setup();
+
+            check_get_original(&keys::NAME, &dest);
+            check_roundtrip(&keys::NAME, &dest);
+        }
+
+        #[test]
+        fn test_with_value() {
+            let (_client, dest) = setup();
+            let property = Properties::name();
+
+            let is_original = property.with_value(&dest, |name| name == NAME_ORIG);
+
+            assert_eq!(is_original, Ok(true));
+        }
+
+        #[test]
+        fn test_cf_value_from() {
+            let (_client, dest) = setup();
+            let property = Properties::name();
+
+            let name = property.cf_value_from(&dest).unwrap();
+
+            assert_eq!(name, NAME_ORIG);
+        }
+
+        #[test]
+        fn test_name() {
+            assert_eq!(StringProperty::new("my-property").name(), "my-property");
+            assert_eq!(
+                BooleanProperty::interned("my-property").name(),
+                "my-property"
+            );
+        }
+
+        #[test]
+        fn test_interned_reuses_well_known_key() {
+            let property = StringProperty::interned("name");
+
+            assert_eq!(
+                property.0.as_string_ref(),
+                Properties::name().0.as_string_ref()
+            );
+        }
+
+        #[test]
+        fn test_interned_creates_custom_key() {
+            let property = StringProperty::interned("my-interned-property");
+
+            assert!(matches!(property.0, PropertyKeyStorage::Owned(_)));
+            assert_eq!(property.name(), "my-interned-property");
+        }
     }
 
     mod integer {
@@ -492,6 +1178,17 @@ mod tests {
             assert!(value.is_err())
         }
 
+        #[test]
+        fn test_try_not_set() {
+            let (_client, dest) = setup();
+            // Is not set by default for Virtual Destinations
+            let property = Properties::advance_schedule_time_musec();
+
+            let value: Result<Option<i32>, _> = property.try_value_from(&dest);
+
+            assert_eq!(value, Ok(None))
+        }
+
         #[test]
         fn test_roundtrip() {
             let (_client, dest) = setup();
@@ -502,6 +1199,136 @@ mod tests {
 
             assert_eq!(num, ADVANCED_SCHEDULE_TIME);
         }
+
+        #[test]
+        fn test_new_roundtrip() {
+            let (_client, dest) = setup();
+            let property = IntegerProperty::new("my-own-integer-property");
+
+            property.set_value(&dest, ADVANCED_SCHEDULE_TIME).unwrap();
+            let num: i32 = property.value_from(&dest).unwrap();
+
+            assert_eq!(num, ADVANCED_SCHEDULE_TIME);
+        }
+
+        #[test]
+        fn test_new_outlives_name() {
+            let (_client, dest) = setup();
+            let property = {
+                let name = String::from("my-own-integer-property");
+                IntegerProperty::new(&name)
+            };
+
+            property.set_value(&dest, ADVANCED_SCHEDULE_TIME).unwrap();
+            let num: i32 = property.value_from(&dest).unwrap();
+
+            assert_eq!(num, ADVANCED_SCHEDULE_TIME);
+        }
+    }
+
+    mod value {
+        use super::*;
+
+        #[test]
+        fn test_roundtrip() {
+            let (_client, dest) = setup();
+
+            dest.set_property_value("my-string", String::from("value"))
+                .unwrap();
+            dest.set_property_value("my-integer", 42).unwrap();
+            dest.set_property_value("my-boolean", true).unwrap();
+            dest.set_property_value("my-data", vec![1u8, 2, 3]).unwrap();
+
+            assert_eq!(
+                dest.property::<String>("my-string"),
+                Ok("value".to_string())
+            );
+            assert_eq!(dest.property::<i32>("my-integer"), Ok(42));
+            assert_eq!(dest.property::<bool>("my-boolean"), Ok(true));
+            assert_eq!(dest.property::<Vec<u8>>("my-data"), Ok(vec![1u8, 2, 3]));
+        }
+
+        #[test]
+        fn test_wrong_type() {
+            let (_client, dest) = setup();
+
+            dest.set_property_value("my-integer", 42).unwrap();
+
+            assert!(dest.property::<String>("my-integer").is_err());
+        }
+
+        #[test]
+        fn test_try_property() {
+            let (_client, dest) = setup();
+
+            dest.set_property_value("my-integer", 42).unwrap();
+
+            assert_eq!(dest.try_property::<i32>("my-integer"), Ok(Some(42)));
+            assert_eq!(dest.try_property::<i32>("my-missing-integer"), Ok(None));
+            assert!(dest.try_property::<String>("my-integer").is_err());
+        }
+
+        #[test]
+        fn test_none_if_unknown() {
+            assert_eq!(none_if_unknown(Ok(1)), Ok(Some(1)));
+            assert_eq!(none_if_unknown::<i32>(Err(kMIDIUnknownProperty)), Ok(None));
+            assert_eq!(none_if_unknown::<i32>(Err(-1)), Err(-1));
+        }
+    }
+
+    mod key {
+        use super::*;
+
+        #[test]
+        fn test_read_properties() {
+            let (_client, dest) = setup();
+            dest.set_property_value("my-integer", 42).unwrap();
+
+            let values = dest.read_properties(&[
+                Properties::name().into(),
+                IntegerProperty::new("my-integer").into(),
+                Properties::transmits_program_changes().into(),
+            ]);
+
+            assert_eq!(
+                values,
+                vec![
+                    Some(AnyPropertyValue::String(NAME_ORIG.to_string())),
+                    Some(AnyPropertyValue::Integer(42)),
+                    None,
+                ]
+            );
+        }
+    }
+
+    mod connection {
+        use super::*;
+
+        #[test]
+        fn test_unique_ids_data() {
+            let data = unique_ids_to_data(&[1, -2]);
+
+            assert_eq!(data, vec![0, 0, 0, 1, 0xff, 0xff, 0xff, 0xfe]);
+            assert_eq!(unique_ids_from_data(&data), vec![1, -2]);
+        }
+
+        #[test]
+        fn test_roundtrip() {
+            let (_client, dest) = setup();
+            let property = Properties::connection_unique_ids();
+
+            property.set_value(&dest, [1]).unwrap();
+            let value: Vec<UniqueId> = property.value_from(&dest).unwrap();
+            assert_eq!(value, vec![1]);
+
+            property.set_value(&dest, vec![1, 2, 3]).unwrap();
+            let value: Vec<UniqueId> = property.value_from(&dest).unwrap();
+            assert_eq!(value, vec![1, 2, 3]);
+
+            property.set_value(&dest, []).unwrap();
+            let value: Result<Option<Vec<UniqueId>>, _> = property.try_value_from(&dest);
+            assert_eq!(value, Ok(None));
+        }
     }
 
     mod boolean {
@@ -518,6 +1345,16 @@ mod tests {
             assert!(value.is_err())
         }
 
+        #[test]
+        fn test_try_not_set() {
+            let (_client, dest) = setup();
+            let property = Properties::transmits_program_changes();
+
+            let value: Result<Option<bool>, _> = property.try_value_from(&dest);
+
+            assert_eq!(value, Ok(None))
+        }
+
         #[test]
         fn test_roundtrip() {
             let (_client, dest) = setup();
@@ -528,5 +1365,22 @@ mod tests {
 
             assert!(value);
         }
+
+        #[test]
+        fn test_new_roundtrip() {
+            let (_client, dest) = setup();
+            let property = {
+                let name = String::from("my-own-boolean-property");
+                BooleanProperty::new(&name)
+            };
+
+            property.set_value(&dest, true).unwrap();
+            let value: bool = property.value_from(&dest).unwrap();
+            assert!(value);
+
+            property.set_value(&dest, false).unwrap();
+            let value: bool = property.value_from(&dest).unwrap();
+            assert!(!value);
+        }
     }
 }
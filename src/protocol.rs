@@ -5,6 +5,7 @@ use coremidi_sys::MIDIProtocolID;
 /// The [MIDI Protocol](https://developer.apple.com/documentation/coremidi/midiprotocolid) to use for messages
 ///
 #[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Protocol {
     /// MIDI 1.0
     Midi10,
@@ -0,0 +1,243 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::{ump_message_len, EventList, Timestamp};
+
+/// The maximum number of words of a message in a [MessageProducer]/[MessageConsumer] queue,
+/// which is the size of the largest Universal MIDI Packet.
+///
+pub const MAX_MESSAGE_WORDS: usize = 4;
+
+/// A MIDI message (as Universal MIDI Packet words) along with its timestamp,
+/// stored by value so it can be moved around without allocating.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampedMessage {
+    timestamp: Timestamp,
+    len: usize,
+    words: [u32; MAX_MESSAGE_WORDS],
+}
+
+impl TimestampedMessage {
+    /// Create a message from its words, or `None` when there are more than [MAX_MESSAGE_WORDS].
+    ///
+    pub fn new(timestamp: Timestamp, words: &[u32]) -> Option<Self> {
+        if words.len() > MAX_MESSAGE_WORDS {
+            return None;
+        }
+        let mut message = Self {
+            timestamp,
+            len: words.len(),
+            words: [0; MAX_MESSAGE_WORDS],
+        };
+        message.words[..words.len()].copy_from_slice(words);
+        Some(message)
+    }
+
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    pub fn words(&self) -> &[u32] {
+        &self.words[..self.len]
+    }
+}
+
+struct Shared {
+    slots: Box<[UnsafeCell<TimestampedMessage>]>,
+    // Both are counters that only grow (wrapping around), the slot is the counter modulo the capacity
+    read: AtomicUsize,
+    write: AtomicUsize,
+}
+
+// The producer only writes into the slots that the consumer is not reading, and the other way around,
+// which is synchronized through the counters.
+unsafe impl Sync for Shared {}
+
+/// Create a wait-free queue with room for `capacity` messages, with a single producer and a single consumer.
+///
+/// It allows to hand the messages received in an input port callback to another thread (for example an audio thread)
+/// without locking nor allocating. All the memory is allocated when the queue is created.
+///
+/// ```
+/// use coremidi::{message_queue, TimestampedMessage};
+/// let (mut producer, mut consumer) = message_queue(256);
+/// // From the input port callback:
+/// producer.push(TimestampedMessage::new(42, &[0x20903c7f]).unwrap()).unwrap();
+/// // From the audio thread:
+/// while let Some(message) = consumer.pop() {
+///     assert_eq!(message.words(), &[0x20903c7f]);
+/// }
+/// ```
+///
+pub fn message_queue(capacity: usize) -> (MessageProducer, MessageConsumer) {
+    let empty = TimestampedMessage {
+        timestamp: 0,
+        len: 0,
+        words: [0; MAX_MESSAGE_WORDS],
+    };
+    let shared = Arc::new(Shared {
+        slots: (0..capacity.max(1))
+            .map(|_| UnsafeCell::new(empty))
+            .collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+    });
+    (
+        MessageProducer {
+            shared: shared.clone(),
+        },
+        MessageConsumer { shared },
+    )
+}
+
+/// The sending half of a [message_queue].
+///
+pub struct MessageProducer {
+    shared: Arc<Shared>,
+}
+
+impl MessageProducer {
+    /// Add a message to the queue, or give it back when the queue is full.
+    ///
+    pub fn push(&mut self, message: TimestampedMessage) -> Result<(), TimestampedMessage> {
+        let shared = &*self.shared;
+        let write = shared.write.load(Ordering::Relaxed);
+        let read = shared.read.load(Ordering::Acquire);
+        if write.wrapping_sub(read) == shared.slots.len() {
+            return Err(message);
+        }
+        unsafe { *shared.slots[write % shared.slots.len()].get() = message };
+        shared.write.store(write.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Add all the messages of an event list, returning the number of them that didn't fit in the queue.
+    ///
+    pub fn push_event_list(&mut self, event_list: &EventList) -> usize {
+        let mut dropped = 0;
+        for packet in event_list.iter() {
+            let mut words = packet.data();
+            while !words.is_empty() {
+                let len = ump_message_len(words[0]).clamp(1, words.len());
+                let (message_words, rest) = words.split_at(len);
+                words = rest;
+                let pushed = TimestampedMessage::new(packet.timestamp(), message_words)
+                    .map(|message| self.push(message).is_ok())
+                    .unwrap_or(false);
+                if !pushed {
+                    dropped += 1;
+                }
+            }
+        }
+        dropped
+    }
+}
+
+/// The receiving half of a [message_queue].
+///
+pub struct MessageConsumer {
+    shared: Arc<Shared>,
+}
+
+impl MessageConsumer {
+    /// Take the oldest message from the queue, if any.
+    ///
+    pub fn pop(&mut self) -> Option<TimestampedMessage> {
+        let shared = &*self.shared;
+        let read = shared.read.load(Ordering::Relaxed);
+        let write = shared.write.load(Ordering::Acquire);
+        if read == write {
+            return None;
+        }
+        let message = unsafe { *shared.slots[read % shared.slots.len()].get() };
+        shared.read.store(read.wrapping_add(1), Ordering::Release);
+        Some(message)
+    }
+
+    /// The number of messages waiting in the queue.
+    ///
+    pub fn len(&self) -> usize {
+        let write = self.shared.write.load(Ordering::Acquire);
+        let read = self.shared.read.load(Ordering::Relaxed);
+        write.wrapping_sub(read)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::queue::{message_queue, TimestampedMessage};
+    use crate::{EventBuffer, Protocol};
+
+    fn message(timestamp: u64) -> TimestampedMessage {
+        TimestampedMessage::new(timestamp, &[0x20903c7f]).unwrap()
+    }
+
+    #[test]
+    fn message_too_long() {
+        assert!(TimestampedMessage::new(0, &[0; 5]).is_none());
+    }
+
+    #[test]
+    fn push_and_pop_in_order() {
+        let (mut producer, mut consumer) = message_queue(2);
+
+        assert_eq!(producer.push(message(1)), Ok(()));
+        assert_eq!(producer.push(message(2)), Ok(()));
+        assert_eq!(producer.push(message(3)), Err(message(3)));
+        assert_eq!(consumer.len(), 2);
+
+        assert_eq!(consumer.pop(), Some(message(1)));
+        assert_eq!(producer.push(message(3)), Ok(()));
+        assert_eq!(consumer.pop(), Some(message(2)));
+        assert_eq!(consumer.pop(), Some(message(3)));
+        assert_eq!(consumer.pop(), None);
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn push_event_list() {
+        let (mut producer, mut consumer) = message_queue(2);
+        let events = EventBuffer::new(Protocol::Midi20)
+            .with_packet(10, &[0x40903c00, 0xffff0000, 0x20803c00])
+            .with_packet(20, &[0x20903e7f]);
+
+        assert_eq!(producer.push_event_list(&events), 1);
+        assert_eq!(
+            consumer.pop().map(|message| message.words().to_vec()),
+            Some(vec![0x40903c00, 0xffff0000])
+        );
+        assert_eq!(
+            consumer.pop().map(|message| message.words().to_vec()),
+            Some(vec![0x20803c00])
+        );
+    }
+
+    #[test]
+    fn between_threads() {
+        let (mut producer, mut consumer) = message_queue(16);
+        let producer_thread = thread::spawn(move || {
+            for timestamp in 0..1000 {
+                while producer.push(message(timestamp)).is_err() {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut expected = 0;
+        while expected < 1000 {
+            if let Some(message) = consumer.pop() {
+                assert_eq!(message.timestamp(), expected);
+                expected += 1;
+            }
+        }
+        producer_thread.join().unwrap();
+    }
+}
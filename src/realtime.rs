@@ -0,0 +1,80 @@
+use std::cell::UnsafeCell;
+use std::os::raw::c_void;
+
+use coremidi_sys::{MIDIEventList, MIDIPacketList};
+
+use crate::{EventList, PacketList};
+
+/// A callback called from the real-time thread of CoreMIDI.
+///
+/// This is the only thing between the blocks registered with CoreMIDI and the user closures,
+/// and it guarantees that nothing happens in between that is not real-time safe:
+///
+/// - No memory is allocated or freed, the lists are passed by reference to the memory owned by CoreMIDI.
+/// - No locks are taken, and there is no borrow checking at runtime either (which could panic).
+///   CoreMIDI calls the block of a port or endpoint from a single thread, one call at a time,
+///   and never from within the closure itself, so the closure can be accessed mutably without any synchronization.
+/// - No formatting or I/O is done.
+///
+/// Whatever happens inside the closure is up to the user, of course.
+///
+/// It is not `Sync`, so it can't be shared with other threads by mistake. A closure that needs to be
+/// called from several threads has to be put behind a lock by its owner, which is the only case where locking is needed.
+///
+pub(crate) struct RtSafe<F> {
+    callback: UnsafeCell<F>,
+}
+
+impl<F> RtSafe<F> {
+    pub(crate) fn new(callback: F) -> Self {
+        Self {
+            callback: UnsafeCell::new(callback),
+        }
+    }
+
+    /// Get exclusive access to the closure.
+    ///
+    /// # Safety
+    ///
+    /// It must not be called while a previous call is still running (see the type docs).
+    ///
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn callback(&self) -> &mut F {
+        &mut *self.callback.get()
+    }
+
+    /// Call the closure with a list of events, and the context given when connecting the source.
+    ///
+    /// # Safety
+    ///
+    /// The event list needs to be valid, and the context needs to point to a `T` (it is the `Box<T>` owned by the port).
+    /// It must not be called while a previous call is still running.
+    ///
+    #[inline]
+    pub(crate) unsafe fn receive<T>(&self, event_list: *const MIDIEventList, context: *mut c_void)
+    where
+        F: FnMut(&EventList, &mut T),
+    {
+        let event_list = &*(event_list as *const EventList);
+        let context = &mut *(context as *mut T);
+        (self.callback())(event_list, context);
+    }
+
+    /// Call the closure with a list of packets, and the context given when connecting the source, if any.
+    ///
+    /// # Safety
+    ///
+    /// The packet list needs to be valid, and the context needs to be either null or point to a `T`.
+    /// It must not be called while a previous call is still running.
+    ///
+    #[inline]
+    pub(crate) unsafe fn read<T>(&self, packet_list: *const MIDIPacketList, context: *mut c_void)
+    where
+        F: FnMut(&PacketList, Option<&T>),
+    {
+        let packet_list = &*(packet_list as *const PacketList);
+        let context = (context as *const T).as_ref();
+        (self.callback())(packet_list, context);
+    }
+}
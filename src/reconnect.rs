@@ -0,0 +1,178 @@
+use core_foundation::base::OSStatus;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use coremidi_sys::kMIDIObjectNotFound;
+
+use crate::{Client, EventList, InputPortWithContext, Notification, Protocol, Source, UniqueId};
+
+struct Connection<T> {
+    context: T,
+    source: Option<Source>,
+}
+
+struct ReconnectState<T> {
+    port: Option<InputPortWithContext<T>>,
+    connections: HashMap<UniqueId, Connection<T>>,
+}
+
+impl<T: Clone> ReconnectState<T> {
+    /// Bring the port connections in line with the sources currently present in the system.
+    fn reconnect(&mut self) {
+        let port = match self.port.as_mut() {
+            Some(port) => port,
+            None => return,
+        };
+        for (unique_id, connection) in self.connections.iter_mut() {
+            let source = Source::from_unique_id(*unique_id);
+            if source == connection.source {
+                continue;
+            }
+            if let Some(previous) = connection.source.take() {
+                // The source might be gone already, there is nothing else to do about it
+                let _ = port.disconnect_source(&previous);
+            }
+            if let Some(source) = source {
+                if port
+                    .connect_source(&source, connection.context.clone())
+                    .is_ok()
+                {
+                    connection.source = Some(source);
+                }
+            }
+        }
+    }
+
+    fn forget(&mut self, unique_id: UniqueId) -> Result<(), OSStatus> {
+        let connection = self.connections.remove(&unique_id);
+        match (self.port.as_mut(), connection.and_then(|c| c.source)) {
+            (Some(port), Some(source)) => port.disconnect_source(&source),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// An input port remembering the sources it is connected to by their unique id,
+/// and connecting to them again whenever they reappear, for example after unplugging and plugging a device back,
+/// or after a `MIDIRestart`.
+///
+/// It owns a client to receive the notifications about changes in the system, so the run loop
+/// that was current when it was created needs to be running (see [Client::new_with_notifications]).
+///
+/// ```rust,no_run
+/// use coremidi::{Protocol, ReconnectingInputPort, Source};
+/// let input_port = ReconnectingInputPort::new("example-port", Protocol::Midi10, |event_list, _: &mut ()| {
+///     println!("{:?}", event_list);
+/// }).unwrap();
+/// input_port.connect_source(&Source::from_index(0).unwrap(), ()).unwrap();
+/// ```
+///
+pub struct ReconnectingInputPort<T> {
+    state: Arc<Mutex<ReconnectState<T>>>,
+    // The client must be dropped after the port, as it owns it
+    _client: Client,
+}
+
+impl<T: Clone + Send + 'static> ReconnectingInputPort<T> {
+    /// Create the input port, along with its own client, both named `name`.
+    ///
+    pub fn new<F>(
+        name: &str,
+        protocol: Protocol,
+        callback: F,
+    ) -> Result<ReconnectingInputPort<T>, OSStatus>
+    where
+        F: FnMut(&EventList, &mut T) + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(ReconnectState {
+            port: None,
+            connections: HashMap::new(),
+        }));
+        let notifications_state = state.clone();
+        let client = Client::new_with_notifications(name, move |notification: &Notification| {
+            match notification {
+                Notification::SetupChanged
+                | Notification::ObjectAdded(_)
+                | Notification::ObjectRemoved(_) => lock_state(&notifications_state).reconnect(),
+                _ => {}
+            }
+        })?;
+        let port = client.input_port_with_protocol(name, protocol, callback)?;
+        lock_state(&state).port = Some(port);
+        Ok(ReconnectingInputPort {
+            state,
+            _client: client,
+        })
+    }
+
+    /// Connect to a source, and remember it by its unique id to connect again when it reappears.
+    ///
+    pub fn connect_source(&self, source: &Source, context: T) -> Result<(), OSStatus> {
+        let unique_id = source.unique_id().ok_or(kMIDIObjectNotFound)?;
+        self.connect_unique_id(unique_id, context)
+    }
+
+    /// Connect to the source with a given unique id, either now if it is present,
+    /// or as soon as it appears otherwise.
+    ///
+    pub fn connect_unique_id(&self, unique_id: UniqueId, context: T) -> Result<(), OSStatus> {
+        let mut state = lock_state(&self.state);
+        state.forget(unique_id)?;
+        state.connections.insert(
+            unique_id,
+            Connection {
+                context,
+                source: None,
+            },
+        );
+        state.reconnect();
+        Ok(())
+    }
+
+    /// Disconnect from a source, and stop connecting to it when it reappears.
+    ///
+    pub fn disconnect_source(&self, source: &Source) -> Result<(), OSStatus> {
+        let unique_id = source.unique_id().ok_or(kMIDIObjectNotFound)?;
+        self.disconnect_unique_id(unique_id)
+    }
+
+    /// Disconnect from the source with a given unique id, and stop connecting to it when it reappears.
+    ///
+    pub fn disconnect_unique_id(&self, unique_id: UniqueId) -> Result<(), OSStatus> {
+        lock_state(&self.state).forget(unique_id)
+    }
+
+    /// Whether the source with a given unique id is currently connected.
+    ///
+    pub fn is_connected(&self, unique_id: UniqueId) -> bool {
+        matches!(
+            lock_state(&self.state).connections.get(&unique_id),
+            Some(Connection {
+                source: Some(_),
+                ..
+            })
+        )
+    }
+
+    /// The unique ids of the sources to connect to, whether they are present or not.
+    ///
+    pub fn unique_ids(&self) -> Vec<UniqueId> {
+        lock_state(&self.state)
+            .connections
+            .keys()
+            .copied()
+            .collect()
+    }
+}
+
+impl<T> Drop for ReconnectingInputPort<T> {
+    fn drop(&mut self) {
+        lock_state(&self.state).port.take();
+    }
+}
+
+fn lock_state<T>(state: &Mutex<ReconnectState<T>>) -> MutexGuard<'_, ReconnectState<T>> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
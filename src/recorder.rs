@@ -0,0 +1,220 @@
+use core_foundation::base::OSStatus;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{
+    host_time_now, Client, Destination, InputPort, OutputPort, PacketList, Source, Timestamp,
+};
+
+/// A packet recorded by a [Recorder], with the host time when it was received.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedPacket {
+    pub timestamp: Timestamp,
+    pub data: Vec<u8>,
+}
+
+type RecordingLog = Arc<Mutex<Vec<RecordedPacket>>>;
+
+fn lock_log(log: &RecordingLog) -> MutexGuard<'_, Vec<RecordedPacket>> {
+    log.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn record(log: &mut Vec<RecordedPacket>, packet_list: &PacketList) {
+    for packet in packet_list.iter() {
+        let timestamp = match packet.timestamp() {
+            0 => host_time_now(),
+            timestamp => timestamp,
+        };
+        log.push(RecordedPacket {
+            timestamp,
+            data: packet.data().to_vec(),
+        });
+    }
+}
+
+/// The events to replay a log, keeping the time between the packets, with the first one at `start`.
+fn replay_events(
+    log: &[RecordedPacket],
+    start: Timestamp,
+) -> impl Iterator<Item = (Timestamp, &[u8])> {
+    let first = log.first().map_or(0, |packet| packet.timestamp);
+    log.iter().map(move |packet| {
+        let offset = packet.timestamp.saturating_sub(first);
+        (start + offset, packet.data.as_slice())
+    })
+}
+
+/// Records the MIDI 1.0 packets received from the chosen sources into an in-memory log,
+/// with the host time when they were received.
+///
+/// The session can be replayed afterwards through an output port, or exported as a Standard MIDI File
+/// (with the `midly` feature).
+///
+/// ```rust,no_run
+/// use coremidi::{Client, Destination, Source};
+/// let client = Client::new("example-client").unwrap();
+/// let recorder = coremidi::Recorder::new(&client, "example-recorder").unwrap();
+/// recorder.connect_source(&Source::from_index(0).unwrap()).unwrap();
+/// // Later on:
+/// recorder.disconnect_source(&Source::from_index(0).unwrap()).unwrap();
+/// let output_port = client.output_port("example-port").unwrap();
+/// recorder.replay(&output_port, &Destination::from_index(0).unwrap(), 0).unwrap();
+/// ```
+///
+pub struct Recorder {
+    log: RecordingLog,
+    port: InputPort,
+}
+
+impl Recorder {
+    /// Create a recorder with its own input port of `client`, named `name`.
+    ///
+    pub fn new(client: &Client, name: &str) -> Result<Recorder, OSStatus> {
+        let log: RecordingLog = Arc::new(Mutex::new(Vec::new()));
+        let port_log = log.clone();
+        let port = client.input_port(name, move |packet_list| {
+            record(&mut lock_log(&port_log), packet_list)
+        })?;
+        Ok(Recorder { log, port })
+    }
+
+    /// Start recording the packets from a source.
+    ///
+    pub fn connect_source(&self, source: &Source) -> Result<(), OSStatus> {
+        self.port.connect_source(source)
+    }
+
+    /// Stop recording the packets from a source.
+    ///
+    pub fn disconnect_source(&self, source: &Source) -> Result<(), OSStatus> {
+        self.port.disconnect_source(source)
+    }
+
+    /// A copy of the packets recorded so far, in the order they were received.
+    ///
+    pub fn packets(&self) -> Vec<RecordedPacket> {
+        lock_log(&self.log).clone()
+    }
+
+    /// Take the packets recorded so far, leaving the log empty.
+    ///
+    pub fn take(&self) -> Vec<RecordedPacket> {
+        std::mem::take(&mut *lock_log(&self.log))
+    }
+
+    pub fn clear(&self) {
+        lock_log(&self.log).clear();
+    }
+
+    pub fn len(&self) -> usize {
+        lock_log(&self.log).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        lock_log(&self.log).is_empty()
+    }
+
+    /// Send the packets recorded so far to a destination, keeping the time between them.
+    /// The first packet is scheduled at the host time `start` (0 means now).
+    ///
+    pub fn replay(
+        &self,
+        output_port: &OutputPort,
+        destination: &Destination,
+        start: Timestamp,
+    ) -> Result<(), OSStatus> {
+        let start = match start {
+            0 => host_time_now(),
+            start => start,
+        };
+        let log = lock_log(&self.log);
+        output_port.send_iter(destination, replay_events(&log, start))
+    }
+
+    /// Write the packets recorded so far as a single track Standard MIDI File,
+    /// with `ticks_per_beat` at the default tempo of 120 BPM.
+    ///
+    #[cfg(feature = "midly")]
+    pub fn write_smf<W: std::io::Write>(
+        &self,
+        writer: W,
+        ticks_per_beat: u16,
+    ) -> std::io::Result<()> {
+        write_smf(&lock_log(&self.log), writer, ticks_per_beat)
+    }
+}
+
+#[cfg(feature = "midly")]
+fn write_smf<W: std::io::Write>(
+    log: &[RecordedPacket],
+    writer: W,
+    ticks_per_beat: u16,
+) -> std::io::Result<()> {
+    use midly::num::u15;
+    use midly::{Arena, Format, Header, Smf, Timing};
+
+    let timing = Timing::Metrical(u15::new(ticks_per_beat.min(u15::max_value().as_int())));
+    let arena = Arena::new();
+    let mut track_recorder =
+        crate::TrackRecorder::new(&arena, crate::tick_duration(timing, 500_000));
+    for packet in log {
+        track_recorder.record_data(packet.timestamp, &packet.data);
+    }
+    let mut smf = Smf::new(Header::new(Format::SingleTrack, timing));
+    smf.tracks.push(track_recorder.into_track());
+    smf.write_std(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::recorder::{record, replay_events, RecordedPacket};
+    use crate::PacketBuffer;
+
+    #[test]
+    fn record_and_replay() {
+        let mut log = Vec::new();
+        let mut packets = PacketBuffer::new(100, &[0x90, 0x40, 0x7f]);
+        packets.push_data(150, &[0x80, 0x40, 0x00]);
+        record(&mut log, &packets);
+
+        assert_eq!(
+            log,
+            vec![
+                RecordedPacket {
+                    timestamp: 100,
+                    data: vec![0x90, 0x40, 0x7f]
+                },
+                RecordedPacket {
+                    timestamp: 150,
+                    data: vec![0x80, 0x40, 0x00]
+                },
+            ]
+        );
+        let events: Vec<_> = replay_events(&log, 1000).collect();
+        assert_eq!(
+            events,
+            vec![
+                (1000, &[0x90, 0x40, 0x7f][..]),
+                (1050, &[0x80, 0x40, 0x00][..])
+            ]
+        );
+    }
+
+    #[cfg(feature = "midly")]
+    #[test]
+    fn export_smf() {
+        use crate::recorder::write_smf;
+
+        let log = vec![RecordedPacket {
+            timestamp: 100,
+            data: vec![0x90, 0x40, 0x7f],
+        }];
+        let mut bytes = Vec::new();
+        write_smf(&log, &mut bytes, 480).unwrap();
+
+        let smf = midly::Smf::parse(&bytes).unwrap();
+        assert_eq!(smf.tracks.len(), 1);
+        // The note and the end of track
+        assert_eq!(smf.tracks[0].len(), 2);
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::notifications::Notification;
+use crate::object::UniqueId;
+use crate::{Destination, Destinations, Source, Sources};
+
+/// An endpoint known by an [EndpointRegistry].
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegisteredEndpoint {
+    Source(Source),
+    Destination(Destination),
+}
+
+/// A change in the presence of an endpoint, as reported to the subscribers of an [EndpointRegistry].
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum EndpointEvent {
+    Appeared(RegisteredEndpoint),
+    Disappeared(RegisteredEndpoint),
+}
+
+type SubscriptionCallback = Arc<Mutex<dyn FnMut(&EndpointEvent) + Send + 'static>>;
+
+struct Subscription {
+    unique_id: UniqueId,
+    callback: SubscriptionCallback,
+}
+
+/// A registry of the endpoints in the system by their unique id.
+///
+/// Unlike the indices, the unique ids stay valid when endpoints appear and disappear,
+/// so the registry allows to keep track of specific endpoints, and to be told when they come and go.
+/// It needs to be fed with the notifications received by a client.
+///
+/// ```rust,no_run
+/// use coremidi::{Client, EndpointRegistry, Notification};
+/// use std::sync::Arc;
+/// let registry = Arc::new(EndpointRegistry::new());
+/// let notifications_registry = registry.clone();
+/// let client = Client::new_with_notifications("example-client", move |notification: &Notification| {
+///     notifications_registry.handle_notification(notification)
+/// }).unwrap();
+/// registry.subscribe(0x12345678, |event| println!("{:?}", event));
+/// ```
+///
+pub struct EndpointRegistry {
+    endpoints: Mutex<HashMap<UniqueId, RegisteredEndpoint>>,
+    subscriptions: Mutex<HashMap<usize, Subscription>>,
+    next_subscription_id: AtomicUsize,
+}
+
+impl EndpointRegistry {
+    /// Create a registry with the endpoints currently in the system.
+    ///
+    pub fn new() -> Self {
+        let registry = Self::empty();
+        registry.refresh();
+        registry
+    }
+
+    fn empty() -> Self {
+        Self {
+            endpoints: Mutex::new(HashMap::new()),
+            subscriptions: Mutex::new(HashMap::new()),
+            next_subscription_id: AtomicUsize::new(0),
+        }
+    }
+
+    /// Get the endpoint with a given unique id, if it is present.
+    ///
+    pub fn get(&self, unique_id: UniqueId) -> Option<RegisteredEndpoint> {
+        lock(&self.endpoints).get(&unique_id).cloned()
+    }
+
+    /// Get the source with a given unique id, if it is present.
+    ///
+    pub fn source(&self, unique_id: UniqueId) -> Option<Source> {
+        match self.get(unique_id) {
+            Some(RegisteredEndpoint::Source(source)) => Some(source),
+            _ => None,
+        }
+    }
+
+    /// Get the destination with a given unique id, if it is present.
+    ///
+    pub fn destination(&self, unique_id: UniqueId) -> Option<Destination> {
+        match self.get(unique_id) {
+            Some(RegisteredEndpoint::Destination(destination)) => Some(destination),
+            _ => None,
+        }
+    }
+
+    /// Call a function every time the endpoint with a given unique id appears or disappears.
+    ///
+    /// It returns an id that allows to [unsubscribe](EndpointRegistry::unsubscribe).
+    ///
+    pub fn subscribe<F>(&self, unique_id: UniqueId, callback: F) -> usize
+    where
+        F: FnMut(&EndpointEvent) + Send + 'static,
+    {
+        let id = self.next_subscription_id.fetch_add(1, Ordering::Relaxed);
+        let subscription = Subscription {
+            unique_id,
+            callback: Arc::new(Mutex::new(callback)),
+        };
+        lock(&self.subscriptions).insert(id, subscription);
+        id
+    }
+
+    /// Stop calling the function of a subscription.
+    ///
+    pub fn unsubscribe(&self, subscription_id: usize) {
+        lock(&self.subscriptions).remove(&subscription_id);
+    }
+
+    /// Update the registry from a notification received by a client.
+    ///
+    pub fn handle_notification(&self, notification: &Notification) {
+        match notification {
+            Notification::SetupChanged
+            | Notification::ObjectAdded(_)
+            | Notification::ObjectRemoved(_) => self.refresh(),
+            _ => {}
+        }
+    }
+
+    /// Enumerate the endpoints in the system again, notifying the subscribers about the changes.
+    ///
+    pub fn refresh(&self) {
+        let sources = Sources.into_iter().filter_map(|source| {
+            let unique_id = source.unique_id()?;
+            Some((unique_id, RegisteredEndpoint::Source(source)))
+        });
+        let destinations = Destinations.into_iter().filter_map(|destination| {
+            let unique_id = destination.unique_id()?;
+            Some((unique_id, RegisteredEndpoint::Destination(destination)))
+        });
+        self.update(sources.chain(destinations).collect());
+    }
+
+    fn update(&self, current: HashMap<UniqueId, RegisteredEndpoint>) {
+        let mut events = Vec::new();
+        {
+            let mut endpoints = lock(&self.endpoints);
+            for (unique_id, endpoint) in endpoints.iter() {
+                if current.get(unique_id) != Some(endpoint) {
+                    events.push((*unique_id, EndpointEvent::Disappeared(endpoint.clone())));
+                }
+            }
+            for (unique_id, endpoint) in current.iter() {
+                if endpoints.get(unique_id) != Some(endpoint) {
+                    events.push((*unique_id, EndpointEvent::Appeared(endpoint.clone())));
+                }
+            }
+            *endpoints = current;
+        }
+        if events.is_empty() {
+            return;
+        }
+        // The callbacks are called without holding the lock of the subscriptions,
+        // so they can subscribe or unsubscribe
+        let calls: Vec<(SubscriptionCallback, &EndpointEvent)> = {
+            let subscriptions = lock(&self.subscriptions);
+            events
+                .iter()
+                .flat_map(|(unique_id, event)| {
+                    subscriptions
+                        .values()
+                        .filter(move |subscription| subscription.unique_id == *unique_id)
+                        .map(move |subscription| (subscription.callback.clone(), event))
+                })
+                .collect()
+        };
+        for (callback, event) in calls {
+            (lock(&callback))(event);
+        }
+    }
+}
+
+impl Default for EndpointRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use crate::registry::{EndpointEvent, EndpointRegistry, RegisteredEndpoint};
+    use crate::{Destination, Source};
+
+    #[test]
+    fn update_notifies_subscribers() {
+        let registry = EndpointRegistry::empty();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let subscription_events = events.clone();
+        registry.subscribe(10, move |event| {
+            subscription_events.lock().unwrap().push(event.clone())
+        });
+
+        let mut endpoints = HashMap::new();
+        endpoints.insert(10, RegisteredEndpoint::Source(Source::new(1)));
+        endpoints.insert(20, RegisteredEndpoint::Destination(Destination::new(2)));
+        registry.update(endpoints);
+
+        assert_eq!(registry.source(10), Some(Source::new(1)));
+        assert_eq!(registry.destination(20), Some(Destination::new(2)));
+        assert_eq!(registry.destination(10), None);
+
+        registry.update(HashMap::new());
+
+        assert_eq!(registry.get(10), None);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                EndpointEvent::Appeared(RegisteredEndpoint::Source(Source::new(1))),
+                EndpointEvent::Disappeared(RegisteredEndpoint::Source(Source::new(1))),
+            ]
+        );
+    }
+
+    #[test]
+    fn unsubscribe() {
+        let registry = EndpointRegistry::empty();
+        let events = Arc::new(Mutex::new(0));
+        let subscription_events = events.clone();
+        let id = registry.subscribe(10, move |_| *subscription_events.lock().unwrap() += 1);
+        registry.unsubscribe(id);
+
+        let mut endpoints = HashMap::new();
+        endpoints.insert(10, RegisteredEndpoint::Source(Source::new(1)));
+        registry.update(endpoints);
+
+        assert_eq!(*events.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn subscribers_can_unsubscribe_from_their_callback() {
+        let registry = Arc::new(EndpointRegistry::empty());
+        let calls = Arc::new(Mutex::new(0));
+        let subscription_id = Arc::new(Mutex::new(None));
+        let (callback_registry, callback_calls, callback_id) =
+            (registry.clone(), calls.clone(), subscription_id.clone());
+        let id = registry.subscribe(10, move |_| {
+            *callback_calls.lock().unwrap() += 1;
+            if let Some(id) = callback_id.lock().unwrap().take() {
+                callback_registry.unsubscribe(id);
+            }
+        });
+        *subscription_id.lock().unwrap() = Some(id);
+
+        let mut endpoints = HashMap::new();
+        endpoints.insert(10, RegisteredEndpoint::Source(Source::new(1)));
+        registry.update(endpoints);
+        registry.update(HashMap::new());
+
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+}
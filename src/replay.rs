@@ -0,0 +1,339 @@
+use core_foundation::base::OSStatus;
+use coremidi_sys::kMIDIObjectNotFound;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{
+    Backend, BackendEndpoint, BackendNotification, MockBackend, MockConnection, PacketList,
+    RecordedPacket, Timestamp,
+};
+
+/// A step of a [ReplayScript]. The endpoints are referred to by name.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayEvent {
+    AddSource(String),
+    RemoveSource(String),
+    AddDestination(String),
+    RemoveDestination(String),
+    /// A packet received from a source.
+    Packet {
+        source: String,
+        timestamp: Timestamp,
+        data: Vec<u8>,
+    },
+}
+
+/// The sequence of events played by a [ReplayBackend], in order.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayScript {
+    events: Vec<ReplayEvent>,
+}
+
+impl ReplayScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_event(mut self, event: ReplayEvent) -> Self {
+        self.events.push(event);
+        self
+    }
+
+    /// Plug a source, as a device appearing.
+    ///
+    pub fn with_source(self, name: &str) -> Self {
+        self.with_event(ReplayEvent::AddSource(name.to_string()))
+    }
+
+    /// Unplug a source, as a device disappearing.
+    ///
+    pub fn with_removed_source(self, name: &str) -> Self {
+        self.with_event(ReplayEvent::RemoveSource(name.to_string()))
+    }
+
+    pub fn with_destination(self, name: &str) -> Self {
+        self.with_event(ReplayEvent::AddDestination(name.to_string()))
+    }
+
+    pub fn with_removed_destination(self, name: &str) -> Self {
+        self.with_event(ReplayEvent::RemoveDestination(name.to_string()))
+    }
+
+    /// Receive a packet from a source.
+    ///
+    pub fn with_packet(self, source: &str, timestamp: Timestamp, data: &[u8]) -> Self {
+        self.with_event(ReplayEvent::Packet {
+            source: source.to_string(),
+            timestamp,
+            data: data.to_vec(),
+        })
+    }
+
+    /// Receive the packets recorded by a [Recorder](crate::Recorder) from a source, with their timestamps.
+    ///
+    pub fn with_recorded(mut self, source: &str, packets: &[RecordedPacket]) -> Self {
+        self.events
+            .extend(packets.iter().map(|packet| ReplayEvent::Packet {
+                source: source.to_string(),
+                timestamp: packet.timestamp,
+                data: packet.data.clone(),
+            }));
+        self
+    }
+
+    pub fn events(&self) -> &[ReplayEvent] {
+        &self.events
+    }
+}
+
+/// A [Backend] playing a [ReplayScript], for deterministic integration tests of hotplug handling
+/// and parsing, without physical devices.
+///
+/// The events are played on demand by the test, from its own thread: the packets are delivered to the connections
+/// of their source, and the changes of the endpoints to the notification callback, before returning.
+/// Everything else behaves as in the [MockBackend] that it is built upon, which is available to check
+/// the packets sent.
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use coremidi::{Backend, BackendNotification, ReplayBackend, ReplayScript};
+///
+/// let backend = ReplayBackend::new(
+///     ReplayScript::new()
+///         .with_source("keyboard")
+///         .with_packet("keyboard", 100, &[0x90, 0x40, 0x7f])
+///         .with_removed_source("keyboard"),
+/// );
+/// let received = Arc::new(Mutex::new(Vec::new()));
+/// let connections = Arc::new(Mutex::new(Vec::new()));
+/// let (notify_received, notify_backend) = (received.clone(), backend.clone());
+/// backend.set_notify_callback(move |notification| {
+///     // Connect to every source that appears
+///     if let BackendNotification::SourceAdded(source) = notification {
+///         let received = notify_received.clone();
+///         let connection = notify_backend.connect_source(source, move |packet_list| {
+///             received.lock().unwrap().extend(packet_list.iter().map(|p| p.timestamp()));
+///         });
+///         connections.lock().unwrap().push(connection.unwrap());
+///     }
+/// });
+///
+/// backend.play_all().unwrap();
+/// assert_eq!(*received.lock().unwrap(), vec![100]);
+/// assert!(backend.sources().is_empty());
+/// ```
+///
+#[derive(Clone)]
+pub struct ReplayBackend {
+    mock: MockBackend,
+    remaining: Arc<Mutex<VecDeque<ReplayEvent>>>,
+}
+
+impl ReplayBackend {
+    pub fn new(script: ReplayScript) -> Self {
+        Self {
+            mock: MockBackend::new(),
+            remaining: Arc::new(Mutex::new(script.events.into())),
+        }
+    }
+
+    /// The mock backend where the script is played.
+    ///
+    pub fn mock(&self) -> &MockBackend {
+        &self.mock
+    }
+
+    /// The number of events not played yet.
+    ///
+    pub fn remaining(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Play the next event, returning whether there was one.
+    /// It fails when the event refers to an endpoint that doesn't exist (anymore).
+    ///
+    pub fn step(&self) -> Result<bool, OSStatus> {
+        // Taken out first, so the callbacks can use the backend
+        let event = self.lock().pop_front();
+        match event {
+            Some(event) => self.play(event).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Play the events up to the first packet with a timestamp after `until`.
+    ///
+    pub fn play_until(&self, until: Timestamp) -> Result<(), OSStatus> {
+        loop {
+            let next_is_due = match self.lock().front() {
+                Some(ReplayEvent::Packet { timestamp, .. }) => *timestamp <= until,
+                Some(_) => true,
+                None => false,
+            };
+            if !next_is_due {
+                return Ok(());
+            }
+            self.step()?;
+        }
+    }
+
+    /// Play all the events left.
+    ///
+    pub fn play_all(&self) -> Result<(), OSStatus> {
+        while self.step()? {}
+        Ok(())
+    }
+
+    fn play(&self, event: ReplayEvent) -> Result<(), OSStatus> {
+        match event {
+            ReplayEvent::AddSource(name) => {
+                self.mock.add_source(&name);
+            }
+            ReplayEvent::RemoveSource(name) => {
+                let source = find_by_name(self.mock.sources(), &name)?;
+                self.mock.remove_source(&source);
+            }
+            ReplayEvent::AddDestination(name) => {
+                self.mock.add_destination(&name);
+            }
+            ReplayEvent::RemoveDestination(name) => {
+                let destination = find_by_name(self.mock.destinations(), &name)?;
+                self.mock.remove_destination(&destination);
+            }
+            ReplayEvent::Packet {
+                source,
+                timestamp,
+                data,
+            } => {
+                let source = find_by_name(self.mock.sources(), &source)?;
+                self.mock.received_data(&source, timestamp, &data)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<ReplayEvent>> {
+        self.remaining
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn find_by_name(endpoints: Vec<BackendEndpoint>, name: &str) -> Result<BackendEndpoint, OSStatus> {
+    endpoints
+        .into_iter()
+        .find(|endpoint| endpoint.name == name)
+        .ok_or(kMIDIObjectNotFound)
+}
+
+impl crate::backend::private::Sealed for ReplayBackend {}
+
+impl Backend for ReplayBackend {
+    type Connection = MockConnection;
+
+    fn sources(&self) -> Vec<BackendEndpoint> {
+        self.mock.sources()
+    }
+
+    fn destinations(&self) -> Vec<BackendEndpoint> {
+        self.mock.destinations()
+    }
+
+    fn send(&self, destination: &BackendEndpoint, packets: &PacketList) -> Result<(), OSStatus> {
+        self.mock.send(destination, packets)
+    }
+
+    fn connect_source<F>(
+        &self,
+        source: &BackendEndpoint,
+        callback: F,
+    ) -> Result<Self::Connection, OSStatus>
+    where
+        F: FnMut(&PacketList) + Send + 'static,
+    {
+        self.mock.connect_source(source, callback)
+    }
+
+    fn set_notify_callback<F>(&self, callback: F)
+    where
+        F: FnMut(&BackendNotification) + Send + 'static,
+    {
+        self.mock.set_notify_callback(callback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::replay::{ReplayBackend, ReplayScript};
+    use crate::{Backend, BackendNotification, RecordedPacket};
+
+    #[test]
+    fn replay_hotplug() {
+        let recorded = vec![
+            RecordedPacket {
+                timestamp: 10,
+                data: vec![0xf8],
+            },
+            RecordedPacket {
+                timestamp: 20,
+                data: vec![0xfa],
+            },
+        ];
+        let backend = ReplayBackend::new(
+            ReplayScript::new()
+                .with_destination("synth")
+                .with_source("clock")
+                .with_recorded("clock", &recorded)
+                .with_removed_source("clock")
+                .with_removed_destination("synth"),
+        );
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+        let callback_notifications = notifications.clone();
+        backend.set_notify_callback(move |notification| {
+            callback_notifications
+                .lock()
+                .unwrap()
+                .push(notification.clone())
+        });
+
+        backend.play_until(10).unwrap();
+        assert_eq!(backend.remaining(), 3);
+        assert_eq!(backend.sources().len(), 1);
+        assert_eq!(backend.destinations().len(), 1);
+
+        backend.play_all().unwrap();
+        assert!(!backend.step().unwrap());
+        let names: Vec<_> = notifications
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|notification| match notification {
+                BackendNotification::SourceAdded(endpoint) => ("+", endpoint.name.clone()),
+                BackendNotification::SourceRemoved(endpoint) => ("-", endpoint.name.clone()),
+                BackendNotification::DestinationAdded(endpoint) => ("+", endpoint.name.clone()),
+                BackendNotification::DestinationRemoved(endpoint) => ("-", endpoint.name.clone()),
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("+", "synth".to_string()),
+                ("+", "clock".to_string()),
+                ("-", "clock".to_string()),
+                ("-", "synth".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_unknown_source() {
+        let backend = ReplayBackend::new(ReplayScript::new().with_packet("nowhere", 0, &[0xf8]));
+
+        assert!(backend.step().is_err());
+        assert_eq!(backend.remaining(), 0);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::endpoints::endpoint::Endpoint;
+use crate::info::EndpointInfo;
+use crate::{Destination, Destinations, Source, Sources, UniqueId};
+
+/// The score of a candidate with the same unique id.
+const UNIQUE_ID_SCORE: u32 = 100;
+/// The score of a candidate with the same name, ignoring the case and the surrounding spaces.
+const EXACT_NAME_SCORE: u32 = 60;
+/// The score of a candidate with the same name, ignoring punctuation and spaces too.
+const NORMALIZED_NAME_SCORE: u32 = 50;
+/// The score of a candidate whose name contains the other one.
+const CONTAINED_NAME_SCORE: u32 = 30;
+/// The maximum score of a candidate sharing some of the words in the name.
+const SHARED_WORDS_SCORE: u32 = 25;
+/// The score of a candidate with the same manufacturer.
+const MANUFACTURER_SCORE: u32 = 10;
+/// The minimum score for a candidate to be considered a match.
+const MIN_SCORE: u32 = 20;
+
+/// A description of an endpoint that can be saved, to find the endpoint again later,
+/// even when its name changed slightly, for example between OS versions or USB ports.
+///
+/// ```rust,no_run
+/// use coremidi::{EndpointDescriptor, Source};
+/// let descriptor = EndpointDescriptor::from(&*Source::from_index(0).unwrap());
+/// // ... later, maybe in another run of the application
+/// if let Some(source) = descriptor.resolve_source() {
+///     println!("Found {:?}", source.display_name());
+/// }
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EndpointDescriptor {
+    pub name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub unique_id: Option<UniqueId>,
+}
+
+impl EndpointDescriptor {
+    /// Create a descriptor that only knows about the name of the endpoint.
+    ///
+    pub fn with_name(name: &str) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Score how likely it is that the candidate is the described endpoint.
+    /// Zero means that there is nothing in common.
+    ///
+    /// The unique id is the strongest hint, followed by the name (compared with both the name
+    /// and the display name of the candidate), and finally the manufacturer.
+    /// Names that only differ slightly don't score when their numbers differ, like "Port 1" and "Port 2".
+    ///
+    pub fn score(&self, candidate: &EndpointInfo) -> u32 {
+        let mut score = 0;
+        if self.unique_id.is_some() && self.unique_id == candidate.unique_id {
+            score += UNIQUE_ID_SCORE;
+        }
+        if let Some(name) = self.name.as_deref() {
+            let name_score = |candidate_name: &Option<String>| {
+                candidate_name
+                    .as_deref()
+                    .map(|candidate_name| score_names(name, candidate_name))
+                    .unwrap_or(0)
+            };
+            score += name_score(&candidate.name).max(name_score(&candidate.display_name));
+        }
+        if let (Some(manufacturer), Some(candidate_manufacturer)) = (
+            self.manufacturer.as_deref(),
+            candidate.manufacturer.as_deref(),
+        ) {
+            if normalize(manufacturer) == normalize(candidate_manufacturer) {
+                score += MANUFACTURER_SCORE;
+            }
+        }
+        score
+    }
+
+    /// Find the index of the candidate that best matches the descriptor, if any of them is good enough.
+    /// The first one wins when several candidates have the same score.
+    ///
+    pub fn best_match<'a, I>(&self, candidates: I) -> Option<usize>
+    where
+        I: IntoIterator<Item = &'a EndpointInfo>,
+    {
+        let mut best: Option<(usize, u32)> = None;
+        for (index, candidate) in candidates.into_iter().enumerate() {
+            let score = self.score(candidate);
+            let is_better = match best {
+                Some((_, best_score)) => score > best_score,
+                None => true,
+            };
+            if score >= MIN_SCORE && is_better {
+                best = Some((index, score));
+            }
+        }
+        best.map(|(index, _)| index)
+    }
+
+    /// Find the source in the system that best matches the descriptor.
+    ///
+    pub fn resolve_source(&self) -> Option<Source> {
+        let mut sources: Vec<Source> = Sources.into_iter().collect();
+        let infos: Vec<EndpointInfo> = sources.iter().map(|source| source.info()).collect();
+        self.best_match(&infos)
+            .map(|index| sources.swap_remove(index))
+    }
+
+    /// Find the destination in the system that best matches the descriptor.
+    ///
+    pub fn resolve_destination(&self) -> Option<Destination> {
+        let mut destinations: Vec<Destination> = Destinations.into_iter().collect();
+        let infos: Vec<EndpointInfo> = destinations
+            .iter()
+            .map(|destination| destination.info())
+            .collect();
+        self.best_match(&infos)
+            .map(|index| destinations.swap_remove(index))
+    }
+}
+
+impl From<&EndpointInfo> for EndpointDescriptor {
+    fn from(info: &EndpointInfo) -> Self {
+        Self {
+            name: info.display_name.clone().or_else(|| info.name.clone()),
+            manufacturer: info.manufacturer.clone(),
+            unique_id: info.unique_id,
+        }
+    }
+}
+
+impl From<&Endpoint> for EndpointDescriptor {
+    fn from(endpoint: &Endpoint) -> Self {
+        Self::from(&endpoint.info())
+    }
+}
+
+fn score_names(name: &str, candidate: &str) -> u32 {
+    if name.trim().to_lowercase() == candidate.trim().to_lowercase() {
+        return EXACT_NAME_SCORE;
+    }
+    let name = normalize(name);
+    let candidate = normalize(candidate);
+    if name.is_empty() || candidate.is_empty() {
+        0
+    } else if name == candidate {
+        NORMALIZED_NAME_SCORE
+    } else if numbers(&name) != numbers(&candidate) {
+        // Names like "Port 1" and "Port 2" share most of their words, but they are different endpoints
+        0
+    } else if name.contains(&candidate) || candidate.contains(&name) {
+        CONTAINED_NAME_SCORE
+    } else {
+        score_shared_words(&name, &candidate)
+    }
+}
+
+/// Score the proportion of words shared by two normalized names.
+fn score_shared_words(name: &str, candidate: &str) -> u32 {
+    let words: Vec<&str> = name.split(' ').collect();
+    let candidate_words: Vec<&str> = candidate.split(' ').collect();
+    let shared = words
+        .iter()
+        .filter(|word| candidate_words.contains(word))
+        .count();
+    let total = words.len().max(candidate_words.len());
+    (SHARED_WORDS_SCORE as usize * shared / total) as u32
+}
+
+/// The words of a normalized name made only of digits, like port or bus numbers.
+fn numbers(name: &str) -> Vec<&str> {
+    name.split(' ')
+        .filter(|word| word.chars().all(|c| c.is_ascii_digit()))
+        .collect()
+}
+
+/// Lowercase the name, keeping only the alphanumeric characters and single spaces between words.
+fn normalize(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resolver::{normalize, score_names, EndpointDescriptor};
+    use crate::EndpointInfo;
+
+    fn endpoint(name: &str, unique_id: i32) -> EndpointInfo {
+        EndpointInfo {
+            name: Some(name.to_string()),
+            display_name: Some(name.to_string()),
+            manufacturer: None,
+            model: None,
+            unique_id: Some(unique_id),
+            offline: false,
+        }
+    }
+
+    #[test]
+    fn normalize_names() {
+        assert_eq!(normalize("  IAC Driver - Bus 1 "), "iac driver bus 1");
+        assert_eq!(normalize("--"), "");
+    }
+
+    #[test]
+    fn score_similar_names() {
+        assert!(score_names("Bus 1", "bus 1") > score_names("Bus-1", "bus 1"));
+        assert!(score_names("Bus-1", "bus 1") > score_names("Bus 1", "IAC Driver Bus 1"));
+        assert!(score_names("Bus 1", "IAC Driver Bus 1") > score_names("Bus 1", "Bus 2"));
+        assert_eq!(score_names("Bus 1", "Keyboard"), 0);
+        assert_eq!(score_names("Bus 1", "Bus 12"), 0);
+    }
+
+    #[test]
+    fn best_match_by_unique_id() {
+        let descriptor = EndpointDescriptor {
+            name: Some("Keyboard".to_string()),
+            manufacturer: None,
+            unique_id: Some(2),
+        };
+        let candidates = vec![endpoint("Keyboard", 1), endpoint("Renamed", 2)];
+
+        assert_eq!(descriptor.best_match(&candidates), Some(1));
+    }
+
+    #[test]
+    fn best_match_by_name() {
+        let descriptor = EndpointDescriptor::with_name("USB MIDI Interface Port 1");
+        let candidates = vec![
+            endpoint("Synth", 1),
+            endpoint("USB MIDI Interface Port 2", 2),
+            endpoint("USB MIDI Interface - Port 1", 3),
+        ];
+
+        assert_eq!(descriptor.best_match(&candidates), Some(2));
+    }
+
+    #[test]
+    fn best_match_not_by_other_port_number() {
+        let descriptor = EndpointDescriptor::with_name("USB MIDI Interface Port 1");
+        let candidates = vec![
+            endpoint("Synth", 1),
+            endpoint("USB MIDI Interface Port 2", 2),
+        ];
+
+        assert_eq!(descriptor.best_match(&candidates), None);
+    }
+
+    #[test]
+    fn best_match_none() {
+        let descriptor = EndpointDescriptor::with_name("Keyboard");
+        let candidates = vec![endpoint("Synth", 1)];
+
+        assert_eq!(descriptor.best_match(&candidates), None);
+    }
+}
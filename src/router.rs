@@ -0,0 +1,280 @@
+use core_foundation::base::OSStatus;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::time::{host_time_from_duration, host_time_now};
+use crate::transforms::{route_transform, KeyboardZone};
+use crate::{
+    Client, Destination, EventBuffer, EventList, InputPortWithContext, OutputPort, Protocol,
+    Source, Timestamp,
+};
+
+/// A function transforming the packets forwarded to a destination.
+///
+/// It receives the timestamp and the data of every incoming packet, and it pushes the packets
+/// to forward into the buffer, which allows to drop, modify or multiply them.
+///
+pub type RouteTransform = Box<dyn FnMut(Timestamp, &[u32], &mut EventBuffer) + Send + 'static>;
+
+struct Route {
+    destination: Destination,
+    transform: Option<RouteTransform>,
+    buffer: EventBuffer,
+}
+
+impl Route {
+    fn new(
+        destination: &Destination,
+        transform: Option<RouteTransform>,
+        protocol: Protocol,
+    ) -> Self {
+        Self {
+            destination: destination.clone(),
+            transform,
+            buffer: EventBuffer::new(protocol),
+        }
+    }
+
+    fn process<'a>(&'a mut self, event_list: &'a EventList, offset: Timestamp) -> &'a EventList {
+        if self.transform.is_none() && offset == 0 {
+            return event_list;
+        }
+        self.buffer.clear();
+        for packet in event_list.iter() {
+            let timestamp = offset_timestamp(packet.timestamp(), offset);
+            match self.transform.as_mut() {
+                Some(transform) => (transform)(timestamp, packet.data(), &mut self.buffer),
+                None => {
+                    self.buffer.push(timestamp, packet.data());
+                }
+            }
+        }
+        &self.buffer
+    }
+}
+
+/// Delay a timestamp, taking into account that a zero timestamp means now.
+fn offset_timestamp(timestamp: Timestamp, offset: Timestamp) -> Timestamp {
+    match (timestamp, offset) {
+        (_, 0) => timestamp,
+        (0, _) => host_time_now().saturating_add(offset),
+        _ => timestamp.saturating_add(offset),
+    }
+}
+
+struct RouterState {
+    output_port: OutputPort,
+    routes: Mutex<Vec<Route>>,
+    timestamp_offset: AtomicU64,
+}
+
+impl RouterState {
+    fn forward(&self, event_list: &EventList) {
+        let offset = self.timestamp_offset.load(Ordering::Relaxed);
+        for route in self.lock_routes().iter_mut() {
+            let destination = route.destination.clone();
+            let packets = route.process(event_list, offset);
+            if !packets.is_empty() {
+                // There is nobody to report the error to from the receiving thread
+                let _ = self.output_port.send(&destination, packets);
+            }
+        }
+    }
+
+    fn lock_routes(&self) -> MutexGuard<'_, Vec<Route>> {
+        self.routes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A software router forwarding the MIDI data from a set of sources to a set of destinations.
+///
+/// It owns an input port connected to the sources, and an output port sending to the destinations,
+/// optionally transforming the data for every destination on the way.
+///
+/// ```rust,no_run
+/// use coremidi::{Client, Destination, Protocol, Router, Source};
+/// let client = Client::new("example-client").unwrap();
+/// let mut router = Router::new(&client, "example-router", Protocol::Midi10).unwrap();
+/// router.connect_source(&Source::from_index(0).unwrap()).unwrap();
+/// router.add_destination(&Destination::from_index(0).unwrap());
+/// // Forward only the first word of every packet to the second destination
+/// router.add_destination_with_transform(&Destination::from_index(1).unwrap(), |timestamp, data, output| {
+///     output.push(timestamp, &data[..1]);
+/// });
+/// ```
+///
+pub struct Router {
+    // The input port must be dropped before the state, so the callback doesn't outlive it
+    input_port: InputPortWithContext<()>,
+    state: Arc<RouterState>,
+    protocol: Protocol,
+}
+
+impl Router {
+    /// Create a router with its own input and output ports, both named `name`.
+    ///
+    pub fn new(client: &Client, name: &str, protocol: Protocol) -> Result<Router, OSStatus> {
+        let state = Arc::new(RouterState {
+            output_port: client.output_port(name)?,
+            routes: Mutex::new(Vec::new()),
+            timestamp_offset: AtomicU64::new(0),
+        });
+        let callback_state = state.clone();
+        let input_port =
+            client.input_port_with_protocol(name, protocol, move |event_list, _: &mut ()| {
+                callback_state.forward(event_list)
+            })?;
+        Ok(Router {
+            input_port,
+            state,
+            protocol,
+        })
+    }
+
+    /// Start forwarding the data coming from a source.
+    ///
+    pub fn connect_source(&mut self, source: &Source) -> Result<(), OSStatus> {
+        self.input_port.connect_source(source, ())
+    }
+
+    /// Stop forwarding the data coming from a source.
+    ///
+    pub fn disconnect_source(&mut self, source: &Source) -> Result<(), OSStatus> {
+        self.input_port.disconnect_source(source)
+    }
+
+    /// Forward the data, as it comes, to a destination.
+    ///
+    pub fn add_destination(&self, destination: &Destination) {
+        self.add_route(Route::new(destination, None, self.protocol));
+    }
+
+    /// Forward the data to a destination, transforming it first.
+    /// See [RouteTransform] for further details.
+    ///
+    pub fn add_destination_with_transform<F>(&self, destination: &Destination, transform: F)
+    where
+        F: FnMut(Timestamp, &[u32], &mut EventBuffer) + Send + 'static,
+    {
+        let transform: RouteTransform = Box::new(transform);
+        self.add_route(Route::new(destination, Some(transform), self.protocol));
+    }
+
+    /// Forward the data within a keyboard zone to a destination.
+    /// See [KeyboardZone] for further details.
+    ///
+    pub fn add_zone(&self, destination: &Destination, zone: KeyboardZone) {
+        self.add_destination_with_transform(destination, route_transform(zone));
+    }
+
+    /// Stop forwarding data to a destination.
+    ///
+    pub fn remove_destination(&self, destination: &Destination) {
+        self.state
+            .lock_routes()
+            .retain(|route| &route.destination != destination);
+    }
+
+    /// Delay the forwarded data by a fixed amount of host time units.
+    ///
+    /// It allows to compensate for a known latency downstream, or to delay the data on purpose.
+    /// The data is forwarded as soon as it arrives, but it is scheduled to be delivered later.
+    ///
+    pub fn set_timestamp_offset(&self, offset: Timestamp) {
+        self.state.timestamp_offset.store(offset, Ordering::Relaxed);
+    }
+
+    /// Delay the forwarded data by a fixed duration.
+    /// See [Router::set_timestamp_offset] for further details.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Client, Protocol, Router};
+    /// use std::time::Duration;
+    /// let client = Client::new("example-client").unwrap();
+    /// let router = Router::new(&client, "example-router", Protocol::Midi10).unwrap();
+    /// router.set_timestamp_offset_duration(Duration::from_millis(10));
+    /// ```
+    ///
+    pub fn set_timestamp_offset_duration(&self, offset: Duration) {
+        self.set_timestamp_offset(host_time_from_duration(offset));
+    }
+
+    /// The delay applied to the forwarded data, in host time units.
+    ///
+    pub fn timestamp_offset(&self) -> Timestamp {
+        self.state.timestamp_offset.load(Ordering::Relaxed)
+    }
+
+    /// The number of destinations the data is forwarded to.
+    ///
+    pub fn num_destinations(&self) -> usize {
+        self.state.lock_routes().len()
+    }
+
+    fn add_route(&self, route: Route) {
+        self.state.lock_routes().push(route);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::router::{offset_timestamp, Route};
+    use crate::{Destination, EventBuffer, Protocol, Timestamp};
+
+    fn packets(buffer: &EventBuffer) -> Vec<(Timestamp, Vec<u32>)> {
+        buffer
+            .iter()
+            .map(|packet| (packet.timestamp(), packet.data().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn route_without_transform() {
+        let mut route = Route::new(&Destination::new(1), None, Protocol::Midi10);
+        let input = EventBuffer::new(Protocol::Midi10).with_packet(10, &[0x20903c7f]);
+
+        let output = route.process(&input, 0);
+
+        assert_eq!(output.len(), 1);
+        assert_eq!(output.iter().next().unwrap().data(), &[0x20903c7f]);
+    }
+
+    #[test]
+    fn route_with_transform() {
+        let transform = Box::new(
+            |timestamp: Timestamp, data: &[u32], output: &mut EventBuffer| {
+                if timestamp > 10 {
+                    output.push(timestamp, data);
+                }
+            },
+        );
+        let mut route = Route::new(&Destination::new(1), Some(transform), Protocol::Midi10);
+        let input = EventBuffer::new(Protocol::Midi10)
+            .with_packet(10, &[0x20903c7f])
+            .with_packet(20, &[0x20803c00]);
+
+        route.process(&input, 0);
+
+        assert_eq!(packets(&route.buffer), vec![(20, vec![0x20803c00])]);
+    }
+
+    #[test]
+    fn route_with_offset() {
+        let mut route = Route::new(&Destination::new(1), None, Protocol::Midi10);
+        let input = EventBuffer::new(Protocol::Midi10).with_packet(10, &[0x20903c7f]);
+
+        route.process(&input, 5);
+
+        assert_eq!(packets(&route.buffer), vec![(15, vec![0x20903c7f])]);
+    }
+
+    #[test]
+    fn offset_zero_timestamp() {
+        assert_eq!(offset_timestamp(0, 0), 0);
+        assert_eq!(offset_timestamp(10, 5), 15);
+        assert!(offset_timestamp(0, 5) >= 5);
+    }
+}
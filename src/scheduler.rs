@@ -0,0 +1,356 @@
+use core_foundation::base::OSStatus;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::{
+    host_time_from_duration, host_time_now, Client, Destination, OutputPort, Properties, Timestamp,
+};
+
+/// The events waiting to be sent, in time order (and in the order they were scheduled for the same time).
+#[derive(Debug, Default)]
+struct EventQueue {
+    events: BinaryHeap<Reverse<(Timestamp, u64, Vec<u8>)>>,
+    next_sequence: u64,
+}
+
+impl EventQueue {
+    fn push(&mut self, timestamp: Timestamp, data: &[u8]) {
+        self.events
+            .push(Reverse((timestamp, self.next_sequence, data.to_vec())));
+        self.next_sequence += 1;
+    }
+
+    /// Move the events up to `until` (included) into `due`.
+    fn pop_until(&mut self, until: Timestamp, due: &mut Vec<(Timestamp, Vec<u8>)>) {
+        while let Some(Reverse((timestamp, _, _))) = self.events.peek() {
+            if *timestamp > until {
+                break;
+            }
+            if let Some(Reverse((timestamp, _, data))) = self.events.pop() {
+                due.push((timestamp, data));
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+/// The shortest time that the scheduler thread waits for changes, so it doesn't spin with a zero look-ahead.
+const MIN_WAIT: Duration = Duration::from_millis(1);
+
+struct State {
+    queue: EventQueue,
+    look_ahead: Duration,
+    // Whether the scheduler thread has to send all the pending events, regardless of the look-ahead
+    flushing: bool,
+    stopped: bool,
+    // The number of cancels so far, to tell whether the events popped by the scheduler thread were cancelled
+    cancels: u64,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    // Held while sending and cancelling, always before locking the state
+    sending: Mutex<()>,
+}
+
+impl Shared {
+    fn new(look_ahead: Duration) -> Self {
+        Self {
+            state: Mutex::new(State {
+                queue: EventQueue::default(),
+                look_ahead,
+                flushing: false,
+                stopped: false,
+                cancels: 0,
+            }),
+            changed: Condvar::new(),
+            sending: Mutex::new(()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_sending(&self) -> MutexGuard<'_, ()> {
+        self.sending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Drop the pending events, and the ones popped by the scheduler thread that were not sent yet,
+    /// and then call `flush` while nothing else can be sent.
+    fn cancel<R>(&self, flush: impl FnOnce() -> R) -> R {
+        let _sending = self.lock_sending();
+        {
+            let mut state = self.lock();
+            state.queue.clear();
+            state.cancels += 1;
+        }
+        flush()
+    }
+
+    /// Call `send` unless there was a cancel since the events were popped (when there were `cancels`).
+    fn send_unless_cancelled(&self, cancels: u64, send: impl FnOnce()) {
+        let _sending = self.lock_sending();
+        if self.lock().cancels == cancels {
+            send();
+        }
+    }
+}
+
+/// Plays events scheduled in the future, handing them to CoreMIDI ahead of time.
+///
+/// The events are kept by the scheduler until they fall within the look-ahead window, and then they are
+/// sent in packet lists with their timestamps (see [OutputPort::send_iter]), so CoreMIDI (and the driver) deliver them on time.
+/// This keeps the latency low for the events scheduled at the last moment, while still allowing
+/// to cancel the events that were not sent yet (for example when the user stops the playback).
+///
+/// The look-ahead is never shorter than the time that the driver of the destination asks to receive
+/// the events in advance (see [Properties::advance_schedule_time_musec]).
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use coremidi::{host_time_from_duration, host_time_now, Client, Destination, Scheduler};
+/// let client = Client::new("example-client").unwrap();
+/// let scheduler = Scheduler::new(&client, &Destination::from_index(0).unwrap()).unwrap();
+/// let now = host_time_now();
+/// for beat in 0..8 {
+///     let timestamp = now + host_time_from_duration(Duration::from_millis(500 * beat));
+///     scheduler.schedule(timestamp, &[0x99, 0x24, 0x7f]);
+/// }
+/// // Stop playing the events not sent yet
+/// scheduler.cancel().unwrap();
+/// ```
+///
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    destination: Destination,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    /// The default time in advance to send the events to CoreMIDI.
+    ///
+    pub const DEFAULT_LOOK_AHEAD: Duration = Duration::from_millis(50);
+
+    /// Create a scheduler sending the events to a destination, through its own output port of `client`.
+    ///
+    pub fn new(client: &Client, destination: &Destination) -> Result<Scheduler, OSStatus> {
+        Self::new_with_look_ahead(client, destination, Self::DEFAULT_LOOK_AHEAD)
+    }
+
+    /// Create a scheduler that sends the events to CoreMIDI `look_ahead` in advance,
+    /// or the time asked by the driver of the destination if it is longer.
+    ///
+    pub fn new_with_look_ahead(
+        client: &Client,
+        destination: &Destination,
+        look_ahead: Duration,
+    ) -> Result<Scheduler, OSStatus> {
+        let output_port = client.output_port("scheduler")?;
+        let min_look_ahead = destination
+            .get_property(&Properties::advance_schedule_time_musec())
+            .map_or(Duration::from_micros(0), |musec: i32| {
+                Duration::from_micros(musec.max(0) as u64)
+            });
+        let shared = Arc::new(Shared::new(look_ahead.max(min_look_ahead)));
+        let thread_shared = shared.clone();
+        let thread_destination = destination.clone();
+        let thread =
+            thread::spawn(move || run_scheduler(&thread_shared, &output_port, &thread_destination));
+        Ok(Scheduler {
+            shared,
+            destination: destination.clone(),
+            thread: Some(thread),
+        })
+    }
+
+    pub fn look_ahead(&self) -> Duration {
+        self.shared.lock().look_ahead
+    }
+
+    /// Schedule a message to be sent at the host time `timestamp`.
+    ///
+    pub fn schedule(&self, timestamp: Timestamp, data: &[u8]) {
+        self.schedule_iter(std::iter::once((timestamp, data)));
+    }
+
+    /// Schedule several messages at once.
+    ///
+    pub fn schedule_iter<'a, I>(&self, events: I)
+    where
+        I: IntoIterator<Item = (Timestamp, &'a [u8])>,
+    {
+        let mut state = self.shared.lock();
+        for (timestamp, data) in events {
+            state.queue.push(timestamp, data);
+        }
+        self.shared.changed.notify_all();
+    }
+
+    /// The number of events that were not sent to CoreMIDI yet.
+    ///
+    pub fn pending(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// Drop the events that were not sent yet, and flush the ones already sent to the destination
+    /// that are still waiting in CoreMIDI.
+    ///
+    pub fn cancel(&self) -> Result<(), OSStatus> {
+        self.shared.cancel(|| self.destination.flush())
+    }
+
+    /// Send all the pending events to CoreMIDI now, regardless of the look-ahead.
+    /// They are still delivered at their timestamps, but they can't be cancelled anymore
+    /// other than by flushing the destination.
+    ///
+    pub fn flush(&self) {
+        let mut state = self.shared.lock();
+        state.flushing = true;
+        self.shared.changed.notify_all();
+        while state.flushing && !state.stopped {
+            state = self
+                .shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+}
+
+fn run_scheduler(shared: &Shared, output_port: &OutputPort, destination: &Destination) {
+    let mut due = Vec::new();
+    let mut state = shared.lock();
+    while !state.stopped {
+        let until = if state.flushing {
+            Timestamp::MAX
+        } else {
+            host_time_now().saturating_add(host_time_from_duration(state.look_ahead))
+        };
+        let flushing = state.flushing;
+        state.queue.pop_until(until, &mut due);
+        let cancels = state.cancels;
+        let wait = wait_timeout(state.look_ahead);
+        if !due.is_empty() {
+            drop(state);
+            shared.send_unless_cancelled(cancels, || {
+                // Split into as many packet lists as needed to fit the maximum size of a list.
+                // There is nobody to report the errors to from the scheduler thread.
+                let events = due.iter().map(|(timestamp, data)| (*timestamp, &data[..]));
+                let _ = output_port.send_iter(destination, events);
+            });
+            due.clear();
+            state = shared.lock();
+        }
+        if flushing {
+            state.flushing = false;
+            // Wake up the ones waiting for the queue to be flushed
+            shared.changed.notify_all();
+            continue;
+        }
+        state = shared
+            .changed
+            .wait_timeout(state, wait)
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .0;
+    }
+}
+
+/// How long the scheduler thread waits for changes before checking the queue again:
+/// half the look-ahead, but not shorter than [MIN_WAIT] nor longer than the default look-ahead.
+fn wait_timeout(look_ahead: Duration) -> Duration {
+    (look_ahead / 2)
+        .min(Scheduler::DEFAULT_LOOK_AHEAD)
+        .max(MIN_WAIT)
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::scheduler::{wait_timeout, EventQueue, Shared, MIN_WAIT};
+    use crate::Scheduler;
+
+    #[test]
+    fn pop_events_in_time_order() {
+        let mut queue = EventQueue::default();
+        queue.push(30, &[0x90, 0x42, 0x7f]);
+        queue.push(10, &[0x90, 0x40, 0x7f]);
+        queue.push(20, &[0x90, 0x41, 0x7f]);
+        queue.push(10, &[0x80, 0x40, 0x00]);
+
+        let mut popped = Vec::new();
+        queue.pop_until(20, &mut popped);
+
+        assert_eq!(
+            popped,
+            vec![
+                (10, vec![0x90, 0x40, 0x7f]),
+                (10, vec![0x80, 0x40, 0x00]),
+                (20, vec![0x90, 0x41, 0x7f]),
+            ]
+        );
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn popped_events_not_sent_after_cancel() {
+        let shared = Shared::new(Duration::ZERO);
+        shared.lock().queue.push(10, &[0x90, 0x40, 0x7f]);
+        let mut due = Vec::new();
+        let cancels = {
+            let mut state = shared.lock();
+            state.queue.pop_until(10, &mut due);
+            state.cancels
+        };
+
+        let mut flushed = false;
+        shared.cancel(|| flushed = true);
+        let mut sent = false;
+        shared.send_unless_cancelled(cancels, || sent = true);
+
+        assert!(flushed);
+        assert!(!sent);
+        let cancels = shared.lock().cancels;
+        shared.send_unless_cancelled(cancels, || sent = true);
+        assert!(sent);
+    }
+
+    #[test]
+    fn wait_between_checks() {
+        assert_eq!(wait_timeout(Duration::ZERO), MIN_WAIT);
+        assert_eq!(
+            wait_timeout(Duration::from_millis(20)),
+            Duration::from_millis(10)
+        );
+        assert_eq!(
+            wait_timeout(Duration::from_secs(1)),
+            Scheduler::DEFAULT_LOOK_AHEAD
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use crate::SysexTransaction;
+
+const UNIVERSAL_NON_REAL_TIME: u8 = 0x7e;
+const DUMP_HEADER: u8 = 0x01;
+const DATA_PACKET: u8 = 0x02;
+const DUMP_REQUEST: u8 = 0x03;
+const WAIT: u8 = 0x7c;
+const CANCEL: u8 = 0x7d;
+const NAK: u8 = 0x7e;
+const ACK: u8 = 0x7f;
+
+/// The number of data bytes in every data packet of a sample dump.
+///
+pub const SAMPLE_DUMP_PACKET_SIZE: usize = 120;
+
+/// The description of a sample sent before its data in a sample dump.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleDumpHeader {
+    pub sample_number: u16,
+    /// The number of bits per sample, from 8 to 28.
+    pub bits: u8,
+    /// The sample period in nanoseconds (1e9 / sample rate).
+    pub period: u32,
+    /// The length of the sample, in words (samples).
+    pub length: u32,
+    pub loop_start: u32,
+    pub loop_end: u32,
+    /// 0 for forward only, 1 for backward/forward, 0x7f for loop off.
+    pub loop_type: u8,
+}
+
+/// A message of the MIDI Sample Dump Standard, used to transfer samples to and from hardware samplers.
+///
+/// The messages can be built into SysEx data with [to_sysex](SampleDumpMessage::to_sysex) and parsed
+/// from the SysEx data received with [parse](SampleDumpMessage::parse). The handshake replies of the device
+/// can be waited for with the [SysexTransaction] returned by [transaction](SampleDumpMessage::transaction).
+///
+/// ```
+/// use coremidi::{SampleDumpHeader, SampleDumpMessage};
+/// let header = SampleDumpMessage::Header(SampleDumpHeader {
+///     sample_number: 1,
+///     bits: 16,
+///     period: 22675,
+///     length: 44100,
+///     loop_start: 0,
+///     loop_end: 44099,
+///     loop_type: 0x7f,
+/// });
+/// let sysex = header.to_sysex(0);
+/// assert_eq!(SampleDumpMessage::parse(&sysex), Some((0, header)));
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SampleDumpMessage {
+    Header(SampleDumpHeader),
+    /// A packet of [SAMPLE_DUMP_PACKET_SIZE] bytes of sample data, numbered from 0 to 127 (wrapping around).
+    Packet {
+        number: u8,
+        data: Vec<u8>,
+    },
+    /// A data packet received with a wrong checksum, that should be replied with a NAK.
+    CorruptPacket {
+        number: u8,
+    },
+    Request {
+        sample_number: u16,
+    },
+    Ack(u8),
+    Nak(u8),
+    Cancel(u8),
+    Wait(u8),
+}
+
+impl SampleDumpMessage {
+    /// Build the SysEx data of the message, for a device channel (or ID) from 0 to 127.
+    ///
+    pub fn to_sysex(&self, channel: u8) -> Vec<u8> {
+        let mut sysex = vec![0xf0, UNIVERSAL_NON_REAL_TIME, channel & 0x7f];
+        match self {
+            SampleDumpMessage::Header(header) => {
+                sysex.push(DUMP_HEADER);
+                push_value(&mut sysex, header.sample_number as u32, 2);
+                sysex.push(header.bits & 0x7f);
+                push_value(&mut sysex, header.period, 3);
+                push_value(&mut sysex, header.length, 3);
+                push_value(&mut sysex, header.loop_start, 3);
+                push_value(&mut sysex, header.loop_end, 3);
+                sysex.push(header.loop_type & 0x7f);
+            }
+            SampleDumpMessage::Packet { number, data } => {
+                sysex.extend_from_slice(&[DATA_PACKET, number & 0x7f]);
+                sysex.extend(
+                    data.iter()
+                        .chain(std::iter::repeat(&0))
+                        .take(SAMPLE_DUMP_PACKET_SIZE)
+                        .map(|byte| byte & 0x7f),
+                );
+                sysex.push(checksum(&sysex[1..]));
+            }
+            SampleDumpMessage::CorruptPacket { number } => {
+                // There is no such message to send, a NAK is the answer to it
+                sysex.extend_from_slice(&[NAK, number & 0x7f]);
+            }
+            SampleDumpMessage::Request { sample_number } => {
+                sysex.push(DUMP_REQUEST);
+                push_value(&mut sysex, *sample_number as u32, 2);
+            }
+            SampleDumpMessage::Ack(number) => sysex.extend_from_slice(&[ACK, number & 0x7f]),
+            SampleDumpMessage::Nak(number) => sysex.extend_from_slice(&[NAK, number & 0x7f]),
+            SampleDumpMessage::Cancel(number) => sysex.extend_from_slice(&[CANCEL, number & 0x7f]),
+            SampleDumpMessage::Wait(number) => sysex.extend_from_slice(&[WAIT, number & 0x7f]),
+        }
+        sysex.push(0xf7);
+        sysex
+    }
+
+    /// Parse the SysEx data of a message (including the 0xF0 and 0xF7 bytes),
+    /// returning the device channel and the message.
+    ///
+    pub fn parse(sysex: &[u8]) -> Option<(u8, SampleDumpMessage)> {
+        let body = match sysex {
+            [0xf0, UNIVERSAL_NON_REAL_TIME, body @ .., 0xf7] => body,
+            _ => return None,
+        };
+        let (channel, id, data) = match body {
+            [channel, id, data @ ..] => (*channel, *id, data),
+            _ => return None,
+        };
+        let message = match (id, data) {
+            (DUMP_HEADER, data) if data.len() == 16 => {
+                SampleDumpMessage::Header(SampleDumpHeader {
+                    sample_number: read_value(&data[0..2]) as u16,
+                    bits: data[2],
+                    period: read_value(&data[3..6]),
+                    length: read_value(&data[6..9]),
+                    loop_start: read_value(&data[9..12]),
+                    loop_end: read_value(&data[12..15]),
+                    loop_type: data[15],
+                })
+            }
+            (DATA_PACKET, [number, data @ .., sum]) if data.len() == SAMPLE_DUMP_PACKET_SIZE => {
+                if checksum(&sysex[1..sysex.len() - 2]) == *sum {
+                    SampleDumpMessage::Packet {
+                        number: *number,
+                        data: data.to_vec(),
+                    }
+                } else {
+                    SampleDumpMessage::CorruptPacket { number: *number }
+                }
+            }
+            (DUMP_REQUEST, [lsb, msb]) => SampleDumpMessage::Request {
+                sample_number: read_value(&[*lsb, *msb]) as u16,
+            },
+            (ACK, [number]) => SampleDumpMessage::Ack(*number),
+            (NAK, [number]) => SampleDumpMessage::Nak(*number),
+            (CANCEL, [number]) => SampleDumpMessage::Cancel(*number),
+            (WAIT, [number]) => SampleDumpMessage::Wait(*number),
+            _ => return None,
+        };
+        Some((channel, message))
+    }
+
+    /// A transaction sending this message to a device, and waiting for its handshake reply
+    /// (ACK, NAK, CANCEL or WAIT), which can be parsed with [parse](SampleDumpMessage::parse).
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Client, Destination, SampleDumpMessage, Source};
+    /// let client = Client::new("example-client").unwrap();
+    /// let (destination, source) = (Destination::from_index(0).unwrap(), Source::from_index(0).unwrap());
+    /// let packet = SampleDumpMessage::Packet { number: 0, data: vec![0; 120] };
+    /// let reply = packet.transaction(0).run(&client, &destination, &source).unwrap();
+    /// match reply.and_then(|reply| SampleDumpMessage::parse(&reply)) {
+    ///     Some((_, SampleDumpMessage::Ack(_))) => println!("Next packet"),
+    ///     _ => println!("Send it again, or give up"),
+    /// }
+    /// ```
+    pub fn transaction(&self, channel: u8) -> SysexTransaction {
+        let channel = channel & 0x7f;
+        SysexTransaction::new(&self.to_sysex(channel)).with_reply_filter(move |reply| {
+            matches!(reply, [0xf0, UNIVERSAL_NON_REAL_TIME, reply_channel, WAIT..=ACK, _, 0xf7]
+                if *reply_channel == channel)
+        })
+    }
+}
+
+/// Encode samples of `bits` bits into the data packets of a sample dump, numbered from `first_number`.
+///
+/// The samples are unsigned, with 0 as the full negative value, and every one of them is stored
+/// left justified into as many 7 bits bytes as needed.
+///
+pub fn sample_dump_packets(samples: &[u32], bits: u8, first_number: u8) -> Vec<SampleDumpMessage> {
+    let bits = bits.clamp(1, 28) as u32;
+    let bytes_per_sample = ((bits - 1) / 7 + 1) as usize;
+    let mut data = Vec::with_capacity(samples.len() * bytes_per_sample);
+    for sample in samples {
+        let justified = (sample & ((1 << bits) - 1)) << (bytes_per_sample as u32 * 7 - bits);
+        for byte in (0..bytes_per_sample).rev() {
+            data.push(((justified >> (byte * 7)) & 0x7f) as u8);
+        }
+    }
+    // The packets are filled with whole samples
+    let packet_size = SAMPLE_DUMP_PACKET_SIZE / bytes_per_sample * bytes_per_sample;
+    data.chunks(packet_size)
+        .enumerate()
+        .map(|(index, chunk)| SampleDumpMessage::Packet {
+            number: (first_number as usize + index) as u8 & 0x7f,
+            data: chunk.to_vec(),
+        })
+        .collect()
+}
+
+fn push_value(sysex: &mut Vec<u8>, value: u32, bytes: usize) {
+    for byte in 0..bytes {
+        sysex.push(((value >> (byte * 7)) & 0x7f) as u8);
+    }
+}
+
+fn read_value(data: &[u8]) -> u32 {
+    data.iter()
+        .rev()
+        .fold(0, |value, byte| (value << 7) | (byte & 0x7f) as u32)
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum ^ byte) & 0x7f
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sds::{sample_dump_packets, SampleDumpMessage, SAMPLE_DUMP_PACKET_SIZE};
+
+    #[test]
+    fn data_packet_checksum() {
+        let packet = SampleDumpMessage::Packet {
+            number: 5,
+            data: vec![0x11; SAMPLE_DUMP_PACKET_SIZE],
+        };
+        let mut sysex = packet.to_sysex(3);
+        assert_eq!(sysex.len(), 127);
+        assert_eq!(SampleDumpMessage::parse(&sysex), Some((3, packet)));
+
+        sysex[10] ^= 0x01;
+        assert_eq!(
+            SampleDumpMessage::parse(&sysex),
+            Some((3, SampleDumpMessage::CorruptPacket { number: 5 }))
+        );
+    }
+
+    #[test]
+    fn handshake() {
+        assert_eq!(
+            SampleDumpMessage::Ack(7).to_sysex(1),
+            vec![0xf0, 0x7e, 0x01, 0x7f, 0x07, 0xf7]
+        );
+        assert_eq!(
+            SampleDumpMessage::parse(&[0xf0, 0x7e, 0x01, 0x03, 0x02, 0x01, 0xf7]),
+            Some((
+                1,
+                SampleDumpMessage::Request {
+                    sample_number: 0x82
+                }
+            ))
+        );
+        assert_eq!(
+            SampleDumpMessage::parse(&[0xf0, 0x7f, 0x01, 0x7f, 0x07, 0xf7]),
+            None
+        );
+    }
+
+    #[test]
+    fn encode_16bits_samples() {
+        let packets = sample_dump_packets(&[0xffff, 0x8000], 16, 127);
+
+        assert_eq!(
+            packets,
+            vec![SampleDumpMessage::Packet {
+                number: 127,
+                data: vec![0x7f, 0x7f, 0x60, 0x40, 0x00, 0x00],
+            }]
+        );
+        assert_eq!(sample_dump_packets(&[0; 41], 16, 127).len(), 2);
+    }
+}
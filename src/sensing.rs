@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{EventList, PacketList};
+
+const ACTIVE_SENSING: u8 = 0xfe;
+
+struct State<K> {
+    // The sources that sent active sensing, with the last time anything was received from them
+    sources: HashMap<K, Instant>,
+    timeout: Duration,
+    stopped: bool,
+}
+
+impl<K: Eq + Hash + Clone> State<K> {
+    fn received(&mut self, source: &K, active_sensing: bool, now: Instant) -> bool {
+        match self.sources.get_mut(source) {
+            Some(last) => {
+                *last = now;
+                false
+            }
+            None if active_sensing => {
+                self.sources.insert(source.clone(), now);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expire(&mut self, now: Instant) -> Vec<K> {
+        let timeout = self.timeout;
+        let expired: Vec<K> = self
+            .sources
+            .iter()
+            .filter(|(_, last)| now.saturating_duration_since(**last) > timeout)
+            .map(|(source, _)| source.clone())
+            .collect();
+        for source in expired.iter() {
+            self.sources.remove(source);
+        }
+        expired
+    }
+
+    fn next_check(&self, now: Instant) -> Duration {
+        self.sources
+            .values()
+            .map(|last| (*last + self.timeout).saturating_duration_since(now))
+            .min()
+            .map_or(self.timeout, |wait| wait + Duration::from_millis(1))
+    }
+}
+
+struct Shared<K> {
+    state: Mutex<State<K>>,
+    changed: Condvar,
+}
+
+impl<K> Shared<K> {
+    fn lock(&self) -> MutexGuard<'_, State<K>> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Watches the Active Sensing (0xFE) messages sent by the sources connected to an input port.
+///
+/// As stated by the MIDI 1.0 specification, once a source has sent an Active Sensing message,
+/// it is expected to send some data at least every 300 ms. Otherwise the connection is assumed to be lost,
+/// and the receiver should turn off the notes still playing. The watchdog calls the callback
+/// (from its own thread) with the source that timed out, which is not watched anymore until it sends
+/// Active Sensing again.
+///
+/// The sources can be anything identifying them, like the [Source](crate::Source) given as context
+/// to an [InputPortWithContext](crate::InputPortWithContext).
+///
+/// ```rust,no_run
+/// use std::sync::Arc;
+/// use coremidi::{ActiveSensingWatchdog, Client, Protocol, Source, Sources};
+/// let watchdog = Arc::new(ActiveSensingWatchdog::new(|source: &Source| {
+///     println!("Connection lost with {:?}, turning off all the notes", source.display_name());
+/// }));
+/// let callback_watchdog = watchdog.clone();
+/// let client = Client::new("example-client").unwrap();
+/// let mut input_port = client.input_port_with_protocol("example-port", Protocol::Midi10, move |event_list, source: &mut Source| {
+///     callback_watchdog.process_events(source, event_list);
+/// }).unwrap();
+/// for source in Sources {
+///     input_port.connect_source(&source, source.clone()).unwrap();
+/// }
+/// ```
+///
+pub struct ActiveSensingWatchdog<K> {
+    shared: Arc<Shared<K>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<K> ActiveSensingWatchdog<K>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+{
+    /// The time without data after which a source is considered disconnected.
+    ///
+    pub const TIMEOUT: Duration = Duration::from_millis(300);
+
+    /// Start watching, calling `callback` with every source that stops sending data.
+    ///
+    pub fn new<F>(mut callback: F) -> Self
+    where
+        F: FnMut(&K) + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                sources: HashMap::new(),
+                timeout: Self::TIMEOUT,
+                stopped: false,
+            }),
+            changed: Condvar::new(),
+        });
+        let thread_shared = shared.clone();
+        let thread = thread::spawn(move || {
+            let mut state = thread_shared.lock();
+            while !state.stopped {
+                let now = Instant::now();
+                let expired = state.expire(now);
+                if !expired.is_empty() {
+                    // The callback is called without holding the lock, so it can use the watchdog
+                    drop(state);
+                    for source in expired.iter() {
+                        callback(source);
+                    }
+                    state = thread_shared.lock();
+                    continue;
+                }
+                let wait = state.next_check(now);
+                state = thread_shared
+                    .changed
+                    .wait_timeout(state, wait)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .0;
+            }
+        });
+        Self {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Use a different timeout than the one from the specification.
+    ///
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.shared.lock().timeout = timeout;
+        self.shared.changed.notify_one();
+        self
+    }
+
+    /// Process the data received from a source as a list of MIDI 1.0 packets.
+    ///
+    pub fn process_packets(&self, source: &K, packet_list: &PacketList) {
+        let mut data = packet_list.iter().map(|packet| packet.data()).peekable();
+        if data.peek().is_some() {
+            let active_sensing = data.any(|data| data.contains(&ACTIVE_SENSING));
+            self.process(source, active_sensing);
+        }
+    }
+
+    /// Process the data received from a source as a list of Universal MIDI Packets.
+    ///
+    pub fn process_events(&self, source: &K, event_list: &EventList) {
+        let mut words = event_list
+            .iter()
+            .flat_map(|packet| packet.data().iter())
+            .peekable();
+        if words.peek().is_some() {
+            let active_sensing =
+                words.any(|word| word >> 28 == 0x1 && (word >> 16) as u8 == ACTIVE_SENSING);
+            self.process(source, active_sensing);
+        }
+    }
+
+    /// Tell that some data was received from a source, and whether it included an Active Sensing message.
+    ///
+    pub fn process(&self, source: &K, active_sensing: bool) {
+        let started = self
+            .shared
+            .lock()
+            .received(source, active_sensing, Instant::now());
+        if started {
+            self.shared.changed.notify_one();
+        }
+    }
+
+    /// Stop watching a source (for example after disconnecting it), without calling the callback.
+    ///
+    pub fn remove(&self, source: &K) {
+        self.shared.lock().sources.remove(source);
+    }
+
+    /// Whether a source has sent Active Sensing and is being watched.
+    ///
+    pub fn is_watching(&self, source: &K) -> bool {
+        self.shared.lock().sources.contains_key(source)
+    }
+}
+
+impl<K> Drop for ActiveSensingWatchdog<K> {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    use crate::sensing::{ActiveSensingWatchdog, State};
+    use crate::{EventBuffer, PacketBuffer, Protocol};
+
+    #[test]
+    fn expire_only_sources_sending_active_sensing() {
+        let mut state = State {
+            sources: Default::default(),
+            timeout: Duration::from_millis(300),
+            stopped: false,
+        };
+        let start = Instant::now();
+        assert!(!state.received(&1, false, start));
+        assert!(state.received(&2, true, start));
+        assert!(!state.received(&2, false, start + Duration::from_millis(200)));
+
+        assert!(state.expire(start + Duration::from_millis(400)).is_empty());
+        assert_eq!(state.expire(start + Duration::from_millis(501)), vec![2]);
+        assert!(state.sources.is_empty());
+    }
+
+    #[test]
+    fn fire_when_data_stops() {
+        let (sender, receiver) = mpsc::channel();
+        let watchdog = ActiveSensingWatchdog::new(move |source: &u32| {
+            let _ = sender.send(*source);
+        })
+        .with_timeout(Duration::from_millis(20));
+
+        watchdog.process_packets(&1, &PacketBuffer::new(0, &[0x90, 0x40, 0x7f]));
+        watchdog.process_packets(&2, &PacketBuffer::new(0, &[0xfe]));
+        watchdog.process_events(
+            &3,
+            &EventBuffer::new(Protocol::Midi20).with_packet(0, &[0x10fe0000]),
+        );
+        assert!(!watchdog.is_watching(&1));
+        assert!(watchdog.is_watching(&2));
+        watchdog.remove(&3);
+
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(2));
+        assert!(!watchdog.is_watching(&2));
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use core_foundation::base::OSStatus;
+use once_cell::sync::Lazy;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::MidiError;
+
+type ServerLostCallback = Box<dyn FnMut(MidiError) + Send + 'static>;
+
+static SERVER_LOST_CALLBACK: Lazy<Mutex<Option<ServerLostCallback>>> =
+    Lazy::new(|| Mutex::new(None));
+
+// Checked first, so the errors don't need to lock anything when there is no callback
+static HAS_SERVER_LOST_CALLBACK: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // Set while the callback is called, so the clients it creates are not reported again
+    #[allow(unknown_lints, clippy::missing_const_for_thread_local)]
+    static REPORTING: Cell<bool> = Cell::new(false);
+}
+
+/// Set a callback to be called whenever creating a [Client](crate::Client) fails because the MIDI server
+/// is unavailable (see [MidiError::from_client_status]), for example to tell the user about it.
+/// The other functions failing are not reported, their errors can be checked with [MidiError::is_server_unavailable].
+///
+/// It is called from the thread creating the client, one report at a time, and the clients created
+/// from the callback itself are not reported again.
+///
+/// ```rust,no_run
+/// use coremidi::{set_server_lost_callback, Client};
+///
+/// set_server_lost_callback(|error| eprintln!("{}", error));
+/// let client = Client::new("example-client");
+/// ```
+///
+pub fn set_server_lost_callback<F>(callback: F)
+where
+    F: FnMut(MidiError) + Send + 'static,
+{
+    *lock_callback() = Some(Box::new(callback));
+    HAS_SERVER_LOST_CALLBACK.store(true, Ordering::Release);
+}
+
+/// Stop calling the callback set with [set_server_lost_callback].
+///
+pub fn clear_server_lost_callback() {
+    HAS_SERVER_LOST_CALLBACK.store(false, Ordering::Release);
+    *lock_callback() = None;
+}
+
+/// Report the error to the server lost callback, if it tells that the MIDI server is unavailable.
+fn report_error(error: MidiError) {
+    if !error.is_server_unavailable() || !HAS_SERVER_LOST_CALLBACK.load(Ordering::Acquire) {
+        return;
+    }
+    if REPORTING.with(|reporting| reporting.replace(true)) {
+        return;
+    }
+    let _reporting = Reporting;
+    if let Some(callback) = lock_callback().as_mut() {
+        callback(error);
+    }
+}
+
+/// Clears the reporting flag of the thread, even if the callback panics.
+struct Reporting;
+
+impl Drop for Reporting {
+    fn drop(&mut self) {
+        REPORTING.with(|reporting| reporting.set(false));
+    }
+}
+
+/// Report the status of a client creation to the server lost callback, when it failed.
+pub(crate) fn report_client_status(status: OSStatus) {
+    if status != 0 {
+        report_error(MidiError::from_client_status(status));
+    }
+}
+
+fn lock_callback() -> std::sync::MutexGuard<'static, Option<ServerLostCallback>> {
+    SERVER_LOST_CALLBACK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::result_from_status;
+    use crate::server::{
+        clear_server_lost_callback, report_client_status, set_server_lost_callback,
+    };
+
+    #[test]
+    fn server_lost_callback() {
+        let lost = Arc::new(AtomicUsize::new(0));
+        let callback_lost = lost.clone();
+        set_server_lost_callback(move |error| {
+            assert!(error.is_server_unavailable());
+            callback_lost.fetch_add(1, Ordering::Relaxed);
+            // Not reported again from the callback
+            report_client_status(-10839);
+        });
+
+        report_client_status(-10839);
+        report_client_status(-50);
+        report_client_status(-10835);
+        report_client_status(0);
+        // Only the client creation is reported
+        assert_eq!(result_from_status(-10839, || ()), Err(-10839));
+        assert_eq!(lost.load(Ordering::Relaxed), 2);
+
+        // The reports from other threads wait for the one being handled
+        let callback_lost = lost.clone();
+        set_server_lost_callback(move |_| {
+            callback_lost.fetch_add(1, Ordering::Relaxed);
+            thread::sleep(Duration::from_millis(20));
+        });
+        let other = thread::spawn(|| report_client_status(-10839));
+        report_client_status(-10839);
+        other.join().unwrap();
+        assert_eq!(lost.load(Ordering::Relaxed), 4);
+
+        clear_server_lost_callback();
+        report_client_status(-10839);
+        assert_eq!(lost.load(Ordering::Relaxed), 4);
+    }
+}
@@ -0,0 +1,292 @@
+use std::time::Duration;
+
+use midly::num::u28;
+use midly::stream::MidiStream;
+use midly::{Arena, MetaMessage, Timing, Track, TrackEvent, TrackEventKind};
+
+use crate::{
+    duration_from_host_time, host_time_from_duration, host_time_now, PacketBuffer, PacketList,
+    Timestamp,
+};
+
+/// The duration of a tick for the timing of a Standard MIDI File, given the tempo in microseconds per beat
+/// (500000 by default, or the one from the last [MetaMessage::Tempo] event).
+/// The tempo doesn't change the duration of the ticks of a timecode timing.
+///
+/// ```
+/// use std::time::Duration;
+/// use midly::{num::u15, Timing};
+/// let tick = coremidi::tick_duration(Timing::Metrical(u15::new(480)), 500_000);
+/// assert_eq!(tick, Duration::from_nanos(1_041_666));
+/// ```
+///
+pub fn tick_duration(timing: Timing, tempo: u32) -> Duration {
+    match timing {
+        Timing::Metrical(ticks_per_beat) => {
+            let nanos = tempo as u64 * 1000 / (ticks_per_beat.as_int().max(1) as u64);
+            Duration::from_nanos(nanos)
+        }
+        Timing::Timecode(fps, subframes) => {
+            Duration::from_secs_f64(1.0 / (fps.as_f32() as f64 * subframes.max(1) as f64))
+        }
+    }
+}
+
+/// Records the packets received from a port as the events of a `midly` track,
+/// computing the delta times from the timestamps of the packets.
+///
+/// The data that needs to be owned by the events (like SysEx messages) is allocated in the [Arena],
+/// and SysEx messages split across several packets are joined back.
+///
+/// ```
+/// use std::time::Duration;
+/// use midly::Arena;
+/// use coremidi::{PacketBuffer, TrackRecorder};
+/// let arena = Arena::new();
+/// let mut recorder = TrackRecorder::new(&arena, Duration::from_millis(1));
+/// recorder.record(&PacketBuffer::new(1, &[0x90, 0x40, 0x7f]));
+/// let track = recorder.into_track();
+/// assert_eq!(track.len(), 2); // Including the end of track
+/// ```
+///
+pub struct TrackRecorder<'a> {
+    arena: &'a Arena,
+    tick: Duration,
+    stream: MidiStream,
+    start: Option<Timestamp>,
+    last_ticks: u64,
+    events: Track<'a>,
+}
+
+impl<'a> TrackRecorder<'a> {
+    /// Create a recorder with the duration of a tick (see [tick_duration]).
+    ///
+    pub fn new(arena: &'a Arena, tick: Duration) -> Self {
+        Self {
+            arena,
+            tick,
+            stream: MidiStream::new(),
+            start: None,
+            last_ticks: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Record all the packets of a list.
+    ///
+    pub fn record(&mut self, packet_list: &PacketList) {
+        for packet in packet_list.iter() {
+            self.record_data(packet.timestamp(), packet.data());
+        }
+    }
+
+    /// Record the data of a packet. A timestamp of 0 means now, as for the packets being sent.
+    ///
+    pub fn record_data(&mut self, timestamp: Timestamp, data: &[u8]) {
+        let timestamp = if timestamp == 0 {
+            host_time_now()
+        } else {
+            timestamp
+        };
+        let start = *self.start.get_or_insert(timestamp);
+        let elapsed = duration_from_host_time(timestamp.saturating_sub(start));
+        let ticks = (elapsed.as_nanos() / self.tick.as_nanos().max(1)) as u64;
+        let mut delta = ticks.saturating_sub(self.last_ticks);
+        self.last_ticks = self.last_ticks.max(ticks);
+
+        let arena = self.arena;
+        let events = &mut self.events;
+        self.stream.feed(data, |event| {
+            events.push(TrackEvent {
+                delta: u28::new(delta.min(u28::max_value().as_int() as u64) as u32),
+                kind: event.as_track_event(arena),
+            });
+            delta = 0;
+        });
+    }
+
+    /// The events recorded so far, without the end of track.
+    ///
+    pub fn events(&self) -> &[TrackEvent<'a>] {
+        &self.events
+    }
+
+    /// Finish the recording and get the track, which ends with an end of track event.
+    ///
+    pub fn into_track(mut self) -> Track<'a> {
+        self.events.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+        self.events
+    }
+}
+
+/// The tempo of a Standard MIDI File until a [MetaMessage::Tempo] event changes it, in microseconds per beat.
+const DEFAULT_TEMPO: u32 = 500_000;
+
+/// Convert the events of a track into packets scheduled from the host time `start`,
+/// with the timing from the header of the file.
+///
+/// The duration of the ticks follows the [MetaMessage::Tempo] events of the track, starting at 120 beats per minute.
+/// The other events that can't be sent live (like the rest of meta events) only contribute their delta times.
+///
+/// ```
+/// use midly::{num::{u4, u7, u15, u28}, MidiMessage, Timing, TrackEvent, TrackEventKind};
+/// let note_on = TrackEvent {
+///     delta: u28::new(10),
+///     kind: TrackEventKind::Midi {
+///         channel: u4::new(0),
+///         message: MidiMessage::NoteOn { key: u7::new(64), vel: u7::new(127) },
+///     },
+/// };
+/// let packets = coremidi::track_to_packets(&[note_on], 0, Timing::Metrical(u15::new(480)));
+/// assert_eq!(packets.iter().next().unwrap().data(), &[0x90, 64, 127]);
+/// ```
+///
+pub fn track_to_packets(track: &[TrackEvent], start: Timestamp, timing: Timing) -> PacketBuffer {
+    let mut packets = PacketBuffer::with_capacity(0);
+    let mut data = Vec::new();
+    let mut tick = tick_duration(timing, DEFAULT_TEMPO);
+    let mut elapsed = Duration::ZERO;
+    for event in track {
+        elapsed += tick * event.delta.as_int();
+        match event.kind {
+            TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                tick = tick_duration(timing, tempo.as_int());
+            }
+            kind => {
+                if let Some(live_event) = kind.as_live_event() {
+                    data.clear();
+                    if live_event.write(&mut data).is_ok() {
+                        packets.push_data(start + host_time_from_duration(elapsed), &data);
+                    }
+                }
+            }
+        }
+    }
+    packets
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use midly::num::{u15, u24, u28, u4, u7};
+    use midly::{Arena, MetaMessage, MidiMessage, Timing, TrackEvent, TrackEventKind};
+
+    use crate::smf::{tick_duration, track_to_packets, TrackRecorder};
+    use crate::{host_time_from_duration, PacketBuffer, Timestamp};
+
+    fn note_on(delta: u32, key: u8) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: u28::new(delta),
+            kind: TrackEventKind::Midi {
+                channel: u4::new(0),
+                message: MidiMessage::NoteOn {
+                    key: u7::new(key),
+                    vel: u7::new(127),
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn metrical_tick_duration() {
+        let tick = tick_duration(Timing::Metrical(u15::new(1000)), 1_000_000);
+        assert_eq!(tick, Duration::from_millis(1));
+    }
+
+    #[test]
+    fn record_with_delta_times() {
+        let arena = Arena::new();
+        let tick = Duration::from_millis(1);
+        let mut recorder = TrackRecorder::new(&arena, tick);
+        let start = host_time_from_duration(Duration::from_secs(1));
+
+        let mut packets = PacketBuffer::new(start, &[0x90, 60, 127, 0x90, 62, 127]);
+        packets.push_data(
+            start + host_time_from_duration(Duration::from_millis(5)),
+            &[0x90, 64, 127],
+        );
+        packets.push_data(
+            start + host_time_from_duration(Duration::from_millis(12)),
+            &[0xf0, 0x01, 0x02, 0xf7],
+        );
+        recorder.record(&packets);
+        let track = recorder.into_track();
+
+        assert_eq!(track.len(), 5);
+        assert_eq!(
+            &track[..3],
+            &[note_on(0, 60), note_on(0, 62), note_on(5, 64)]
+        );
+        assert_eq!(track[3].delta, u28::new(7));
+        assert_eq!(track[3].kind, TrackEventKind::SysEx(&[0x01, 0x02, 0xf7]));
+        assert_eq!(track[4].kind, TrackEventKind::Meta(MetaMessage::EndOfTrack));
+    }
+
+    #[test]
+    fn packets_from_track() {
+        // 1 millisecond per tick
+        let timing = Timing::Metrical(u15::new(500));
+        let track = [
+            note_on(0, 60),
+            TrackEvent {
+                delta: u28::new(3),
+                kind: TrackEventKind::Meta(MetaMessage::Marker(b"verse")),
+            },
+            note_on(2, 64),
+        ];
+
+        let packets = track_to_packets(&track, 100, timing);
+
+        assert_eq!(
+            timestamped_data(&packets),
+            vec![
+                (100, vec![0x90, 60, 127]),
+                (
+                    100 + host_time_from_duration(Duration::from_millis(5)),
+                    vec![0x90, 64, 127]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn packets_from_track_with_tempo_changes() {
+        let timing = Timing::Metrical(u15::new(500));
+        let tempo = |delta, tempo| TrackEvent {
+            delta: u28::new(delta),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(tempo))),
+        };
+        let track = [
+            note_on(10, 60),
+            // 2 milliseconds per tick from here
+            tempo(5, 1_000_000),
+            note_on(5, 62),
+            // 0.5 milliseconds per tick from here
+            tempo(0, 250_000),
+            note_on(10, 64),
+        ];
+
+        let packets = track_to_packets(&track, 0, timing);
+
+        let at = |millis| host_time_from_duration(Duration::from_millis(millis));
+        assert_eq!(
+            timestamped_data(&packets),
+            vec![
+                (at(10), vec![0x90, 60, 127]),
+                (at(25), vec![0x90, 62, 127]),
+                (at(30), vec![0x90, 64, 127]),
+            ]
+        );
+    }
+
+    fn timestamped_data(packets: &PacketBuffer) -> Vec<(Timestamp, Vec<u8>)> {
+        packets
+            .iter()
+            .map(|packet| (packet.timestamp(), packet.data().to_vec()))
+            .collect()
+    }
+}
@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+
+use crate::info::{DeviceInfo, EndpointInfo};
+use crate::{Destinations, Devices, ExternalDevices, Sources, UniqueId};
+
+/// The maximum number of times to take the snapshot when the system keeps changing while taking it.
+const MAX_SNAPSHOT_ATTEMPTS: usize = 3;
+
+/// A consistent view of the MIDI objects in the system:
+/// the devices with their entities and endpoints, and the virtual endpoints not owned by any entity.
+///
+/// ```rust,no_run
+/// let snapshot = coremidi::system_snapshot();
+/// for device in &snapshot.devices {
+///     println!("{:?}", device.name);
+/// }
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SystemSnapshot {
+    pub devices: Vec<DeviceInfo>,
+    pub external_devices: Vec<DeviceInfo>,
+    pub virtual_sources: Vec<EndpointInfo>,
+    pub virtual_destinations: Vec<EndpointInfo>,
+}
+
+impl SystemSnapshot {
+    /// Compute the changes needed to go from this snapshot to a newer one.
+    ///
+    /// The objects are matched by their unique id, so the ones without it are ignored.
+    ///
+    /// ```rust,no_run
+    /// let before = coremidi::system_snapshot();
+    /// // ... after some time
+    /// let after = coremidi::system_snapshot();
+    /// for change in before.diff(&after) {
+    ///     println!("{:?}", change);
+    /// }
+    /// ```
+    ///
+    pub fn diff(&self, newer: &SystemSnapshot) -> Vec<SetupChange> {
+        let mut changes = Vec::new();
+        diff_objects(
+            self.all_devices()
+                .map(|device| (device.unique_id, &device.name, device)),
+            newer
+                .all_devices()
+                .map(|device| (device.unique_id, &device.name, device)),
+            SetupChange::DeviceAdded,
+            SetupChange::DeviceRemoved,
+            |unique_id, old_name, new_name| SetupChange::DeviceRenamed {
+                unique_id,
+                old_name,
+                new_name,
+            },
+            &mut changes,
+        );
+        diff_objects(
+            self.all_sources().map(endpoint_key),
+            newer.all_sources().map(endpoint_key),
+            SetupChange::SourceAdded,
+            SetupChange::SourceRemoved,
+            endpoint_renamed,
+            &mut changes,
+        );
+        diff_objects(
+            self.all_destinations().map(endpoint_key),
+            newer.all_destinations().map(endpoint_key),
+            SetupChange::DestinationAdded,
+            SetupChange::DestinationRemoved,
+            endpoint_renamed,
+            &mut changes,
+        );
+        changes
+    }
+
+    fn all_devices(&self) -> impl Iterator<Item = &DeviceInfo> {
+        self.devices.iter().chain(self.external_devices.iter())
+    }
+
+    fn all_sources(&self) -> impl Iterator<Item = &EndpointInfo> {
+        self.all_devices()
+            .flat_map(|device| device.entities.iter())
+            .flat_map(|entity| entity.sources.iter())
+            .chain(self.virtual_sources.iter())
+    }
+
+    fn all_destinations(&self) -> impl Iterator<Item = &EndpointInfo> {
+        self.all_devices()
+            .flat_map(|device| device.entities.iter())
+            .flat_map(|entity| entity.destinations.iter())
+            .chain(self.virtual_destinations.iter())
+    }
+}
+
+/// A change in the MIDI setup between two [SystemSnapshot]s.
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SetupChange {
+    DeviceAdded(DeviceInfo),
+    DeviceRemoved(DeviceInfo),
+    DeviceRenamed {
+        unique_id: UniqueId,
+        old_name: Option<String>,
+        new_name: Option<String>,
+    },
+    SourceAdded(EndpointInfo),
+    SourceRemoved(EndpointInfo),
+    DestinationAdded(EndpointInfo),
+    DestinationRemoved(EndpointInfo),
+    EndpointRenamed {
+        unique_id: UniqueId,
+        old_name: Option<String>,
+        new_name: Option<String>,
+    },
+}
+
+/// Keeps the latest snapshot of the system to turn the coarse [crate::Notification::SetupChanged]
+/// notifications into a list of [SetupChange]s.
+///
+/// ```rust,no_run
+/// use coremidi::{Client, Notification, SetupTracker};
+/// let mut tracker = SetupTracker::new();
+/// let client = Client::new_with_notifications("example-client", move |notification: &Notification| {
+///     if let Notification::SetupChanged = notification {
+///         for change in tracker.update() {
+///             println!("{:?}", change);
+///         }
+///     }
+/// }).unwrap();
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct SetupTracker {
+    snapshot: SystemSnapshot,
+}
+
+impl SetupTracker {
+    /// Start tracking the changes from the current state of the system.
+    ///
+    pub fn new() -> Self {
+        Self {
+            snapshot: system_snapshot(),
+        }
+    }
+
+    /// The latest snapshot taken.
+    ///
+    pub fn snapshot(&self) -> &SystemSnapshot {
+        &self.snapshot
+    }
+
+    /// Take a new snapshot, and return the changes since the previous one.
+    ///
+    pub fn update(&mut self) -> Vec<SetupChange> {
+        let snapshot = system_snapshot();
+        let changes = self.snapshot.diff(&snapshot);
+        self.snapshot = snapshot;
+        changes
+    }
+}
+
+impl Default for SetupTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type ObjectKey<'a, T> = (Option<UniqueId>, &'a Option<String>, &'a T);
+
+fn endpoint_key(endpoint: &EndpointInfo) -> ObjectKey<'_, EndpointInfo> {
+    (endpoint.unique_id, &endpoint.name, endpoint)
+}
+
+fn endpoint_renamed(
+    unique_id: UniqueId,
+    old_name: Option<String>,
+    new_name: Option<String>,
+) -> SetupChange {
+    SetupChange::EndpointRenamed {
+        unique_id,
+        old_name,
+        new_name,
+    }
+}
+
+fn diff_objects<'a, T, O, N, A, R, M>(
+    old: O,
+    new: N,
+    added: A,
+    removed: R,
+    renamed: M,
+    changes: &mut Vec<SetupChange>,
+) where
+    T: Clone + 'a,
+    O: Iterator<Item = ObjectKey<'a, T>>,
+    N: Iterator<Item = ObjectKey<'a, T>>,
+    A: Fn(T) -> SetupChange,
+    R: Fn(T) -> SetupChange,
+    M: Fn(UniqueId, Option<String>, Option<String>) -> SetupChange,
+{
+    let old: Vec<(UniqueId, &Option<String>, &T)> = old
+        .filter_map(|(unique_id, name, object)| unique_id.map(|id| (id, name, object)))
+        .collect();
+    let new: Vec<(UniqueId, &Option<String>, &T)> = new
+        .filter_map(|(unique_id, name, object)| unique_id.map(|id| (id, name, object)))
+        .collect();
+    let old_names: HashMap<UniqueId, &Option<String>> =
+        old.iter().map(|(id, name, _)| (*id, *name)).collect();
+    let new_names: HashMap<UniqueId, &Option<String>> =
+        new.iter().map(|(id, name, _)| (*id, *name)).collect();
+
+    for (unique_id, _, object) in old.iter() {
+        if !new_names.contains_key(unique_id) {
+            changes.push(removed((*object).clone()));
+        }
+    }
+    for (unique_id, name, object) in new.iter() {
+        match old_names.get(unique_id) {
+            None => changes.push(added((*object).clone())),
+            Some(old_name) if old_name != name => {
+                changes.push(renamed(*unique_id, (*old_name).clone(), (*name).clone()))
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(PartialEq)]
+struct Counts {
+    devices: usize,
+    external_devices: usize,
+    sources: usize,
+    destinations: usize,
+}
+
+impl Counts {
+    fn current() -> Self {
+        Self {
+            devices: Devices::count(),
+            external_devices: ExternalDevices::count(),
+            sources: Sources::count(),
+            destinations: Destinations::count(),
+        }
+    }
+}
+
+/// Take a snapshot of all the MIDI objects in the system.
+///
+/// The objects are enumerated again when the number of them changes in the meanwhile,
+/// so devices being plugged or unplugged while taking it don't leave it half way.
+///
+pub fn system_snapshot() -> SystemSnapshot {
+    let mut attempts = 1;
+    loop {
+        let counts = Counts::current();
+        let snapshot = take_snapshot();
+        if attempts >= MAX_SNAPSHOT_ATTEMPTS || Counts::current() == counts {
+            return snapshot;
+        }
+        attempts += 1;
+    }
+}
+
+fn take_snapshot() -> SystemSnapshot {
+    SystemSnapshot {
+        devices: Devices.into_iter().map(|device| device.info()).collect(),
+        external_devices: ExternalDevices
+            .into_iter()
+            .map(|device| device.info())
+            .collect(),
+        virtual_sources: Sources
+            .into_iter()
+            .filter(|source| source.entity().is_none())
+            .map(|source| source.info())
+            .collect(),
+        virtual_destinations: Destinations
+            .into_iter()
+            .filter(|destination| destination.entity().is_none())
+            .map(|destination| destination.info())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        system_snapshot, Client, DeviceInfo, EndpointInfo, EntityInfo, Protocol, SetupChange,
+        SystemSnapshot,
+    };
+
+    fn endpoint(unique_id: i32, name: &str) -> EndpointInfo {
+        EndpointInfo {
+            name: Some(name.to_string()),
+            display_name: Some(name.to_string()),
+            manufacturer: None,
+            model: None,
+            unique_id: Some(unique_id),
+            offline: false,
+        }
+    }
+
+    fn device(unique_id: i32, name: &str, sources: Vec<EndpointInfo>) -> DeviceInfo {
+        DeviceInfo {
+            name: Some(name.to_string()),
+            manufacturer: None,
+            model: None,
+            unique_id: Some(unique_id),
+            offline: false,
+            entities: vec![EntityInfo {
+                name: None,
+                unique_id: Some(unique_id + 1),
+                sources,
+                destinations: vec![],
+            }],
+        }
+    }
+
+    fn snapshot(
+        devices: Vec<DeviceInfo>,
+        virtual_destinations: Vec<EndpointInfo>,
+    ) -> SystemSnapshot {
+        SystemSnapshot {
+            devices,
+            external_devices: vec![],
+            virtual_sources: vec![],
+            virtual_destinations,
+        }
+    }
+
+    #[test]
+    fn diff_unchanged() {
+        let before = snapshot(vec![device(1, "D", vec![endpoint(10, "S")])], vec![]);
+
+        assert!(before.diff(&before.clone()).is_empty());
+    }
+
+    #[test]
+    fn diff_changes() {
+        let before = snapshot(
+            vec![
+                device(1, "D1", vec![endpoint(10, "S1")]),
+                device(2, "D2", vec![endpoint(20, "S2")]),
+            ],
+            vec![endpoint(30, "V")],
+        );
+        let after = snapshot(
+            vec![
+                device(1, "D1*", vec![endpoint(10, "S1*")]),
+                device(3, "D3", vec![]),
+            ],
+            vec![endpoint(30, "V"), endpoint(40, "W")],
+        );
+
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                SetupChange::DeviceRemoved(before.devices[1].clone()),
+                SetupChange::DeviceRenamed {
+                    unique_id: 1,
+                    old_name: Some("D1".to_string()),
+                    new_name: Some("D1*".to_string()),
+                },
+                SetupChange::DeviceAdded(after.devices[1].clone()),
+                SetupChange::SourceRemoved(endpoint(20, "S2")),
+                SetupChange::EndpointRenamed {
+                    unique_id: 10,
+                    old_name: Some("S1".to_string()),
+                    new_name: Some("S1*".to_string()),
+                },
+                SetupChange::DestinationAdded(endpoint(40, "W")),
+            ]
+        );
+    }
+
+    #[test]
+    fn virtual_endpoints() {
+        let client = Client::new("Test Client").unwrap();
+        let dest = client
+            .virtual_destination_with_protocol("A", Protocol::Midi10, |_| ())
+            .unwrap();
+
+        let snapshot = system_snapshot();
+
+        assert!(snapshot
+            .virtual_destinations
+            .iter()
+            .any(|info| info.unique_id == dest.unique_id()));
+    }
+}
@@ -0,0 +1,384 @@
+//! Bindings for the parts of the CoreMIDI framework that are not available from coremidi-sys.
+//!
+//! The framework is already linked by coremidi-sys, so there is no need for a `#[link]` attribute here.
+
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use core_foundation::{base::OSStatus, data::CFDataRef, string::CFStringRef};
+
+use std::os::raw::c_void;
+
+use coremidi_sys::{
+    ItemCount, MIDIDeviceRef, MIDIEndpointRef, MIDIEntityRef, MIDIObjectRef, MIDIUniqueID,
+};
+
+pub type MIDIThruConnectionRef = MIDIObjectRef;
+
+pub const kMIDIThruConnection_MaxEndpoints: usize = 8;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MIDIThruConnectionEndpoint {
+    pub endpointRef: MIDIEndpointRef,
+    pub uniqueID: MIDIUniqueID,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MIDITransform {
+    pub transform: u16,
+    pub param: i16,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MIDIThruConnectionParams {
+    pub version: u32,
+    pub numSources: u32,
+    pub sources: [MIDIThruConnectionEndpoint; kMIDIThruConnection_MaxEndpoints],
+    pub numDestinations: u32,
+    pub destinations: [MIDIThruConnectionEndpoint; kMIDIThruConnection_MaxEndpoints],
+    pub channelMap: [u8; 16],
+    pub lowVelocity: u8,
+    pub highVelocity: u8,
+    pub lowNote: u8,
+    pub highNote: u8,
+    pub noteNumber: MIDITransform,
+    pub velocity: MIDITransform,
+    pub keyPressure: MIDITransform,
+    pub channelPressure: MIDITransform,
+    pub programChange: MIDITransform,
+    pub pitchBend: MIDITransform,
+    pub filterOutSysEx: u8,
+    pub filterOutMTC: u8,
+    pub filterOutBeatClock: u8,
+    pub filterOutTuneRequest: u8,
+    pub reserved2: [u8; 3],
+    pub filterOutAllControls: u8,
+    pub numControlTransforms: u16,
+    pub numMaps: u16,
+    pub reserved3: [u16; 4],
+}
+
+extern "C" {
+    pub fn MIDIThruConnectionParamsInitialize(inConnectionParams: *mut MIDIThruConnectionParams);
+
+    pub fn MIDIThruConnectionCreate(
+        inPersistentOwnerID: CFStringRef,
+        inConnectionParams: CFDataRef,
+        outConnection: *mut MIDIThruConnectionRef,
+    ) -> OSStatus;
+
+    pub fn MIDIThruConnectionDispose(connection: MIDIThruConnectionRef) -> OSStatus;
+
+    pub fn MIDIThruConnectionGetParams(
+        connection: MIDIThruConnectionRef,
+        outConnectionParams: *mut CFDataRef,
+    ) -> OSStatus;
+
+    pub fn MIDIThruConnectionSetParams(
+        connection: MIDIThruConnectionRef,
+        inConnectionParams: CFDataRef,
+    ) -> OSStatus;
+
+    pub fn MIDIThruConnectionFind(
+        inPersistentOwnerID: CFStringRef,
+        outConnectionList: *mut CFDataRef,
+    ) -> OSStatus;
+}
+
+pub type MIDIDriverRef = *mut c_void;
+
+extern "C" {
+    pub fn MIDIDeviceCreate(
+        owner: MIDIDriverRef,
+        name: CFStringRef,
+        manufacturer: CFStringRef,
+        model: CFStringRef,
+        outDevice: *mut MIDIDeviceRef,
+    ) -> OSStatus;
+
+    pub fn MIDIDeviceAddEntity(
+        device: MIDIDeviceRef,
+        name: CFStringRef,
+        embedded: u8,
+        numSourceEndpoints: ItemCount,
+        numDestinationEndpoints: ItemCount,
+        newEntity: *mut MIDIEntityRef,
+    ) -> OSStatus;
+
+    // Looked up at runtime instead with the `runtime-linking` feature (see the availability module)
+    #[cfg(not(feature = "runtime-linking"))]
+    pub fn MIDIDeviceNewEntity(
+        device: MIDIDeviceRef,
+        name: CFStringRef,
+        protocol: coremidi_sys::MIDIProtocolID,
+        embedded: u8,
+        numSourceEndpoints: ItemCount,
+        numDestinationEndpoints: ItemCount,
+        newEntity: *mut MIDIEntityRef,
+    ) -> OSStatus;
+
+    pub fn MIDIDeviceDispose(device: MIDIDeviceRef) -> OSStatus;
+
+    pub fn MIDISetupAddDevice(device: MIDIDeviceRef) -> OSStatus;
+
+    pub fn MIDISetupRemoveDevice(device: MIDIDeviceRef) -> OSStatus;
+
+    pub fn MIDIExternalDeviceCreate(
+        name: CFStringRef,
+        manufacturer: CFStringRef,
+        model: CFStringRef,
+        outDevice: *mut MIDIDeviceRef,
+    ) -> OSStatus;
+
+    pub fn MIDISetupAddExternalDevice(device: MIDIDeviceRef) -> OSStatus;
+
+    pub fn MIDISetupRemoveExternalDevice(device: MIDIDeviceRef) -> OSStatus;
+}
+
+#[cfg(all(feature = "driver", not(target_os = "ios")))]
+pub use self::driver::*;
+
+#[cfg(all(feature = "driver", not(target_os = "ios")))]
+mod driver {
+    use core_foundation::base::OSStatus;
+    use core_foundation_sys::uuid::CFUUIDBytes;
+    use std::os::raw::c_void;
+
+    use coremidi_sys::{ItemCount, MIDIDeviceRef, MIDIEndpointRef, MIDIObjectRef, MIDIPacketList};
+
+    use super::MIDIDriverRef;
+
+    pub type MIDIDeviceListRef = MIDIObjectRef;
+
+    #[repr(C)]
+    pub struct MIDIDriverInterface {
+        pub _reserved: *mut c_void,
+        pub QueryInterface:
+            extern "C" fn(this: *mut c_void, iid: CFUUIDBytes, ppv: *mut *mut c_void) -> i32,
+        pub AddRef: extern "C" fn(this: *mut c_void) -> u32,
+        pub Release: extern "C" fn(this: *mut c_void) -> u32,
+        pub FindDevices: extern "C" fn(this: MIDIDriverRef, devList: MIDIDeviceListRef) -> OSStatus,
+        pub Start: extern "C" fn(this: MIDIDriverRef, devList: MIDIDeviceListRef) -> OSStatus,
+        pub Stop: extern "C" fn(this: MIDIDriverRef) -> OSStatus,
+        pub Configure: extern "C" fn(this: MIDIDriverRef, device: MIDIDeviceRef) -> OSStatus,
+        pub Send: extern "C" fn(
+            this: MIDIDriverRef,
+            pktlist: *const MIDIPacketList,
+            destRefCon1: *mut c_void,
+            destRefCon2: *mut c_void,
+        ) -> OSStatus,
+        pub EnableSource:
+            extern "C" fn(this: MIDIDriverRef, src: MIDIEndpointRef, enabled: u8) -> OSStatus,
+        pub Flush: extern "C" fn(
+            this: MIDIDriverRef,
+            dest: MIDIEndpointRef,
+            destRefCon1: *mut c_void,
+            destRefCon2: *mut c_void,
+        ) -> OSStatus,
+        pub Monitor: extern "C" fn(
+            this: MIDIDriverRef,
+            dest: MIDIEndpointRef,
+            pktlist: *const MIDIPacketList,
+        ) -> OSStatus,
+    }
+
+    extern "C" {
+        pub fn MIDIGetDriverDeviceList(driver: MIDIDriverRef) -> MIDIDeviceListRef;
+
+        pub fn MIDIDeviceListGetNumberOfDevices(devList: MIDIDeviceListRef) -> ItemCount;
+
+        pub fn MIDIDeviceListGetDevice(
+            devList: MIDIDeviceListRef,
+            index0: ItemCount,
+        ) -> MIDIDeviceRef;
+
+        pub fn MIDIDeviceListAddDevice(devList: MIDIDeviceListRef, dev: MIDIDeviceRef) -> OSStatus;
+
+        pub fn MIDIDeviceListDispose(devList: MIDIDeviceListRef) -> OSStatus;
+
+        pub fn MIDIEndpointSetRefCons(
+            endpt: MIDIEndpointRef,
+            ref1: *mut c_void,
+            ref2: *mut c_void,
+        ) -> OSStatus;
+
+        pub fn MIDIEndpointGetRefCons(
+            endpt: MIDIEndpointRef,
+            ref1: *mut *mut c_void,
+            ref2: *mut *mut c_void,
+        ) -> OSStatus;
+
+        pub fn MIDIDriverEnableMonitoring(driver: MIDIDriverRef, enabled: u8) -> OSStatus;
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct mach_timebase_info {
+    pub numer: u32,
+    pub denom: u32,
+}
+
+extern "C" {
+    pub fn mach_timebase_info(info: *mut mach_timebase_info) -> i32;
+
+    pub fn mach_absolute_time() -> u64;
+}
+
+// The Objective-C runtime, to use the classes of the framework (like MIDINetworkSession)
+pub type ObjcId = *mut c_void;
+pub type ObjcSel = *const c_void;
+
+#[cfg_attr(target_vendor = "apple", link(name = "objc"))]
+extern "C" {
+    pub fn objc_getClass(name: *const std::os::raw::c_char) -> ObjcId;
+
+    pub fn sel_registerName(name: *const std::os::raw::c_char) -> ObjcSel;
+
+    /// It needs to be transmuted into the signature of the method called.
+    pub fn objc_msgSend();
+
+    pub fn objc_retain(object: ObjcId) -> ObjcId;
+
+    pub fn objc_release(object: ObjcId);
+
+    pub fn objc_autoreleasePoolPush() -> *mut c_void;
+
+    pub fn objc_autoreleasePoolPop(pool: *mut c_void);
+}
+
+// DNS Service Discovery (Bonjour), from the system library
+pub type DNSServiceRef = *mut c_void;
+pub type DNSServiceFlags = u32;
+pub type DNSServiceErrorType = i32;
+
+pub const kDNSServiceFlagsAdd: DNSServiceFlags = 0x2;
+
+pub type DNSServiceBrowseReply = extern "C" fn(
+    sdRef: DNSServiceRef,
+    flags: DNSServiceFlags,
+    interfaceIndex: u32,
+    errorCode: DNSServiceErrorType,
+    serviceName: *const std::os::raw::c_char,
+    regtype: *const std::os::raw::c_char,
+    replyDomain: *const std::os::raw::c_char,
+    context: *mut c_void,
+);
+
+#[repr(C)]
+pub struct pollfd {
+    pub fd: std::os::raw::c_int,
+    pub events: i16,
+    pub revents: i16,
+}
+
+pub const POLLIN: i16 = 0x1;
+
+extern "C" {
+    pub fn DNSServiceBrowse(
+        sdRef: *mut DNSServiceRef,
+        flags: DNSServiceFlags,
+        interfaceIndex: u32,
+        regtype: *const std::os::raw::c_char,
+        domain: *const std::os::raw::c_char,
+        callBack: DNSServiceBrowseReply,
+        context: *mut c_void,
+    ) -> DNSServiceErrorType;
+
+    pub fn DNSServiceRefSockFD(sdRef: DNSServiceRef) -> std::os::raw::c_int;
+
+    pub fn DNSServiceProcessResult(sdRef: DNSServiceRef) -> DNSServiceErrorType;
+
+    pub fn DNSServiceRefDeallocate(sdRef: DNSServiceRef);
+
+    pub fn poll(
+        fds: *mut pollfd,
+        nfds: std::os::raw::c_uint,
+        timeout: std::os::raw::c_int,
+    ) -> std::os::raw::c_int;
+}
+
+// Defining Objective-C classes at runtime, for the delegates of CoreBluetooth
+#[cfg(feature = "bluetooth")]
+#[cfg_attr(target_vendor = "apple", link(name = "objc"))]
+extern "C" {
+    pub fn objc_allocateClassPair(
+        superclass: ObjcId,
+        name: *const std::os::raw::c_char,
+        extraBytes: usize,
+    ) -> ObjcId;
+
+    pub fn objc_registerClassPair(class: ObjcId);
+
+    /// The implementation needs to be transmuted from the `extern "C"` function of the method.
+    pub fn class_addMethod(
+        class: ObjcId,
+        name: ObjcSel,
+        imp: *const c_void,
+        types: *const std::os::raw::c_char,
+    ) -> i8;
+
+    pub fn class_addIvar(
+        class: ObjcId,
+        name: *const std::os::raw::c_char,
+        size: usize,
+        alignment: u8,
+        types: *const std::os::raw::c_char,
+    ) -> i8;
+
+    pub fn object_getInstanceVariable(
+        object: ObjcId,
+        name: *const std::os::raw::c_char,
+        outValue: *mut *mut c_void,
+    ) -> *mut c_void;
+
+    pub fn object_setInstanceVariable(
+        object: ObjcId,
+        name: *const std::os::raw::c_char,
+        value: *mut c_void,
+    ) -> *mut c_void;
+}
+
+// Grand Central Dispatch, from the system library
+#[cfg(feature = "bluetooth")]
+pub type DispatchQueue = *mut c_void;
+
+#[cfg(feature = "bluetooth")]
+extern "C" {
+    pub fn dispatch_queue_create(
+        label: *const std::os::raw::c_char,
+        attr: *mut c_void,
+    ) -> DispatchQueue;
+
+    pub fn dispatch_sync_f(
+        queue: DispatchQueue,
+        context: *mut c_void,
+        work: extern "C" fn(context: *mut c_void),
+    );
+
+    pub fn dispatch_release(object: *mut c_void);
+}
+
+// The classes of CoreBluetooth are looked up at runtime, but the framework needs to be loaded
+#[cfg(feature = "bluetooth")]
+#[cfg_attr(
+    target_vendor = "apple",
+    link(name = "CoreBluetooth", kind = "framework")
+)]
+extern "C" {}
+
+// Looking up the functions that might not be available in the running system
+#[cfg(feature = "runtime-linking")]
+#[cfg(target_vendor = "apple")]
+pub const RTLD_DEFAULT: *mut c_void = -2isize as *mut c_void;
+
+#[cfg(feature = "runtime-linking")]
+#[cfg(not(target_vendor = "apple"))]
+pub const RTLD_DEFAULT: *mut c_void = std::ptr::null_mut();
+
+#[cfg(feature = "runtime-linking")]
+extern "C" {
+    pub fn dlsym(handle: *mut c_void, symbol: *const std::os::raw::c_char) -> *mut c_void;
+}
@@ -0,0 +1,268 @@
+use core_foundation::base::OSStatus;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{Client, Destination, PacketBuffer, PacketList, Source};
+
+const SYSEX_START: u8 = 0xf0;
+const SYSEX_END: u8 = 0xf7;
+
+type ReplyFilter = Arc<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+/// Joins the SysEx messages that are split across several packets.
+#[derive(Debug, Default)]
+struct SysexAssembler {
+    data: Vec<u8>,
+    receiving: bool,
+}
+
+impl SysexAssembler {
+    fn feed<F>(&mut self, data: &[u8], mut on_message: F)
+    where
+        F: FnMut(&[u8]),
+    {
+        for byte in data.iter().copied() {
+            match byte {
+                SYSEX_START => {
+                    self.data.clear();
+                    self.data.push(byte);
+                    self.receiving = true;
+                }
+                SYSEX_END if self.receiving => {
+                    self.data.push(byte);
+                    self.receiving = false;
+                    on_message(&self.data);
+                }
+                // Realtime messages can be interleaved with the SysEx data
+                0xf8..=0xff => {}
+                // Any other status byte aborts the message
+                0x80..=0xff => self.receiving = false,
+                _ if self.receiving => self.data.push(byte),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Sends a SysEx request to a destination and waits for the matching reply from a source,
+/// as needed for example to dump the patches of a device.
+///
+/// The replies are matched with a header (like the manufacturer ID and the model) or any other filter,
+/// and the request is sent again when there is no reply within the timeout, up to the number of retries.
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use coremidi::{Client, Destination, Source, SysexTransaction};
+/// let client = Client::new("example-client").unwrap();
+/// let destination = Destination::from_index(0).unwrap();
+/// let source = Source::from_index(0).unwrap();
+/// // Universal Device Inquiry
+/// let reply = SysexTransaction::new(&[0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7])
+///     .with_reply_header(&[0xf0, 0x7e, 0x7f, 0x06, 0x02])
+///     .with_timeout(Duration::from_millis(500))
+///     .with_retries(3)
+///     .run(&client, &destination, &source)
+///     .unwrap();
+/// match reply {
+///     Some(reply) => println!("Device identity: {:02x?}", reply),
+///     None => println!("No reply"),
+/// }
+/// ```
+///
+pub struct SysexTransaction {
+    request: Vec<u8>,
+    reply_filter: ReplyFilter,
+    timeout: Duration,
+    retries: usize,
+}
+
+impl SysexTransaction {
+    /// The default time to wait for the reply after every request.
+    ///
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// The default number of times that the request is sent again when there is no reply.
+    ///
+    pub const DEFAULT_RETRIES: usize = 2;
+
+    /// Create a transaction for a request, including the 0xF0 and 0xF7 bytes.
+    /// By default any SysEx message received is taken as the reply.
+    ///
+    pub fn new(request: &[u8]) -> Self {
+        Self {
+            request: request.to_vec(),
+            reply_filter: Arc::new(|_| true),
+            timeout: Self::DEFAULT_TIMEOUT,
+            retries: Self::DEFAULT_RETRIES,
+        }
+    }
+
+    /// Only take as the reply the SysEx messages starting with `header` (including the 0xF0 byte).
+    ///
+    pub fn with_reply_header(self, header: &[u8]) -> Self {
+        let header = header.to_vec();
+        self.with_reply_filter(move |reply| reply.starts_with(&header))
+    }
+
+    /// Only take as the reply the SysEx messages for which `filter` returns true.
+    ///
+    pub fn with_reply_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.reply_filter = Arc::new(filter);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Send the request to the destination and wait for the reply from the source, using ports of `client`.
+    /// It returns `None` when there was no reply after all the retries.
+    ///
+    pub fn run(
+        &self,
+        client: &Client,
+        destination: &Destination,
+        source: &Source,
+    ) -> Result<Option<Vec<u8>>, OSStatus> {
+        let (sender, receiver) = mpsc::channel();
+        let reply_filter = self.reply_filter.clone();
+        let mut assembler = SysexAssembler::default();
+        let input_port = client.input_port("sysex-transaction", move |packet_list| {
+            receive_replies(&mut assembler, packet_list, |reply| {
+                if reply_filter(reply) {
+                    let _ = sender.send(reply.to_vec());
+                }
+            })
+        })?;
+        input_port.connect_source(source)?;
+        let output_port = client.output_port("sysex-transaction")?;
+        let request = PacketBuffer::new(0, &self.request);
+
+        for _ in 0..=self.retries {
+            output_port.send(destination, &request)?;
+            if let Ok(reply) = receiver.recv_timeout(self.timeout) {
+                return Ok(Some(reply));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn receive_replies<F>(assembler: &mut SysexAssembler, packet_list: &PacketList, mut on_reply: F)
+where
+    F: FnMut(&[u8]),
+{
+    for packet in packet_list.iter() {
+        assembler.feed(packet.data(), &mut on_reply);
+    }
+}
+
+/// The General MIDI System On message, resetting a device to its General MIDI defaults.
+///
+pub const GM_SYSTEM_ON: [u8; 6] = [0xf0, 0x7e, 0x7f, 0x09, 0x01, 0xf7];
+
+/// The General MIDI 2 System On message.
+///
+pub const GM2_SYSTEM_ON: [u8; 6] = [0xf0, 0x7e, 0x7f, 0x09, 0x03, 0xf7];
+
+/// The Roland GS Reset message (to device ID 0x10).
+///
+pub const GS_RESET: [u8; 11] = [
+    0xf0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7f, 0x00, 0x41, 0xf7,
+];
+
+/// The Yamaha XG System On message (to device number 0).
+///
+pub const XG_SYSTEM_ON: [u8; 9] = [0xf0, 0x43, 0x10, 0x4c, 0x00, 0x00, 0x7e, 0x00, 0xf7];
+
+/// The standard messages resetting a sound module, used by [OutputPort::send_reset](crate::OutputPort::send_reset).
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResetKind {
+    GeneralMidi,
+    GeneralMidi2,
+    Gs,
+    Xg,
+}
+
+impl ResetKind {
+    /// The SysEx data of the reset message, including the 0xF0 and 0xF7 bytes.
+    ///
+    pub fn sysex(&self) -> &'static [u8] {
+        match self {
+            ResetKind::GeneralMidi => &GM_SYSTEM_ON,
+            ResetKind::GeneralMidi2 => &GM2_SYSTEM_ON,
+            ResetKind::Gs => &GS_RESET,
+            ResetKind::Xg => &XG_SYSTEM_ON,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sysex::{receive_replies, ResetKind, SysexAssembler, SysexTransaction};
+    use crate::PacketBuffer;
+
+    fn replies(packets: &PacketBuffer) -> Vec<Vec<u8>> {
+        let mut assembler = SysexAssembler::default();
+        let mut replies = Vec::new();
+        receive_replies(&mut assembler, packets, |reply| {
+            replies.push(reply.to_vec())
+        });
+        replies
+    }
+
+    #[test]
+    fn join_split_messages() {
+        let mut packets = PacketBuffer::new(1, &[0x90, 0x40, 0x7f, 0xf0, 0x41, 0x10]);
+        packets.push_data(2, &[0x42, 0xf8, 0x12, 0xf7]);
+        packets.push_data(3, &[0xf0, 0x43]);
+        packets.push_data(4, &[0x80, 0x40, 0x00, 0xf7]);
+
+        assert_eq!(
+            replies(&packets),
+            vec![vec![0xf0, 0x41, 0x10, 0x42, 0x12, 0xf7]]
+        );
+    }
+
+    #[test]
+    fn match_reply_header() {
+        let transaction = SysexTransaction::new(&[0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7])
+            .with_reply_header(&[0xf0, 0x7e, 0x7f, 0x06, 0x02]);
+
+        assert!((transaction.reply_filter)(&[
+            0xf0, 0x7e, 0x7f, 0x06, 0x02, 0x41, 0xf7
+        ]));
+        assert!(!(transaction.reply_filter)(&[0xf0, 0x41, 0x10, 0xf7]));
+    }
+
+    #[test]
+    fn reset_messages() {
+        let gs_reset = ResetKind::Gs.sysex();
+        // The Roland checksum makes the address, data and checksum add up to a multiple of 128
+        let sum: u32 = gs_reset[5..gs_reset.len() - 1]
+            .iter()
+            .map(|byte| *byte as u32)
+            .sum();
+        assert_eq!(sum % 128, 0);
+
+        for kind in [
+            ResetKind::GeneralMidi,
+            ResetKind::GeneralMidi2,
+            ResetKind::Xg,
+        ] {
+            let sysex = kind.sysex();
+            assert_eq!((sysex[0], sysex[sysex.len() - 1]), (0xf0, 0xf7));
+        }
+    }
+}
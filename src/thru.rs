@@ -0,0 +1,454 @@
+use core_foundation::{
+    base::{OSStatus, TCFType},
+    data::CFData,
+    string::CFString,
+};
+use std::{mem, mem::MaybeUninit, ops::Deref, ptr, slice};
+
+use coremidi_sys::kMIDIUnknownError;
+
+use crate::object::Object;
+use crate::sys::{
+    kMIDIThruConnection_MaxEndpoints, MIDIThruConnectionCreate, MIDIThruConnectionDispose,
+    MIDIThruConnectionEndpoint, MIDIThruConnectionFind, MIDIThruConnectionGetParams,
+    MIDIThruConnectionParams, MIDIThruConnectionParamsInitialize, MIDIThruConnectionRef,
+    MIDIThruConnectionSetParams,
+};
+use crate::{result_from_status, unit_result_from_status, Destination, Source};
+
+/// The maximum number of sources, and the maximum number of destinations, of a thru connection.
+pub const THRU_CONNECTION_MAX_ENDPOINTS: usize = kMIDIThruConnection_MaxEndpoints;
+
+/// The [parameters](https://developer.apple.com/documentation/coremidi/midithruconnectionparams)
+/// describing how a [ThruConnection] routes MIDI data.
+///
+/// By default nothing is filtered nor transformed, and all the data is routed from the sources
+/// to the destinations:
+///
+/// ```rust,no_run
+/// use coremidi::{Destination, Source, ThruConnectionParams};
+/// let params = ThruConnectionParams::new()
+///     .with_source(&Source::from_index(0).unwrap())
+///     .with_destination(&Destination::from_index(0).unwrap())
+///     .with_filter_out_sysex(true);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct ThruConnectionParams {
+    raw: MIDIThruConnectionParams,
+    // The variable-length control transforms and value maps following the fixed-size parameters
+    extra: Vec<u8>,
+}
+
+impl ThruConnectionParams {
+    /// Create the default parameters.
+    /// See [MIDIThruConnectionParamsInitialize](https://developer.apple.com/documentation/coremidi/midithruconnectionparamsinitialize(_:)).
+    ///
+    pub fn new() -> Self {
+        let mut raw = MaybeUninit::uninit();
+        unsafe { MIDIThruConnectionParamsInitialize(raw.as_mut_ptr()) };
+        Self {
+            raw: unsafe { raw.assume_init() },
+            extra: Vec::new(),
+        }
+    }
+
+    /// Add a source to route the data from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters already contain [THRU_CONNECTION_MAX_ENDPOINTS] sources.
+    ///
+    pub fn with_source(mut self, source: &Source) -> Self {
+        let index = self.raw.numSources as usize;
+        assert!(
+            index < THRU_CONNECTION_MAX_ENDPOINTS,
+            "too many sources for a thru connection"
+        );
+        self.raw.sources[index] = Self::endpoint(source);
+        self.raw.numSources += 1;
+        self
+    }
+
+    /// Add a destination to route the data to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters already contain [THRU_CONNECTION_MAX_ENDPOINTS] destinations.
+    ///
+    pub fn with_destination(mut self, destination: &Destination) -> Self {
+        let index = self.raw.numDestinations as usize;
+        assert!(
+            index < THRU_CONNECTION_MAX_ENDPOINTS,
+            "too many destinations for a thru connection"
+        );
+        self.raw.destinations[index] = Self::endpoint(destination);
+        self.raw.numDestinations += 1;
+        self
+    }
+
+    /// Only route the notes within the range (both ends included).
+    ///
+    pub fn with_note_range(mut self, low: u8, high: u8) -> Self {
+        self.raw.lowNote = low;
+        self.raw.highNote = high;
+        self
+    }
+
+    /// Only route the notes which velocity is within the range (both ends included).
+    ///
+    pub fn with_velocity_range(mut self, low: u8, high: u8) -> Self {
+        self.raw.lowVelocity = low;
+        self.raw.highVelocity = high;
+        self
+    }
+
+    /// Filter out the system exclusive messages.
+    ///
+    pub fn with_filter_out_sysex(mut self, filter_out: bool) -> Self {
+        self.raw.filterOutSysEx = filter_out as u8;
+        self
+    }
+
+    /// Filter out the MIDI Time Code messages.
+    ///
+    pub fn with_filter_out_mtc(mut self, filter_out: bool) -> Self {
+        self.raw.filterOutMTC = filter_out as u8;
+        self
+    }
+
+    /// Filter out the beat clock messages (clock, start, stop, continue and song position).
+    ///
+    pub fn with_filter_out_beat_clock(mut self, filter_out: bool) -> Self {
+        self.raw.filterOutBeatClock = filter_out as u8;
+        self
+    }
+
+    /// Filter out the tune request messages.
+    ///
+    pub fn with_filter_out_tune_request(mut self, filter_out: bool) -> Self {
+        self.raw.filterOutTuneRequest = filter_out as u8;
+        self
+    }
+
+    /// Filter out all the control change messages.
+    ///
+    pub fn with_filter_out_all_controls(mut self, filter_out: bool) -> Self {
+        self.raw.filterOutAllControls = filter_out as u8;
+        self
+    }
+
+    /// The number of sources the data is routed from.
+    ///
+    pub fn num_sources(&self) -> usize {
+        self.raw.numSources as usize
+    }
+
+    /// The number of destinations the data is routed to.
+    ///
+    pub fn num_destinations(&self) -> usize {
+        self.raw.numDestinations as usize
+    }
+
+    /// The sources the data is routed from.
+    ///
+    pub fn sources(&self) -> Vec<Source> {
+        self.raw.sources[..self.num_sources()]
+            .iter()
+            .map(|endpoint| Source::new(endpoint.endpointRef))
+            .collect()
+    }
+
+    /// The destinations the data is routed to.
+    ///
+    pub fn destinations(&self) -> Vec<Destination> {
+        self.raw.destinations[..self.num_destinations()]
+            .iter()
+            .map(|endpoint| Destination::new(endpoint.endpointRef))
+            .collect()
+    }
+
+    fn endpoint(object: &Object) -> MIDIThruConnectionEndpoint {
+        MIDIThruConnectionEndpoint {
+            endpointRef: object.0,
+            uniqueID: object.unique_id().unwrap_or(0),
+        }
+    }
+
+    pub(crate) fn to_data(&self) -> CFData {
+        CFData::from_buffer(&self.to_bytes())
+    }
+
+    /// Serialize the parameters into the same representation used by CoreMIDI,
+    /// for example to store them in the user preferences.
+    ///
+    /// ```rust,no_run
+    /// use coremidi::{Destination, Source, ThruConnectionParams};
+    /// let params = ThruConnectionParams::new()
+    ///     .with_source(&Source::from_index(0).unwrap())
+    ///     .with_destination(&Destination::from_index(0).unwrap());
+    /// let bytes = params.to_bytes();
+    /// // ...
+    /// let params = ThruConnectionParams::from_bytes(&bytes).unwrap();
+    /// ```
+    ///
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                &self.raw as *const MIDIThruConnectionParams as *const u8,
+                mem::size_of::<MIDIThruConnectionParams>(),
+            )
+        };
+        [bytes, &self.extra].concat()
+    }
+
+    /// Deserialize the parameters from the representation used by CoreMIDI,
+    /// as returned by [ThruConnectionParams::to_bytes].
+    ///
+    /// The endpoints are stored with their unique ids too, which CoreMIDI uses to find them
+    /// when their references are not valid anymore (for example after a reboot).
+    /// It returns `None` when the data is not valid.
+    ///
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let size = mem::size_of::<MIDIThruConnectionParams>();
+        if data.len() < size {
+            return None;
+        }
+        let raw = unsafe { ptr::read_unaligned(data.as_ptr() as *const MIDIThruConnectionParams) };
+        if raw.numSources as usize > THRU_CONNECTION_MAX_ENDPOINTS
+            || raw.numDestinations as usize > THRU_CONNECTION_MAX_ENDPOINTS
+        {
+            return None;
+        }
+        Some(Self {
+            raw,
+            extra: data[size..].to_vec(),
+        })
+    }
+}
+
+impl Default for ThruConnectionParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How long a [ThruConnection] lasts.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThruConnectionPersistence {
+    /// The connection persists after the process exits, and even across system reboots,
+    /// until it is explicitly disposed. The owner id (in reverse-DNS form, e.g. `com.example.my-app`)
+    /// allows to [find](ThruConnection::find) it later.
+    Persistent(String),
+    /// The connection lasts until it is dropped or the process exits.
+    Transient,
+}
+
+/// A [MIDI thru connection](https://developer.apple.com/documentation/coremidi/midithruconnectionref).
+///
+/// Thru connections route MIDI data from sources to destinations inside the MIDI server,
+/// so the data keeps flowing without the need of a process forwarding it.
+///
+/// Transient connections are disposed when dropped, while persistent ones need to be disposed explicitly.
+///
+/// ```rust,no_run
+/// use coremidi::{Destination, Source, ThruConnection, ThruConnectionParams, ThruConnectionPersistence};
+/// let params = ThruConnectionParams::new()
+///     .with_source(&Source::from_index(0).unwrap())
+///     .with_destination(&Destination::from_index(0).unwrap());
+/// let connection = ThruConnection::create(ThruConnectionPersistence::Transient, &params).unwrap();
+/// ```
+///
+#[derive(Debug)]
+pub struct ThruConnection {
+    object: Object,
+    transient: bool,
+}
+
+impl ThruConnection {
+    /// Create a thru connection.
+    /// See [MIDIThruConnectionCreate](https://developer.apple.com/documentation/coremidi/midithruconnectioncreate(_:_:_:)).
+    ///
+    pub fn create(
+        persistence: ThruConnectionPersistence,
+        params: &ThruConnectionParams,
+    ) -> Result<ThruConnection, OSStatus> {
+        let owner_id = match &persistence {
+            ThruConnectionPersistence::Persistent(owner_id) => Some(CFString::new(owner_id)),
+            ThruConnectionPersistence::Transient => None,
+        };
+        let owner_id_ref = owner_id
+            .as_ref()
+            .map_or(ptr::null(), |owner_id| owner_id.as_concrete_TypeRef());
+        let data = params.to_data();
+        let mut connection_ref = MaybeUninit::uninit();
+        let status = unsafe {
+            MIDIThruConnectionCreate(
+                owner_id_ref,
+                data.as_concrete_TypeRef(),
+                connection_ref.as_mut_ptr(),
+            )
+        };
+        result_from_status(status, || {
+            let connection_ref = unsafe { connection_ref.assume_init() };
+            ThruConnection {
+                object: Object(connection_ref),
+                transient: persistence == ThruConnectionPersistence::Transient,
+            }
+        })
+    }
+
+    /// Find the persistent thru connections created with the given owner id.
+    /// See [MIDIThruConnectionFind](https://developer.apple.com/documentation/coremidi/midithruconnectionfind(_:_:)).
+    ///
+    /// ```rust,no_run
+    /// // Remove the connections left by a previous run of the application
+    /// for connection in coremidi::ThruConnection::find("com.example.my-app").unwrap() {
+    ///     connection.dispose().unwrap();
+    /// }
+    /// ```
+    ///
+    pub fn find(persistent_owner_id: &str) -> Result<Vec<ThruConnection>, OSStatus> {
+        let owner_id = CFString::new(persistent_owner_id);
+        let mut data_ref = MaybeUninit::uninit();
+        let status = unsafe {
+            MIDIThruConnectionFind(owner_id.as_concrete_TypeRef(), data_ref.as_mut_ptr())
+        };
+        result_from_status(status, || {
+            let data_ref = unsafe { data_ref.assume_init() };
+            if data_ref.is_null() {
+                return Vec::new();
+            }
+            let data: CFData = unsafe { TCFType::wrap_under_create_rule(data_ref) };
+            data.bytes()
+                .chunks_exact(mem::size_of::<MIDIThruConnectionRef>())
+                .map(|chunk| {
+                    let connection_ref = unsafe {
+                        ptr::read_unaligned(chunk.as_ptr() as *const MIDIThruConnectionRef)
+                    };
+                    ThruConnection {
+                        object: Object(connection_ref),
+                        transient: false,
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// Get the parameters of the thru connection.
+    /// See [MIDIThruConnectionGetParams](https://developer.apple.com/documentation/coremidi/midithruconnectiongetparams(_:_:)).
+    ///
+    pub fn params(&self) -> Result<ThruConnectionParams, OSStatus> {
+        let mut data_ref = MaybeUninit::uninit();
+        let status = unsafe { MIDIThruConnectionGetParams(self.object.0, data_ref.as_mut_ptr()) };
+        unit_result_from_status(status)?;
+        let data_ref = unsafe { data_ref.assume_init() };
+        if data_ref.is_null() {
+            return Err(kMIDIUnknownError);
+        }
+        let data: CFData = unsafe { TCFType::wrap_under_create_rule(data_ref) };
+        ThruConnectionParams::from_bytes(data.bytes()).ok_or(kMIDIUnknownError)
+    }
+
+    /// Change the parameters of the thru connection.
+    /// See [MIDIThruConnectionSetParams](https://developer.apple.com/documentation/coremidi/midithruconnectionsetparams(_:_:)).
+    ///
+    pub fn set_params(&self, params: &ThruConnectionParams) -> Result<(), OSStatus> {
+        let data = params.to_data();
+        let status =
+            unsafe { MIDIThruConnectionSetParams(self.object.0, data.as_concrete_TypeRef()) };
+        unit_result_from_status(status)
+    }
+
+    /// Dispose the thru connection, which stops routing data.
+    /// See [MIDIThruConnectionDispose](https://developer.apple.com/documentation/coremidi/midithruconnectiondispose(_:)).
+    ///
+    pub fn dispose(self) -> Result<(), OSStatus> {
+        let status = unsafe { MIDIThruConnectionDispose(self.object.0) };
+        mem::forget(self);
+        unit_result_from_status(status)
+    }
+
+    /// Whether the connection persists after the process exits.
+    ///
+    pub fn is_persistent(&self) -> bool {
+        !self.transient
+    }
+}
+
+impl Drop for ThruConnection {
+    fn drop(&mut self) {
+        if self.transient {
+            unsafe { MIDIThruConnectionDispose(self.object.0) };
+        }
+    }
+}
+
+impl Deref for ThruConnection {
+    type Target = Object;
+
+    fn deref(&self) -> &Object {
+        &self.object
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use crate::sys::MIDIThruConnectionParams;
+    use crate::{Destination, Source, ThruConnectionParams};
+
+    #[test]
+    fn params_layout() {
+        assert_eq!(mem::size_of::<MIDIThruConnectionParams>(), 204);
+        assert_eq!(mem::align_of::<MIDIThruConnectionParams>(), 4);
+    }
+
+    #[test]
+    fn params_default() {
+        let params = ThruConnectionParams::new();
+
+        assert_eq!(params.num_sources(), 0);
+        assert_eq!(params.num_destinations(), 0);
+        assert_eq!(
+            params.raw.channelMap,
+            [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+        );
+        assert_eq!(params.raw.highNote, 127);
+        assert_eq!(params.raw.highVelocity, 127);
+    }
+
+    #[test]
+    fn params_data_roundtrip() {
+        let mut params = ThruConnectionParams::new()
+            .with_source(&Source::new(1))
+            .with_filter_out_mtc(true);
+        params.extra = vec![1, 2, 3, 4];
+
+        let data = params.to_bytes();
+        let decoded = ThruConnectionParams::from_bytes(&data).unwrap();
+
+        assert_eq!(decoded.raw, params.raw);
+        assert_eq!(decoded.extra, params.extra);
+        assert!(ThruConnectionParams::from_bytes(&data[..100]).is_none());
+
+        let mut invalid = data.clone();
+        invalid[4] = 9;
+        assert!(ThruConnectionParams::from_bytes(&invalid).is_none());
+    }
+
+    #[test]
+    fn params_endpoints() {
+        let params = ThruConnectionParams::new()
+            .with_source(&Source::new(1))
+            .with_destination(&Destination::new(2))
+            .with_destination(&Destination::new(3));
+
+        assert_eq!(params.num_sources(), 1);
+        assert_eq!(params.num_destinations(), 2);
+        assert_eq!(params.raw.sources[0].endpointRef, 1);
+        assert_eq!(params.raw.destinations[1].endpointRef, 3);
+    }
+}
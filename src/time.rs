@@ -0,0 +1,119 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::sys::{mach_absolute_time, mach_timebase_info};
+use crate::Timestamp;
+
+/// The current host time, in the same units as the [Timestamp] of the packets.
+///
+pub fn host_time_now() -> Timestamp {
+    unsafe { mach_absolute_time() }
+}
+
+/// Convert a duration into host time units.
+///
+pub fn host_time_from_duration(duration: Duration) -> Timestamp {
+    let timebase = timebase();
+    let nanos = duration.as_nanos();
+    (nanos * timebase.denom as u128 / timebase.numer as u128) as Timestamp
+}
+
+/// Convert an amount of host time units into a duration.
+///
+pub fn duration_from_host_time(host_time: Timestamp) -> Duration {
+    let timebase = timebase();
+    let nanos = host_time as u128 * timebase.numer as u128 / timebase.denom as u128;
+    Duration::from_nanos(nanos as u64)
+}
+
+/// Convert a sequence of host times into durations, reading the timebase only once.
+///
+/// ```
+/// let durations: Vec<_> = coremidi::durations_from_host_times([0, 0]).collect();
+/// assert_eq!(durations.len(), 2);
+/// ```
+///
+pub fn durations_from_host_times<I>(host_times: I) -> impl Iterator<Item = Duration>
+where
+    I: IntoIterator<Item = Timestamp>,
+{
+    let timebase = timebase();
+    host_times.into_iter().map(move |host_time| {
+        let nanos = host_time as u128 * timebase.numer as u128 / timebase.denom as u128;
+        Duration::from_nanos(nanos as u64)
+    })
+}
+
+// The timebase packed as `numer << 32 | denom`, or 0 until it has been read.
+// An atomic is used instead of a lazy lock so that it can be used safely from the realtime threads.
+static TIMEBASE: AtomicU64 = AtomicU64::new(0);
+
+fn timebase() -> mach_timebase_info {
+    let packed = TIMEBASE.load(Ordering::Relaxed);
+    if packed != 0 {
+        return mach_timebase_info {
+            numer: (packed >> 32) as u32,
+            denom: packed as u32,
+        };
+    }
+    let info = read_timebase();
+    // Racing threads would store the same value
+    TIMEBASE.store(
+        (info.numer as u64) << 32 | info.denom as u64,
+        Ordering::Relaxed,
+    );
+    info
+}
+
+fn read_timebase() -> mach_timebase_info {
+    let mut info = mach_timebase_info::default();
+    unsafe { mach_timebase_info(&mut info) };
+    if info.denom == 0 || info.numer == 0 {
+        // Nanoseconds, as a fallback
+        mach_timebase_info { numer: 1, denom: 1 }
+    } else {
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::time::{
+        duration_from_host_time, durations_from_host_times, host_time_from_duration, timebase,
+    };
+
+    #[test]
+    fn duration_roundtrip() {
+        let duration = Duration::from_millis(250);
+
+        let host_time = host_time_from_duration(duration);
+
+        assert_eq!(duration_from_host_time(host_time), duration);
+    }
+
+    #[test]
+    fn timebase_is_cached() {
+        let first = timebase();
+        let second = timebase();
+
+        assert_eq!((first.numer, first.denom), (second.numer, second.denom));
+    }
+
+    #[test]
+    fn durations_batch() {
+        let host_times: Vec<u64> = [1, 10, 250]
+            .iter()
+            .map(|millis| host_time_from_duration(Duration::from_millis(*millis)))
+            .collect();
+
+        let durations: Vec<Duration> = durations_from_host_times(host_times.clone()).collect();
+
+        let expected: Vec<Duration> = host_times
+            .into_iter()
+            .map(duration_from_host_time)
+            .collect();
+        assert_eq!(durations, expected);
+    }
+}
@@ -0,0 +1,946 @@
+use crate::{EventBuffer, RouteTransform, Timestamp};
+
+/// A transform applied to individual MIDI messages, which can modify them in place or drop them.
+///
+/// It can be used from the receiving callbacks through [transform_ump_data] and [transform_midi1_data],
+/// or to transform the data forwarded by a [Router](crate::Router) through [route_transform].
+///
+/// Transforms can be chained by grouping them into tuples, for example `(first, second)`.
+///
+pub trait MessageTransform {
+    /// Transform a [Universal MIDI Packet](https://www.midi.org/specifications/midi-2-0-specifications) message,
+    /// returning whether it must be kept.
+    fn transform_ump(&mut self, message: &mut [u32]) -> bool;
+
+    /// Transform a MIDI 1.0 message, always starting with its status byte, returning whether it must be kept.
+    fn transform_midi1(&mut self, message: &mut [u8]) -> bool;
+}
+
+impl<A, B> MessageTransform for (A, B)
+where
+    A: MessageTransform,
+    B: MessageTransform,
+{
+    fn transform_ump(&mut self, message: &mut [u32]) -> bool {
+        self.0.transform_ump(message) && self.1.transform_ump(message)
+    }
+
+    fn transform_midi1(&mut self, message: &mut [u8]) -> bool {
+        self.0.transform_midi1(message) && self.1.transform_midi1(message)
+    }
+}
+
+/// The number of 32 bits words of a Universal MIDI Packet message, given its first word.
+///
+pub fn ump_message_len(first_word: u32) -> usize {
+    match first_word >> 28 {
+        0x0 | 0x1 | 0x2 | 0x6 | 0x7 => 1,
+        0x3 | 0x4 | 0x8 | 0x9 | 0xa => 2,
+        0xb | 0xc => 3,
+        _ => 4,
+    }
+}
+
+/// The number of data bytes following a MIDI 1.0 status byte, except for system exclusive messages.
+///
+pub fn midi1_data_len(status: u8) -> usize {
+    match status {
+        0x80..=0xbf | 0xe0..=0xef | 0xf2 => 2,
+        0xc0..=0xdf | 0xf1 | 0xf3 => 1,
+        _ => 0,
+    }
+}
+
+/// Apply a transform to every message of some Universal MIDI Packet data (like the one from an [EventPacket](crate::EventPacket)),
+/// appending the messages kept into the output.
+///
+pub fn transform_ump_data<T>(transform: &mut T, data: &[u32], output: &mut Vec<u32>)
+where
+    T: MessageTransform + ?Sized,
+{
+    let mut remaining = data;
+    while !remaining.is_empty() {
+        let len = ump_message_len(remaining[0]).min(remaining.len());
+        let start = output.len();
+        output.extend_from_slice(&remaining[..len]);
+        if !transform.transform_ump(&mut output[start..]) {
+            output.truncate(start);
+        }
+        remaining = &remaining[len..];
+    }
+}
+
+/// Apply a transform to every message of some MIDI 1.0 data (like the one from a [Packet](crate::Packet)),
+/// appending the messages kept into the output.
+///
+/// Messages using running status are written with their status byte, and stray data bytes are dropped.
+///
+pub fn transform_midi1_data<T>(transform: &mut T, data: &[u8], output: &mut Vec<u8>)
+where
+    T: MessageTransform + ?Sized,
+{
+    for_each_midi1_message(data, |message| {
+        let start = output.len();
+        if message[0] == 0xf0 {
+            output.extend(message.iter().filter(|byte| **byte < 0xf8));
+        } else {
+            output.extend_from_slice(message);
+        }
+        if !transform.transform_midi1(&mut output[start..]) {
+            output.truncate(start);
+        }
+    })
+}
+
+/// Call `f` with every message of some MIDI 1.0 data, always starting with its status byte
+/// (even when using running status). Stray data bytes are skipped.
+///
+/// Real-time messages in the middle of another message are passed on their own,
+/// before the message they interrupted. System exclusive messages are passed as they are in the data,
+/// so any real-time bytes interleaved in them must be skipped when reading their contents.
+///
+/// It never allocates, so it can be used from the receiving callbacks.
+///
+pub(crate) fn for_each_midi1_message<F>(data: &[u8], mut f: F)
+where
+    F: FnMut(&[u8]),
+{
+    let mut running_status = None;
+    let mut index = 0;
+    while index < data.len() {
+        let byte = data[index];
+        let (status, data_start) = if byte & 0x80 != 0 {
+            (byte, index + 1)
+        } else {
+            match running_status {
+                Some(status) => (status, index),
+                None => {
+                    index += 1;
+                    continue;
+                }
+            }
+        };
+        if status == 0xf0 {
+            let end = data[index..]
+                .iter()
+                .position(|byte| *byte == 0xf7)
+                .map_or(data.len(), |position| index + position + 1);
+            let sysex = &data[index..end];
+            for byte in sysex.iter().filter(|byte| **byte >= 0xf8) {
+                f(&[*byte]);
+            }
+            f(sysex);
+            index = end;
+        } else {
+            let mut message = [status, 0, 0];
+            let message_len = 1 + midi1_data_len(status);
+            let mut len = 1;
+            let mut end = data_start;
+            while len < message_len && end < data.len() {
+                let byte = data[end];
+                if byte >= 0xf8 {
+                    f(&[byte]);
+                } else if byte & 0x80 != 0 {
+                    // Interrupted by another message
+                    break;
+                } else {
+                    message[len] = byte;
+                    len += 1;
+                }
+                end += 1;
+            }
+            f(&message[..len]);
+            index = end;
+        }
+
+        // Real-time messages don't affect the running status
+        if status < 0xf8 {
+            running_status = if status < 0xf0 { Some(status) } else { None };
+        }
+    }
+}
+
+/// Build a transform for the [Router](crate::Router) that applies a [MessageTransform] to every message.
+///
+/// ```rust,no_run
+/// use coremidi::{route_transform, ChannelMap, Client, Destination, Protocol, Router};
+/// let client = Client::new("example-client").unwrap();
+/// let router = Router::new(&client, "example-router", Protocol::Midi10).unwrap();
+/// let destination = Destination::from_index(0).unwrap();
+/// router.add_destination_with_transform(&destination, route_transform(ChannelMap::force_to(9)));
+/// ```
+///
+pub fn route_transform<T>(mut transform: T) -> RouteTransform
+where
+    T: MessageTransform + Send + 'static,
+{
+    let mut words = Vec::new();
+    Box::new(
+        move |timestamp: Timestamp, data: &[u32], output: &mut EventBuffer| {
+            words.clear();
+            transform_ump_data(&mut transform, data, &mut words);
+            if !words.is_empty() {
+                output.push(timestamp, &words);
+            }
+        },
+    )
+}
+
+/// A mapping of MIDI channels, which allows to drop, remap or force the channel of channel voice messages.
+///
+/// Channels are numbered from 0 to 15, as they are encoded in the messages.
+/// Messages that don't belong to a channel are always kept.
+///
+/// ```rust
+/// use coremidi::ChannelMap;
+/// // Keep only the channels 0 and 1, moving the channel 1 into the channel 2
+/// let channel_map = ChannelMap::keep_only(&[0, 1]).with_remap(1, 2);
+/// assert_eq!(channel_map.map(0), Some(0));
+/// assert_eq!(channel_map.map(1), Some(2));
+/// assert_eq!(channel_map.map(3), None);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelMap([Option<u8>; 16]);
+
+impl ChannelMap {
+    /// Create a mapping that keeps all the channels unchanged.
+    ///
+    pub fn identity() -> Self {
+        let mut channels = [None; 16];
+        for (channel, mapped) in channels.iter_mut().enumerate() {
+            *mapped = Some(channel as u8);
+        }
+        Self(channels)
+    }
+
+    /// Create a mapping that keeps only the given channels, dropping the rest.
+    ///
+    pub fn keep_only(channels: &[u8]) -> Self {
+        let mut channel_map = Self([None; 16]);
+        for channel in channels {
+            let channel = channel & 0x0f;
+            channel_map.0[channel as usize] = Some(channel);
+        }
+        channel_map
+    }
+
+    /// Create a mapping that moves the messages from one channel to another, leaving the rest unchanged.
+    ///
+    pub fn remap(from: u8, to: u8) -> Self {
+        Self::identity().with_remap(from, to)
+    }
+
+    /// Create a mapping that moves the messages from all the channels into one.
+    ///
+    pub fn force_to(channel: u8) -> Self {
+        Self([Some(channel & 0x0f); 16])
+    }
+
+    /// Move the messages from one channel to another.
+    ///
+    pub fn with_remap(mut self, from: u8, to: u8) -> Self {
+        self.0[(from & 0x0f) as usize] = Some(to & 0x0f);
+        self
+    }
+
+    /// Drop the messages from a channel.
+    ///
+    pub fn with_dropped(mut self, channel: u8) -> Self {
+        self.0[(channel & 0x0f) as usize] = None;
+        self
+    }
+
+    /// Get the channel where the messages from a channel go, or `None` when they are dropped.
+    ///
+    pub fn map(&self, channel: u8) -> Option<u8> {
+        self.0[(channel & 0x0f) as usize]
+    }
+}
+
+impl Default for ChannelMap {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl MessageTransform for ChannelMap {
+    fn transform_ump(&mut self, message: &mut [u32]) -> bool {
+        let word = message[0];
+        match word >> 28 {
+            // MIDI 1.0 and MIDI 2.0 channel voice messages
+            0x2 | 0x4 => match self.map((word >> 16) as u8) {
+                Some(channel) => {
+                    message[0] = (word & !0x000f_0000) | ((channel as u32) << 16);
+                    true
+                }
+                None => false,
+            },
+            _ => true,
+        }
+    }
+
+    fn transform_midi1(&mut self, message: &mut [u8]) -> bool {
+        let status = message[0];
+        match status {
+            0x80..=0xef => match self.map(status) {
+                Some(channel) => {
+                    message[0] = (status & 0xf0) | channel;
+                    true
+                }
+                None => false,
+            },
+            _ => true,
+        }
+    }
+}
+
+/// The kinds of MIDI messages, regardless of the protocol used to encode them.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    NoteOff,
+    NoteOn,
+    PolyPressure,
+    ControlChange,
+    ProgramChange,
+    ChannelPressure,
+    PitchBend,
+    SystemExclusive,
+    TimeCode,
+    SongPosition,
+    SongSelect,
+    TuneRequest,
+    Clock,
+    Start,
+    Continue,
+    Stop,
+    ActiveSensing,
+    SystemReset,
+}
+
+impl MessageKind {
+    /// Get the kind of a MIDI 1.0 message from its status byte.
+    ///
+    pub fn from_status(status: u8) -> Option<Self> {
+        match status {
+            0x80..=0x8f => Some(Self::NoteOff),
+            0x90..=0x9f => Some(Self::NoteOn),
+            0xa0..=0xaf => Some(Self::PolyPressure),
+            0xb0..=0xbf => Some(Self::ControlChange),
+            0xc0..=0xcf => Some(Self::ProgramChange),
+            0xd0..=0xdf => Some(Self::ChannelPressure),
+            0xe0..=0xef => Some(Self::PitchBend),
+            0xf0 => Some(Self::SystemExclusive),
+            0xf1 => Some(Self::TimeCode),
+            0xf2 => Some(Self::SongPosition),
+            0xf3 => Some(Self::SongSelect),
+            0xf6 => Some(Self::TuneRequest),
+            0xf8 => Some(Self::Clock),
+            0xfa => Some(Self::Start),
+            0xfb => Some(Self::Continue),
+            0xfc => Some(Self::Stop),
+            0xfe => Some(Self::ActiveSensing),
+            0xff => Some(Self::SystemReset),
+            _ => None,
+        }
+    }
+
+    /// Get the kind of a Universal MIDI Packet message from its first word.
+    ///
+    pub fn from_ump(first_word: u32) -> Option<Self> {
+        match first_word >> 28 {
+            // System common and real-time messages
+            0x1 => Self::from_status((first_word >> 16) as u8),
+            // MIDI 1.0 and MIDI 2.0 channel voice messages, which share the status nibble
+            0x2 | 0x4 => Self::from_status((first_word >> 16) as u8 & 0xf0),
+            // 7-bits system exclusive data
+            0x3 => Some(Self::SystemExclusive),
+            _ => None,
+        }
+    }
+
+    fn mask(self) -> u32 {
+        1 << (self as u32)
+    }
+}
+
+/// A filter dropping some kinds of messages, like the high-rate clock or active sensing
+/// messages sent by some hardware.
+///
+/// It can be applied to an input port with [Client::input_port_with_filter](crate::Client::input_port_with_filter),
+/// so the filtered messages never reach the callback, or used as any other [MessageTransform].
+///
+/// ```rust
+/// use coremidi::{MessageFilter, MessageKind};
+/// let filter = MessageFilter::new()
+///     .with_dropped(MessageKind::Clock)
+///     .with_dropped(MessageKind::ActiveSensing);
+/// assert!(filter.drops(MessageKind::Clock));
+/// assert!(!filter.drops(MessageKind::NoteOn));
+/// ```
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageFilter {
+    dropped: u32,
+}
+
+impl MessageFilter {
+    /// Create a filter that keeps all the messages.
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop the messages of a kind.
+    ///
+    pub fn with_dropped(mut self, kind: MessageKind) -> Self {
+        self.dropped |= kind.mask();
+        self
+    }
+
+    /// Keep the messages of a kind.
+    ///
+    pub fn with_kept(mut self, kind: MessageKind) -> Self {
+        self.dropped &= !kind.mask();
+        self
+    }
+
+    /// Whether the messages of a kind are dropped.
+    ///
+    pub fn drops(&self, kind: MessageKind) -> bool {
+        self.dropped & kind.mask() != 0
+    }
+
+    /// Whether the filter keeps all the messages.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.dropped == 0
+    }
+
+    /// Whether the filter keeps all the messages in some Universal MIDI Packet data.
+    ///
+    pub fn keeps_all_ump(&self, data: &[u32]) -> bool {
+        let mut remaining = data;
+        while !remaining.is_empty() {
+            if !self.keeps(MessageKind::from_ump(remaining[0])) {
+                return false;
+            }
+            remaining = &remaining[ump_message_len(remaining[0]).min(remaining.len())..];
+        }
+        true
+    }
+
+    fn keeps(&self, kind: Option<MessageKind>) -> bool {
+        !matches!(kind, Some(kind) if self.drops(kind))
+    }
+}
+
+impl MessageTransform for MessageFilter {
+    fn transform_ump(&mut self, message: &mut [u32]) -> bool {
+        self.keeps(MessageKind::from_ump(message[0]))
+    }
+
+    fn transform_midi1(&mut self, message: &mut [u8]) -> bool {
+        self.keeps(MessageKind::from_status(message[0]))
+    }
+}
+
+/// A curve remapping the velocity of the note on messages, for example to make a keyboard feel heavier or lighter.
+///
+/// Velocities are mapped through a table with an entry for each MIDI 1.0 velocity (0 to 127),
+/// and MIDI 2.0 velocities are mapped through their 7 most significant bits.
+/// Non-zero velocities are never mapped to zero, since a MIDI 1.0 note on with zero velocity means a note off.
+///
+/// ```rust
+/// use coremidi::VelocityCurve;
+/// let curve = VelocityCurve::gamma(0.5);
+/// assert_eq!(curve.apply(0), 0);
+/// assert_eq!(curve.apply(32), 64);
+/// assert_eq!(curve.apply(127), 127);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VelocityCurve([u8; 128]);
+
+impl VelocityCurve {
+    /// Create a curve that leaves the velocities unchanged.
+    ///
+    pub fn identity() -> Self {
+        Self::from_fn(|velocity| velocity)
+    }
+
+    /// Create a curve that multiplies the velocities by a factor.
+    ///
+    pub fn linear(scale: f32) -> Self {
+        Self::from_fn(|velocity| velocity * scale)
+    }
+
+    /// Create a curve that applies a gamma correction to the velocities.
+    ///
+    /// Values below 1.0 make the soft notes louder, while values above 1.0 make them quieter.
+    ///
+    pub fn gamma(gamma: f32) -> Self {
+        Self::from_fn(|velocity| (velocity / 127.0).powf(gamma) * 127.0)
+    }
+
+    /// Create a curve from a table with the mapped velocity for each velocity.
+    ///
+    pub fn from_table(table: [u8; 128]) -> Self {
+        let mut table = table;
+        for mapped in table.iter_mut().skip(1) {
+            *mapped = (*mapped).clamp(1, 127);
+        }
+        table[0] = 0;
+        Self(table)
+    }
+
+    /// Map a MIDI 1.0 velocity.
+    ///
+    pub fn apply(&self, velocity: u8) -> u8 {
+        self.0[(velocity & 0x7f) as usize]
+    }
+
+    /// Map a MIDI 2.0 velocity.
+    ///
+    /// The velocities too low to be told apart from zero in 7 bits are mapped like the lowest non-zero one.
+    ///
+    pub fn apply_16bits(&self, velocity: u16) -> u16 {
+        match velocity {
+            0 => 0,
+            _ => upscale_7bits(self.apply(((velocity >> 9) as u8).max(1))),
+        }
+    }
+
+    fn from_fn<F: Fn(f32) -> f32>(f: F) -> Self {
+        let mut table = [0u8; 128];
+        for (velocity, mapped) in table.iter_mut().enumerate() {
+            *mapped = f(velocity as f32).round().clamp(0.0, 127.0) as u8;
+        }
+        Self::from_table(table)
+    }
+}
+
+impl Default for VelocityCurve {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl MessageTransform for VelocityCurve {
+    fn transform_ump(&mut self, message: &mut [u32]) -> bool {
+        let word = message[0];
+        match (word >> 28, (word >> 20) & 0x0f) {
+            // MIDI 1.0 note on
+            (0x2, 0x9) => {
+                let velocity = self.apply(word as u8) as u32;
+                message[0] = (word & !0x7f) | velocity;
+            }
+            // MIDI 2.0 note on
+            (0x4, 0x9) if message.len() > 1 => {
+                let velocity = self.apply_16bits((message[1] >> 16) as u16) as u32;
+                message[1] = (message[1] & 0xffff) | (velocity << 16);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn transform_midi1(&mut self, message: &mut [u8]) -> bool {
+        if message[0] & 0xf0 == 0x90 && message.len() > 2 {
+            message[2] = self.apply(message[2]);
+        }
+        true
+    }
+}
+
+/// Scale a 7 bits value into 16 bits, following the MIDI 2.0 recommendations (min-center-max preserving).
+fn upscale_7bits(value: u8) -> u16 {
+    let value = (value & 0x7f) as u16;
+    let shifted = value << 9;
+    if value <= 0x40 {
+        shifted
+    } else {
+        let repeat = value & 0x3f;
+        shifted | (repeat << 3) | (repeat >> 3)
+    }
+}
+
+/// A zone of a keyboard, which keeps only the notes within a range, for splitting or layering a keyboard
+/// when routing its data to several destinations.
+///
+/// The zone remembers the notes that it let through, so their note offs are let through too,
+/// even when the range changes while the notes are held. Messages other than notes and polyphonic pressure
+/// are kept by default, so all the zones receive controllers like the sustain pedal.
+///
+/// ```rust,no_run
+/// use coremidi::{Client, Destination, KeyboardZone, Protocol, Router, Source};
+/// let client = Client::new("example-client").unwrap();
+/// let mut router = Router::new(&client, "example-router", Protocol::Midi10).unwrap();
+/// router.connect_source(&Source::from_index(0).unwrap()).unwrap();
+/// // Notes below C3 go to the first destination on channel 0, and the rest to the second one on channel 1
+/// router.add_zone(&Destination::from_index(0).unwrap(), KeyboardZone::new(0, 59).with_channel(0));
+/// router.add_zone(&Destination::from_index(1).unwrap(), KeyboardZone::new(60, 127).with_channel(1));
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct KeyboardZone {
+    low: u8,
+    high: u8,
+    channel: Option<u8>,
+    keep_other_messages: bool,
+    // For every note, a bit for each channel where it is active
+    active_notes: [u16; 128],
+}
+
+impl KeyboardZone {
+    /// Create a zone for the notes in the range (both ends included).
+    ///
+    pub fn new(low: u8, high: u8) -> Self {
+        Self {
+            low,
+            high,
+            channel: None,
+            keep_other_messages: true,
+            active_notes: [0; 128],
+        }
+    }
+
+    /// Move all the messages kept into a channel.
+    ///
+    pub fn with_channel(mut self, channel: u8) -> Self {
+        self.channel = Some(channel & 0x0f);
+        self
+    }
+
+    /// Whether to keep the messages that are not related to notes.
+    ///
+    pub fn with_other_messages(mut self, keep: bool) -> Self {
+        self.keep_other_messages = keep;
+        self
+    }
+
+    /// Change the range of notes, without affecting the notes that are currently active.
+    ///
+    pub fn set_range(&mut self, low: u8, high: u8) {
+        self.low = low;
+        self.high = high;
+    }
+
+    /// Whether a note is within the range of the zone.
+    ///
+    pub fn contains(&self, note: u8) -> bool {
+        (self.low..=self.high).contains(&note)
+    }
+
+    fn keep_note_event(&mut self, event: NoteEvent, channel: u8, note: u8) -> bool {
+        let note = (note & 0x7f) as usize;
+        let channel_mask = 1u16 << (channel & 0x0f);
+        let active = self.active_notes[note] & channel_mask != 0;
+        match event {
+            NoteEvent::On if self.contains(note as u8) => {
+                self.active_notes[note] |= channel_mask;
+                true
+            }
+            NoteEvent::On => false,
+            NoteEvent::Off => {
+                self.active_notes[note] &= !channel_mask;
+                active || self.contains(note as u8)
+            }
+            NoteEvent::Pressure => active,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NoteEvent {
+    On,
+    Off,
+    Pressure,
+}
+
+impl MessageTransform for KeyboardZone {
+    fn transform_ump(&mut self, message: &mut [u32]) -> bool {
+        let word = message[0];
+        let message_type = word >> 28;
+        if message_type != 0x2 && message_type != 0x4 {
+            return self.keep_other_messages;
+        }
+        let channel = ((word >> 16) & 0x0f) as u8;
+        let note = ((word >> 8) & 0x7f) as u8;
+        let event = match (message_type, (word >> 20) & 0x0f) {
+            // A MIDI 1.0 note on with zero velocity is a note off
+            (0x2, 0x9) if word & 0x7f == 0 => Some(NoteEvent::Off),
+            (_, 0x9) => Some(NoteEvent::On),
+            (_, 0x8) => Some(NoteEvent::Off),
+            (_, 0xa) => Some(NoteEvent::Pressure),
+            _ => None,
+        };
+        let keep = match event {
+            Some(event) => self.keep_note_event(event, channel, note),
+            None => self.keep_other_messages,
+        };
+        if keep {
+            if let Some(channel) = self.channel {
+                message[0] = (word & !0x000f_0000) | ((channel as u32) << 16);
+            }
+        }
+        keep
+    }
+
+    fn transform_midi1(&mut self, message: &mut [u8]) -> bool {
+        let status = message[0];
+        if !(0x80..=0xef).contains(&status) {
+            return self.keep_other_messages;
+        }
+        let channel = status & 0x0f;
+        let note = message.get(1).copied().unwrap_or(0);
+        let velocity = message.get(2).copied().unwrap_or(0);
+        let event = match status & 0xf0 {
+            0x90 if velocity == 0 => Some(NoteEvent::Off),
+            0x90 => Some(NoteEvent::On),
+            0x80 => Some(NoteEvent::Off),
+            0xa0 => Some(NoteEvent::Pressure),
+            _ => None,
+        };
+        let keep = match event {
+            Some(event) => self.keep_note_event(event, channel, note),
+            None => self.keep_other_messages,
+        };
+        if keep {
+            if let Some(channel) = self.channel {
+                message[0] = (status & 0xf0) | channel;
+            }
+        }
+        keep
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transforms::upscale_7bits;
+    use crate::transforms::{
+        for_each_midi1_message, transform_midi1_data, transform_ump_data, ump_message_len,
+    };
+    use crate::{ChannelMap, KeyboardZone, MessageFilter, MessageKind, VelocityCurve};
+
+    #[test]
+    fn message_len() {
+        assert_eq!(ump_message_len(0x20903c7f), 1);
+        assert_eq!(ump_message_len(0x40903c00), 2);
+        assert_eq!(ump_message_len(0x30160102), 2);
+        assert_eq!(ump_message_len(0x50000000), 4);
+    }
+
+    #[test]
+    fn channel_map_ump() {
+        let mut channel_map = ChannelMap::keep_only(&[0, 1]).with_remap(1, 2);
+        let data = [
+            0x20903c7f, 0x20913c7f, 0x20923c7f, 0x40913c00, 0xffff0000, 0x10f80000,
+        ];
+        let mut output = Vec::new();
+
+        transform_ump_data(&mut channel_map, &data, &mut output);
+
+        assert_eq!(
+            output,
+            vec![0x20903c7f, 0x20923c7f, 0x40923c00, 0xffff0000, 0x10f80000]
+        );
+    }
+
+    #[test]
+    fn channel_map_midi1() {
+        let mut channel_map = ChannelMap::force_to(3);
+        // Note on in channel 0 with running status, a clock in between, and a sysex
+        let data = [
+            0x90, 0x3c, 0x7f, 0xf8, 0x3e, 0x7f, 0xf0, 0x01, 0xf7, 0xc5, 0x01,
+        ];
+        let mut output = Vec::new();
+
+        transform_midi1_data(&mut channel_map, &data, &mut output);
+
+        assert_eq!(
+            output,
+            vec![0x93, 0x3c, 0x7f, 0xf8, 0x93, 0x3e, 0x7f, 0xf0, 0x01, 0xf7, 0xc3, 0x01]
+        );
+    }
+
+    #[test]
+    fn realtime_in_the_middle_of_messages() {
+        let data = [
+            0x90, 0x3c, 0xf8, 0x7f, 0x3e, 0xfe, 0xfa, 0x7f, 0xf0, 0x01, 0xf8, 0x02, 0xf7,
+        ];
+        let mut messages = Vec::new();
+
+        for_each_midi1_message(&data, |message| messages.push(message.to_vec()));
+
+        assert_eq!(
+            messages,
+            vec![
+                vec![0xf8],
+                vec![0x90, 0x3c, 0x7f],
+                vec![0xfe],
+                vec![0xfa],
+                vec![0x90, 0x3e, 0x7f],
+                vec![0xf8],
+                vec![0xf0, 0x01, 0xf8, 0x02, 0xf7],
+            ]
+        );
+    }
+
+    #[test]
+    fn realtime_in_the_middle_of_sysex_midi1() {
+        let data = [0xf0, 0x01, 0xf8, 0x02, 0xfe, 0xf7, 0x90, 0x3c, 0x7f];
+        let mut output = Vec::new();
+
+        transform_midi1_data(&mut ChannelMap::identity(), &data, &mut output);
+
+        assert_eq!(
+            output,
+            vec![0xf8, 0xfe, 0xf0, 0x01, 0x02, 0xf7, 0x90, 0x3c, 0x7f]
+        );
+    }
+
+    #[test]
+    fn channel_map_drop_midi1() {
+        let mut channel_map = ChannelMap::identity().with_dropped(0);
+        let data = [0x90, 0x3c, 0x7f, 0x3e, 0x7f, 0x91, 0x3c, 0x7f];
+        let mut output = Vec::new();
+
+        transform_midi1_data(&mut channel_map, &data, &mut output);
+
+        assert_eq!(output, vec![0x91, 0x3c, 0x7f]);
+    }
+
+    #[test]
+    fn message_kind() {
+        assert_eq!(MessageKind::from_status(0x93), Some(MessageKind::NoteOn));
+        assert_eq!(MessageKind::from_status(0xf8), Some(MessageKind::Clock));
+        assert_eq!(MessageKind::from_status(0xf4), None);
+        assert_eq!(
+            MessageKind::from_ump(0x20a33c7f),
+            Some(MessageKind::PolyPressure)
+        );
+        assert_eq!(
+            MessageKind::from_ump(0x40d30000),
+            Some(MessageKind::ChannelPressure)
+        );
+        assert_eq!(
+            MessageKind::from_ump(0x10fe0000),
+            Some(MessageKind::ActiveSensing)
+        );
+        assert_eq!(
+            MessageKind::from_ump(0x30160102),
+            Some(MessageKind::SystemExclusive)
+        );
+        assert_eq!(MessageKind::from_ump(0x00000000), None);
+    }
+
+    #[test]
+    fn message_filter() {
+        let mut filter = MessageFilter::new()
+            .with_dropped(MessageKind::Clock)
+            .with_dropped(MessageKind::PolyPressure);
+        let data = [0x10f80000, 0x20903c7f, 0x20a03c10, 0x10fa0000];
+        let mut output = Vec::new();
+
+        assert!(!filter.keeps_all_ump(&data));
+        assert!(filter.keeps_all_ump(&data[1..2]));
+        transform_ump_data(&mut filter, &data, &mut output);
+
+        assert_eq!(output, vec![0x20903c7f, 0x10fa0000]);
+    }
+
+    #[test]
+    fn message_filter_midi1() {
+        let mut filter = MessageFilter::new().with_dropped(MessageKind::ActiveSensing);
+        let data = [0xfe, 0x90, 0x3c, 0x7f, 0xfe, 0x3e, 0x7f];
+        let mut output = Vec::new();
+
+        transform_midi1_data(&mut filter, &data, &mut output);
+
+        assert_eq!(output, vec![0x90, 0x3c, 0x7f, 0x90, 0x3e, 0x7f]);
+    }
+
+    #[test]
+    fn velocity_curve_table() {
+        let mut table = [0u8; 128];
+        table[1] = 0;
+        table[100] = 200;
+        let curve = VelocityCurve::from_table(table);
+
+        assert_eq!(curve.apply(0), 0);
+        assert_eq!(curve.apply(1), 1);
+        assert_eq!(curve.apply(100), 127);
+    }
+
+    #[test]
+    fn velocity_curve_low_16bits() {
+        let curve = VelocityCurve::identity();
+
+        assert_eq!(curve.apply_16bits(0), 0);
+        assert_eq!(curve.apply_16bits(1), upscale_7bits(1));
+        assert_eq!(curve.apply_16bits(511), upscale_7bits(1));
+        assert_eq!(curve.apply_16bits(512), upscale_7bits(1));
+    }
+
+    #[test]
+    fn velocity_curve_linear() {
+        let curve = VelocityCurve::linear(0.5);
+
+        assert_eq!(curve.apply(1), 1);
+        assert_eq!(curve.apply(100), 50);
+        assert_eq!(VelocityCurve::linear(2.0).apply(100), 127);
+    }
+
+    #[test]
+    fn velocity_curve_transform() {
+        let mut curve = VelocityCurve::linear(0.5);
+        let mut output = Vec::new();
+        let data = [0x20903c64, 0x20803c64, 0x40903c00, 0xffff0000];
+
+        transform_ump_data(&mut curve, &data, &mut output);
+
+        assert_eq!(output, vec![0x20903c32, 0x20803c64, 0x40903c00, 0x80000000]);
+    }
+
+    #[test]
+    fn velocity_upscale() {
+        assert_eq!(upscale_7bits(0), 0);
+        assert_eq!(upscale_7bits(0x40), 0x8000);
+        assert_eq!(upscale_7bits(0x7f), 0xffff);
+    }
+
+    #[test]
+    fn keyboard_zone_split() {
+        let mut lower = KeyboardZone::new(0, 59).with_channel(1);
+        let mut upper = KeyboardZone::new(60, 127).with_channel(2);
+        // Note on 59 and 60, sustain pedal, and the note offs
+        let data = [0x20903b7f, 0x20903c7f, 0x20b0407f, 0x20803b00, 0x20903c00];
+        let mut lower_output = Vec::new();
+        let mut upper_output = Vec::new();
+
+        transform_ump_data(&mut lower, &data, &mut lower_output);
+        transform_ump_data(&mut upper, &data, &mut upper_output);
+
+        assert_eq!(lower_output, vec![0x20913b7f, 0x20b1407f, 0x20813b00]);
+        assert_eq!(upper_output, vec![0x20923c7f, 0x20b2407f, 0x20923c00]);
+    }
+
+    #[test]
+    fn keyboard_zone_note_off_after_range_change() {
+        let mut zone = KeyboardZone::new(0, 59);
+        let mut output = Vec::new();
+
+        transform_midi1_data(&mut zone, &[0x90, 0x3b, 0x7f], &mut output);
+        zone.set_range(0, 47);
+        transform_midi1_data(
+            &mut zone,
+            &[0x90, 0x3b, 0x7f, 0x80, 0x3b, 0x00],
+            &mut output,
+        );
+        transform_midi1_data(&mut zone, &[0x80, 0x3b, 0x00], &mut output);
+
+        assert_eq!(output, vec![0x90, 0x3b, 0x7f, 0x80, 0x3b, 0x00]);
+    }
+}
@@ -0,0 +1,85 @@
+//! The allocations of the receive path are counted with a global allocator, which would count
+//! the ones of every other test of the library too, so these tests are built on their own.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::os::raw::c_void;
+use std::ptr;
+
+use coremidi::{EventBuffer, EventList, PacketBuffer, PacketList, Protocol};
+use coremidi_sys::{MIDIEventList, MIDIPacketList};
+
+// The wrapper is private to the library, so its module is built into these tests directly
+#[path = "../src/realtime.rs"]
+#[allow(dead_code)]
+mod realtime;
+
+use realtime::RtSafe;
+
+/// Counts the allocations done by the current thread, so the tests running in parallel don't interfere.
+struct CountingAllocator;
+
+thread_local! {
+    // A const initializer would need a newer compiler than the minimum supported one
+    #[allow(unknown_lints, clippy::missing_const_for_thread_local)]
+    static ALLOCATIONS: Cell<usize> = Cell::new(0);
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(|allocations| allocations.get());
+    f();
+    ALLOCATIONS.with(|allocations| allocations.get()) - before
+}
+
+#[test]
+fn receive_without_allocations() {
+    let buffer = EventBuffer::new(Protocol::Midi10)
+        .with_packet(10, &[0x20903c7f])
+        .with_packet(20, &[0x20803c00]);
+    let mut context = 0u32;
+    let callback = RtSafe::new(|event_list: &EventList, context: &mut u32| {
+        for packet in event_list.iter() {
+            *context += packet.data().len() as u32;
+        }
+    });
+    let event_list = buffer.as_ref() as *const EventList as *const MIDIEventList;
+    let context_ptr = &mut context as *mut u32 as *mut c_void;
+
+    let allocations = count_allocations(|| unsafe {
+        callback.receive::<u32>(event_list, context_ptr);
+    });
+
+    assert_eq!(allocations, 0);
+    assert_eq!(context, 2);
+}
+
+#[test]
+fn read_without_allocations() {
+    let buffer = PacketBuffer::new(10, &[0x90, 0x40, 0x7f]);
+    let mut received = 0;
+    let callback = RtSafe::new(|packet_list: &PacketList, _: Option<&()>| {
+        received += packet_list.len();
+    });
+    let packet_list = buffer.as_ref() as *const PacketList as *const MIDIPacketList;
+
+    let allocations =
+        count_allocations(|| unsafe { callback.read::<()>(packet_list, ptr::null_mut()) });
+
+    assert_eq!(allocations, 0);
+    assert_eq!(received, 1);
+}